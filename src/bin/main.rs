@@ -14,12 +14,13 @@ use quick_renderer::{
 };
 
 use rt::{
-    camera::CameraExtension,
+    camera::{CameraExtension, PathTraceCamera},
     file,
     fps::FPS,
     glm, icons,
     image::Image,
     inputs::InputArguments,
+    light::Light,
     object::objects::Mesh as MeshObject,
     path_trace::{
         self,
@@ -152,7 +153,7 @@ fn main() {
 fn main_headless(
     ray_trace_main_thread_handle: thread::JoinHandle<()>,
     ray_trace_thread_sender: mpsc::Sender<RayTraceMessage>,
-    path_trace_camera: Camera,
+    path_trace_camera: PathTraceCamera,
     rendered_image: Arc<RwLock<Image>>,
     path_trace_progress: Arc<RwLock<Progress>>,
     arguments: InputArguments,
@@ -170,6 +171,18 @@ fn main_headless(
     let samples_per_pixel = arguments
         .get_samples()
         .unwrap_or_else(rt::default_samples_per_pixel);
+    let shutter_open = arguments
+        .get_shutter_open()
+        .unwrap_or_else(rt::default_shutter_open);
+    let shutter_close = arguments
+        .get_shutter_close()
+        .unwrap_or_else(rt::default_shutter_close);
+
+    // image width and height are of the view of a single eye, the
+    // rendered image contains the views of all the eyes
+    let (rendered_image_width, rendered_image_height) = path_trace_camera
+        .get_stereo()
+        .get_image_size(image_width, image_height);
 
     ray_trace_thread_sender
         .send(RayTraceMessage::StartRender(Box::new(RayTraceParams::new(
@@ -178,6 +191,8 @@ fn main_headless(
             trace_max_depth,
             samples_per_pixel,
             path_trace_camera,
+            shutter_open,
+            shutter_close,
             rendered_image.clone(),
         ))))
         .unwrap();
@@ -191,9 +206,10 @@ fn main_headless(
 
     // total number of samples must be kept consistent with ray trace
     // thread to ensure the progress bar shows things accurately
-    let total_number_of_samples: u64 = (samples_per_pixel * image_width * image_height)
-        .try_into()
-        .unwrap();
+    let total_number_of_samples: u64 =
+        (samples_per_pixel * rendered_image_width * rendered_image_height)
+            .try_into()
+            .unwrap();
 
     if let Some(sender) = &path_trace_progress_sender {
         sender.send(total_number_of_samples).unwrap();
@@ -252,7 +268,7 @@ fn main_gui(
     scene: Arc<RwLock<Scene>>,
     shader_list: Arc<RwLock<ShaderList>>,
     texture_list: Arc<RwLock<TextureList>>,
-    path_trace_camera: Arc<RwLock<Camera>>,
+    path_trace_camera: Arc<RwLock<PathTraceCamera>>,
     environment: Arc<RwLock<Environment>>,
     rendered_image: Arc<RwLock<Image>>,
     path_trace_progress: Arc<RwLock<Progress>>,
@@ -331,7 +347,7 @@ fn main_gui(
         &rendered_image.read().unwrap(),
     )));
     let environment_texture = Rc::new(RefCell::new(TextureRGBAFloat::from_image(
-        environment.read().unwrap().get_hdr(),
+        environment.read().unwrap().get_image(),
     )));
 
     let mut key_mods = glfw::Modifiers::empty();
@@ -363,6 +379,12 @@ fn main_gui(
     let mut samples_per_pixel = arguments
         .get_samples()
         .unwrap_or_else(rt::default_samples_per_pixel);
+    let mut shutter_open = arguments
+        .get_shutter_open()
+        .unwrap_or_else(rt::default_shutter_open);
+    let mut shutter_close = arguments
+        .get_shutter_close()
+        .unwrap_or_else(rt::default_shutter_close);
     let mut ray_traversal_info: Vec<TraversalInfo> = Vec::new();
     let mut ray_to_shoot = (3, 3);
     let mut ray_pixel_start = (
@@ -392,7 +414,7 @@ fn main_gui(
                 &mut window,
                 &mut key_mods,
                 &mut camera,
-                &path_trace_camera.read().unwrap(),
+                path_trace_camera.read().unwrap().get_camera(),
                 &mut should_cast_scene_ray,
                 &mut try_select_object,
                 &mut use_top_panel,
@@ -589,9 +611,29 @@ fn main_gui(
                                             scene.unapply_model_matrices();
                                         }
 
+                                        // use the render resolution of the
+                                        // blend file so that the framing of
+                                        // the camera matches Blender
+                                        if let Some(blend_scene) =
+                                            rt::blend::get_all_scenes_from_path(&path)
+                                                .ok()
+                                                .and_then(|scenes| scenes.into_iter().next())
+                                        {
+                                            let (width, height) =
+                                                blend_scene.get_render_data().get_resolution();
+                                            image_width = width;
+                                            image_height = height;
+                                        }
+
                                         if let Ok(objects) =
-                                            rt::blend::get_all_objects_from_path(path)
+                                            rt::blend::get_all_objects_from_path(&path)
                                         {
+                                            objects.iter().filter_map(Light::from_blend).for_each(
+                                                |light| {
+                                                    scene.write().unwrap().add_light(light);
+                                                },
+                                            );
+
                                             if let Some(blend_camera) =
                                                 objects.iter().find(|object| {
                                                     matches!(
@@ -600,8 +642,18 @@ fn main_gui(
                                                     )
                                                 })
                                             {
-                                                *path_trace_camera.write().unwrap() =
-                                                    Camera::from_blend(blend_camera).unwrap();
+                                                let mut camera =
+                                                    PathTraceCamera::from_blend(blend_camera)
+                                                        .unwrap();
+                                                camera
+                                                    .get_camera_mut()
+                                                    .get_sensor_mut()
+                                                    .as_mut()
+                                                    .unwrap()
+                                                    .change_aspect_ratio(
+                                                        image_width as f64 / image_height as f64,
+                                                    );
+                                                *path_trace_camera.write().unwrap() = camera;
                                             } else {
                                                 eprintln!("no camera in given blend file");
                                             }
@@ -648,7 +700,7 @@ fn main_gui(
                             }
                             environment_texture
                                 .borrow_mut()
-                                .update_from_image(environment.read().unwrap().get_hdr());
+                                .update_from_image(environment.read().unwrap().get_image());
                             ui.collapsing("Environment Image", |ui| {
                                 let environment_texture_ui_width =
                                     250.0_f32.min(0.3 * window_viewport.get_width() as f32);
@@ -792,13 +844,14 @@ fn main_gui(
                                     let mut camera_sensor_width = path_trace_camera
                                         .read()
                                         .unwrap()
+                                        .get_camera()
                                         .get_sensor()
                                         .unwrap()
                                         .get_width();
                                     ui.add(
                                         egui::Slider::new(&mut camera_sensor_width, 0.0..=36.0)
                                             .clamp_to_range(false)
-                                            .text("Camera Sensor Width"),
+                                            .text("Camera Sensor Size"),
                                     );
                                     camera_sensor_width
                                 };
@@ -807,6 +860,7 @@ fn main_gui(
                                     let mut camera_focal_length = path_trace_camera
                                         .read()
                                         .unwrap()
+                                        .get_camera()
                                         .get_focal_length()
                                         .unwrap();
                                     ui.add(
@@ -818,8 +872,11 @@ fn main_gui(
                                 };
 
                                 let camera_position = {
-                                    let mut camera_position =
-                                        path_trace_camera.read().unwrap().get_position();
+                                    let mut camera_position = path_trace_camera
+                                        .read()
+                                        .unwrap()
+                                        .get_camera()
+                                        .get_position();
                                     ui.label("Camera Position");
                                     ui.add(
                                         egui::Slider::new(&mut camera_position[0], -10.0..=10.0)
@@ -842,7 +899,7 @@ fn main_gui(
                                 ui.label("Camera Rotation");
                                 let camera_yaw = {
                                     let mut camera_yaw =
-                                        path_trace_camera.read().unwrap().get_yaw();
+                                        path_trace_camera.read().unwrap().get_camera().get_yaw();
                                     ui.add(
                                         egui::Slider::new(&mut camera_yaw, 0.0..=360.0)
                                             .clamp_to_range(false)
@@ -852,7 +909,7 @@ fn main_gui(
                                 };
                                 let camera_pitch = {
                                     let mut camera_pitch =
-                                        path_trace_camera.read().unwrap().get_pitch();
+                                        path_trace_camera.read().unwrap().get_camera().get_pitch();
                                     ui.add(
                                         egui::Slider::new(&mut camera_pitch, 0.0..=360.0)
                                             .clamp_to_range(false)
@@ -862,6 +919,7 @@ fn main_gui(
                                 };
 
                                 if let Ok(mut path_trace_camera) = path_trace_camera.try_write() {
+                                    let path_trace_camera = path_trace_camera.get_camera_mut();
                                     let sensor =
                                         path_trace_camera.get_sensor_mut().as_mut().unwrap();
                                     sensor.change_width(camera_sensor_width);
@@ -876,11 +934,76 @@ fn main_gui(
                                 if ui.button("Set from Viewport Camera").clicked() {
                                     if let Ok(mut path_trace_camera) = path_trace_camera.try_write()
                                     {
+                                        let path_trace_camera = path_trace_camera.get_camera_mut();
                                         let sensor = path_trace_camera.get_sensor_no_ref();
                                         *path_trace_camera = camera.clone();
                                         *path_trace_camera.get_sensor_mut() = sensor;
                                     }
                                 }
+
+                                if let Ok(mut path_trace_camera) = path_trace_camera.try_write() {
+                                    let mut roll = path_trace_camera.get_roll().to_degrees();
+                                    ui.add(
+                                        egui::Slider::new(&mut roll, -180.0..=180.0)
+                                            .clamp_to_range(false)
+                                            .text("roll"),
+                                    );
+                                    path_trace_camera.set_roll(roll.to_radians());
+
+                                    ui.label("Camera Shift");
+                                    let mut shift = *path_trace_camera.get_shift();
+                                    ui.add(
+                                        egui::Slider::new(&mut shift[0], -1.0..=1.0)
+                                            .clamp_to_range(false)
+                                            .text("x"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(&mut shift[1], -1.0..=1.0)
+                                            .clamp_to_range(false)
+                                            .text("y"),
+                                    );
+                                    path_trace_camera.set_shift(shift);
+
+                                    ui.label("Camera Sensor Fit");
+                                    let mut sensor_fit = path_trace_camera.get_sensor_fit();
+                                    sensor_fit.draw_ui_mut(ui, &egui::Id::new("Camera Sensor Fit"));
+                                    path_trace_camera.set_sensor_fit(sensor_fit);
+                                }
+
+                                ui.label("Camera Projection");
+                                if let Ok(mut path_trace_camera) = path_trace_camera.try_write() {
+                                    let mut projection = path_trace_camera.get_projection();
+                                    projection.draw_ui_mut(ui, &egui::Id::new("Camera Projection"));
+                                    path_trace_camera.set_projection(projection);
+                                }
+
+                                ui.collapsing("Stereo", |ui| {
+                                    if let Ok(mut path_trace_camera) = path_trace_camera.try_write()
+                                    {
+                                        path_trace_camera
+                                            .get_stereo_mut()
+                                            .draw_ui_mut(ui, &egui::Id::new("Stereo"));
+                                    }
+                                });
+
+                                ui.collapsing("Depth of Field", |ui| {
+                                    if let Ok(mut path_trace_camera) = path_trace_camera.try_write()
+                                    {
+                                        path_trace_camera
+                                            .get_depth_of_field_mut()
+                                            .draw_ui_mut(ui, &egui::Id::new("Depth of Field"));
+                                    }
+                                });
+
+                                ui.collapsing("Exposure", |ui| {
+                                    if let Ok(mut path_trace_camera) = path_trace_camera.try_write()
+                                    {
+                                        path_trace_camera.get_exposure_mut().draw_ui_mut(ui, &());
+                                        path_trace_camera
+                                            .get_white_balance_mut()
+                                            .draw_ui_mut(ui, &());
+                                    }
+                                });
                             });
 
                             // path trace camera depends on image
@@ -892,7 +1015,11 @@ fn main_gui(
                             // image width and height is modified and
                             // must be done so separately here
                             if let Ok(mut path_trace_camera) = path_trace_camera.try_write() {
-                                let sensor = path_trace_camera.get_sensor_mut().as_mut().unwrap();
+                                let sensor = path_trace_camera
+                                    .get_camera_mut()
+                                    .get_sensor_mut()
+                                    .as_mut()
+                                    .unwrap();
                                 sensor
                                     .change_aspect_ratio(image_width as f64 / image_height as f64);
                             }
@@ -934,6 +1061,19 @@ fn main_gui(
                                 }
                                 restart_viewport_rendered_shading = true;
                             }
+                            ui.add(
+                                egui::Slider::new(&mut shutter_open, 0.0..=1.0)
+                                    .clamp_to_range(false)
+                                    .text("Shutter Open"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut shutter_close, 0.0..=1.0)
+                                    .clamp_to_range(false)
+                                    .text("Shutter Close"),
+                            );
+                            if shutter_close < shutter_open {
+                                shutter_close = shutter_open;
+                            }
 
                             ui.horizontal(|ui| {
                                 if ui.button("Ray Trace Scene").clicked() {
@@ -945,6 +1085,8 @@ fn main_gui(
                                                 trace_max_depth,
                                                 samples_per_pixel,
                                                 path_trace_camera.read().unwrap().clone(),
+                                                shutter_open,
+                                                shutter_close,
                                                 rendered_image.clone(),
                                             ),
                                         )))
@@ -963,6 +1105,7 @@ fn main_gui(
                                     .add_filter("image", &["image"])
                                     .add_filter("png", &["png"])
                                     .add_filter("jpg", &["jpg", "jpeg"])
+                                    .add_filter("exr", &["exr"])
                                     .add_filter("tiff", &["tiff"])
                                     .add_filter("Any", &["*"])
                                     .set_directory(".")
@@ -1066,22 +1209,25 @@ fn main_gui(
                                                 - 0.5)
                                                 * 2.0;
 
-                                            let ray = path_trace_camera
-                                                .get_ray(&glm::vec2(u, v))
-                                                .unwrap();
+                                            let ray =
+                                                match path_trace_camera.get_ray(&glm::vec2(u, v)) {
+                                                    Some(ray) => ray,
+                                                    None => continue,
+                                                };
 
                                             let environment: &Environment =
                                                 &environment.read().unwrap();
-                                            let (_color, traversal_info) = path_trace::trace_ray(
-                                                &ray,
-                                                &path_trace_camera,
-                                                &scene.read().unwrap(),
-                                                trace_max_depth,
-                                                &shader_list.read().unwrap(),
-                                                &texture_list.read().unwrap(),
-                                                &environment.into(),
-                                                &mut Mediums::with_air(),
-                                            );
+                                            let (_color, _transparency, traversal_info) =
+                                                path_trace::trace_ray(
+                                                    &ray,
+                                                    &path_trace_camera,
+                                                    &scene.read().unwrap(),
+                                                    trace_max_depth,
+                                                    &shader_list.read().unwrap(),
+                                                    &texture_list.read().unwrap(),
+                                                    &environment.into(),
+                                                    &mut Mediums::with_air(),
+                                                );
                                             ray_traversal_info.push(traversal_info);
                                         }
                                     }
@@ -1247,7 +1393,8 @@ fn main_gui(
                 .vscroll(true)
                 .show(egui.get_egui_ctx(), |ui| {
                     // let camera = &camera;
-                    let camera = &path_trace_camera.read().unwrap();
+                    let path_trace_camera = path_trace_camera.read().unwrap();
+                    let camera = path_trace_camera.get_camera();
                     ui.label(format!(
                         "position: {}",
                         vec_to_string(&camera.get_position())
@@ -1405,7 +1552,7 @@ fn main_gui(
             // trace ray into scene from the rasterizer camera
             // position to get the first hitpoint
             let environment: &Environment = &environment.read().unwrap();
-            let (_color, traversal_info) = path_trace::trace_ray(
+            let (_color, _transparency, traversal_info) = path_trace::trace_ray(
                 &Ray::new(camera.get_position(), ray_direction),
                 &path_trace_camera,
                 &scene.read().unwrap(),
@@ -1420,15 +1567,15 @@ fn main_gui(
             // position towards the first hitpoint
             let ray_direction = if let Some(hit_point) = traversal_info.get_traversal()[0].get_co()
             {
-                (hit_point - path_trace_camera.get_position()).normalize()
+                (hit_point - path_trace_camera.get_camera().get_position()).normalize()
             } else {
                 (traversal_info.get_traversal()[0].get_ray().at(1000.0)
-                    - path_trace_camera.get_position())
+                    - path_trace_camera.get_camera().get_position())
                 .normalize()
             };
 
-            let (_color, traversal_info) = path_trace::trace_ray(
-                &Ray::new(path_trace_camera.get_position(), ray_direction),
+            let (_color, _transparency, traversal_info) = path_trace::trace_ray(
+                &Ray::new(path_trace_camera.get_camera().get_position(), ray_direction),
                 &path_trace_camera,
                 &scene.read().unwrap(),
                 trace_max_depth,
@@ -1479,11 +1626,8 @@ fn main_gui(
                 let mut scene = scene.write().unwrap();
                 scene.apply_model_matrices();
                 scene.rebuild_bvh_if_needed(0.01);
-                if let Some(hit_info) = scene.hit(
-                    &Ray::new(camera.get_position(), ray_direction),
-                    0.01,
-                    1000.0,
-                ) {
+                let ray = Ray::new(camera.get_position(), ray_direction);
+                if let Some(hit_info) = scene.hit(&ray, 0.0, scene.get_ray_t_max(&ray)) {
                     let object_id = hit_info.get_object_id().unwrap();
                     scene.get_objects_mut().for_each(|object| {
                         if object_id == object.get_object_id() {
@@ -1514,6 +1658,7 @@ fn main_gui(
             path_trace_camera
                 .read()
                 .unwrap()
+                .get_camera()
                 .draw(&CameraDrawData::new(
                     imm.clone(),
                     Some(rendered_texture.clone()),
@@ -21,6 +21,16 @@ pub struct Camera {
     shifty: f32,
 
     sensor_fit: SensorFit,
+
+    /// Panorama settings, part of Cycles' custom properties in blend
+    /// files prior to Blender 3.0, Blender's defaults are used for
+    /// such files.
+    panorama_type: PanoramaType,
+    fisheye_fov: f32,
+    fisheye_lens: f32,
+
+    /// Depth of field settings, not available in older blend files.
+    dof: Option<DOFSettings>,
 }
 
 impl Camera {
@@ -83,6 +93,146 @@ impl Camera {
     pub fn get_sensor_fit(&self) -> SensorFit {
         self.sensor_fit
     }
+
+    /// Get camera's panorama type.
+    pub fn get_panorama_type(&self) -> PanoramaType {
+        self.panorama_type
+    }
+
+    /// Get camera's fisheye fov.
+    pub fn get_fisheye_fov(&self) -> f32 {
+        self.fisheye_fov
+    }
+
+    /// Get camera's fisheye lens.
+    pub fn get_fisheye_lens(&self) -> f32 {
+        self.fisheye_lens
+    }
+
+    /// Get a reference to the camera's dof.
+    pub fn get_dof(&self) -> Option<&DOFSettings> {
+        self.dof.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanoramaType {
+    Equirectangular,
+    FisheyeEquidistant,
+    FisheyeEquisolid,
+    Mirrorball,
+    FisheyeLensPolynomial,
+}
+
+impl TryFrom<i8> for PanoramaType {
+    type Error = ();
+
+    fn try_from(value: i8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Equirectangular),
+            1 => Ok(Self::FisheyeEquidistant),
+            2 => Ok(Self::FisheyeEquisolid),
+            3 => Ok(Self::Mirrorball),
+            4 => Ok(Self::FisheyeLensPolynomial),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Depth of field settings of the camera, CameraDOFSettings in
+/// Blender.
+#[derive(Debug)]
+pub struct DOFSettings {
+    /// Location of the object to focus on, taken from the final
+    /// worldspace matrix of the object.
+    focus_object_location: Option<[f32; 3]>,
+    focus_distance: f32,
+    aperture_fstop: f32,
+    aperture_rotation: f32,
+    aperture_ratio: f32,
+    aperture_blades: i16,
+    flag: i16,
+}
+
+impl DOFSettings {
+    /// CAM_DOF_ENABLED flag.
+    const ENABLED: i16 = 1 << 0;
+
+    /// Get dof settings's focus object location.
+    pub fn get_focus_object_location(&self) -> Option<&[f32; 3]> {
+        self.focus_object_location.as_ref()
+    }
+
+    /// Get dof settings's focus distance.
+    pub fn get_focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    /// Get dof settings's aperture fstop.
+    pub fn get_aperture_fstop(&self) -> f32 {
+        self.aperture_fstop
+    }
+
+    /// Get dof settings's aperture rotation.
+    pub fn get_aperture_rotation(&self) -> f32 {
+        self.aperture_rotation
+    }
+
+    /// Get dof settings's aperture ratio.
+    pub fn get_aperture_ratio(&self) -> f32 {
+        self.aperture_ratio
+    }
+
+    /// Get dof settings's aperture blades.
+    pub fn get_aperture_blades(&self) -> i16 {
+        self.aperture_blades
+    }
+
+    /// Get dof settings's flag.
+    pub fn get_flag(&self) -> i16 {
+        self.flag
+    }
+
+    /// Check if depth of field is enabled.
+    pub fn get_enabled(&self) -> bool {
+        self.flag & Self::ENABLED != 0
+    }
+}
+
+impl FromBlend for DOFSettings {
+    fn from_blend_instance(instance: &Instance) -> Option<Self> {
+        if !instance.is_valid("focus_distance")
+            || !instance.is_valid("aperture_fstop")
+            || !instance.is_valid("aperture_rotation")
+            || !instance.is_valid("aperture_ratio")
+            || !instance.is_valid("aperture_blades")
+            || !instance.is_valid("flag")
+        {
+            return None;
+        }
+
+        let focus_object_location = if instance.is_valid("focus_object") {
+            let focus_object = instance.get("focus_object");
+            if focus_object.is_valid("obmat") {
+                let obmat = focus_object.get_f32_vec("obmat");
+                Some([obmat[12], obmat[13], obmat[14]])
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        Some(Self {
+            focus_object_location,
+            focus_distance: instance.get_f32("focus_distance"),
+            aperture_fstop: instance.get_f32("aperture_fstop"),
+            aperture_rotation: instance.get_f32("aperture_rotation"),
+            aperture_ratio: instance.get_f32("aperture_ratio"),
+            aperture_blades: instance.get_i16("aperture_blades"),
+            flag: instance.get_i16("flag"),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -159,6 +309,29 @@ impl FromBlend for Camera {
             shiftx: instance.get_f32("shiftx"),
             shifty: instance.get_f32("shifty"),
             sensor_fit: instance.get_i8("sensor_fit").try_into().unwrap(),
+            panorama_type: if instance.is_valid("panorama_type") {
+                instance
+                    .get_i8("panorama_type")
+                    .try_into()
+                    .unwrap_or(PanoramaType::FisheyeEquisolid)
+            } else {
+                PanoramaType::FisheyeEquisolid
+            },
+            fisheye_fov: if instance.is_valid("fisheye_fov") {
+                instance.get_f32("fisheye_fov")
+            } else {
+                std::f32::consts::PI
+            },
+            fisheye_lens: if instance.is_valid("fisheye_lens") {
+                instance.get_f32("fisheye_lens")
+            } else {
+                10.5
+            },
+            dof: if instance.is_valid("dof") {
+                DOFSettings::from_blend_instance(&instance.get("dof"))
+            } else {
+                None
+            },
         })
     }
 }
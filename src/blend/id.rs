@@ -4,7 +4,7 @@ use lazy_static::lazy_static;
 
 use blend::Instance;
 
-use super::{camera::Camera, mesh::Mesh, FromBlend};
+use super::{camera::Camera, light::Light, mesh::Mesh, FromBlend};
 
 lazy_static! {
     static ref ID_NAME_MAP: HashMap<&'static [u8; 4], &'static str> = {
@@ -141,6 +141,7 @@ impl FromBlend for ID {
 pub enum IDObject {
     Mesh(Mesh),
     Camera(Camera),
+    Light(Light),
 }
 
 impl FromBlend for IDObject {
@@ -149,6 +150,8 @@ impl FromBlend for IDObject {
             Some(Self::Mesh(Mesh::from_blend_instance(instance)?))
         } else if instance.code()[0..=1] == *b"CA" {
             Some(Self::Camera(Camera::from_blend_instance(instance)?))
+        } else if instance.code()[0..=1] == *b"LA" {
+            Some(Self::Light(Light::from_blend_instance(instance)?))
         } else {
             eprintln!(
                 "TODO: Need to implement for id: {} code: {:?}",
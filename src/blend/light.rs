@@ -0,0 +1,181 @@
+use std::convert::{TryFrom, TryInto};
+
+use blend::Instance;
+
+use super::{id::ID, FromBlend};
+
+#[derive(Debug)]
+pub struct Light {
+    id: ID,
+
+    /// LA_LOCAL, LA_SUN, LA_SPOT or LA_AREA.
+    light_type: Type,
+    r: f32,
+    g: f32,
+    b: f32,
+    energy: f32,
+
+    /// Radius of point and spot lights, stored in `area_size` in
+    /// blend files prior to Blender 4.0.
+    radius: f32,
+
+    /// Full angle of the spot light cone.
+    spotsize: f32,
+    spotblend: f32,
+
+    area_shape: AreaShape,
+    area_size: f32,
+    area_sizey: f32,
+
+    /// Angular diameter of the sun light, not available in older
+    /// blend files.
+    sun_angle: f32,
+}
+
+impl Light {
+    /// Get a reference to the light's id.
+    pub fn get_id(&self) -> &ID {
+        &self.id
+    }
+
+    /// Get light's light type.
+    pub fn get_light_type(&self) -> Type {
+        self.light_type
+    }
+
+    /// Get light's color.
+    pub fn get_color(&self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    /// Get light's energy.
+    pub fn get_energy(&self) -> f32 {
+        self.energy
+    }
+
+    /// Get light's radius.
+    pub fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Get light's spotsize.
+    pub fn get_spotsize(&self) -> f32 {
+        self.spotsize
+    }
+
+    /// Get light's spotblend.
+    pub fn get_spotblend(&self) -> f32 {
+        self.spotblend
+    }
+
+    /// Get light's area shape.
+    pub fn get_area_shape(&self) -> AreaShape {
+        self.area_shape
+    }
+
+    /// Get light's area size.
+    pub fn get_area_size(&self) -> f32 {
+        self.area_size
+    }
+
+    /// Get light's area sizey.
+    pub fn get_area_sizey(&self) -> f32 {
+        self.area_sizey
+    }
+
+    /// Get light's sun angle.
+    pub fn get_sun_angle(&self) -> f32 {
+        self.sun_angle
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Point,
+    Sun,
+    Spot,
+    Area,
+}
+
+impl TryFrom<i16> for Type {
+    type Error = ();
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Point),
+            1 => Ok(Self::Sun),
+            2 => Ok(Self::Spot),
+            4 => Ok(Self::Area),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaShape {
+    Square,
+    Rectangle,
+    Disk,
+    Ellipse,
+}
+
+impl TryFrom<i16> for AreaShape {
+    type Error = ();
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Square),
+            1 => Ok(Self::Rectangle),
+            4 => Ok(Self::Disk),
+            5 => Ok(Self::Ellipse),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromBlend for Light {
+    fn from_blend_instance(instance: &Instance) -> Option<Self> {
+        if !instance.is_valid("id")
+            || !instance.is_valid("type")
+            || !instance.is_valid("r")
+            || !instance.is_valid("g")
+            || !instance.is_valid("b")
+            || !instance.is_valid("energy")
+            || !instance.is_valid("spotsize")
+            || !instance.is_valid("spotblend")
+            || !instance.is_valid("area_shape")
+            || !instance.is_valid("area_size")
+            || !instance.is_valid("area_sizey")
+        {
+            println!("something not available, might not be a light");
+            return None;
+        }
+
+        Some(Self {
+            id: ID::from_blend_instance(&instance.get("id"))?,
+            light_type: instance.get_i16("type").try_into().ok()?,
+            r: instance.get_f32("r"),
+            g: instance.get_f32("g"),
+            b: instance.get_f32("b"),
+            energy: instance.get_f32("energy"),
+            radius: if instance.is_valid("radius") {
+                instance.get_f32("radius")
+            } else {
+                instance.get_f32("area_size")
+            },
+            spotsize: instance.get_f32("spotsize"),
+            spotblend: instance.get_f32("spotblend"),
+            area_shape: instance
+                .get_i16("area_shape")
+                .try_into()
+                .unwrap_or(AreaShape::Square),
+            area_size: instance.get_f32("area_size"),
+            area_sizey: instance.get_f32("area_sizey"),
+            sun_angle: if instance.is_valid("sun_angle") {
+                instance.get_f32("sun_angle")
+            } else {
+                0.526_f32.to_radians()
+            },
+        })
+    }
+}
@@ -14,12 +14,14 @@ use blend::{Blend, Instance};
 
 use crate::util;
 
-use self::object::Object;
+use self::{object::Object, scene::Scene};
 
 pub mod camera;
 pub mod id;
+pub mod light;
 pub mod mesh;
 pub mod object;
+pub mod scene;
 
 pub use crate::util::RotationModes;
 
@@ -66,3 +68,18 @@ pub fn get_all_objects(data: impl Read) -> Vec<Object> {
         .filter_map(Object::from_blend_instance)
         .collect()
 }
+
+pub fn get_all_scenes_from_path(path: impl AsRef<Path>) -> Result<Vec<Scene>, std::io::Error> {
+    Ok(get_all_scenes(std::io::Cursor::new(
+        load_blend_data_from_path(path)?,
+    )))
+}
+
+pub fn get_all_scenes(data: impl Read) -> Vec<Scene> {
+    let blend = Blend::new(data);
+    blend
+        .get_by_code(*b"SC")
+        .iter()
+        .filter_map(Scene::from_blend_instance)
+        .collect()
+}
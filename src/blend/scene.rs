@@ -0,0 +1,116 @@
+use blend::Instance;
+
+use super::{id::ID, FromBlend};
+
+#[derive(Debug)]
+pub struct Scene {
+    id: ID,
+
+    /// Render settings of the scene.
+    render_data: RenderData,
+}
+
+impl Scene {
+    /// Get a reference to the scene's id.
+    pub fn get_id(&self) -> &ID {
+        &self.id
+    }
+
+    /// Get a reference to the scene's render data.
+    pub fn get_render_data(&self) -> &RenderData {
+        &self.render_data
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderData {
+    /// Resolution percentage.
+    size: i16,
+    /// Render resolution in x.
+    xsch: i32,
+    /// Render resolution in y.
+    ysch: i32,
+    /// Pixel aspect ratio in x.
+    xasp: f32,
+    /// Pixel aspect ratio in y.
+    yasp: f32,
+}
+
+impl RenderData {
+    /// Get render data's size.
+    pub fn get_size(&self) -> i16 {
+        self.size
+    }
+
+    /// Get render data's xsch.
+    pub fn get_xsch(&self) -> i32 {
+        self.xsch
+    }
+
+    /// Get render data's ysch.
+    pub fn get_ysch(&self) -> i32 {
+        self.ysch
+    }
+
+    /// Get render data's xasp.
+    pub fn get_xasp(&self) -> f32 {
+        self.xasp
+    }
+
+    /// Get render data's yasp.
+    pub fn get_yasp(&self) -> f32 {
+        self.yasp
+    }
+
+    /// Get the final render resolution, takes the resolution
+    /// percentage into account.
+    pub fn get_resolution(&self) -> (usize, usize) {
+        let size = self.size.max(1) as usize;
+        (
+            (self.xsch.max(1) as usize * size / 100).max(1),
+            (self.ysch.max(1) as usize * size / 100).max(1),
+        )
+    }
+
+    /// Get the aspect ratio (width / height) of the rendered image,
+    /// takes the pixel aspect ratio into account.
+    pub fn get_aspect_ratio(&self) -> f64 {
+        (self.xsch as f64 * self.xasp as f64) / (self.ysch as f64 * self.yasp as f64)
+    }
+}
+
+impl FromBlend for RenderData {
+    fn from_blend_instance(instance: &Instance) -> Option<Self> {
+        if !instance.is_valid("size")
+            || !instance.is_valid("xsch")
+            || !instance.is_valid("ysch")
+            || !instance.is_valid("xasp")
+            || !instance.is_valid("yasp")
+        {
+            println!("something not available, might not be render data");
+            return None;
+        }
+
+        Some(Self {
+            size: instance.get_i16("size"),
+            xsch: instance.get_i32("xsch"),
+            ysch: instance.get_i32("ysch"),
+            xasp: instance.get_f32("xasp"),
+            yasp: instance.get_f32("yasp"),
+        })
+    }
+}
+
+impl FromBlend for Scene {
+    fn from_blend_instance(instance: &Instance) -> Option<Self> {
+        if !instance.is_valid("id") || !instance.is_valid("r") {
+            println!("something not available, might not be a scene");
+            return None;
+        }
+
+        Some(Self {
+            id: ID::from_blend_instance(&instance.get("id"))?,
+            render_data: RenderData::from_blend_instance(&instance.get("r"))?,
+        })
+    }
+}
@@ -1,6 +1,16 @@
 use quick_renderer::camera::{Camera, Sensor};
+use serde::{Deserialize, Serialize};
 
-use crate::{blend, glm, path_trace::ray::Ray, util};
+use crate::{
+    blend, egui, glm, math,
+    path_trace::{
+        intersectable::Intersectable,
+        ray::{Ray, RayDifferential},
+    },
+    scene::Scene,
+    ui::DrawUI,
+    util,
+};
 
 pub trait CameraExtension {
     /// Get ray given the UVs on the camera sensor.
@@ -12,15 +22,42 @@ pub trait CameraExtension {
     /// Create new camera from camera data available in the Camera
     /// Object of a Blend file
     ///
-    /// The camera roll is not taken into account, is not supported
-    /// yet.
-    ///
     /// If the given object is not a camera, [`None`] is returned.
     fn from_blend(camera: &blend::object::Object) -> Option<Self>
     where
         Self: Sized;
 }
 
+/// Get the position, front and up of the Camera Object of a Blend
+/// file from its final worldspace matrix (so parenting and
+/// constraints are taken into account) converted to the axis used
+/// here.
+fn get_blend_camera_frame(camera: &blend::object::Object) -> (glm::DVec3, glm::DVec3, glm::DVec3) {
+    let obmat: glm::DMat4 = glm::convert(glm::make_mat4(camera.get_obmat()));
+    let obmat = util::axis_conversion_matrix_from_blender() * obmat;
+
+    let position = util::vec3_apply_model_matrix(&glm::zero(), &obmat);
+
+    // Blender cameras look along -Z with Y as up, the matrix can
+    // have scale so must normalize
+    let rotation = glm::mat4_to_mat3(&obmat);
+    let front = (rotation * glm::vec3(0.0, 0.0, -1.0)).normalize();
+    let up = (rotation * glm::vec3(0.0, 1.0, 0.0)).normalize();
+
+    (position, front, up)
+}
+
+/// Get the sensor size of the Camera of a Blend file that is fit to
+/// the rendered image, see [`SensorFit`].
+fn get_blend_camera_sensor_size(camera_data: &blend::camera::Camera) -> f64 {
+    match camera_data.get_sensor_fit() {
+        blend::camera::SensorFit::Auto | blend::camera::SensorFit::Horizontal => {
+            camera_data.get_sensor_x().into()
+        }
+        blend::camera::SensorFit::Vertical => camera_data.get_sensor_y().into(),
+    }
+}
+
 impl CameraExtension for Camera {
     fn get_ray(&self, uv: &glm::DVec2) -> Option<Ray> {
         Some(Ray::new(
@@ -29,63 +66,2365 @@ impl CameraExtension for Camera {
         ))
     }
 
+    /// Create new camera from camera data available in the Camera
+    /// Object of a Blend file
+    ///
+    /// [`Camera`] does not support camera roll, lens shift, sensor
+    /// fit and other projections, use [`PathTraceCamera`] for these.
+    ///
+    /// If the given object is not a camera, [`None`] is returned.
     fn from_blend(camera: &blend::object::Object) -> Option<Self> {
         let camera_data = match camera.get_data()? {
             blend::id::IDObject::Camera(data) => data,
             _ => return None,
         };
 
-        let camera_sensor_size = match camera_data.get_sensor_fit() {
-            blend::camera::SensorFit::Auto => {
-                if camera_data.get_sensor_x() > camera_data.get_sensor_y() {
-                    camera_data.get_sensor_x()
-                } else {
-                    camera_data.get_sensor_y()
-                }
-            }
-            blend::camera::SensorFit::Horizontal => camera_data.get_sensor_x(),
-            blend::camera::SensorFit::Vertical => camera_data.get_sensor_y(),
-        }
-        .into();
-
-        let position = util::vec3_apply_model_matrix(
-            &glm::convert(glm::make_vec3(camera.get_loc())),
-            &util::axis_conversion_matrix_from_blender(),
-        );
+        let camera_sensor_size = get_blend_camera_sensor_size(camera_data);
+        let camera_sensor_aspect_ratio =
+            camera_data.get_sensor_x() as f64 / camera_data.get_sensor_y() as f64;
 
-        let rotation = util::vec3_apply_model_matrix(
-            &glm::convert(glm::make_vec3(camera.get_rot())),
-            &util::axis_conversion_matrix_from_blender(),
-        );
+        let (position, front, _up) = get_blend_camera_frame(camera);
 
-        let roll_pitch_yaw = {
-            let roll_pitch_yaw = util::euler_rotation_change_mode(
-                &rotation,
-                camera.get_rotmode(),
-                util::RotationModes::RollPitchYaw,
-            );
-            glm::vec3(
-                roll_pitch_yaw[0].to_degrees(),
-                roll_pitch_yaw[1].to_degrees(),
-                roll_pitch_yaw[2].to_degrees(),
-            )
-        };
+        // yaw and pitch as defined in
+        // https://learnopengl.com/Getting-started/Camera
+        let yaw = front[2].atan2(front[0]).to_degrees();
+        let pitch = front[1].clamp(-1.0, 1.0).asin().to_degrees();
 
         let mut res = Self::new(
             position,
             glm::vec3(0.0, 1.0, 0.0),
-            roll_pitch_yaw[2],
-            roll_pitch_yaw[1],
+            yaw,
+            pitch,
             util::focal_length_to_fov(camera_data.get_lens().into(), camera_sensor_size)
                 .to_degrees(),
             Some(Sensor::new(
-                camera_data.get_sensor_x().into(),
-                camera_data.get_sensor_y().into(),
+                camera_sensor_size,
+                camera_sensor_size / camera_sensor_aspect_ratio,
             )),
         );
+        res.set_focal_length(camera_data.get_lens().into());
         res.set_near_plane(camera_data.get_clip_start().into());
         res.set_far_plane(camera_data.get_clip_end().into());
 
         Some(res)
     }
 }
+
+/// Aperture of the camera lens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Aperture {
+    /// F-stop (f-number) of the lens, the radius of the aperture
+    /// depends on the focal length of the camera.
+    FStop(f64),
+    /// Radius of the aperture in world units.
+    Radius(f64),
+}
+
+impl Aperture {
+    /// Get the radius of the aperture in world units given the focal
+    /// length (in mm) of the camera.
+    pub fn get_radius(&self, focal_length: f64) -> f64 {
+        match self {
+            // focal length is in mm, world units are in m
+            Aperture::FStop(fstop) => {
+                if *fstop > 0.0 {
+                    focal_length * 0.001 / (2.0 * fstop)
+                } else {
+                    0.0
+                }
+            }
+            Aperture::Radius(radius) => radius.max(0.0),
+        }
+    }
+}
+
+impl std::fmt::Display for Aperture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Aperture::FStop(_) => write!(f, "F-Stop"),
+            Aperture::Radius(_) => write!(f, "Radius"),
+        }
+    }
+}
+
+/// Depth of field settings of the camera, models a thin lens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthOfField {
+    enabled: bool,
+    aperture: Aperture,
+    /// Distance from the camera (along the camera's front) at which
+    /// objects are in perfect focus.
+    focus_distance: f64,
+    /// Number of aperture blades, a circular aperture is used if it
+    /// is less than 3.
+    blades: usize,
+    /// Rotation of the aperture blades in radians.
+    rotation: f64,
+    /// Set the focus distance to the distance of the first hit of the
+    /// ray cast through `autofocus_point` at the start of the render.
+    autofocus: bool,
+    /// Point on the camera sensor used for autofocus. Uses opengl
+    /// coords, (0.0, 0.0) is center; (1.0, 1.0) is top right; (-1.0,
+    /// -1.0) is bottom left.
+    autofocus_point: glm::DVec2,
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            aperture: Aperture::FStop(2.8),
+            focus_distance: 10.0,
+            blades: 0,
+            rotation: 0.0,
+            autofocus: false,
+            autofocus_point: glm::zero(),
+        }
+    }
+}
+
+impl DepthOfField {
+    /// Get depth of field's enabled.
+    pub fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Set depth of field's enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Get depth of field's aperture.
+    pub fn get_aperture(&self) -> Aperture {
+        self.aperture
+    }
+
+    /// Set depth of field's aperture.
+    pub fn set_aperture(&mut self, aperture: Aperture) {
+        self.aperture = aperture;
+    }
+
+    /// Get depth of field's focus distance.
+    pub fn get_focus_distance(&self) -> f64 {
+        self.focus_distance
+    }
+
+    /// Set depth of field's focus distance.
+    pub fn set_focus_distance(&mut self, focus_distance: f64) {
+        self.focus_distance = focus_distance;
+    }
+
+    /// Get depth of field's blades.
+    pub fn get_blades(&self) -> usize {
+        self.blades
+    }
+
+    /// Set depth of field's blades.
+    pub fn set_blades(&mut self, blades: usize) {
+        self.blades = blades;
+    }
+
+    /// Get depth of field's rotation.
+    pub fn get_rotation(&self) -> f64 {
+        self.rotation
+    }
+
+    /// Set depth of field's rotation.
+    pub fn set_rotation(&mut self, rotation: f64) {
+        self.rotation = rotation;
+    }
+
+    /// Get depth of field's autofocus.
+    pub fn get_autofocus(&self) -> bool {
+        self.autofocus
+    }
+
+    /// Set depth of field's autofocus.
+    pub fn set_autofocus(&mut self, autofocus: bool) {
+        self.autofocus = autofocus;
+    }
+
+    /// Get a reference to the depth of field's autofocus point.
+    pub fn get_autofocus_point(&self) -> &glm::DVec2 {
+        &self.autofocus_point
+    }
+
+    /// Set depth of field's autofocus point.
+    pub fn set_autofocus_point(&mut self, autofocus_point: glm::DVec2) {
+        self.autofocus_point = autofocus_point;
+    }
+}
+
+impl DrawUI for DepthOfField {
+    type ExtraData = egui::Id;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, id: &Self::ExtraData) {
+        ui.checkbox(&mut self.enabled, "Depth of Field");
+
+        egui::ComboBox::from_id_source(id.with("Aperture Type"))
+            .selected_text(format!("{}", self.aperture))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut self.aperture,
+                    Aperture::FStop(2.8),
+                    format!("{}", Aperture::FStop(2.8)),
+                );
+                ui.selectable_value(
+                    &mut self.aperture,
+                    Aperture::Radius(0.01),
+                    format!("{}", Aperture::Radius(0.01)),
+                );
+            });
+
+        match &mut self.aperture {
+            Aperture::FStop(fstop) => {
+                ui.add(
+                    egui::Slider::new(fstop, 0.1..=64.0)
+                        .clamp_to_range(false)
+                        .text("F-Stop"),
+                );
+            }
+            Aperture::Radius(radius) => {
+                ui.add(
+                    egui::Slider::new(radius, 0.0..=1.0)
+                        .clamp_to_range(false)
+                        .text("Aperture Radius"),
+                );
+            }
+        }
+
+        ui.add(
+            egui::Slider::new(&mut self.focus_distance, 0.0..=100.0)
+                .clamp_to_range(false)
+                .text("Focus Distance"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.blades, 0..=16)
+                .clamp_to_range(false)
+                .text("Aperture Blades"),
+        );
+        {
+            let mut rotation = self.rotation.to_degrees();
+            ui.add(
+                egui::Slider::new(&mut rotation, -180.0..=180.0)
+                    .clamp_to_range(false)
+                    .text("Aperture Rotation"),
+            );
+            self.rotation = rotation.to_radians();
+        }
+
+        ui.checkbox(&mut self.autofocus, "Autofocus");
+        ui.label("Autofocus Point");
+        ui.add(
+            egui::Slider::new(&mut self.autofocus_point[0], -1.0..=1.0)
+                .clamp_to_range(true)
+                .text("x"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.autofocus_point[1], -1.0..=1.0)
+                .clamp_to_range(true)
+                .text("y"),
+        );
+    }
+}
+
+/// Projection of the camera, defines how the rays are generated
+/// given the UVs on the camera sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    Perspective,
+    /// Orthographic projection, `ortho_scale` is the size of the
+    /// view in world units, fit to the rendered image in the same way
+    /// as the sensor size, see [`SensorFit`].
+    Orthographic {
+        ortho_scale: f64,
+    },
+    /// Equirectangular 360° projection, the entire sensor width
+    /// covers 360° of longitude and the entire sensor height covers
+    /// 180° of latitude.
+    Equirectangular,
+    /// Equidistant fisheye projection, the angle from the front of
+    /// the camera is linearly proportional to the distance from the
+    /// center of the sensor. `fov` (in radians) is the field of view
+    /// covered by the sensor width.
+    FisheyeEquidistant {
+        fov: f64,
+    },
+    /// Equisolid fisheye projection, models real fisheye lenses.
+    /// `lens` is the focal length (in mm) and `fov` (in radians) is
+    /// the maximum field of view of the lens.
+    FisheyeEquisolid {
+        lens: f64,
+        fov: f64,
+    },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::Perspective
+    }
+}
+
+impl std::fmt::Display for Projection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Projection::Perspective => write!(f, "Perspective"),
+            Projection::Orthographic { .. } => write!(f, "Orthographic"),
+            Projection::Equirectangular => write!(f, "Equirectangular"),
+            Projection::FisheyeEquidistant { .. } => write!(f, "Fisheye Equidistant"),
+            Projection::FisheyeEquisolid { .. } => write!(f, "Fisheye Equisolid"),
+        }
+    }
+}
+
+impl Projection {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use Projection::*;
+        [
+            Perspective,
+            Orthographic { ortho_scale: 6.0 },
+            Equirectangular,
+            FisheyeEquidistant {
+                fov: std::f64::consts::PI,
+            },
+            FisheyeEquisolid {
+                lens: 10.5,
+                fov: std::f64::consts::PI,
+            },
+        ]
+        .iter()
+        .copied()
+    }
+
+    /// Check if the projection is panoramic, rays can be generated in
+    /// directions away from the front of the camera.
+    pub fn is_panoramic(&self) -> bool {
+        match self {
+            Projection::Perspective | Projection::Orthographic { .. } => false,
+            Projection::Equirectangular
+            | Projection::FisheyeEquidistant { .. }
+            | Projection::FisheyeEquisolid { .. } => true,
+        }
+    }
+}
+
+impl DrawUI for Projection {
+    type ExtraData = egui::Id;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, id: &Self::ExtraData) {
+        egui::ComboBox::from_id_source(id.with("Projection"))
+            .selected_text(format!("{}", self))
+            .show_ui(ui, |ui| {
+                Self::all().for_each(|projection| {
+                    // only compare the type of projection, must not
+                    // reset the parameters when the same projection
+                    // type is selected again
+                    let selected =
+                        std::mem::discriminant(self) == std::mem::discriminant(&projection);
+                    if ui
+                        .selectable_label(selected, format!("{}", projection))
+                        .clicked()
+                        && !selected
+                    {
+                        *self = projection;
+                    }
+                });
+            });
+
+        match self {
+            Projection::Perspective | Projection::Equirectangular => {}
+            Projection::Orthographic { ortho_scale } => {
+                ui.add(
+                    egui::Slider::new(ortho_scale, 0.0..=100.0)
+                        .clamp_to_range(false)
+                        .text("Orthographic Scale"),
+                );
+            }
+            Projection::FisheyeEquidistant { fov } => {
+                let mut fov_degrees = fov.to_degrees();
+                ui.add(
+                    egui::Slider::new(&mut fov_degrees, 0.0..=360.0)
+                        .clamp_to_range(true)
+                        .text("Field of View"),
+                );
+                *fov = fov_degrees.to_radians();
+            }
+            Projection::FisheyeEquisolid { lens, fov } => {
+                ui.add(
+                    egui::Slider::new(lens, 0.0..=100.0)
+                        .clamp_to_range(false)
+                        .text("Fisheye Lens"),
+                );
+                let mut fov_degrees = fov.to_degrees();
+                ui.add(
+                    egui::Slider::new(&mut fov_degrees, 0.0..=360.0)
+                        .clamp_to_range(true)
+                        .text("Field of View"),
+                );
+                *fov = fov_degrees.to_radians();
+            }
+        }
+    }
+}
+
+/// Eye of a stereo camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoEye {
+    Left,
+    Right,
+}
+
+/// How the views of the eyes of a stereo camera converge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StereoConvergence {
+    /// The views of the eyes are shifted such that they overlap at
+    /// the convergence distance, the eyes look in the same
+    /// direction.
+    OffAxis,
+    /// The eyes look in the same direction and the views do not
+    /// converge.
+    Parallel,
+    /// The eyes are rotated to look at the point at the convergence
+    /// distance in front of the camera.
+    ToeIn,
+}
+
+impl StereoConvergence {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use StereoConvergence::*;
+        [OffAxis, Parallel, ToeIn].iter().copied()
+    }
+}
+
+impl std::fmt::Display for StereoConvergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StereoConvergence::OffAxis => write!(f, "Off-Axis"),
+            StereoConvergence::Parallel => write!(f, "Parallel"),
+            StereoConvergence::ToeIn => write!(f, "Toe-In"),
+        }
+    }
+}
+
+impl std::str::FromStr for StereoConvergence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off-axis" => Ok(Self::OffAxis),
+            "parallel" => Ok(Self::Parallel),
+            "toe-in" => Ok(Self::ToeIn),
+            _ => Err(format!("unknown stereo convergence mode: {}", s)),
+        }
+    }
+}
+
+/// Layout of the views of the eyes in the rendered image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StereoLayout {
+    /// Left eye on the left, right eye on the right.
+    SideBySide,
+    /// Left eye on the top, right eye on the bottom.
+    TopBottom,
+}
+
+impl StereoLayout {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use StereoLayout::*;
+        [SideBySide, TopBottom].iter().copied()
+    }
+}
+
+impl std::fmt::Display for StereoLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StereoLayout::SideBySide => write!(f, "Side by Side"),
+            StereoLayout::TopBottom => write!(f, "Top Bottom"),
+        }
+    }
+}
+
+impl std::str::FromStr for StereoLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "side-by-side" => Ok(Self::SideBySide),
+            "top-bottom" => Ok(Self::TopBottom),
+            _ => Err(format!("unknown stereo layout: {}", s)),
+        }
+    }
+}
+
+/// Stereo settings of the camera.
+///
+/// For panoramic projections, omni-directional stereo is used, the
+/// eyes are offset perpendicular to the horizontal direction of each
+/// ray instead of the right of the camera.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stereo {
+    enabled: bool,
+    /// Distance between the eyes in world units.
+    interocular_distance: f64,
+    /// Distance from the camera at which the views of the eyes
+    /// converge.
+    convergence_distance: f64,
+    convergence: StereoConvergence,
+    layout: StereoLayout,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interocular_distance: 0.065,
+            convergence_distance: 1.95,
+            convergence: StereoConvergence::OffAxis,
+            layout: StereoLayout::SideBySide,
+        }
+    }
+}
+
+impl Stereo {
+    /// Get stereo's enabled.
+    pub fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Set stereo's enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Get stereo's interocular distance.
+    pub fn get_interocular_distance(&self) -> f64 {
+        self.interocular_distance
+    }
+
+    /// Set stereo's interocular distance.
+    pub fn set_interocular_distance(&mut self, interocular_distance: f64) {
+        self.interocular_distance = interocular_distance;
+    }
+
+    /// Get stereo's convergence distance.
+    pub fn get_convergence_distance(&self) -> f64 {
+        self.convergence_distance
+    }
+
+    /// Set stereo's convergence distance.
+    pub fn set_convergence_distance(&mut self, convergence_distance: f64) {
+        self.convergence_distance = convergence_distance;
+    }
+
+    /// Get stereo's convergence.
+    pub fn get_convergence(&self) -> StereoConvergence {
+        self.convergence
+    }
+
+    /// Set stereo's convergence.
+    pub fn set_convergence(&mut self, convergence: StereoConvergence) {
+        self.convergence = convergence;
+    }
+
+    /// Get stereo's layout.
+    pub fn get_layout(&self) -> StereoLayout {
+        self.layout
+    }
+
+    /// Set stereo's layout.
+    pub fn set_layout(&mut self, layout: StereoLayout) {
+        self.layout = layout;
+    }
+
+    /// Get the size of the rendered image given the size of the view
+    /// of a single eye.
+    pub fn get_image_size(&self, width: usize, height: usize) -> (usize, usize) {
+        if !self.enabled {
+            return (width, height);
+        }
+        match self.layout {
+            StereoLayout::SideBySide => (width * 2, height),
+            StereoLayout::TopBottom => (width, height * 2),
+        }
+    }
+
+    /// Get the eye and the pixel within the view of the eye given the
+    /// pixel of the rendered image. `width` and `height` are the size
+    /// of the view of a single eye. `j` is the row of the image
+    /// starting from the top.
+    ///
+    /// The eye is [`None`] if stereo is not enabled.
+    pub fn get_eye_pixel(
+        &self,
+        i: usize,
+        j: usize,
+        width: usize,
+        height: usize,
+    ) -> (Option<StereoEye>, usize, usize) {
+        if !self.enabled {
+            return (None, i, j);
+        }
+        match self.layout {
+            StereoLayout::SideBySide => {
+                if i < width {
+                    (Some(StereoEye::Left), i, j)
+                } else {
+                    (Some(StereoEye::Right), i - width, j)
+                }
+            }
+            StereoLayout::TopBottom => {
+                if j < height {
+                    (Some(StereoEye::Left), i, j)
+                } else {
+                    (Some(StereoEye::Right), i, j - height)
+                }
+            }
+        }
+    }
+}
+
+impl DrawUI for Stereo {
+    type ExtraData = egui::Id;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, id: &Self::ExtraData) {
+        ui.checkbox(&mut self.enabled, "Stereo");
+
+        ui.add(
+            egui::Slider::new(&mut self.interocular_distance, 0.0..=1.0)
+                .clamp_to_range(false)
+                .text("Interocular Distance"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.convergence_distance, 0.0..=100.0)
+                .clamp_to_range(false)
+                .text("Convergence Distance"),
+        );
+
+        egui::ComboBox::from_id_source(id.with("Convergence"))
+            .selected_text(format!("{}", self.convergence))
+            .show_ui(ui, |ui| {
+                StereoConvergence::all().for_each(|convergence| {
+                    ui.selectable_value(
+                        &mut self.convergence,
+                        convergence,
+                        format!("{}", convergence),
+                    );
+                });
+            });
+
+        egui::ComboBox::from_id_source(id.with("Layout"))
+            .selected_text(format!("{}", self.layout))
+            .show_ui(ui, |ui| {
+                StereoLayout::all().for_each(|layout| {
+                    ui.selectable_value(&mut self.layout, layout, format!("{}", layout));
+                });
+            });
+    }
+}
+
+/// Physically based exposure of the camera. The ISO, shutter speed
+/// and f-number are combined into an exposure value (EV100) which
+/// scales the radiance reaching the film.
+///
+/// The radiance of the renderer has no physical unit (a white
+/// diffuse surface lit by a light of strength 1.0 is 1.0 on the
+/// film), so the EV100 is relative to `reference_ev100`, the EV100 at
+/// which the radiance reaches the film unchanged. The reference
+/// defaults to 0.0 (ISO 100, 1 second, f/1) so that the default
+/// settings leave existing scenes unchanged. For scenes with radiance
+/// in nits (cd/m^2) use [`Exposure::PHYSICAL_REFERENCE_EV100`].
+///
+/// The settings are independent of the aperture of
+/// [`DepthOfField`] and the shutter used for motion blur, so the
+/// exposure can be changed without changing the look of the scene.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exposure {
+    enabled: bool,
+    /// Sensitivity of the film.
+    iso: f64,
+    /// Time (in seconds) for which the film is exposed.
+    shutter_speed: f64,
+    f_number: f64,
+    /// EV100 at which the radiance reaches the film unchanged.
+    #[serde(default)]
+    reference_ev100: f64,
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        // EV100 of 0 at the reference, the film is developed without
+        // any change
+        Self {
+            enabled: false,
+            iso: 100.0,
+            shutter_speed: 1.0,
+            f_number: 1.0,
+            reference_ev100: 0.0,
+        }
+    }
+}
+
+impl Exposure {
+    /// Reference EV100 for radiance in nits (cd/m^2), -log2(1.2).
+    ///
+    /// With saturation based sensitivity (lens attenuation of 0.65)
+    /// the radiance that saturates the film is 1.2 * 2^EV100, so a
+    /// radiance of 1.0 reaches the film unchanged at this EV100.
+    pub const PHYSICAL_REFERENCE_EV100: f64 = -0.263_034_405_833_793_8;
+
+    /// Get exposure's enabled.
+    pub fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Set exposure's enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Get exposure's iso.
+    pub fn get_iso(&self) -> f64 {
+        self.iso
+    }
+
+    /// Set exposure's iso.
+    pub fn set_iso(&mut self, iso: f64) {
+        self.iso = iso;
+    }
+
+    /// Get exposure's shutter speed.
+    pub fn get_shutter_speed(&self) -> f64 {
+        self.shutter_speed
+    }
+
+    /// Set exposure's shutter speed.
+    pub fn set_shutter_speed(&mut self, shutter_speed: f64) {
+        self.shutter_speed = shutter_speed;
+    }
+
+    /// Get exposure's f number.
+    pub fn get_f_number(&self) -> f64 {
+        self.f_number
+    }
+
+    /// Set exposure's f number.
+    pub fn set_f_number(&mut self, f_number: f64) {
+        self.f_number = f_number;
+    }
+
+    /// Get exposure's reference ev100.
+    pub fn get_reference_ev100(&self) -> f64 {
+        self.reference_ev100
+    }
+
+    /// Set exposure's reference ev100.
+    pub fn set_reference_ev100(&mut self, reference_ev100: f64) {
+        self.reference_ev100 = reference_ev100;
+    }
+
+    /// Get the exposure value at ISO 100 (EV100) of the settings.
+    pub fn get_ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_speed).log2() - (self.iso / 100.0).log2()
+    }
+
+    /// Get the scale applied to the radiance reaching the film. An
+    /// EV100 equal to the reference EV100 leaves the radiance
+    /// unchanged and every increase of the EV100 by 1 halves it.
+    ///
+    /// Returns 1.0 if exposure is not enabled.
+    pub fn get_scale(&self) -> f64 {
+        if !self.enabled {
+            return 1.0;
+        }
+        2.0_f64.powf(self.reference_ev100 - self.get_ev100())
+    }
+}
+
+impl DrawUI for Exposure {
+    type ExtraData = ();
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.checkbox(&mut self.enabled, "Exposure");
+
+        ui.add(
+            egui::Slider::new(&mut self.iso, 25.0..=6400.0)
+                .clamp_to_range(false)
+                .text("ISO"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.shutter_speed, 0.0001..=30.0)
+                .clamp_to_range(false)
+                .text("Shutter Speed"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.f_number, 0.5..=64.0)
+                .clamp_to_range(false)
+                .text("F-Number"),
+        );
+        self.iso = self.iso.max(f64::EPSILON);
+        self.shutter_speed = self.shutter_speed.max(f64::EPSILON);
+        self.f_number = self.f_number.max(f64::EPSILON);
+
+        ui.add(
+            egui::Slider::new(&mut self.reference_ev100, -5.0..=20.0)
+                .clamp_to_range(false)
+                .text("Reference EV100"),
+        );
+
+        ui.label(format!("EV100: {:.2}", self.get_ev100()));
+    }
+}
+
+/// White balance of the camera. The colors are adapted such that a
+/// light of the given temperature and tint is white on the film.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhiteBalance {
+    enabled: bool,
+    /// Temperature (in kelvin) of the light that is white.
+    temperature: f64,
+    /// Offset of the light that is white from the planckian locus,
+    /// in 1/3000ths of Duv. Positive tint is a greener light making
+    /// the film more magenta.
+    tint: f64,
+}
+
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            temperature: Self::REFERENCE_TEMPERATURE,
+            tint: 0.0,
+        }
+    }
+}
+
+impl WhiteBalance {
+    /// Temperature (in kelvin) of the white of the film. A white
+    /// balance of this temperature without tint does not change the
+    /// colors.
+    pub const REFERENCE_TEMPERATURE: f64 = 6500.0;
+
+    /// Get white balance's enabled.
+    pub fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Set white balance's enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Get white balance's temperature.
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Set white balance's temperature.
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = temperature;
+    }
+
+    /// Get white balance's tint.
+    pub fn get_tint(&self) -> f64 {
+        self.tint
+    }
+
+    /// Set white balance's tint.
+    pub fn set_tint(&mut self, tint: f64) {
+        self.tint = tint;
+    }
+
+    /// Get the matrix that applies the white balance to linear rgb
+    /// colors.
+    ///
+    /// Returns identity if white balance is not enabled.
+    pub fn get_matrix(&self) -> glm::DMat3 {
+        if !self.enabled {
+            return glm::identity();
+        }
+
+        let source_white = util::xy_to_xyz(
+            &util::white_point_xy(self.temperature, self.tint / 3000.0),
+            1.0,
+        );
+        let destination_white =
+            util::xy_to_xyz(&util::white_point_xy(Self::REFERENCE_TEMPERATURE, 0.0), 1.0);
+
+        util::xyz_to_linear_rgb_matrix()
+            * util::chromatic_adaptation_matrix(&source_white, &destination_white)
+            * util::linear_rgb_to_xyz_matrix()
+    }
+}
+
+impl DrawUI for WhiteBalance {
+    type ExtraData = ();
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.checkbox(&mut self.enabled, "White Balance");
+
+        ui.add(
+            egui::Slider::new(&mut self.temperature, 1667.0..=25000.0)
+                .clamp_to_range(true)
+                .text("Temperature"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.tint, -150.0..=150.0)
+                .clamp_to_range(false)
+                .text("Tint"),
+        );
+    }
+}
+
+/// How the sensor size of the camera is fit to the aspect ratio of
+/// the rendered image. The sensor size is the width of the sensor of
+/// the [`Camera`], the aspect ratio of the sensor of the [`Camera`]
+/// must be the aspect ratio of the rendered image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SensorFit {
+    /// Sensor size is fit to the larger dimension of the rendered
+    /// image.
+    Auto,
+    /// Sensor size is fit to the width of the rendered image.
+    Horizontal,
+    /// Sensor size is fit to the height of the rendered image.
+    Vertical,
+}
+
+impl Default for SensorFit {
+    fn default() -> Self {
+        Self::Horizontal
+    }
+}
+
+impl SensorFit {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use SensorFit::*;
+        [Auto, Horizontal, Vertical].iter().copied()
+    }
+}
+
+impl std::fmt::Display for SensorFit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SensorFit::Auto => write!(f, "Auto"),
+            SensorFit::Horizontal => write!(f, "Horizontal"),
+            SensorFit::Vertical => write!(f, "Vertical"),
+        }
+    }
+}
+
+impl DrawUI for SensorFit {
+    type ExtraData = egui::Id;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, id: &Self::ExtraData) {
+        egui::ComboBox::from_id_source(id.with("Sensor Fit"))
+            .selected_text(format!("{}", self))
+            .show_ui(ui, |ui| {
+                Self::all().for_each(|sensor_fit| {
+                    ui.selectable_value(self, sensor_fit, format!("{}", sensor_fit));
+                });
+            });
+    }
+}
+
+impl From<blend::camera::SensorFit> for SensorFit {
+    fn from(sensor_fit: blend::camera::SensorFit) -> Self {
+        match sensor_fit {
+            blend::camera::SensorFit::Auto => Self::Auto,
+            blend::camera::SensorFit::Horizontal => Self::Horizontal,
+            blend::camera::SensorFit::Vertical => Self::Vertical,
+        }
+    }
+}
+
+/// Camera used for path tracing, it is the [`Camera`] along with
+/// the settings that are specific to path tracing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathTraceCamera {
+    camera: Camera,
+
+    #[serde(default)]
+    projection: Projection,
+    #[serde(default)]
+    sensor_fit: SensorFit,
+    /// Lens shift as a fraction of the sensor size (or the
+    /// orthographic scale).
+    #[serde(default = "glm::zero")]
+    shift: glm::DVec2,
+    /// Rotation (in radians) of the camera about its front.
+    #[serde(default)]
+    roll: f64,
+    #[serde(default)]
+    depth_of_field: DepthOfField,
+    #[serde(default)]
+    stereo: Stereo,
+    #[serde(default)]
+    exposure: Exposure,
+    #[serde(default)]
+    white_balance: WhiteBalance,
+}
+
+impl From<Camera> for PathTraceCamera {
+    fn from(camera: Camera) -> Self {
+        Self::new(camera)
+    }
+}
+
+impl PathTraceCamera {
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            projection: Projection::default(),
+            sensor_fit: SensorFit::default(),
+            shift: glm::zero(),
+            roll: 0.0,
+            depth_of_field: DepthOfField::default(),
+            stereo: Stereo::default(),
+            exposure: Exposure::default(),
+            white_balance: WhiteBalance::default(),
+        }
+    }
+
+    /// Get a reference to the path trace camera's camera.
+    pub fn get_camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Get a mutable reference to the path trace camera's camera.
+    pub fn get_camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Get path trace camera's projection.
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Set path trace camera's projection.
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    /// Get path trace camera's sensor fit.
+    pub fn get_sensor_fit(&self) -> SensorFit {
+        self.sensor_fit
+    }
+
+    /// Set path trace camera's sensor fit.
+    pub fn set_sensor_fit(&mut self, sensor_fit: SensorFit) {
+        self.sensor_fit = sensor_fit;
+    }
+
+    /// Get a reference to the path trace camera's shift.
+    pub fn get_shift(&self) -> &glm::DVec2 {
+        &self.shift
+    }
+
+    /// Set path trace camera's shift.
+    pub fn set_shift(&mut self, shift: glm::DVec2) {
+        self.shift = shift;
+    }
+
+    /// Get path trace camera's roll.
+    pub fn get_roll(&self) -> f64 {
+        self.roll
+    }
+
+    /// Set path trace camera's roll.
+    pub fn set_roll(&mut self, roll: f64) {
+        self.roll = roll;
+    }
+
+    /// Get the right of the camera, takes roll into account.
+    pub fn get_right(&self) -> glm::DVec3 {
+        glm::rotate_vec3(
+            &self.camera.get_right(),
+            self.roll,
+            &self.camera.get_front(),
+        )
+    }
+
+    /// Get the up of the camera, takes roll into account.
+    pub fn get_up(&self) -> glm::DVec3 {
+        glm::rotate_vec3(&self.camera.get_up(), self.roll, &self.camera.get_front())
+    }
+
+    /// Get the sensor size (in mm), see [`SensorFit`].
+    pub fn get_sensor_size(&self) -> Option<f64> {
+        Some(self.camera.get_sensor()?.get_width())
+    }
+
+    /// Get the width and height (in mm) of the sensor after fitting
+    /// the sensor size to the aspect ratio of the rendered image, see
+    /// [`SensorFit`].
+    pub fn get_fitted_sensor_size(&self) -> Option<(f64, f64)> {
+        self.fit_size(self.get_sensor_size()?)
+    }
+
+    /// Get the width and height after fitting the given size to the
+    /// aspect ratio of the rendered image based on the sensor fit.
+    fn fit_size(&self, size: f64) -> Option<(f64, f64)> {
+        let sensor = self.camera.get_sensor()?;
+        let aspect_ratio = sensor.get_width() / sensor.get_height();
+        let fit_horizontal = match self.sensor_fit {
+            SensorFit::Auto => aspect_ratio >= 1.0,
+            SensorFit::Horizontal => true,
+            SensorFit::Vertical => false,
+        };
+        Some(if fit_horizontal {
+            (size, size / aspect_ratio)
+        } else {
+            (size * aspect_ratio, size)
+        })
+    }
+
+    /// Get a reference to the path trace camera's depth of field.
+    pub fn get_depth_of_field(&self) -> &DepthOfField {
+        &self.depth_of_field
+    }
+
+    /// Get a mutable reference to the path trace camera's depth of
+    /// field.
+    pub fn get_depth_of_field_mut(&mut self) -> &mut DepthOfField {
+        &mut self.depth_of_field
+    }
+
+    /// Get a reference to the path trace camera's stereo.
+    pub fn get_stereo(&self) -> &Stereo {
+        &self.stereo
+    }
+
+    /// Get a mutable reference to the path trace camera's stereo.
+    pub fn get_stereo_mut(&mut self) -> &mut Stereo {
+        &mut self.stereo
+    }
+
+    /// Get a reference to the path trace camera's exposure.
+    pub fn get_exposure(&self) -> &Exposure {
+        &self.exposure
+    }
+
+    /// Get a mutable reference to the path trace camera's exposure.
+    pub fn get_exposure_mut(&mut self) -> &mut Exposure {
+        &mut self.exposure
+    }
+
+    /// Get a reference to the path trace camera's white balance.
+    pub fn get_white_balance(&self) -> &WhiteBalance {
+        &self.white_balance
+    }
+
+    /// Get a mutable reference to the path trace camera's white balance.
+    pub fn get_white_balance_mut(&mut self) -> &mut WhiteBalance {
+        &mut self.white_balance
+    }
+
+    /// Get the matrix applied to the linear rgb radiance accumulated
+    /// on the film when the film is developed into the rendered
+    /// image, applies the exposure and the white balance.
+    pub fn get_film_development_matrix(&self) -> glm::DMat3 {
+        self.white_balance.get_matrix() * self.exposure.get_scale()
+    }
+
+    /// Get the radius of the aperture in world units, it is 0.0 if
+    /// depth of field is disabled.
+    pub fn get_aperture_radius(&self) -> f64 {
+        if !self.depth_of_field.get_enabled() || self.projection.is_panoramic() {
+            return 0.0;
+        }
+        self.camera.get_focal_length().map_or(0.0, |focal_length| {
+            self.depth_of_field.get_aperture().get_radius(focal_length)
+        })
+    }
+
+    /// Set the focus distance to the distance of the first hit of the
+    /// ray cast through the autofocus point if autofocus is enabled.
+    ///
+    /// The model matrices of the scene must be applied and the BVH
+    /// must be built before calling this.
+    pub fn autofocus(&mut self, scene: &Scene) {
+        if !self.depth_of_field.get_autofocus() {
+            return;
+        }
+
+        let ray = match self.get_ray_without_lens(self.depth_of_field.get_autofocus_point()) {
+            Some(ray) => ray,
+            None => return,
+        };
+
+        if let Some(info) = scene.hit(&ray, 0.0, scene.get_ray_t_max(&ray)) {
+            self.depth_of_field.set_focus_distance(glm::dot(
+                &(info.get_point() - ray.get_origin()),
+                &self.camera.get_front(),
+            ));
+        }
+    }
+
+    /// Get ray given the UVs on the camera sensor based on the
+    /// projection of the camera without taking the lens (depth of
+    /// field) into account.
+    ///
+    /// [`None`] is returned if the UVs are outside the region of the
+    /// sensor covered by the projection (for example outside the
+    /// circle of a fisheye projection).
+    pub fn get_ray_without_lens(&self, uv: &glm::DVec2) -> Option<Ray> {
+        let position = self.camera.get_position();
+        let front = self.camera.get_front();
+        let right = self.get_right();
+        let up = self.get_up();
+
+        // direction from spherical coordinates with respect to the
+        // front of the camera, theta is the angle from the front and
+        // phi is the angle from the right about the front
+        let direction_from_spherical = |theta: f64, phi: f64| {
+            (front * theta.cos() + (right * phi.cos() + up * phi.sin()) * theta.sin()).normalize()
+        };
+
+        match self.projection {
+            Projection::Perspective => {
+                let (sensor_width, sensor_height) = self.get_fitted_sensor_size()?;
+                let focal_length = self.camera.get_focal_length()?;
+                let sensor_size = self.get_sensor_size()?;
+
+                // position on the sensor in mm, the sensor is placed
+                // in front of the camera at the focal length
+                let x = uv[0] * sensor_width * 0.5 + self.shift[0] * sensor_size;
+                let y = uv[1] * sensor_height * 0.5 + self.shift[1] * sensor_size;
+                Some(Ray::new(
+                    position,
+                    (front * focal_length + right * x + up * y).normalize(),
+                ))
+            }
+            Projection::Orthographic { ortho_scale } => {
+                let (width, height) = self.fit_size(ortho_scale)?;
+                let x = uv[0] * width * 0.5 + self.shift[0] * ortho_scale;
+                let y = uv[1] * height * 0.5 + self.shift[1] * ortho_scale;
+                Some(Ray::new(position + right * x + up * y, front))
+            }
+            Projection::Equirectangular => {
+                let longitude = uv[0] * std::f64::consts::PI;
+                let latitude = uv[1] * std::f64::consts::FRAC_PI_2;
+                Some(Ray::new(
+                    position,
+                    (front * latitude.cos() * longitude.cos()
+                        + right * latitude.cos() * longitude.sin()
+                        + up * latitude.sin())
+                    .normalize(),
+                ))
+            }
+            Projection::FisheyeEquidistant { fov } => {
+                let (sensor_width, sensor_height) = self.get_fitted_sensor_size()?;
+                // the circle of the fisheye spans the sensor width
+                let x = uv[0];
+                let y = uv[1] * sensor_height / sensor_width;
+                let r = (x * x + y * y).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+                Some(Ray::new(
+                    position,
+                    direction_from_spherical(r * fov * 0.5, y.atan2(x)),
+                ))
+            }
+            Projection::FisheyeEquisolid { lens, fov } => {
+                let (sensor_width, sensor_height) = self.get_fitted_sensor_size()?;
+                // position on the sensor in mm
+                let x = uv[0] * sensor_width * 0.5;
+                let y = uv[1] * sensor_height * 0.5;
+                let r = (x * x + y * y).sqrt();
+                let sin_half_theta = r / (2.0 * lens);
+                if sin_half_theta > 1.0 {
+                    return None;
+                }
+                let theta = 2.0 * sin_half_theta.asin();
+                if theta > fov * 0.5 {
+                    return None;
+                }
+                Some(Ray::new(
+                    position,
+                    direction_from_spherical(theta, y.atan2(x)),
+                ))
+            }
+        }
+    }
+
+    /// Get the UVs on the camera sensor that the given ray (starting
+    /// from the camera) passes through, inverse of
+    /// [`PathTraceCamera::get_ray_without_lens()`]. The lens (depth
+    /// of field) and the stereo offset of the eyes are not taken
+    /// into account.
+    ///
+    /// [`None`] is returned if the ray does not pass through the
+    /// sensor.
+    pub fn get_sensor_uv(&self, ray: &Ray) -> Option<glm::DVec2> {
+        let front = self.camera.get_front();
+        let right = self.get_right();
+        let up = self.get_up();
+
+        let direction = ray.get_direction().normalize();
+        let x = glm::dot(&direction, &right);
+        let y = glm::dot(&direction, &up);
+        let z = glm::dot(&direction, &front);
+
+        // angle from the front and the angle from the right about the
+        // front, see `direction_from_spherical` in
+        // `get_ray_without_lens()`
+        let theta = z.clamp(-1.0, 1.0).acos();
+        let phi = y.atan2(x);
+
+        match self.projection {
+            Projection::Perspective => {
+                if z <= 0.0 {
+                    return None;
+                }
+                let (sensor_width, sensor_height) = self.get_fitted_sensor_size()?;
+                let focal_length = self.camera.get_focal_length()?;
+                let sensor_size = self.get_sensor_size()?;
+
+                let x = x / z * focal_length - self.shift[0] * sensor_size;
+                let y = y / z * focal_length - self.shift[1] * sensor_size;
+                Some(glm::vec2(
+                    x / (sensor_width * 0.5),
+                    y / (sensor_height * 0.5),
+                ))
+            }
+            Projection::Orthographic { ortho_scale } => {
+                let (width, height) = self.fit_size(ortho_scale)?;
+                let offset = ray.get_origin() - self.camera.get_position();
+                let x = glm::dot(&offset, &right) - self.shift[0] * ortho_scale;
+                let y = glm::dot(&offset, &up) - self.shift[1] * ortho_scale;
+                Some(glm::vec2(x / (width * 0.5), y / (height * 0.5)))
+            }
+            Projection::Equirectangular => {
+                let longitude = x.atan2(z);
+                let latitude = y.clamp(-1.0, 1.0).asin();
+                Some(glm::vec2(
+                    longitude / std::f64::consts::PI,
+                    latitude / std::f64::consts::FRAC_PI_2,
+                ))
+            }
+            Projection::FisheyeEquidistant { fov } => {
+                let (sensor_width, sensor_height) = self.get_fitted_sensor_size()?;
+                let r = theta / (fov * 0.5);
+                if r > 1.0 {
+                    return None;
+                }
+                Some(glm::vec2(
+                    r * phi.cos(),
+                    r * phi.sin() * sensor_width / sensor_height,
+                ))
+            }
+            Projection::FisheyeEquisolid { lens, fov } => {
+                if theta > fov * 0.5 {
+                    return None;
+                }
+                let (sensor_width, sensor_height) = self.get_fitted_sensor_size()?;
+                let r = 2.0 * lens * (theta * 0.5).sin();
+                Some(glm::vec2(
+                    r * phi.cos() / (sensor_width * 0.5),
+                    r * phi.sin() / (sensor_height * 0.5),
+                ))
+            }
+        }
+    }
+
+    /// Get ray for the given eye given the UVs on the view of the
+    /// eye. If `eye` is [`None`], the ray from the center of the
+    /// camera is returned.
+    ///
+    /// The ray is generated based on the projection of the camera, see
+    /// [`PathTraceCamera::get_ray_without_lens()`]. It is then
+    /// offset for the eye, see [`Stereo`]. If depth of field is
+    /// enabled, the origin of the ray is sampled on the aperture and
+    /// the ray passes through the point on the focus plane that the
+    /// ray without the lens passes through.
+    pub fn get_eye_ray(&self, uv: &glm::DVec2, eye: Option<StereoEye>) -> Option<Ray> {
+        let ray = self.get_ray_without_lens(uv)?;
+        let ray = match eye {
+            Some(eye) => self.offset_ray_for_eye(&ray, eye),
+            None => ray,
+        };
+
+        let aperture_radius = self.get_aperture_radius();
+        if aperture_radius <= 0.0 {
+            return Some(ray);
+        }
+
+        let direction = ray.get_direction().normalize();
+        let front = self.camera.get_front();
+        let cos_theta = glm::dot(&direction, &front);
+        if cos_theta <= 0.0 {
+            return Some(ray);
+        }
+
+        let focus_point =
+            ray.get_origin() + direction * (self.depth_of_field.get_focus_distance() / cos_theta);
+
+        let lens_point = math::random_in_regular_polygon(
+            self.depth_of_field.get_blades(),
+            self.depth_of_field.get_rotation(),
+        ) * aperture_radius;
+        let origin =
+            ray.get_origin() + self.get_right() * lens_point[0] + self.get_up() * lens_point[1];
+
+        Some(Ray::new(origin, (focus_point - origin).normalize()))
+    }
+
+    /// Get the differential of the ray for the given eye given the
+    /// UVs on the view of the eye, the rays are offset by the given
+    /// size of a pixel (in UVs) along x and y, see
+    /// [`PathTraceCamera::get_eye_ray()`].
+    ///
+    /// The lens is not taken into account, the differential rays
+    /// start from the center of the camera (or eye).
+    pub fn get_eye_ray_differential(
+        &self,
+        uv: &glm::DVec2,
+        pixel_size: &glm::DVec2,
+        eye: Option<StereoEye>,
+    ) -> Option<RayDifferential> {
+        let get_ray = |uv: &glm::DVec2| {
+            let ray = self.get_ray_without_lens(uv)?;
+            Some(match eye {
+                Some(eye) => self.offset_ray_for_eye(&ray, eye),
+                None => ray,
+            })
+        };
+        let rx = get_ray(&glm::vec2(uv[0] + pixel_size[0], uv[1]))?;
+        let ry = get_ray(&glm::vec2(uv[0], uv[1] + pixel_size[1]))?;
+        Some(RayDifferential::new(
+            *rx.get_origin(),
+            *rx.get_direction(),
+            *ry.get_origin(),
+            *ry.get_direction(),
+        ))
+    }
+
+    /// Offset the given ray (from the center of the camera) for the
+    /// given eye based on the stereo settings.
+    fn offset_ray_for_eye(&self, ray: &Ray, eye: StereoEye) -> Ray {
+        let half_interocular_distance = self.stereo.get_interocular_distance() * 0.5;
+        let sign = match eye {
+            StereoEye::Left => -1.0,
+            StereoEye::Right => 1.0,
+        };
+        let convergence_distance = self.stereo.get_convergence_distance();
+
+        let direction = ray.get_direction().normalize();
+        let front = self.camera.get_front();
+        let up = self.get_up();
+
+        let right = if self.projection.is_panoramic() {
+            // omni-directional stereo, the eyes are offset
+            // perpendicular to the horizontal direction of the ray
+            let horizontal = direction - up * glm::dot(&direction, &up);
+            if glm::length2(&horizontal) < 1e-12 {
+                // ray towards the poles, no offset possible
+                return *ray;
+            }
+            glm::cross(&horizontal, &up).normalize()
+        } else {
+            self.get_right()
+        };
+
+        let origin = ray.get_origin() + right * (sign * half_interocular_distance);
+
+        if convergence_distance <= 0.0 {
+            return Ray::new(origin, direction);
+        }
+
+        // direction towards the point that the ray from the center
+        // of the camera passes through at the convergence distance
+        let converged_direction = |distance: f64| {
+            let convergence_point = ray.get_origin() + direction * distance;
+            (convergence_point - origin).normalize()
+        };
+
+        let direction = match self.stereo.get_convergence() {
+            StereoConvergence::Parallel => direction,
+            StereoConvergence::OffAxis | StereoConvergence::ToeIn
+                if self.projection.is_panoramic() =>
+            {
+                converged_direction(convergence_distance)
+            }
+            StereoConvergence::OffAxis => {
+                let cos_theta = glm::dot(&direction, &front);
+                if cos_theta > 0.0 {
+                    converged_direction(convergence_distance / cos_theta)
+                } else {
+                    direction
+                }
+            }
+            StereoConvergence::ToeIn => {
+                // left eye is rotated towards the right and the right
+                // eye towards the left
+                let angle = (half_interocular_distance / convergence_distance).atan();
+                glm::rotate_vec3(&direction, sign * angle, &up)
+            }
+        };
+
+        Ray::new(origin, direction)
+    }
+}
+
+impl CameraExtension for PathTraceCamera {
+    /// Get ray given the UVs on the camera sensor.
+    ///
+    /// See [`PathTraceCamera::get_eye_ray()`] for more details, the
+    /// ray is from the center of the camera.
+    fn get_ray(&self, uv: &glm::DVec2) -> Option<Ray> {
+        self.get_eye_ray(uv, None)
+    }
+
+    /// Create new camera from camera data available in the Camera
+    /// Object of a Blend file
+    ///
+    /// Supports camera roll, lens shift, sensor fit, clipping,
+    /// projections and depth of field. The aspect ratio of the sensor
+    /// of the camera must be set to the aspect ratio of the rendered
+    /// image, see [`blend::scene::RenderData::get_aspect_ratio()`].
+    ///
+    /// If the given object is not a camera, [`None`] is returned.
+    fn from_blend(camera: &blend::object::Object) -> Option<Self> {
+        let mut res = Self::new(Camera::from_blend(camera)?);
+
+        let camera_data = match camera.get_data()? {
+            blend::id::IDObject::Camera(data) => data,
+            _ => return None,
+        };
+
+        res.set_sensor_fit(camera_data.get_sensor_fit().into());
+        res.set_shift(glm::vec2(
+            camera_data.get_shiftx().into(),
+            camera_data.get_shifty().into(),
+        ));
+
+        // roll is the angle from the up of the camera (without roll)
+        // to the up of the Blender camera about the front
+        {
+            let (_position, _front, blend_up) = get_blend_camera_frame(camera);
+            let front = res.get_camera().get_front();
+            let up = res.get_camera().get_up();
+            res.set_roll(
+                glm::dot(&glm::cross(&up, &blend_up), &front).atan2(glm::dot(&up, &blend_up)),
+            );
+        }
+
+        match camera_data.get_camera_type() {
+            blend::camera::Type::Perspective => {}
+            blend::camera::Type::Orthographic => res.set_projection(Projection::Orthographic {
+                ortho_scale: camera_data.get_ortho_scale().into(),
+            }),
+            blend::camera::Type::Panoramic => {
+                res.set_projection(match camera_data.get_panorama_type() {
+                    blend::camera::PanoramaType::Equirectangular => Projection::Equirectangular,
+                    blend::camera::PanoramaType::FisheyeEquidistant => {
+                        Projection::FisheyeEquidistant {
+                            fov: camera_data.get_fisheye_fov().into(),
+                        }
+                    }
+                    blend::camera::PanoramaType::FisheyeEquisolid => Projection::FisheyeEquisolid {
+                        lens: camera_data.get_fisheye_lens().into(),
+                        fov: camera_data.get_fisheye_fov().into(),
+                    },
+                    panorama_type => {
+                        eprintln!(
+                            "{:?} panorama type not supported, using equirectangular",
+                            panorama_type
+                        );
+                        Projection::Equirectangular
+                    }
+                })
+            }
+        }
+
+        if let Some(dof) = camera_data.get_dof() {
+            let depth_of_field = res.get_depth_of_field_mut();
+            depth_of_field.set_enabled(dof.get_enabled());
+            depth_of_field.set_aperture(Aperture::FStop(dof.get_aperture_fstop().into()));
+            depth_of_field.set_blades(dof.get_aperture_blades().max(0) as _);
+            depth_of_field.set_rotation(dof.get_aperture_rotation().into());
+            depth_of_field.set_focus_distance(dof.get_focus_distance().into());
+
+            // focus object overrides the focus distance, the distance
+            // is along the camera's front
+            if let Some(focus_object_location) = dof.get_focus_object_location() {
+                let focus_object_location = util::vec3_apply_model_matrix(
+                    &glm::convert(glm::make_vec3(focus_object_location)),
+                    &util::axis_conversion_matrix_from_blender(),
+                );
+                let focus_distance = glm::dot(
+                    &(focus_object_location - res.get_camera().get_position()),
+                    &res.get_camera().get_front(),
+                );
+                res.get_depth_of_field_mut()
+                    .set_focus_distance(focus_distance);
+            }
+        }
+
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        blend, glm,
+        object::{objects::Sphere, Object},
+        path_trace::{intersectable::Intersectable, ray::Ray},
+        scene::Scene,
+        sphere::Sphere as SphereData,
+        util,
+    };
+
+    use super::{
+        Aperture, Camera, CameraExtension, Exposure, PathTraceCamera, Projection, Sensor,
+        SensorFit, Stereo, StereoConvergence, StereoEye, StereoLayout,
+    };
+
+    fn assert_vec3_eq(a: &glm::DVec3, b: &glm::DVec3) {
+        assert!(glm::distance(a, b) < 1e-4, "{} != {}", a, b);
+    }
+
+    /// Camera at (0.0, 0.0, 3.0) looking along -Z with a 36mm x 36mm
+    /// sensor and a 50mm lens.
+    fn get_camera() -> PathTraceCamera {
+        let mut camera = Camera::new(
+            glm::vec3(0.0, 0.0, 3.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            270.0,
+            0.0,
+            45.0,
+            Some(Sensor::new(36.0, 36.0)),
+        );
+        camera.set_focal_length(50.0);
+        PathTraceCamera::new(camera)
+    }
+
+    /// Compare the camera imported from the reference blend files with
+    /// the final worldspace matrix and the camera settings stored in
+    /// the blend files.
+    #[test]
+    fn path_trace_camera_from_blend_test_01() {
+        let cargo_manifest_dir = std::path::PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR").expect("could not find cargo manifest dir"),
+        );
+
+        ["blend_test_01.blend", "blend_test_02.blend"]
+            .iter()
+            .for_each(|file_name| {
+                let blend_path = cargo_manifest_dir.join("tests").join(file_name);
+
+                let render_data_resolution = blend::get_all_scenes_from_path(&blend_path)
+                    .unwrap()
+                    .first()
+                    .unwrap()
+                    .get_render_data()
+                    .get_resolution();
+                assert_eq!(render_data_resolution, (1920, 1080));
+                let aspect_ratio = 1920.0 / 1080.0;
+
+                let objects = blend::get_all_objects_from_path(&blend_path).unwrap();
+                let blend_camera = objects
+                    .iter()
+                    .find(|object| {
+                        matches!(object.get_data(), Some(blend::id::IDObject::Camera(_)))
+                    })
+                    .unwrap();
+
+                let mut camera = PathTraceCamera::from_blend(blend_camera).unwrap();
+                camera
+                    .get_camera_mut()
+                    .get_sensor_mut()
+                    .as_mut()
+                    .unwrap()
+                    .change_aspect_ratio(aspect_ratio);
+
+                // expected frame of the camera from the worldspace
+                // matrix, Blender cameras look along -Z with Y as up
+                let obmat = util::axis_conversion_matrix_from_blender()
+                    * glm::convert::<_, glm::DMat4>(glm::make_mat4(blend_camera.get_obmat()));
+                let rotation = glm::mat4_to_mat3(&obmat);
+                let expected_position = util::vec3_apply_model_matrix(&glm::zero(), &obmat);
+                let expected_right = (rotation * glm::vec3(1.0, 0.0, 0.0)).normalize();
+                let expected_up = (rotation * glm::vec3(0.0, 1.0, 0.0)).normalize();
+                let expected_front = (rotation * glm::vec3(0.0, 0.0, -1.0)).normalize();
+
+                assert_vec3_eq(&camera.get_camera().get_position(), &expected_position);
+                assert_vec3_eq(&camera.get_camera().get_front(), &expected_front);
+                assert_vec3_eq(&camera.get_right(), &expected_right);
+                assert_vec3_eq(&camera.get_up(), &expected_up);
+
+                assert_eq!(camera.get_projection(), Projection::Perspective);
+                assert_eq!(camera.get_sensor_fit(), SensorFit::Auto);
+                assert!(camera.get_roll().abs() < 1e-4);
+                assert_eq!(camera.get_shift(), &glm::zero::<glm::DVec2>());
+                assert!((camera.get_camera().get_near_plane() - 0.1).abs() < 1e-6);
+                assert!((camera.get_camera().get_far_plane() - 100.0).abs() < 1e-6);
+                assert!((camera.get_camera().get_focal_length().unwrap() - 50.0).abs() < 1e-6);
+
+                // sensor size of 36mm is fit to the width since the
+                // rendered image is wider than it is tall
+                let (sensor_width, sensor_height) = camera.get_fitted_sensor_size().unwrap();
+                assert!((sensor_width - 36.0).abs() < 1e-6);
+                assert!((sensor_height - 36.0 / aspect_ratio).abs() < 1e-6);
+
+                // ray through the top right corner of the sensor
+                let ray = camera.get_ray(&glm::vec2(1.0, 1.0)).unwrap();
+                assert_vec3_eq(ray.get_origin(), &expected_position);
+                assert_vec3_eq(
+                    &ray.get_direction().normalize(),
+                    &(expected_front * 50.0
+                        + expected_right * 18.0
+                        + expected_up * (18.0 / aspect_ratio))
+                        .normalize(),
+                );
+            });
+    }
+
+    /// Rays of the camera imported from the reference blend file with
+    /// lens shift, roll and an explicit sensor fit.
+    #[test]
+    fn path_trace_camera_from_blend_test_02() {
+        let cargo_manifest_dir = std::path::PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR").expect("could not find cargo manifest dir"),
+        );
+        let blend_path = cargo_manifest_dir.join("tests").join("blend_test_01.blend");
+        let objects = blend::get_all_objects_from_path(&blend_path).unwrap();
+        let blend_camera = objects
+            .iter()
+            .find(|object| matches!(object.get_data(), Some(blend::id::IDObject::Camera(_))))
+            .unwrap();
+
+        let aspect_ratio = 1920.0 / 1080.0;
+        let mut camera = PathTraceCamera::from_blend(blend_camera).unwrap();
+        camera
+            .get_camera_mut()
+            .get_sensor_mut()
+            .as_mut()
+            .unwrap()
+            .change_aspect_ratio(aspect_ratio);
+
+        let position = camera.get_camera().get_position();
+        let front = camera.get_camera().get_front();
+        let right = camera.get_right();
+        let up = camera.get_up();
+
+        // roll of 90 degrees, the right of the sensor points down
+        camera.set_roll(90.0_f64.to_radians());
+        camera.set_sensor_fit(SensorFit::Horizontal);
+        assert_vec3_eq(&camera.get_right(), &-up);
+        assert_vec3_eq(&camera.get_up(), &right);
+        let ray = camera.get_ray(&glm::vec2(1.0, 0.0)).unwrap();
+        assert_vec3_eq(ray.get_origin(), &position);
+        assert_vec3_eq(
+            &ray.get_direction().normalize(),
+            &(front * 50.0 - up * 18.0).normalize(),
+        );
+
+        // roll rotates the sensor about the front of the camera
+        let roll = 30.0_f64.to_radians();
+        let rolled_right = right * roll.cos() - up * roll.sin();
+        let rolled_up = up * roll.cos() + right * roll.sin();
+        camera.set_roll(roll);
+        camera.set_shift(glm::vec2(0.1, -0.05));
+        assert_vec3_eq(&camera.get_right(), &rolled_right);
+        assert_vec3_eq(&camera.get_up(), &rolled_up);
+
+        [
+            (SensorFit::Horizontal, (36.0, 36.0 / aspect_ratio)),
+            (SensorFit::Vertical, (36.0 * aspect_ratio, 36.0)),
+        ]
+        .iter()
+        .for_each(|(sensor_fit, (expected_width, expected_height))| {
+            camera.set_sensor_fit(*sensor_fit);
+            assert_eq!(camera.get_sensor_fit(), *sensor_fit);
+
+            let (sensor_width, sensor_height) = camera.get_fitted_sensor_size().unwrap();
+            assert!((sensor_width - expected_width).abs() < 1e-6);
+            assert!((sensor_height - expected_height).abs() < 1e-6);
+
+            [
+                glm::vec2(0.0, 0.0),
+                glm::vec2(1.0, 1.0),
+                glm::vec2(-0.5, 0.25),
+            ]
+            .iter()
+            .for_each(|uv| {
+                // shift is a fraction of the sensor size (36mm)
+                let x = uv[0] * expected_width * 0.5 + 0.1 * 36.0;
+                let y = uv[1] * expected_height * 0.5 - 0.05 * 36.0;
+                let ray = camera.get_ray(uv).unwrap();
+                assert_vec3_eq(ray.get_origin(), &position);
+                assert_vec3_eq(
+                    &ray.get_direction().normalize(),
+                    &(front * 50.0 + rolled_right * x + rolled_up * y).normalize(),
+                );
+            });
+        });
+    }
+
+    /// Compare the camera imported from the reference blend files with
+    /// lens shift, roll and each sensor fit with the settings stored
+    /// in the blend files.
+    #[test]
+    fn path_trace_camera_from_blend_test_03() {
+        let cargo_manifest_dir = std::path::PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR").expect("could not find cargo manifest dir"),
+        );
+        let aspect_ratio = 1920.0 / 1080.0;
+
+        // file name, sensor fit, sensor size, lens shift and roll (in
+        // degrees), the sensor is 36mm x 24mm
+        [
+            (
+                "blend_test_03.blend",
+                SensorFit::Horizontal,
+                36.0,
+                glm::vec2(0.1, -0.05),
+                30.0_f64,
+            ),
+            (
+                "blend_test_04.blend",
+                SensorFit::Vertical,
+                24.0,
+                glm::vec2(-0.2, 0.15),
+                -45.0,
+            ),
+            (
+                "blend_test_05.blend",
+                SensorFit::Auto,
+                36.0,
+                glm::vec2(0.0, 0.25),
+                90.0,
+            ),
+        ]
+        .iter()
+        .for_each(|(file_name, sensor_fit, sensor_size, shift, roll)| {
+            let blend_path = cargo_manifest_dir.join("tests").join(file_name);
+            let objects = blend::get_all_objects_from_path(&blend_path).unwrap();
+            let blend_camera = objects
+                .iter()
+                .find(|object| matches!(object.get_data(), Some(blend::id::IDObject::Camera(_))))
+                .unwrap();
+
+            let mut camera = PathTraceCamera::from_blend(blend_camera).unwrap();
+            camera
+                .get_camera_mut()
+                .get_sensor_mut()
+                .as_mut()
+                .unwrap()
+                .change_aspect_ratio(aspect_ratio);
+
+            assert_eq!(camera.get_sensor_fit(), *sensor_fit);
+            assert!(glm::distance(camera.get_shift(), shift) < 1e-6);
+            assert!((camera.get_roll() - roll.to_radians()).abs() < 1e-4);
+            assert!((camera.get_sensor_size().unwrap() - sensor_size).abs() < 1e-6);
+
+            // horizontal (and auto, the rendered image is wider than
+            // it is tall) fits the sensor size to the width, vertical
+            // to the height
+            let (expected_width, expected_height) = match sensor_fit {
+                SensorFit::Auto | SensorFit::Horizontal => {
+                    (*sensor_size, sensor_size / aspect_ratio)
+                }
+                SensorFit::Vertical => (sensor_size * aspect_ratio, *sensor_size),
+            };
+            let (sensor_width, sensor_height) = camera.get_fitted_sensor_size().unwrap();
+            assert!((sensor_width - expected_width).abs() < 1e-6);
+            assert!((sensor_height - expected_height).abs() < 1e-6);
+
+            // expected frame (with the roll) of the camera from the
+            // worldspace matrix
+            let obmat = util::axis_conversion_matrix_from_blender()
+                * glm::convert::<_, glm::DMat4>(glm::make_mat4(blend_camera.get_obmat()));
+            let rotation = glm::mat4_to_mat3(&obmat);
+            let expected_position = util::vec3_apply_model_matrix(&glm::zero(), &obmat);
+            let expected_right = (rotation * glm::vec3(1.0, 0.0, 0.0)).normalize();
+            let expected_up = (rotation * glm::vec3(0.0, 1.0, 0.0)).normalize();
+            let expected_front = (rotation * glm::vec3(0.0, 0.0, -1.0)).normalize();
+            assert_vec3_eq(&camera.get_camera().get_front(), &expected_front);
+            assert_vec3_eq(&camera.get_right(), &expected_right);
+            assert_vec3_eq(&camera.get_up(), &expected_up);
+
+            [
+                glm::vec2(0.0, 0.0),
+                glm::vec2(1.0, 1.0),
+                glm::vec2(-0.5, 0.25),
+            ]
+            .iter()
+            .for_each(|uv| {
+                // shift is a fraction of the sensor size
+                let x = uv[0] * expected_width * 0.5 + shift[0] * sensor_size;
+                let y = uv[1] * expected_height * 0.5 + shift[1] * sensor_size;
+                let ray = camera.get_ray(uv).unwrap();
+                assert_vec3_eq(ray.get_origin(), &expected_position);
+                assert_vec3_eq(
+                    &ray.get_direction().normalize(),
+                    &(expected_front * 50.0 + expected_right * x + expected_up * y).normalize(),
+                );
+            });
+        });
+    }
+
+    /// The sensor UVs of the rays generated for the sensor UVs must
+    /// be the same UVs for all the projections.
+    #[test]
+    fn path_trace_camera_sensor_uv_test_01() {
+        let cargo_manifest_dir = std::path::PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR").expect("could not find cargo manifest dir"),
+        );
+        let blend_path = cargo_manifest_dir.join("tests").join("blend_test_01.blend");
+        let objects = blend::get_all_objects_from_path(&blend_path).unwrap();
+        let blend_camera = objects
+            .iter()
+            .find(|object| matches!(object.get_data(), Some(blend::id::IDObject::Camera(_))))
+            .unwrap();
+
+        let mut camera = PathTraceCamera::from_blend(blend_camera).unwrap();
+        camera.set_shift(glm::vec2(0.1, -0.05));
+        camera.set_roll(0.3);
+
+        [
+            Projection::Perspective,
+            Projection::Orthographic { ortho_scale: 6.0 },
+            Projection::Equirectangular,
+            Projection::FisheyeEquidistant {
+                fov: std::f64::consts::PI,
+            },
+            Projection::FisheyeEquisolid {
+                lens: 10.5,
+                fov: std::f64::consts::PI,
+            },
+        ]
+        .iter()
+        .for_each(|projection| {
+            camera.set_projection(*projection);
+            [
+                glm::vec2(0.0, 0.0),
+                glm::vec2(0.5, -0.25),
+                glm::vec2(-0.3, 0.2),
+            ]
+            .iter()
+            .for_each(|uv| {
+                let ray = camera.get_ray_without_lens(uv).unwrap();
+                let sensor_uv = camera.get_sensor_uv(&ray).unwrap();
+                assert!(
+                    glm::distance(uv, &sensor_uv) < 1e-6,
+                    "{:?}: {} != {}",
+                    projection,
+                    uv,
+                    sensor_uv
+                );
+            });
+        });
+    }
+
+    /// Orthographic rays are parallel to the front of the camera and
+    /// start on the view plane of size `ortho_scale`.
+    #[test]
+    fn path_trace_camera_orthographic_test_01() {
+        let mut camera = get_camera();
+        camera.set_projection(Projection::Orthographic { ortho_scale: 6.0 });
+        let position = camera.get_camera().get_position();
+        let front = camera.get_camera().get_front();
+        let right = camera.get_right();
+        let up = camera.get_up();
+
+        [
+            glm::vec2(0.0, 0.0),
+            glm::vec2(1.0, 1.0),
+            glm::vec2(-0.5, 0.25),
+        ]
+        .iter()
+        .for_each(|uv| {
+            let ray = camera.get_ray(uv).unwrap();
+            assert_vec3_eq(&ray.get_direction().normalize(), &front);
+            assert_vec3_eq(
+                ray.get_origin(),
+                &(position + right * (uv[0] * 3.0) + up * (uv[1] * 3.0)),
+            );
+        });
+    }
+
+    /// The fisheye projections cover only the circle (of the field of
+    /// view) on the sensor.
+    #[test]
+    fn path_trace_camera_fisheye_test_01() {
+        let mut camera = get_camera();
+        let front = camera.get_camera().get_front();
+        let right = camera.get_right();
+        let up = camera.get_up();
+
+        // the circle spans the sensor width, the angle from the front
+        // is proportional to the distance from the center
+        camera.set_projection(Projection::FisheyeEquidistant {
+            fov: std::f64::consts::PI,
+        });
+        assert_vec3_eq(
+            camera
+                .get_ray(&glm::vec2(0.0, 0.0))
+                .unwrap()
+                .get_direction(),
+            &front,
+        );
+        assert_vec3_eq(
+            camera
+                .get_ray(&glm::vec2(1.0, 0.0))
+                .unwrap()
+                .get_direction(),
+            &right,
+        );
+        assert_vec3_eq(
+            camera
+                .get_ray(&glm::vec2(0.0, -0.5))
+                .unwrap()
+                .get_direction(),
+            &(front + -up).normalize(),
+        );
+        assert!(camera.get_ray(&glm::vec2(0.7, 0.7)).is_some());
+        assert!(camera.get_ray(&glm::vec2(0.8, 0.8)).is_none());
+        assert!(camera.get_ray(&glm::vec2(1.0, 1.0)).is_none());
+
+        // r = 2 * lens * sin(theta / 2), cut off at half the field of
+        // view
+        camera.set_projection(Projection::FisheyeEquisolid {
+            lens: 10.5,
+            fov: std::f64::consts::PI,
+        });
+        let theta = 2.0 * (9.0_f64 / 21.0).asin();
+        assert_vec3_eq(
+            camera
+                .get_ray(&glm::vec2(0.5, 0.0))
+                .unwrap()
+                .get_direction(),
+            &(front * theta.cos() + right * theta.sin()),
+        );
+        assert!(camera.get_ray(&glm::vec2(0.0, 0.82)).is_some());
+        assert!(camera.get_ray(&glm::vec2(0.0, 0.85)).is_none());
+        assert!(camera.get_ray(&glm::vec2(1.0, 1.0)).is_none());
+    }
+
+    /// The sensor width covers 360° of longitude and the sensor height
+    /// covers 180° of latitude about the front of the camera.
+    #[test]
+    fn path_trace_camera_equirectangular_test_01() {
+        let mut camera = get_camera();
+        camera.set_projection(Projection::Equirectangular);
+        let position = camera.get_camera().get_position();
+        let front = camera.get_camera().get_front();
+        let right = camera.get_right();
+        let up = camera.get_up();
+
+        [
+            (glm::vec2(0.0, 0.0), front),
+            (glm::vec2(0.5, 0.0), right),
+            (glm::vec2(-0.5, 0.0), -right),
+            (glm::vec2(1.0, 0.0), -front),
+            (glm::vec2(0.0, 1.0), up),
+            (glm::vec2(0.0, -1.0), -up),
+            (
+                glm::vec2(0.25, 0.5),
+                (front + right + up * 2.0_f64.sqrt()).normalize(),
+            ),
+        ]
+        .iter()
+        .for_each(|(uv, direction)| {
+            let ray = camera.get_ray(uv).unwrap();
+            assert_vec3_eq(ray.get_origin(), &position);
+            assert_vec3_eq(ray.get_direction(), direction);
+        });
+    }
+
+    /// Rays through the lens for the same sensor UVs meet at the point
+    /// on the focus plane that the ray without the lens passes
+    /// through, so objects at the focus distance are in focus.
+    #[test]
+    fn path_trace_camera_depth_of_field_test_01() {
+        let mut camera = get_camera();
+        let depth_of_field = camera.get_depth_of_field_mut();
+        depth_of_field.set_enabled(true);
+        depth_of_field.set_aperture(Aperture::Radius(0.1));
+        depth_of_field.set_focus_distance(5.0);
+        depth_of_field.set_blades(6);
+        let position = camera.get_camera().get_position();
+        let front = camera.get_camera().get_front();
+
+        [
+            glm::vec2(0.0, 0.0),
+            glm::vec2(1.0, 1.0),
+            glm::vec2(-0.5, 0.25),
+        ]
+        .iter()
+        .for_each(|uv| {
+            let pinhole_ray = camera.get_ray_without_lens(uv).unwrap();
+            let focus_point = pinhole_ray.at(5.0 / glm::dot(pinhole_ray.get_direction(), &front));
+
+            let rays = (0..64)
+                .map(|_| camera.get_ray(uv).unwrap())
+                .collect::<Vec<_>>();
+            rays.iter().for_each(|ray| {
+                // origin on the aperture
+                let offset = ray.get_origin() - position;
+                assert!(glm::dot(&offset, &front).abs() < 1e-12);
+                assert!(glm::length(&offset) <= 0.1 + 1e-12);
+
+                let t = glm::dot(&(focus_point - ray.get_origin()), &front)
+                    / glm::dot(ray.get_direction(), &front);
+                assert!(glm::distance(&ray.at(t), &focus_point) < 1e-9);
+            });
+            // the lens is sampled
+            assert!(rays
+                .iter()
+                .any(|ray| glm::distance(ray.get_origin(), &position) > 1e-3));
+
+            // the focus point is seen at the same sensor UVs
+            let sensor_uv = camera
+                .get_sensor_uv(&Ray::new(position, (focus_point - position).normalize()))
+                .unwrap();
+            assert!(glm::distance(uv, &sensor_uv) < 1e-9);
+        });
+    }
+
+    /// Without an aperture (or with depth of field disabled) the rays
+    /// are the rays of the pinhole camera.
+    #[test]
+    fn path_trace_camera_depth_of_field_test_02() {
+        let mut camera = get_camera();
+        camera.get_depth_of_field_mut().set_focus_distance(5.0);
+
+        [
+            (false, Aperture::Radius(0.1)),
+            (true, Aperture::Radius(0.0)),
+            (true, Aperture::FStop(0.0)),
+        ]
+        .iter()
+        .for_each(|(enabled, aperture)| {
+            let depth_of_field = camera.get_depth_of_field_mut();
+            depth_of_field.set_enabled(*enabled);
+            depth_of_field.set_aperture(*aperture);
+            assert_eq!(camera.get_aperture_radius(), 0.0);
+
+            [
+                glm::vec2(0.0, 0.0),
+                glm::vec2(1.0, 1.0),
+                glm::vec2(-0.5, 0.25),
+            ]
+            .iter()
+            .for_each(|uv| {
+                let pinhole_ray = camera.get_ray_without_lens(uv).unwrap();
+                let ray = camera.get_ray(uv).unwrap();
+                assert_eq!(ray.get_origin(), pinhole_ray.get_origin());
+                assert_eq!(ray.get_direction(), pinhole_ray.get_direction());
+            });
+        });
+    }
+
+    /// Autofocus sets the focus distance to the distance (along the
+    /// front of the camera) of the hit through the autofocus point.
+    #[test]
+    fn path_trace_camera_autofocus_test_01() {
+        let mut sphere = Sphere::new(
+            SphereData::new(glm::vec3(0.0, 0.0, -2.0), 0.5),
+            glm::vec4(0.0, 0.0, 0.0, 1.0),
+            glm::vec4(0.0, 0.0, 0.0, 1.0),
+        );
+        sphere.set_model_matrix(glm::identity());
+        let mut scene = Scene::new();
+        scene.add_object(Box::new(sphere));
+        scene.apply_model_matrices();
+        scene.build_bvh(0.01);
+
+        let mut camera = get_camera();
+        camera.get_depth_of_field_mut().set_enabled(true);
+        camera.get_depth_of_field_mut().set_focus_distance(10.0);
+
+        // disabled, the focus distance is unchanged
+        camera.autofocus(&scene);
+        assert_eq!(camera.get_depth_of_field().get_focus_distance(), 10.0);
+
+        // the front of the sphere is at 4.5 from the camera
+        camera.get_depth_of_field_mut().set_autofocus(true);
+        camera.autofocus(&scene);
+        assert!((camera.get_depth_of_field().get_focus_distance() - 4.5).abs() < 1e-6);
+
+        // the hit is projected on to the front of the camera
+        let ray = camera.get_ray_without_lens(&glm::vec2(0.05, 0.0)).unwrap();
+        let hit_distance = glm::dot(
+            &(scene.hit(&ray, 0.0, 100.0).unwrap().get_point() - ray.get_origin()),
+            &camera.get_camera().get_front(),
+        );
+        camera
+            .get_depth_of_field_mut()
+            .set_autofocus_point(glm::vec2(0.05, 0.0));
+        camera.autofocus(&scene);
+        assert!((camera.get_depth_of_field().get_focus_distance() - hit_distance).abs() < 1e-9);
+        assert!(hit_distance > 4.5);
+
+        // nothing hit, the focus distance is unchanged
+        camera
+            .get_depth_of_field_mut()
+            .set_autofocus_point(glm::vec2(0.9, 0.9));
+        camera.autofocus(&scene);
+        assert!((camera.get_depth_of_field().get_focus_distance() - hit_distance).abs() < 1e-9);
+    }
+
+    /// The eyes are separated by the interocular distance along the
+    /// right of the camera, their rays converge based on the
+    /// convergence mode.
+    #[test]
+    fn path_trace_camera_stereo_test_01() {
+        let mut camera = get_camera();
+        let stereo = camera.get_stereo_mut();
+        stereo.set_enabled(true);
+        stereo.set_interocular_distance(0.065);
+        stereo.set_convergence_distance(2.0);
+        let position = camera.get_camera().get_position();
+        let front = camera.get_camera().get_front();
+        let right = camera.get_right();
+        let up = camera.get_up();
+
+        StereoConvergence::all().for_each(|convergence| {
+            camera.get_stereo_mut().set_convergence(convergence);
+            [
+                glm::vec2(0.0, 0.0),
+                glm::vec2(1.0, 1.0),
+                glm::vec2(-0.5, 0.25),
+            ]
+            .iter()
+            .for_each(|uv| {
+                let ray = camera.get_eye_ray(uv, None).unwrap();
+                let left_ray = camera.get_eye_ray(uv, Some(StereoEye::Left)).unwrap();
+                let right_ray = camera.get_eye_ray(uv, Some(StereoEye::Right)).unwrap();
+
+                assert_vec3_eq(ray.get_origin(), &position);
+                assert_vec3_eq(left_ray.get_origin(), &(position - right * 0.0325));
+                assert_vec3_eq(right_ray.get_origin(), &(position + right * 0.0325));
+
+                // point that the ray from the center of the camera
+                // passes through at the convergence distance (along
+                // the front)
+                let convergence_point =
+                    ray.at(2.0 / glm::dot(&ray.get_direction().normalize(), &front));
+                let passes_through_convergence_point = |eye_ray: &Ray| {
+                    let direction = eye_ray.get_direction().normalize();
+                    let to_point = convergence_point - eye_ray.get_origin();
+                    glm::length(&glm::cross(&direction, &to_point)) < 1e-9
+                        && glm::dot(&direction, &to_point) > 0.0
+                };
+
+                match convergence {
+                    StereoConvergence::OffAxis => {
+                        assert!(passes_through_convergence_point(&left_ray));
+                        assert!(passes_through_convergence_point(&right_ray));
+                    }
+                    StereoConvergence::Parallel => {
+                        assert_vec3_eq(left_ray.get_direction(), ray.get_direction());
+                        assert_vec3_eq(right_ray.get_direction(), ray.get_direction());
+                    }
+                    StereoConvergence::ToeIn => {
+                        // the eyes are rotated about the up of the
+                        // camera, only the center rays converge
+                        let angle = (0.0325_f64 / 2.0).atan();
+                        assert_vec3_eq(
+                            left_ray.get_direction(),
+                            &glm::rotate_vec3(ray.get_direction(), -angle, &up),
+                        );
+                        assert_vec3_eq(
+                            right_ray.get_direction(),
+                            &glm::rotate_vec3(ray.get_direction(), angle, &up),
+                        );
+                        if *uv == glm::zero::<glm::DVec2>() {
+                            assert!(passes_through_convergence_point(&left_ray));
+                            assert!(passes_through_convergence_point(&right_ray));
+                        }
+                    }
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn stereo_image_size_test_01() {
+        let mut stereo = Stereo::default();
+        assert_eq!(stereo.get_image_size(640, 480), (640, 480));
+        assert_eq!(stereo.get_eye_pixel(700, 500, 640, 480), (None, 700, 500));
+
+        stereo.set_enabled(true);
+        stereo.set_layout(StereoLayout::SideBySide);
+        assert_eq!(stereo.get_image_size(640, 480), (1280, 480));
+        assert_eq!(
+            stereo.get_eye_pixel(10, 20, 640, 480),
+            (Some(StereoEye::Left), 10, 20)
+        );
+        assert_eq!(
+            stereo.get_eye_pixel(650, 20, 640, 480),
+            (Some(StereoEye::Right), 10, 20)
+        );
+
+        stereo.set_layout(StereoLayout::TopBottom);
+        assert_eq!(stereo.get_image_size(640, 480), (640, 960));
+        assert_eq!(
+            stereo.get_eye_pixel(10, 20, 640, 480),
+            (Some(StereoEye::Left), 10, 20)
+        );
+        assert_eq!(
+            stereo.get_eye_pixel(10, 500, 640, 480),
+            (Some(StereoEye::Right), 10, 20)
+        );
+    }
+
+    #[test]
+    fn exposure_scale_test_01() {
+        let mut exposure = Exposure::default();
+        assert_eq!(exposure.get_scale(), 1.0);
+        exposure.set_enabled(true);
+        assert!(exposure.get_ev100().abs() < 1e-12);
+        assert!((exposure.get_scale() - 1.0).abs() < 1e-12);
+
+        // sunny 16 at ISO 100
+        exposure.set_shutter_speed(1.0 / 100.0);
+        exposure.set_f_number(16.0);
+        let ev100 = (256.0_f64 * 100.0).log2();
+        assert!((exposure.get_ev100() - ev100).abs() < 1e-12);
+        assert!((exposure.get_scale() - 1.0 / 25600.0).abs() < 1e-12);
+        exposure.set_iso(200.0);
+        assert!((exposure.get_scale() - 2.0 / 25600.0).abs() < 1e-12);
+
+        // the radiance is unchanged at the reference
+        exposure.set_reference_ev100(exposure.get_ev100());
+        assert!((exposure.get_scale() - 1.0).abs() < 1e-12);
+
+        assert!((Exposure::PHYSICAL_REFERENCE_EV100 + 1.2_f64.log2()).abs() < 1e-12);
+        exposure.set_reference_ev100(Exposure::PHYSICAL_REFERENCE_EV100);
+        exposure.set_iso(100.0);
+        exposure.set_shutter_speed(1.0);
+        exposure.set_f_number(1.0);
+        assert!((exposure.get_scale() - 1.0 / 1.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn exposure_deserialize_without_reference_test_01() {
+        let exposure: Exposure = serde_json::from_value(serde_json::json!({
+            "enabled": true,
+            "iso": 100.0,
+            "shutter_speed": 1.0,
+            "f_number": 2.0,
+        }))
+        .unwrap();
+        assert_eq!(exposure.get_reference_ev100(), 0.0);
+        assert!((exposure.get_scale() - 0.25).abs() < 1e-12);
+    }
+}
@@ -4,7 +4,7 @@ use embree_rust::{GeometryID, SceneID};
 use itertools::Itertools;
 
 use crate::{
-    glm,
+    glm, math,
     mesh::Mesh,
     object::{Object, ObjectID},
     path_trace::{
@@ -12,6 +12,7 @@ use crate::{
         ray::Ray,
     },
     sphere::Sphere,
+    util,
 };
 
 #[derive(Debug)]
@@ -22,6 +23,15 @@ pub struct Embree {
     scene_id: Option<SceneID>,
     /// Mapping from embree's [`GeometryID`] to [`ObjectID`]
     geometry_ids: HashMap<GeometryID, ObjectID>,
+    /// Objects that move within the shutter, they are not added to
+    /// the embree scene and must be intersected separately.
+    ///
+    /// TODO: add them with embree's motion blur once `embree_rust`
+    /// exposes the time steps of geometry in a pinned release
+    moving_objects: Vec<ObjectID>,
+    /// Shutter open and close time, the geometry of the objects that
+    /// are added to the embree scene is at shutter open.
+    shutter: (f64, f64),
 }
 
 impl Embree {
@@ -30,26 +40,56 @@ impl Embree {
             embree: embree_rust::Embree::new(),
             scene_id: None,
             geometry_ids: HashMap::new(),
+            moving_objects: Vec::new(),
+            shutter: (0.0, 0.0),
         }
     }
 
+    /// Set the shutter open and close time, must be set before adding
+    /// any objects.
+    pub fn set_shutter(&mut self, shutter_open: f64, shutter_close: f64) {
+        debug_assert!(self.geometry_ids.is_empty() && self.moving_objects.is_empty());
+        self.shutter = (shutter_open, shutter_close);
+    }
+
+    /// Check if the object moves within the given shutter.
+    pub fn is_object_moving(object: &dyn Object, shutter: (f64, f64)) -> bool {
+        let (shutter_open, shutter_close) = shutter;
+        object.is_moving() && shutter_close > shutter_open
+    }
+
+    /// Get the objects that move within the shutter, they are not part
+    /// of the embree scene.
+    pub fn get_moving_objects(&self) -> &[ObjectID] {
+        &self.moving_objects
+    }
+
     pub fn commit_scene(&mut self) {
         let scene_id = self.get_scene_id();
         self.scene_id = Some(self.embree.commit_scene(scene_id));
     }
 
     pub fn add_object(&mut self, object: &dyn Object) {
-        object.add_object_to_embree(self);
+        if Self::is_object_moving(object, self.shutter) {
+            self.moving_objects.push(object.get_object_id());
+        } else {
+            object.add_object_to_embree(self);
+        }
     }
 
-    pub fn add_mesh(&mut self, mesh: &Mesh, object_id: ObjectID) {
+    /// Add the mesh of the given object at shutter open. The mesh must
+    /// have the model matrix of the object applied.
+    pub fn add_mesh(&mut self, mesh: &Mesh, object: &dyn Object) {
         let scene_id = self.get_scene_id();
 
-        let verts = mesh
-            .get_vertices()
-            .iter()
-            .map(|vert| {
-                let pos = vert.get_pos();
+        let time = self.shutter.0;
+        let motion_matrix = object.get_motion_matrix(time);
+        let verts = (0..mesh.get_vertices().len())
+            .map(|v_index| {
+                let pos = mesh.get_vertex_pos_at_time(v_index, time);
+                let pos = motion_matrix.map_or(pos, |motion_matrix| {
+                    util::vec3_apply_model_matrix(&pos, &motion_matrix)
+                });
                 embree_rust::Vert::new(embree_rust::Vec3::new(
                     pos[0] as f32,
                     pos[1] as f32,
@@ -84,21 +124,27 @@ impl Embree {
         let geometry_id = self.embree.add_geometry_triangle(&verts, &triangles);
 
         self.embree.attach_geometry_to_scene(geometry_id, scene_id);
-        self.geometry_ids.insert(geometry_id, object_id);
+        self.geometry_ids
+            .insert(geometry_id, object.get_object_id());
     }
 
-    pub fn add_sphere(&mut self, sphere: &Sphere, object_id: ObjectID) {
+    /// Add the sphere of the given object at shutter open. The sphere
+    /// must have the model matrix of the object applied.
+    pub fn add_sphere(&mut self, sphere: &Sphere, object: &dyn Object) {
         let scene_id = self.get_scene_id();
+
+        let center = object
+            .get_motion_matrix(self.shutter.0)
+            .map_or(*sphere.get_center(), |motion_matrix| {
+                util::vec3_apply_model_matrix(sphere.get_center(), &motion_matrix)
+            });
         let geometry_id = self.embree.add_geometry_sphere(&[embree_rust::Sphere::new(
-            embree_rust::Vec3::new(
-                sphere.get_center()[0] as f32,
-                sphere.get_center()[1] as f32,
-                sphere.get_center()[2] as f32,
-            ),
+            embree_rust::Vec3::new(center[0] as f32, center[1] as f32, center[2] as f32),
             sphere.get_radius() as f32,
         )]);
         self.embree.attach_geometry_to_scene(geometry_id, scene_id);
-        self.geometry_ids.insert(geometry_id, object_id);
+        self.geometry_ids
+            .insert(geometry_id, object.get_object_id());
     }
 
     /// Get scene id, create new scene is necessary
@@ -131,6 +177,7 @@ impl Intersectable for Embree {
                     ray.get_direction()[1] as f32,
                     ray.get_direction()[2] as f32,
                 ),
+                // the geometry in the scene does not move
                 0.0,
             ),
         );
@@ -158,6 +205,22 @@ impl Intersectable for Embree {
                         .unwrap(),
                 )
                 .unwrap();
+            // embree computes the intersection (and stores the
+            // geometry) with single precision floats, the error
+            // bounds must reflect that
+            info.set_point_error(
+                math::gamma_f32(7)
+                    * (glm::abs(ray.get_origin()) + glm::abs(&(ray.get_direction() * t))),
+            );
+            info.set_geometric_normal(
+                glm::vec3(
+                    ray_hit.hit.Ng_x.into(),
+                    ray_hit.hit.Ng_y.into(),
+                    ray_hit.hit.Ng_z.into(),
+                )
+                .normalize(),
+            );
+            info.set_time(ray.get_time());
             info.set_object_id(object_id);
             info.set_primitive_index(ray_hit.hit.primID.try_into().unwrap());
             Some(info)
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
 
 use crate::{
+    camera::PathTraceCamera,
     glm,
     path_trace::{environment::Environment, shader_list::ShaderList},
     scene::Scene,
@@ -40,15 +41,19 @@ impl From<OldPathTraceCamera> for Camera {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum CameraIO {
+    // latest version must be tried first, untagged enums are tried
+    // in order
+    V2(PathTraceCamera),
     V0(OldPathTraceCamera),
     V1(Camera),
 }
 
-impl From<CameraIO> for Camera {
+impl From<CameraIO> for PathTraceCamera {
     fn from(io: CameraIO) -> Self {
         match io {
-            CameraIO::V0(cam) => cam.into(),
-            CameraIO::V1(cam) => cam,
+            CameraIO::V0(cam) => Camera::from(cam).into(),
+            CameraIO::V1(cam) => cam.into(),
+            CameraIO::V2(cam) => cam,
         }
     }
 }
@@ -58,7 +63,7 @@ impl From<CameraIO> for Camera {
 pub struct File {
     scene: Arc<RwLock<Scene>>,
     shader_list: Arc<RwLock<ShaderList>>,
-    path_trace_camera: Arc<RwLock<Camera>>,
+    path_trace_camera: Arc<RwLock<PathTraceCamera>>,
 
     environment: Arc<RwLock<Environment>>,
 }
@@ -98,7 +103,7 @@ impl File {
     pub fn new(
         scene: Arc<RwLock<Scene>>,
         shader_list: Arc<RwLock<ShaderList>>,
-        path_trace_camera: Arc<RwLock<Camera>>,
+        path_trace_camera: Arc<RwLock<PathTraceCamera>>,
         environment: Arc<RwLock<Environment>>,
     ) -> Self {
         Self {
@@ -114,7 +119,7 @@ pub fn load_rt_file<P>(
     path: P,
     scene: Arc<RwLock<Scene>>,
     shader_list: Arc<RwLock<ShaderList>>,
-    path_trace_camera: Arc<RwLock<Camera>>,
+    path_trace_camera: Arc<RwLock<PathTraceCamera>>,
     environment: Arc<RwLock<Environment>>,
 ) where
     P: AsRef<std::path::Path>,
@@ -140,7 +145,7 @@ pub fn save_rt_file<P>(
     path: P,
     scene: Arc<RwLock<Scene>>,
     shader_list: Arc<RwLock<ShaderList>>,
-    path_trace_camera: Arc<RwLock<Camera>>,
+    path_trace_camera: Arc<RwLock<PathTraceCamera>>,
     environment: Arc<RwLock<Environment>>,
 ) where
     P: AsRef<std::path::Path>,
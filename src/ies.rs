@@ -0,0 +1,375 @@
+use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
+
+use std::{io::Read, path::Path};
+
+use crate::{egui, glm};
+
+#[derive(Debug)]
+pub enum IESError {
+    Io(String),
+    InvalidFile,
+    /// Only type C photometry (the most common one, used by almost
+    /// all architectural luminaires) is supported.
+    UnsupportedPhotometricType(u32),
+}
+
+impl From<std::io::Error> for IESError {
+    fn from(err: std::io::Error) -> IESError {
+        IESError::Io(err.to_string())
+    }
+}
+
+impl std::fmt::Display for IESError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IESError::Io(error) => write!(f, "io error {}", error),
+            IESError::InvalidFile => write!(f, "invalid file"),
+            IESError::UnsupportedPhotometricType(photometric_type) => {
+                write!(f, "unsupported photometric type {}", photometric_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IESError {}
+
+/// IES (IESNA LM-63) photometric profile, the luminous intensity
+/// of a luminaire in every direction.
+///
+/// The intensities are normalized such that the brightest direction
+/// has an intensity of 1.0, the profile only shapes the emission of
+/// the light, the power of the light is set by the light itself.
+///
+/// The profile is defined in the local space of the light, the
+/// vertical angle is measured from the -Z axis (nadir, the direction
+/// the light points towards) and the horizontal angle is measured
+/// from the +X axis towards the +Y axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IESProfile {
+    /// name of the profile, usually the name of the file it was
+    /// loaded from
+    name: String,
+    /// vertical angles (in degrees) in increasing order
+    vertical_angles: Vec<f64>,
+    /// horizontal angles (in degrees) in increasing order
+    horizontal_angles: Vec<f64>,
+    /// normalized intensity for each vertical angle for each
+    /// horizontal angle
+    intensities: Vec<Vec<f64>>,
+}
+
+impl IESProfile {
+    /// Read the IES profile from the file at the given path, the
+    /// name of the profile is the name of the file.
+    pub fn read_from_path(path: impl AsRef<Path>) -> Result<Self, IESError> {
+        let name = path.as_ref().file_name().map_or_else(
+            || "IES".to_string(),
+            |name| name.to_string_lossy().to_string(),
+        );
+        Self::read(std::fs::File::open(path)?, name)
+    }
+
+    /// Read the IES profile from the given reader.
+    pub fn read(mut reader: impl Read, name: String) -> Result<Self, IESError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        // IES files are not guaranteed to be utf-8, the keywords can
+        // have any (usually latin-1) characters but the photometric
+        // data is always ascii
+        Self::parse(&String::from_utf8_lossy(&data), name)
+    }
+
+    /// Parse the IES profile from the contents of an IES file.
+    pub fn parse(data: &str, name: String) -> Result<Self, IESError> {
+        let mut lines = data.lines();
+
+        // skip the keywords till the tilt
+        let tilt = lines
+            .by_ref()
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .ok_or(IESError::InvalidFile)?
+            .trim()
+            .to_string();
+
+        let mut values = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<f64>().map_err(|_| IESError::InvalidFile));
+        let mut next_value = || values.next().unwrap_or(Err(IESError::InvalidFile));
+
+        // the tilt of the lamp within the luminaire only matters for
+        // luminaires that can be tilted, it is ignored
+        if tilt == "INCLUDE" {
+            let _lamp_to_luminaire_geometry = next_value()?;
+            let num_tilt_angles = next_value()? as usize;
+            for _ in 0..(2 * num_tilt_angles) {
+                next_value()?;
+            }
+        }
+
+        let _num_lamps = next_value()?;
+        let _lumens_per_lamp = next_value()?;
+        let _candela_multiplier = next_value()?;
+        let num_vertical_angles = next_value()? as usize;
+        let num_horizontal_angles = next_value()? as usize;
+        let photometric_type = next_value()? as u32;
+        let _units_type = next_value()?;
+        let _width = next_value()?;
+        let _length = next_value()?;
+        let _height = next_value()?;
+        let _ballast_factor = next_value()?;
+        let _future_use = next_value()?;
+        let _input_watts = next_value()?;
+
+        if photometric_type != 1 {
+            return Err(IESError::UnsupportedPhotometricType(photometric_type));
+        }
+        if num_vertical_angles == 0 || num_horizontal_angles == 0 {
+            return Err(IESError::InvalidFile);
+        }
+
+        let vertical_angles = (0..num_vertical_angles)
+            .map(|_| next_value())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..num_horizontal_angles)
+            .map(|_| next_value())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut intensities = (0..num_horizontal_angles)
+            .map(|_| {
+                (0..num_vertical_angles)
+                    .map(|_| next_value().map(|value| value.max(0.0)))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let is_increasing = |angles: &[f64]| angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !is_increasing(&vertical_angles) || !is_increasing(&horizontal_angles) {
+            return Err(IESError::InvalidFile);
+        }
+
+        let max_intensity = intensities
+            .iter()
+            .flatten()
+            .fold(0.0_f64, |acc, &intensity| acc.max(intensity));
+        if max_intensity <= 0.0 {
+            return Err(IESError::InvalidFile);
+        }
+        intensities
+            .iter_mut()
+            .flatten()
+            .for_each(|intensity| *intensity /= max_intensity);
+
+        Ok(Self {
+            name,
+            vertical_angles,
+            horizontal_angles,
+            intensities,
+        })
+    }
+
+    /// Load the IES profile with a file dialog to choose the file.
+    /// Returns `None` if no file was chosen or if the file could not
+    /// be read.
+    pub fn load_file_dialog() -> Option<Self> {
+        let path = FileDialog::new()
+            .add_filter("IES", &["ies", "IES"])
+            .add_filter("Any", &["*"])
+            .set_directory(".")
+            .pick_file()?;
+        match Self::read_from_path(&path) {
+            Ok(profile) => Some(profile),
+            Err(error) => {
+                eprintln!("error: could not load {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    /// Get a reference to the IES profile's name.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the normalized intensity at the given vertical and
+    /// horizontal angles (in degrees).
+    pub fn eval(&self, vertical_angle: f64, horizontal_angle: f64) -> f64 {
+        let horizontal_angle = self.fold_horizontal_angle(horizontal_angle);
+
+        let (h_index, h_factor) = match find_interval(&self.horizontal_angles, horizontal_angle) {
+            Some(interval) => interval,
+            None => return 0.0,
+        };
+        let eval_vertical = |h_index: usize| {
+            find_interval(&self.vertical_angles, vertical_angle).map_or(0.0, |(index, factor)| {
+                let intensities = &self.intensities[h_index];
+                let next = (index + 1).min(intensities.len() - 1);
+                intensities[index] * (1.0 - factor) + intensities[next] * factor
+            })
+        };
+
+        let next_h_index = (h_index + 1).min(self.horizontal_angles.len() - 1);
+        eval_vertical(h_index) * (1.0 - h_factor) + eval_vertical(next_h_index) * h_factor
+    }
+
+    /// Get the normalized intensity in the given direction (need not
+    /// be normalized) in the local space of the profile.
+    pub fn eval_local_direction(&self, direction: &glm::DVec3) -> f64 {
+        let length = direction.norm();
+        if length == 0.0 {
+            return 0.0;
+        }
+        let vertical_angle = (-direction[2] / length)
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees();
+        let horizontal_angle = direction[1].atan2(direction[0]).to_degrees();
+        self.eval(vertical_angle, horizontal_angle)
+    }
+
+    /// Get the normalized intensity in the given direction (world
+    /// space). `down` is the direction the profile points towards
+    /// (nadir) and `axis_x` is the direction of the horizontal angle
+    /// 0, both must be normalized and perpendicular to each other.
+    pub fn eval_direction(
+        &self,
+        direction: &glm::DVec3,
+        down: &glm::DVec3,
+        axis_x: &glm::DVec3,
+    ) -> f64 {
+        let axis_y = glm::cross(&-down, axis_x);
+        self.eval_local_direction(&glm::vec3(
+            glm::dot(direction, axis_x),
+            glm::dot(direction, &axis_y),
+            -glm::dot(direction, down),
+        ))
+    }
+
+    /// Map the horizontal angle (in degrees) into the range of
+    /// horizontal angles of the profile based on the symmetry of the
+    /// profile.
+    fn fold_horizontal_angle(&self, horizontal_angle: f64) -> f64 {
+        let first = self.horizontal_angles[0];
+        let last = *self.horizontal_angles.last().unwrap();
+        let angle = horizontal_angle.rem_euclid(360.0);
+
+        if self.horizontal_angles.len() == 1 {
+            // rotationally symmetric
+            first
+        } else if first == 0.0 && last == 90.0 {
+            // symmetric in each quadrant
+            let angle = if angle > 180.0 { 360.0 - angle } else { angle };
+            if angle > 90.0 {
+                180.0 - angle
+            } else {
+                angle
+            }
+        } else if first == 0.0 && last == 180.0 {
+            // symmetric about the 0-180 degree plane
+            if angle > 180.0 {
+                360.0 - angle
+            } else {
+                angle
+            }
+        } else if first == 90.0 && last == 270.0 {
+            // symmetric about the 90-270 degree plane
+            if angle < 90.0 {
+                180.0 - angle
+            } else if angle > 270.0 {
+                540.0 - angle
+            } else {
+                angle
+            }
+        } else {
+            angle
+        }
+    }
+}
+
+/// Find the index of the interval of the (increasing) `values` that
+/// contains `value` along with the interpolation factor within the
+/// interval. Returns `None` if `value` lies outside `values`.
+fn find_interval(values: &[f64], value: f64) -> Option<(usize, f64)> {
+    let first = values[0];
+    let last = *values.last().unwrap();
+    if value < first || value > last {
+        return None;
+    }
+    if values.len() == 1 {
+        return Some((0, 0.0));
+    }
+    let index = values
+        .windows(2)
+        .position(|pair| value <= pair[1])
+        .unwrap_or(values.len() - 2);
+    let factor = (value - values[index]) / (values[index + 1] - values[index]);
+    Some((index, factor))
+}
+
+/// Draw the UI to load or remove the IES profile.
+pub fn ies_profile_ui(ui: &mut egui::Ui, ies_profile: &mut Option<IESProfile>) {
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "IES Profile: {}",
+            ies_profile
+                .as_ref()
+                .map_or("None", |ies_profile| ies_profile.get_name())
+        ));
+        if ui.button("Load").clicked() {
+            if let Some(profile) = IESProfile::load_file_dialog() {
+                *ies_profile = Some(profile);
+            }
+        }
+        if ies_profile.is_some() && ui.button("Remove").clicked() {
+            *ies_profile = None;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IES_TEST_01: &str = "IESNA:LM-63-2002
+[TEST] test
+[MANUFAC] rt
+TILT=NONE
+1 1000 1 5 3 1 2 0 0 0
+1 1 100
+0 22.5 45 67.5 90
+0 45 90
+100 80 60 40 0
+200 160 120 80 0
+100 80 60 40 0
+";
+
+    #[test]
+    fn ies_profile_parse_test_01() {
+        let profile = IESProfile::parse(IES_TEST_01, "test".to_string()).unwrap();
+        assert_eq!(profile.vertical_angles.len(), 5);
+        assert_eq!(profile.horizontal_angles.len(), 3);
+        assert_eq!(profile.intensities.len(), 3);
+
+        assert!((profile.eval(0.0, 45.0) - 1.0).abs() < 1e-6);
+        assert!((profile.eval(0.0, 0.0) - 0.5).abs() < 1e-6);
+        assert!((profile.eval(11.25, 0.0) - 0.45).abs() < 1e-6);
+        assert!((profile.eval(0.0, 22.5) - 0.75).abs() < 1e-6);
+        // quadrant symmetry
+        assert!((profile.eval(0.0, 135.0) - 1.0).abs() < 1e-6);
+        assert!((profile.eval(0.0, -45.0) - 1.0).abs() < 1e-6);
+        // outside the vertical angles
+        assert_eq!(profile.eval(120.0, 0.0), 0.0);
+
+        // straight down along nadir
+        assert!((profile.eval_local_direction(&glm::vec3(0.0, 0.0, -1.0)) - 0.5).abs() < 1e-6);
+        assert_eq!(profile.eval_local_direction(&glm::vec3(0.0, 0.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn ies_profile_parse_test_02() {
+        match IESProfile::parse("IESNA:LM-63-2002\nTILT=NONE\n1 1000", "test".to_string()) {
+            Err(IESError::InvalidFile) => (),
+            _ => panic!("Should have gotten an invalid file error"),
+        }
+    }
+}
@@ -1,8 +1,50 @@
-use image::Pixel;
+use image::{GenericImageView, Pixel};
 use quick_renderer::texture::TextureRGBAFloat;
 use serde::{Deserialize, Serialize};
 
-use crate::glm;
+use crate::{glm, texture::TextureSampler};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageLoadError {
+    Io(String),
+    /// File could not be decoded, contains the reason.
+    Decode(String),
+}
+
+impl From<std::io::Error> for ImageLoadError {
+    fn from(err: std::io::Error) -> ImageLoadError {
+        ImageLoadError::Io(err.to_string())
+    }
+}
+
+impl From<image::ImageError> for ImageLoadError {
+    fn from(err: image::ImageError) -> ImageLoadError {
+        match err {
+            image::ImageError::IoError(err) => err.into(),
+            err => ImageLoadError::Decode(err.to_string()),
+        }
+    }
+}
+
+impl From<exr::error::Error> for ImageLoadError {
+    fn from(err: exr::error::Error) -> ImageLoadError {
+        match err {
+            exr::error::Error::Io(err) => err.into(),
+            err => ImageLoadError::Decode(err.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageLoadError::Io(error) => write!(f, "io error {}", error),
+            ImageLoadError::Decode(error) => write!(f, "decode error {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ImageLoadError {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Image {
@@ -13,6 +55,10 @@ pub struct Image {
 
     /// pixels of the image stored from top left row wise
     pixels: Vec<glm::DVec3>,
+    /// alpha of the pixels of the image stored in the same order as
+    /// the pixels, the image is opaque if it is `None`
+    #[serde(default)]
+    alphas: Option<Vec<f64>>,
 
     width: usize,
     height: usize,
@@ -30,6 +76,7 @@ impl Image {
         Image {
             id: rand::random(),
             pixels,
+            alphas: None,
             width,
             height,
         }
@@ -50,6 +97,13 @@ impl Image {
                         .map(|pixel| glm::vec3(pixel[0].into(), pixel[1].into(), pixel[2].into()))
                 })
                 .collect(),
+            alphas: Some(
+                tex.get_pixels()
+                    .chunks(tex.get_width())
+                    .rev()
+                    .flat_map(|row| row.iter().map(|pixel| pixel[3].into()))
+                    .collect(),
+            ),
             width: tex.get_width(),
             height: tex.get_height(),
         }
@@ -68,11 +122,203 @@ impl Image {
                     )
                 })
                 .collect(),
+            alphas: None,
             width,
             height,
         }
     }
 
+    /// Load image from disk given the path to the image, the format
+    /// is chosen based on the extension of the file.
+    ///
+    /// Supports OpenEXR (`exr`, half/float/uint channels, scanline
+    /// and tiled), Radiance HDR (`hdr`), Portable Float Map (`pfm`)
+    /// and the formats supported by the `image` crate (`png`, `jpg`,
+    /// `tiff`, etc., 8 and 16 bits per channel). The values are
+    /// stored as is, no colour space conversion is done, and the
+    /// alpha channel is loaded if present.
+    pub fn load_from_disk<P>(path: P) -> Result<Image, ImageLoadError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("exr") => Self::load_exr(path),
+            Some("hdr") => Self::load_radiance_hdr(path),
+            Some("pfm") => Self::from_pfm(&std::fs::read(path)?),
+            _ => Self::load_generic(path),
+        }
+    }
+
+    fn load_exr(path: &std::path::Path) -> Result<Image, ImageLoadError> {
+        // alpha is 1.0 if the file does not have an alpha channel
+        let exr_image = exr::prelude::read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| {
+                (
+                    resolution.width(),
+                    vec![(glm::zero(), 1.0); resolution.width() * resolution.height()],
+                )
+            },
+            |(width, pixels): &mut (usize, Vec<(glm::DVec3, f64)>),
+             position,
+             (r, g, b, a): (f32, f32, f32, f32)| {
+                pixels[position.y() * *width + position.x()] =
+                    (glm::vec3(r.into(), g.into(), b.into()), a.into());
+            },
+        )?;
+
+        let size = exr_image.layer_data.size;
+        let (_, pixels) = exr_image.layer_data.channel_data.pixels;
+        // pixels of exr are also stored from the top left
+        let (pixels, alphas): (Vec<_>, Vec<_>) = pixels.into_iter().unzip();
+        let mut image = Self::from_pixels(size.width(), size.height(), pixels);
+        if alphas.iter().any(|alpha| *alpha != 1.0) {
+            image.alphas = Some(alphas);
+        }
+        Ok(image)
+    }
+
+    fn load_radiance_hdr(path: &std::path::Path) -> Result<Image, ImageLoadError> {
+        let hdr = image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(
+            std::fs::File::open(path)?,
+        ))?;
+        let width = hdr.metadata().width as _;
+        let height = hdr.metadata().height as _;
+        Ok(Self::from_vec_rgb_f32(
+            &hdr.read_image_hdr()?,
+            width,
+            height,
+        ))
+    }
+
+    /// Create image from the contents of a Portable Float Map (`pfm`)
+    /// file, both colour (`PF`) and greyscale (`Pf`) are supported.
+    pub fn from_pfm(data: &[u8]) -> Result<Image, ImageLoadError> {
+        let invalid = |reason: &str| ImageLoadError::Decode(format!("invalid pfm: {}", reason));
+
+        // header is 3 whitespace separated lines (magic, size,
+        // scale) followed by a single whitespace character
+        let mut tokens = Vec::with_capacity(4);
+        let mut index = 0;
+        while tokens.len() < 4 {
+            while index < data.len() && data[index].is_ascii_whitespace() {
+                index += 1;
+            }
+            let start = index;
+            while index < data.len() && !data[index].is_ascii_whitespace() {
+                index += 1;
+            }
+            if start == index {
+                return Err(invalid("incomplete header"));
+            }
+            tokens.push(std::str::from_utf8(&data[start..index]).map_err(|_| invalid("header"))?);
+        }
+        // single whitespace character after the scale
+        let data = &data[(index + 1).min(data.len())..];
+
+        let num_channels = match tokens[0] {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid("unknown magic")),
+        };
+        let width: usize = tokens[1].parse().map_err(|_| invalid("width"))?;
+        let height: usize = tokens[2].parse().map_err(|_| invalid("height"))?;
+        let scale: f64 = tokens[3].parse().map_err(|_| invalid("scale"))?;
+        // negative scale means little endian
+        let little_endian = scale < 0.0;
+
+        if width == 0 || height == 0 {
+            return Err(invalid("empty image"));
+        }
+        let data_len = width
+            .checked_mul(height)
+            .and_then(|len| len.checked_mul(num_channels * 4))
+            .ok_or_else(|| invalid("size"))?;
+        if data.len() < data_len {
+            return Err(invalid("not enough pixel data"));
+        }
+        let values: Vec<f64> = data
+            .chunks_exact(4)
+            .take(width * height * num_channels)
+            .map(|bytes| {
+                let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                if little_endian {
+                    f32::from_le_bytes(bytes)
+                } else {
+                    f32::from_be_bytes(bytes)
+                }
+                .into()
+            })
+            .collect();
+
+        // rows of pfm are stored from the bottom
+        let pixels = values
+            .chunks_exact(width * num_channels)
+            .rev()
+            .flat_map(|row| {
+                row.chunks_exact(num_channels).map(|pixel| {
+                    if num_channels == 3 {
+                        glm::vec3(pixel[0], pixel[1], pixel[2])
+                    } else {
+                        glm::vec3(pixel[0], pixel[0], pixel[0])
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+
+    fn load_generic(path: &std::path::Path) -> Result<Image, ImageLoadError> {
+        let dynamic_image = image::open(path)?;
+        let has_alpha = dynamic_image.color().has_alpha();
+        let is_16_bit = matches!(
+            dynamic_image,
+            image::DynamicImage::ImageLuma16(_)
+                | image::DynamicImage::ImageLumaA16(_)
+                | image::DynamicImage::ImageRgb16(_)
+                | image::DynamicImage::ImageRgba16(_)
+        );
+
+        let (width, height) = (
+            dynamic_image.width() as usize,
+            dynamic_image.height() as usize,
+        );
+        // converted to 16 bits per channel only when needed, to
+        // preserve the precision of 16 bit images
+        let pixels: Vec<[f64; 4]> = if is_16_bit {
+            dynamic_image
+                .to_rgba16()
+                .pixels()
+                .map(|pixel| pixel.0.map(|channel| channel as f64 / u16::MAX as f64))
+                .collect()
+        } else {
+            dynamic_image
+                .to_rgba8()
+                .pixels()
+                .map(|pixel| pixel.0.map(|channel| channel as f64 / u8::MAX as f64))
+                .collect()
+        };
+
+        let mut image = Self::from_pixels(
+            width,
+            height,
+            pixels
+                .iter()
+                .map(|pixel| glm::vec3(pixel[0], pixel[1], pixel[2]))
+                .collect(),
+        );
+        if has_alpha {
+            image.alphas = Some(pixels.iter().map(|pixel| pixel[3]).collect());
+        }
+        Ok(image)
+    }
+
     pub fn get_id(&self) -> usize {
         self.id
     }
@@ -88,8 +334,9 @@ impl Image {
 
     /// Get the pixel from the specified UV coordinates
     ///
-    /// Wrapping mode is set to repeat. TODO: need to make wrapping
-    /// mode user definable
+    /// Wrapping mode is set to repeat and there is no filtering, use
+    /// [`Self::sample_uv()`] to choose the filtering and the wrapping
+    /// mode.
     ///
     /// UV bottom left is (0.0, 0.0) and top right is (1.0, 1.0), same
     /// as OpenGL
@@ -102,6 +349,19 @@ impl Image {
         )
     }
 
+    /// Sample the image at the specified UV coordinates with the
+    /// given sampler, see [`TextureSampler`].
+    ///
+    /// UV bottom left is (0.0, 0.0) and top right is (1.0, 1.0), same
+    /// as OpenGL
+    pub fn sample_uv(&self, uv: &glm::DVec2, sampler: &TextureSampler) -> glm::DVec3 {
+        // pixels of the image are stored from the top left but the
+        // texel coordinates of the sampler are from the bottom left
+        sampler.sample(uv, self.width, self.height, |i, j| {
+            *self.get_pixel(i, self.height - j - 1)
+        })
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -118,4 +378,115 @@ impl Image {
     pub fn get_pixels(&self) -> &Vec<glm::DVec3> {
         &self.pixels
     }
+
+    /// Check if the image has an alpha channel.
+    pub fn has_alpha(&self) -> bool {
+        self.alphas.is_some()
+    }
+
+    /// Get the alpha of the pixel, it is 1.0 if the image does not
+    /// have an alpha channel.
+    pub fn get_alpha(&self, i: usize, j: usize) -> f64 {
+        self.alphas
+            .as_ref()
+            .map_or(1.0, |alphas| alphas[j * self.width + i])
+    }
+
+    /// Set the alpha of the pixel, an alpha channel (opaque) is added
+    /// to the image if it does not have one.
+    pub fn set_alpha(&mut self, i: usize, j: usize, alpha: f64) {
+        let width = self.width;
+        self.get_alphas_mut()[j * width + i] = alpha;
+    }
+
+    /// Get the alphas of the pixels, stored in the same order as the
+    /// pixels. It is `None` if the image does not have an alpha
+    /// channel.
+    pub fn get_alphas(&self) -> Option<&Vec<f64>> {
+        self.alphas.as_ref()
+    }
+
+    /// Get the alphas of the pixels mutably, an alpha channel
+    /// (opaque) is added to the image if it does not have one.
+    pub fn get_alphas_mut(&mut self) -> &mut Vec<f64> {
+        self.id = rand::random();
+        let len = self.pixels.len();
+        self.alphas.get_or_insert_with(|| vec![1.0; len])
+    }
+
+    /// Get the pixels and the alphas of the pixels mutably, see
+    /// [`Self::get_alphas_mut()`].
+    pub fn get_pixels_and_alphas_mut(&mut self) -> (&mut Vec<glm::DVec3>, &mut Vec<f64>) {
+        self.id = rand::random();
+        let len = self.pixels.len();
+        (
+            &mut self.pixels,
+            self.alphas.get_or_insert_with(|| vec![1.0; len]),
+        )
+    }
+
+    /// Remove the alpha channel of the image, the image becomes
+    /// opaque.
+    pub fn remove_alpha(&mut self) {
+        self.id = rand::random();
+        self.alphas = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_from_pfm_test_01() {
+        let mut data = b"PF\n2 2\n-1.0\n".to_vec();
+        // bottom row first
+        [
+            [0.0f32, 0.1, 0.2],
+            [1.0, 1.1, 1.2],
+            [2.0, 2.1, 2.2],
+            [3.0, 3.1, 3.2],
+        ]
+        .iter()
+        .flatten()
+        .for_each(|value| data.extend_from_slice(&value.to_le_bytes()));
+
+        let vec3 = |x: f32, y: f32, z: f32| glm::vec3(x as f64, y as f64, z as f64);
+
+        let image = Image::from_pfm(&data).unwrap();
+        assert_eq!(image.width(), 2);
+        assert_eq!(image.height(), 2);
+        assert!(!image.has_alpha());
+        assert_eq!(*image.get_pixel(0, 0), vec3(2.0, 2.1, 2.2));
+        assert_eq!(*image.get_pixel(1, 0), vec3(3.0, 3.1, 3.2));
+        assert_eq!(*image.get_pixel(0, 1), vec3(0.0, 0.1, 0.2));
+        assert_eq!(*image.get_pixel(1, 1), vec3(1.0, 1.1, 1.2));
+    }
+
+    #[test]
+    fn image_from_pfm_test_02() {
+        let mut data = b"Pf 1 2 1.0 ".to_vec();
+        [0.25f32, 0.5]
+            .iter()
+            .for_each(|value| data.extend_from_slice(&value.to_be_bytes()));
+
+        let image = Image::from_pfm(&data).unwrap();
+        assert_eq!(*image.get_pixel(0, 0), glm::vec3(0.5, 0.5, 0.5));
+        assert_eq!(*image.get_pixel(0, 1), glm::vec3(0.25, 0.25, 0.25));
+
+        assert!(Image::from_pfm(b"P6 1 1 1.0 ").is_err());
+        assert!(Image::from_pfm(b"PF 2 2 -1.0 ").is_err());
+    }
+
+    #[test]
+    fn image_from_pfm_test_03() {
+        let is_decode_error =
+            |data: &[u8]| matches!(Image::from_pfm(data), Err(ImageLoadError::Decode(_)));
+        assert!(is_decode_error(b"PF 0 2 -1.0 "));
+        assert!(is_decode_error(b"Pf 2 0 -1.0 "));
+        assert!(is_decode_error(b"PF 0 0 -1.0 "));
+        assert!(is_decode_error(
+            format!("PF {} {} -1.0 ", usize::MAX, usize::MAX).as_bytes()
+        ));
+    }
 }
@@ -1,5 +1,5 @@
 use clap::{value_t, values_t};
-use clap::{App, Arg};
+use clap::{App, Arg, ArgGroup};
 use itertools::Itertools;
 use quick_renderer::{
     camera::{self, Camera},
@@ -10,13 +10,20 @@ use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use crate::{
+    camera::{PathTraceCamera, StereoConvergence, StereoLayout},
     file, glm,
     image::Image,
+    object::{objects::Mesh as MeshObject, Object},
     path_trace::{
-        self, bsdfs::utils::ColorPicker, environment::Environment, shader_list::ShaderList,
-        texture_list::TextureList, RayTraceParams,
+        self,
+        bsdfs::utils::{ColorPicker, TextureSlot},
+        environment::{Environment, EnvironmentOverride, EnvironmentRayType},
+        shader_list::ShaderList,
+        texture_list::TextureList,
+        RayTraceParams,
     },
     scene::Scene,
+    texture::TextureRGBAFloatExtension,
     transform::Transform,
 };
 
@@ -36,6 +43,31 @@ pub struct InputArguments {
     environment_location: Option<glm::DVec3>,
     environment_rotation: Option<glm::DVec3>,
     environment_scale: Option<glm::DVec3>,
+    /// Use the procedural sky for the environment
+    sky: bool,
+    /// Sun elevation (in degrees) of the sky
+    sun_elevation: Option<f64>,
+    /// Sun azimuth (in degrees) of the sky
+    sun_azimuth: Option<f64>,
+    turbidity: Option<f64>,
+    ground_albedo: Option<f64>,
+    /// Angular diameter (in degrees) of the sun disc of the sky
+    sun_size: Option<f64>,
+    /// Render with a transparent background
+    transparent_background: bool,
+    /// Shutter open and close time used for motion blur
+    shutter: Option<(f64, f64)>,
+    /// Render stereo with the given layout of the views of the eyes
+    stereo_layout: Option<StereoLayout>,
+    interocular_distance: Option<f64>,
+    convergence_distance: Option<f64>,
+    convergence_mode: Option<StereoConvergence>,
+    /// ISO, shutter speed and f-number of the camera exposure
+    exposure: Option<(f64, f64, f64)>,
+    /// EV100 at which the radiance reaches the film unchanged
+    exposure_reference: Option<f64>,
+    /// Temperature and tint of the camera white balance
+    white_balance: Option<(f64, f64)>,
     /// If provided with a server name (see crate ipc-channel), a
     /// sender is created that sends a progress update of the path trace.
     path_trace_progress_server_name: Option<String>,
@@ -44,6 +76,10 @@ pub struct InputArguments {
     /// A list of object and shader pairs, assigns a shader with the
     /// given shader name to the object with given object name.
     object_shader: Vec<(String, String)>,
+    /// A list of (object name, time, mesh file), the positions of the
+    /// vertices of the object at the given time are read from the
+    /// mesh file. Used for deformation motion blur.
+    deformation_keys: Vec<(String, f64, PathBuf)>,
 }
 
 // Function to return test args processed using clap via cli
@@ -93,9 +129,62 @@ impl InputArguments {
                 Arg::with_name("environment")
                     .long("environment")
                     .short("e")
-                    .help("Environment map path")
+                    .help("Environment map path (hdr, exr or pfm)")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("sky")
+                    .long("sky")
+                    .help("Use the procedural sky for the environment"),
+            )
+            .group(ArgGroup::with_name("environment-source").args(&["environment", "sky"]))
+            .arg(
+                Arg::with_name("sun-elevation")
+                    .long("sun-elevation")
+                    .help("Angle (in degrees) of the sun above the horizon")
+                    .requires("sky")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .value_name("degrees"),
+            )
+            .arg(
+                Arg::with_name("sun-azimuth")
+                    .long("sun-azimuth")
+                    .help("Angle (in degrees) of the sun about the up axis")
+                    .requires("sky")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .value_name("degrees"),
+            )
+            .arg(
+                Arg::with_name("turbidity")
+                    .long("turbidity")
+                    .help("Turbidity of the atmosphere of the sky, 1 is perfectly clear")
+                    .requires("sky")
+                    .takes_value(true)
+                    .value_name("turbidity"),
+            )
+            .arg(
+                Arg::with_name("ground-albedo")
+                    .long("ground-albedo")
+                    .help("Albedo of the ground of the sky")
+                    .requires("sky")
+                    .takes_value(true)
+                    .value_name("albedo"),
+            )
+            .arg(
+                Arg::with_name("sun-size")
+                    .long("sun-size")
+                    .help("Angular diameter (in degrees) of the sun disc of the sky")
+                    .requires("sky")
+                    .takes_value(true)
+                    .value_name("degrees"),
+            )
+            .arg(
+                Arg::with_name("transparent-background")
+                    .long("transparent-background")
+                    .help("Render with a transparent background, the environment still lights the scene"),
+            )
             .arg(
                 Arg::with_name("rt-file")
                     .long("rt-file")
@@ -124,7 +213,7 @@ impl InputArguments {
                     .long("environment-strength")
                     .alias("es")
                     .help("Strength of the environment")
-                    .requires("environment")
+                    .requires("environment-source")
                     .takes_value(true)
                     .value_name("strength"),
             )
@@ -141,7 +230,7 @@ impl InputArguments {
                     .long("environment-location")
                     .alias("env-loc")
                     .help("Environment Location")
-                    .requires("environment")
+                    .requires("environment-source")
                     .takes_value(true)
                     .number_of_values(3)
                     .value_names(&["x", "y", "z"]),
@@ -151,7 +240,7 @@ impl InputArguments {
                     .long("environment-rotation")
                     .alias("env-rot")
                     .help("Environment Rotation")
-                    .requires("environment")
+                    .requires("environment-source")
                     .takes_value(true)
                     .number_of_values(3)
                     .value_names(&["x", "y", "z"]),
@@ -161,11 +250,80 @@ impl InputArguments {
                     .long("environment-scale")
                     .alias("env-scale")
                     .help("Environment Scale")
-                    .requires("environment")
+                    .requires("environment-source")
                     .takes_value(true)
                     .number_of_values(3)
                     .value_names(&["x", "y", "z"]),
             )
+            .arg(
+                Arg::with_name("shutter")
+                    .long("shutter")
+                    .help("Shutter open and close time used for motion blur")
+                    .takes_value(true)
+                    .number_of_values(2)
+                    .value_names(&["open", "close"]),
+            )
+            .arg(
+                Arg::with_name("stereo")
+                    .long("stereo")
+                    .help("Render stereo with the views of the eyes in the given layout")
+                    .takes_value(true)
+                    .possible_values(&["side-by-side", "top-bottom"])
+                    .value_name("layout"),
+            )
+            .arg(
+                Arg::with_name("interocular-distance")
+                    .long("interocular-distance")
+                    .help("Distance between the eyes for stereo")
+                    .requires("stereo")
+                    .takes_value(true)
+                    .value_name("distance"),
+            )
+            .arg(
+                Arg::with_name("convergence-distance")
+                    .long("convergence-distance")
+                    .help("Distance at which the views of the eyes converge for stereo")
+                    .requires("stereo")
+                    .takes_value(true)
+                    .value_name("distance"),
+            )
+            .arg(
+                Arg::with_name("convergence-mode")
+                    .long("convergence-mode")
+                    .help("How the views of the eyes converge for stereo")
+                    .requires("stereo")
+                    .takes_value(true)
+                    .possible_values(&["off-axis", "parallel", "toe-in"])
+                    .value_name("mode"),
+            )
+            .arg(
+                Arg::with_name("exposure")
+                    .long("exposure")
+                    .help("ISO, shutter speed (in seconds) and f-number of the camera exposure")
+                    .takes_value(true)
+                    .number_of_values(3)
+                    .value_names(&["iso", "shutter-speed", "f-number"]),
+            )
+            .arg(
+                Arg::with_name("exposure-reference")
+                    .long("exposure-reference")
+                    .help(
+                        "EV100 at which the radiance reaches the film unchanged, \
+                         -0.263 for radiance in nits",
+                    )
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .value_name("ev100"),
+            )
+            .arg(
+                Arg::with_name("white-balance")
+                    .long("white-balance")
+                    .help("Temperature (in kelvin) and tint of the camera white balance")
+                    .takes_value(true)
+                    .number_of_values(2)
+                    .allow_hyphen_values(true)
+                    .value_names(&["temperature", "tint"]),
+            )
             .arg(
                 Arg::with_name("path-trace-progress-server-name")
                     .long("path-trace-progress-server-name")
@@ -203,6 +361,21 @@ impl InputArguments {
                     .use_delimiter(true)
                     .require_delimiter(true),
             )
+            .arg(
+                Arg::with_name("deformation-key")
+                    .long("deformation-key")
+                    .help(
+                        "Positions of the vertices of an object (loaded from the mesh files) \
+                         at the given time for deformation motion blur, read from the \
+                         object of the same name in the mesh file",
+                    )
+                    .takes_value(true)
+                    .number_of_values(3)
+                    .value_names(&["object-name", "time", "mesh-file"])
+                    .multiple(true)
+                    .use_delimiter(true)
+                    .require_delimiter(true),
+            )
     }
 
     fn from_matches(matches: clap::ArgMatches) -> Self {
@@ -236,6 +409,27 @@ impl InputArguments {
             environment_scale: values_t!(matches, "environment-scale", f64)
                 .ok()
                 .map(|scale| glm::vec3(scale[0], scale[1], scale[2])),
+            sky: matches.is_present("sky"),
+            sun_elevation: value_t!(matches, "sun-elevation", f64).ok(),
+            sun_azimuth: value_t!(matches, "sun-azimuth", f64).ok(),
+            turbidity: value_t!(matches, "turbidity", f64).ok(),
+            ground_albedo: value_t!(matches, "ground-albedo", f64).ok(),
+            sun_size: value_t!(matches, "sun-size", f64).ok(),
+            transparent_background: matches.is_present("transparent-background"),
+            shutter: values_t!(matches, "shutter", f64)
+                .ok()
+                .map(|shutter| (shutter[0], shutter[1])),
+            stereo_layout: value_t!(matches, "stereo", StereoLayout).ok(),
+            interocular_distance: value_t!(matches, "interocular-distance", f64).ok(),
+            convergence_distance: value_t!(matches, "convergence-distance", f64).ok(),
+            convergence_mode: value_t!(matches, "convergence-mode", StereoConvergence).ok(),
+            exposure: values_t!(matches, "exposure", f64)
+                .ok()
+                .map(|exposure| (exposure[0], exposure[1], exposure[2])),
+            exposure_reference: value_t!(matches, "exposure-reference", f64).ok(),
+            white_balance: values_t!(matches, "white-balance", f64)
+                .ok()
+                .map(|white_balance| (white_balance[0], white_balance[1])),
             path_trace_progress_server_name: value_t!(
                 matches,
                 "path-trace-progress-server-name",
@@ -260,6 +454,32 @@ impl InputArguments {
                         .map(|(object, shader)| (object.to_string(), shader.to_string()))
                         .collect()
                 }),
+            deformation_keys: matches.values_of("deformation-key").map_or(
+                vec![],
+                |deformation_keys| {
+                    deformation_keys
+                        .tuples()
+                        .map(|(object, time, mesh_file)| {
+                            let time = time
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|time| time.is_finite())
+                                .unwrap_or_else(|| {
+                                    clap::Error::with_description(
+                                        &format!(
+                                            "The time of the deformation key of {} must be a \
+                                             finite number, got {}",
+                                            object, time
+                                        ),
+                                        clap::ErrorKind::ValueValidation,
+                                    )
+                                    .exit()
+                                });
+                            (object.to_string(), time, PathBuf::from(mesh_file))
+                        })
+                        .collect()
+                },
+            ),
         }
     }
 
@@ -332,6 +552,88 @@ impl InputArguments {
         self.environment_scale.as_ref()
     }
 
+    /// Get the input arguments's sky.
+    pub fn get_sky(&self) -> bool {
+        self.sky
+    }
+
+    /// Get the input arguments's sun elevation (in degrees).
+    pub fn get_sun_elevation(&self) -> Option<f64> {
+        self.sun_elevation
+    }
+
+    /// Get the input arguments's sun azimuth (in degrees).
+    pub fn get_sun_azimuth(&self) -> Option<f64> {
+        self.sun_azimuth
+    }
+
+    /// Get the input arguments's turbidity.
+    pub fn get_turbidity(&self) -> Option<f64> {
+        self.turbidity
+    }
+
+    /// Get the input arguments's ground albedo.
+    pub fn get_ground_albedo(&self) -> Option<f64> {
+        self.ground_albedo
+    }
+
+    /// Get the input arguments's sun size (in degrees).
+    pub fn get_sun_size(&self) -> Option<f64> {
+        self.sun_size
+    }
+
+    /// Get the input arguments's transparent background.
+    pub fn get_transparent_background(&self) -> bool {
+        self.transparent_background
+    }
+
+    /// Get the input arguments's shutter open time.
+    pub fn get_shutter_open(&self) -> Option<f64> {
+        self.shutter.map(|shutter| shutter.0)
+    }
+
+    /// Get the input arguments's shutter close time.
+    pub fn get_shutter_close(&self) -> Option<f64> {
+        self.shutter.map(|shutter| shutter.1)
+    }
+
+    /// Get the input arguments's stereo layout.
+    pub fn get_stereo_layout(&self) -> Option<StereoLayout> {
+        self.stereo_layout
+    }
+
+    /// Get the input arguments's interocular distance.
+    pub fn get_interocular_distance(&self) -> Option<f64> {
+        self.interocular_distance
+    }
+
+    /// Get the input arguments's convergence distance.
+    pub fn get_convergence_distance(&self) -> Option<f64> {
+        self.convergence_distance
+    }
+
+    /// Get the input arguments's convergence mode.
+    pub fn get_convergence_mode(&self) -> Option<StereoConvergence> {
+        self.convergence_mode
+    }
+
+    /// Get the input arguments's exposure as (iso, shutter speed,
+    /// f-number).
+    pub fn get_exposure(&self) -> Option<(f64, f64, f64)> {
+        self.exposure
+    }
+
+    /// Get the input arguments's exposure reference EV100.
+    pub fn get_exposure_reference(&self) -> Option<f64> {
+        self.exposure_reference
+    }
+
+    /// Get the input arguments's white balance as (temperature,
+    /// tint).
+    pub fn get_white_balance(&self) -> Option<(f64, f64)> {
+        self.white_balance
+    }
+
     /// Get a reference to the input arguments's path trace progress server name.
     pub fn get_path_trace_progress_server_name(&self) -> Option<&String> {
         self.path_trace_progress_server_name.as_ref()
@@ -352,6 +654,51 @@ impl InputArguments {
         self.object_shader.as_slice()
     }
 
+    /// Get a reference to the input arguments's deformation keys.
+    pub fn get_deformation_keys(&self) -> &[(String, f64, PathBuf)] {
+        self.deformation_keys.as_slice()
+    }
+
+    /// Set the positions of the vertices of the object keyed at the
+    /// times given by the deformation keys of the object, see
+    /// [`Self::get_deformation_keys()`].
+    fn set_deformation_keys(&self, object: &mut MeshObject) {
+        let motion_positions: Vec<_> = self
+            .get_deformation_keys()
+            .iter()
+            .filter(|(object_name, _, _)| object_name == object.get_object_name())
+            .map(|(object_name, time, mesh_file)| {
+                let key_object = crate::load_meshes(mesh_file)
+                    .into_iter()
+                    .find(|key_object| key_object.get_object_name() == object_name)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "No object with name {} was found in {}",
+                            object_name,
+                            mesh_file.display()
+                        )
+                    });
+                let positions: Vec<_> = key_object
+                    .get_data()
+                    .get_vertices()
+                    .iter()
+                    .map(|vertex| *vertex.get_pos())
+                    .collect();
+                if positions.len() != object.get_data().get_vertices().len() {
+                    panic!(
+                        "Object {} in {} does not have the same vertices as the object",
+                        object_name,
+                        mesh_file.display()
+                    );
+                }
+                (*time, positions)
+            })
+            .collect();
+        if !motion_positions.is_empty() {
+            object.get_data_mut().set_motion_positions(motion_positions);
+        }
+    }
+
     /// generates most of the necessary render info from the input
     /// arguments
     ///
@@ -392,7 +739,7 @@ impl InputArguments {
                 )),
             );
             camera.set_focal_length(camera_focal_length);
-            camera
+            PathTraceCamera::new(camera)
         }));
 
         let scene = Arc::new(RwLock::new({
@@ -434,31 +781,74 @@ impl InputArguments {
             );
         }
 
+        let environment_transform = || {
+            Transform::new(
+                self.get_environment_location()
+                    .map_or(glm::zero(), |location| *location),
+                self.get_environment_rotation()
+                    .map_or(glm::zero(), |rotation| *rotation),
+                self.get_environment_scale()
+                    .map_or(glm::vec3(1.0, 1.0, 1.0), |scale| *scale),
+            )
+        };
+
         // set environment map from the given path overriding the
         // environment map stored in the rt file
         if let Some(path) = self.get_environment_map() {
-            let hdr = image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(
-                std::fs::File::open(path).unwrap(),
-            ))
-            .unwrap();
-            let width = hdr.metadata().width as _;
-            let height = hdr.metadata().height as _;
-            let image = Image::from_vec_rgb_f32(&hdr.read_image_hdr().unwrap(), width, height);
+            let image = Image::load_from_disk(path).unwrap_or_else(|err| {
+                panic!(
+                    "could not load the environment map from specified path: {}: {}",
+                    path.to_str().unwrap(),
+                    err
+                )
+            });
             *environment.write().unwrap() = Environment::new(
                 image,
                 self.get_environment_strength()
                     .unwrap_or_else(crate::default_environment_strength),
-                Transform::new(
-                    self.get_environment_location()
-                        .map_or(glm::zero(), |location| *location),
-                    self.get_environment_rotation()
-                        .map_or(glm::zero(), |rotation| *rotation),
-                    self.get_environment_scale()
-                        .map_or(glm::vec3(1.0, 1.0, 1.0), |scale| *scale),
-                ),
+                environment_transform(),
+            );
+        }
+
+        // use the procedural sky overriding the environment stored in
+        // the rt file, the sky settings not given in the arguments
+        // are taken from the rt file
+        if self.get_sky() {
+            let mut sky = environment.read().unwrap().get_sky().clone();
+            if let Some(sun_elevation) = self.get_sun_elevation() {
+                sky.set_sun_elevation(sun_elevation.to_radians());
+            }
+            if let Some(sun_azimuth) = self.get_sun_azimuth() {
+                sky.set_sun_azimuth(sun_azimuth.to_radians());
+            }
+            if let Some(turbidity) = self.get_turbidity() {
+                sky.set_turbidity(turbidity);
+            }
+            if let Some(ground_albedo) = self.get_ground_albedo() {
+                sky.set_ground_albedo(ground_albedo);
+            }
+            if let Some(sun_size) = self.get_sun_size() {
+                sky.set_sun_size(sun_size.to_radians());
+            }
+            *environment.write().unwrap() = Environment::new_sky(
+                sky,
+                self.get_environment_strength()
+                    .unwrap_or_else(crate::default_environment_strength),
+                environment_transform(),
             );
         }
 
+        if self.get_transparent_background() {
+            environment
+                .write()
+                .unwrap()
+                .get_visibility_mut()
+                .set_override(
+                    EnvironmentRayType::Camera,
+                    Some(EnvironmentOverride::Transparent),
+                );
+        }
+
         // if image width or image height are present in the arguments,
         // must overide image width and height and also the camera
         if let Some((image_width, image_height)) =
@@ -467,22 +857,70 @@ impl InputArguments {
             path_trace_camera
                 .write()
                 .unwrap()
+                .get_camera_mut()
                 .get_sensor_mut()
                 .as_mut()
                 .unwrap()
                 .change_aspect_ratio(image_width as f64 / image_height as f64);
         }
 
+        // stereo settings from the arguments override the stereo
+        // settings stored in the rt file
+        if let Some(layout) = self.get_stereo_layout() {
+            let mut path_trace_camera = path_trace_camera.write().unwrap();
+            let stereo = path_trace_camera.get_stereo_mut();
+            stereo.set_enabled(true);
+            stereo.set_layout(layout);
+            if let Some(interocular_distance) = self.get_interocular_distance() {
+                stereo.set_interocular_distance(interocular_distance);
+            }
+            if let Some(convergence_distance) = self.get_convergence_distance() {
+                stereo.set_convergence_distance(convergence_distance);
+            }
+            if let Some(convergence_mode) = self.get_convergence_mode() {
+                stereo.set_convergence(convergence_mode);
+            }
+        }
+
+        // exposure and white balance from the arguments override the
+        // ones stored in the rt file
+        if let Some((iso, shutter_speed, f_number)) = self.get_exposure() {
+            let mut path_trace_camera = path_trace_camera.write().unwrap();
+            let exposure = path_trace_camera.get_exposure_mut();
+            exposure.set_enabled(true);
+            exposure.set_iso(iso);
+            exposure.set_shutter_speed(shutter_speed);
+            exposure.set_f_number(f_number);
+        }
+        if let Some(reference_ev100) = self.get_exposure_reference() {
+            path_trace_camera
+                .write()
+                .unwrap()
+                .get_exposure_mut()
+                .set_reference_ev100(reference_ev100);
+        }
+        if let Some((temperature, tint)) = self.get_white_balance() {
+            let mut path_trace_camera = path_trace_camera.write().unwrap();
+            let white_balance = path_trace_camera.get_white_balance_mut();
+            white_balance.set_enabled(true);
+            white_balance.set_temperature(temperature);
+            white_balance.set_tint(tint);
+        }
+
         // add more textures to texture_list if provided in the arguments
         self.get_textures().iter().for_each(|path| {
-            texture_list.write().unwrap().add_texture(
-                TextureRGBAFloat::load_from_disk(path).unwrap_or_else(|| {
-                    panic!(
-                        "could not load the texture from specified path: {}",
-                        path.to_str().unwrap()
-                    )
-                }),
-            );
+            texture_list
+                .write()
+                .unwrap()
+                .add_texture(TextureRGBAFloat::from_image(
+                    &Image::load_from_disk(path).unwrap_or_else(|err| {
+                        panic!(
+                            "could not load the texture from specified path: {}: {}",
+                            path.to_str().unwrap(),
+                            err
+                        )
+                    }),
+                ));
         });
 
         // assign texture to shader
@@ -497,7 +935,7 @@ impl InputArguments {
                     .find(|shader| shader.get_shader_name() == shader_name)
                     .unwrap_or_else(|| panic!("no shader found with shader name: {}", shader_name))
                     .get_bsdf_mut()
-                    .set_base_color(ColorPicker::Texture(Some(texture_id)));
+                    .set_base_color(ColorPicker::Texture(TextureSlot::with_texture(texture_id)));
             });
 
         // add more objects to the scene (loading obj files)
@@ -505,7 +943,8 @@ impl InputArguments {
             self.get_obj_files().iter().for_each(|obj_file_path| {
                 crate::load_meshes(obj_file_path)
                     .drain(0..)
-                    .for_each(|object| {
+                    .for_each(|mut object| {
+                        self.set_deformation_keys(&mut object);
                         scene.write().unwrap().add_object(Box::new(object));
                     });
             });
@@ -554,6 +993,10 @@ impl InputArguments {
                     .unwrap()
                     .into_inner()
                     .unwrap(),
+                self.get_shutter_open()
+                    .unwrap_or_else(crate::default_shutter_open),
+                self.get_shutter_close()
+                    .unwrap_or_else(crate::default_shutter_close),
                 Arc::new(RwLock::new(Image::new(1, 1))),
             ),
             scene,
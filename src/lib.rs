@@ -5,8 +5,10 @@ pub mod embree;
 pub mod file;
 pub mod fps;
 pub mod icons;
+pub mod ies;
 pub mod image;
 pub mod inputs;
+pub mod light;
 pub mod math;
 pub mod mesh;
 pub mod meshio;
@@ -28,13 +30,13 @@ pub(crate) use egui_glfw::egui;
 extern crate image as external_image;
 
 pub use nalgebra_glm as glm;
-use quick_renderer::camera::Camera;
 
 use std::{
     convert::TryInto,
     sync::{Arc, RwLock},
 };
 
+use camera::PathTraceCamera;
 use mesh::MeshUseShader;
 use meshio::MeshIO;
 use object::Object;
@@ -45,7 +47,7 @@ pub struct UiData {
     scene: Arc<RwLock<Scene>>,
     shader_list: Arc<RwLock<ShaderList>>,
     texture_list: Arc<RwLock<TextureList>>,
-    camera: Arc<RwLock<Camera>>,
+    camera: Arc<RwLock<PathTraceCamera>>,
     environment: Arc<RwLock<Environment>>,
 }
 
@@ -54,7 +56,7 @@ impl UiData {
         scene: Arc<RwLock<Scene>>,
         shader_list: Arc<RwLock<ShaderList>>,
         texture_list: Arc<RwLock<TextureList>>,
-        camera: Arc<RwLock<Camera>>,
+        camera: Arc<RwLock<PathTraceCamera>>,
         environment: Arc<RwLock<Environment>>,
     ) -> Self {
         Self {
@@ -82,7 +84,7 @@ impl UiData {
     }
 
     /// Get a reference to the ui data's camera.
-    pub fn get_camera(&self) -> &Arc<RwLock<Camera>> {
+    pub fn get_camera(&self) -> &Arc<RwLock<PathTraceCamera>> {
         &self.camera
     }
 
@@ -112,6 +114,14 @@ pub fn default_environment_strength() -> f64 {
     1.0
 }
 
+pub fn default_shutter_open() -> f64 {
+    0.0
+}
+
+pub fn default_shutter_close() -> f64 {
+    0.0
+}
+
 use crate::object::objects::Mesh as MeshObject;
 
 pub fn load_meshes<P>(path: P) -> Vec<MeshObject>
@@ -149,10 +159,16 @@ where
 
 /// Save image to disk, based on the extension picks the correct file format.
 ///
+/// The alpha of the image is saved as well. The pixels of a rendered
+/// image are premultiplied by the alpha, they are saved as is to
+/// `exr` files and converted to straight alpha for the other file
+/// formats (`png`, `tiff`, etc.).
+///
 /// # Note
 ///
 /// `linear_to_srgb` conversion is not done when saving to `image`
-/// file format (the custom file format)
+/// file format (the custom file format) and to `exr` file format
+/// since these store linear values
 pub fn save_image<P>(image: &image::Image, linear_to_srgb: bool, path: P)
 where
     P: AsRef<std::path::Path>,
@@ -162,7 +178,12 @@ where
             image.width().try_into().unwrap(),
             image.height().try_into().unwrap(),
             |i, j| {
-                let pixel = image.get_pixel(i.try_into().unwrap(), j.try_into().unwrap());
+                let (i, j) = (i.try_into().unwrap(), j.try_into().unwrap());
+                let pixel = image.get_pixel(i, j);
+                let alpha = image.get_alpha(i, j);
+
+                // convert to straight alpha
+                let pixel = if alpha > 0.0 { pixel / alpha } else { *pixel };
                 let pixel = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
 
                 let pixel = if linear_to_srgb {
@@ -179,7 +200,7 @@ where
                     (pixel[0] * 255.0).round(),
                     (pixel[1] * 255.0).round(),
                     (pixel[2] * 255.0).round(),
-                    255.0,
+                    (alpha.clamp(0.0, 1.0) as f32 * 255.0).round(),
                 ];
 
                 external_image::Rgba([
@@ -194,6 +215,19 @@ where
         image.save(&path).unwrap();
     };
 
+    let save_to_exr_format = || {
+        exr::prelude::write_rgba_file(&path, image.width(), image.height(), |i, j| {
+            let pixel = image.get_pixel(i, j);
+            (
+                pixel[0] as f32,
+                pixel[1] as f32,
+                pixel[2] as f32,
+                image.get_alpha(i, j) as f32,
+            )
+        })
+        .unwrap();
+    };
+
     let save_to_custom_format = || {
         let file = serde_json::to_string(image).unwrap();
         std::fs::write(&path, file).unwrap();
@@ -202,6 +236,8 @@ where
     if let Some(extension) = path.as_ref().extension() {
         if extension == "image" {
             save_to_custom_format();
+        } else if extension == "exr" {
+            save_to_exr_format();
         } else {
             save_to_generic_format();
         }
@@ -209,3 +245,66 @@ where
         save_to_custom_format();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Image with a partially transparent, a fully transparent and an
+    /// opaque pixel, premultiplied by the alpha like rendered images.
+    fn get_image_with_alpha() -> image::Image {
+        let mut image = image::Image::from_pixels(
+            3,
+            1,
+            vec![
+                glm::vec3(0.2, 0.4, 0.1),
+                glm::zero(),
+                glm::vec3(0.3, 0.6, 0.9),
+            ],
+        );
+        image.set_alpha(0, 0, 0.4);
+        image.set_alpha(1, 0, 0.0);
+        image.set_alpha(2, 0, 1.0);
+        image
+    }
+
+    /// Save the image to a temporary file with the given extension and
+    /// load it back.
+    fn save_and_load_image(image: &image::Image, extension: &str) -> image::Image {
+        let path = std::env::temp_dir().join(format!(
+            "rt_save_image_test_{}.{}",
+            rand::random::<u64>(),
+            extension
+        ));
+        save_image(image, false, &path);
+        let loaded_image = image::Image::load_from_disk(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded_image.unwrap()
+    }
+
+    #[test]
+    fn save_image_alpha_test_01() {
+        // png stores straight alpha with 8 bits per channel
+        let image = save_and_load_image(&get_image_with_alpha(), "png");
+        assert!(image.has_alpha());
+        let tolerance = 1.0 / 255.0;
+        assert!((image.get_alpha(0, 0) - 0.4).abs() < tolerance);
+        assert!(glm::distance(image.get_pixel(0, 0), &glm::vec3(0.5, 1.0, 0.25)) < tolerance);
+        assert_eq!(image.get_alpha(1, 0), 0.0);
+        assert_eq!(*image.get_pixel(1, 0), glm::zero());
+        assert_eq!(image.get_alpha(2, 0), 1.0);
+        assert!(glm::distance(image.get_pixel(2, 0), &glm::vec3(0.3, 0.6, 0.9)) < tolerance);
+    }
+
+    #[test]
+    fn save_image_alpha_test_02() {
+        // exr stores premultiplied alpha as single precision floats
+        let expected_image = get_image_with_alpha();
+        let image = save_and_load_image(&expected_image, "exr");
+        assert!(image.has_alpha());
+        (0..3).for_each(|i| {
+            assert!((image.get_alpha(i, 0) - expected_image.get_alpha(i, 0)).abs() < 1e-7);
+            assert!(glm::distance(image.get_pixel(i, 0), expected_image.get_pixel(i, 0)) < 1e-7);
+        });
+    }
+}
@@ -0,0 +1,1246 @@
+use quick_renderer::{
+    drawable::{Drawable, NoSpecificDrawError},
+    gpu_immediate::{GPUImmediate, GPUPrimType, GPUVertCompType, GPUVertFetchMode},
+    shader,
+};
+use serde::{Deserialize, Serialize};
+
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{
+    blend, egui, glm,
+    ies::{self, IESProfile},
+    math,
+    object::ObjectID,
+    path_trace::ray::Ray,
+    transform::Transform,
+    ui,
+    ui::DrawUI,
+    util, UiData,
+};
+
+/// A unique identifier given to each `Light` during its addition to
+/// the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LightID(usize);
+
+impl LightID {
+    /// # Safety
+    ///
+    /// The actual usize stored can be random, so doesn't make sense
+    /// to create it from raw most of the time. Only Scene must create
+    /// a LightID from raw.
+    pub unsafe fn from_raw(id: usize) -> Self {
+        Self(id)
+    }
+}
+
+/// Set of objects illuminated by a [`Light`] or by an object with an
+/// emissive shader (emitter).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightLinking {
+    /// All objects are illuminated.
+    All,
+    /// Only the given objects are illuminated.
+    Include(HashSet<ObjectID>),
+    /// All objects other than the given objects are illuminated.
+    Exclude(HashSet<ObjectID>),
+}
+
+impl Default for LightLinking {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+impl LightLinking {
+    pub fn all_names() -> impl Iterator<Item = &'static str> {
+        ["All", "Include", "Exclude"].iter().copied()
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LightLinking::All => "All",
+            LightLinking::Include(_) => "Include",
+            LightLinking::Exclude(_) => "Exclude",
+        }
+    }
+
+    /// Check if the object with the given id is illuminated.
+    pub fn illuminates(&self, object_id: ObjectID) -> bool {
+        match self {
+            LightLinking::All => true,
+            LightLinking::Include(objects) => objects.contains(&object_id),
+            LightLinking::Exclude(objects) => !objects.contains(&object_id),
+        }
+    }
+}
+
+pub struct LightLinkingUiData {
+    id: egui::Id,
+    /// id and name of all the objects of the scene
+    objects: Vec<(ObjectID, String)>,
+}
+
+impl LightLinkingUiData {
+    pub fn new(id: egui::Id, objects: Vec<(ObjectID, String)>) -> Self {
+        Self { id, objects }
+    }
+}
+
+impl DrawUI for LightLinking {
+    type ExtraData = LightLinkingUiData;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        let mut name = self.get_name();
+        egui::ComboBox::from_id_source(extra_data.id.with("Light Linking"))
+            .selected_text(format!("Light Linking: {}", name))
+            .show_ui(ui, |ui| {
+                Self::all_names().for_each(|linking_name| {
+                    ui.selectable_value(&mut name, linking_name, linking_name);
+                });
+            });
+        if name != self.get_name() {
+            // keep the objects when switching between include and
+            // exclude
+            let objects = match self {
+                LightLinking::All => HashSet::new(),
+                LightLinking::Include(objects) | LightLinking::Exclude(objects) => {
+                    std::mem::take(objects)
+                }
+            };
+            *self = match name {
+                "Include" => LightLinking::Include(objects),
+                "Exclude" => LightLinking::Exclude(objects),
+                _ => LightLinking::All,
+            };
+        }
+
+        if let LightLinking::Include(objects) | LightLinking::Exclude(objects) = self {
+            extra_data
+                .objects
+                .iter()
+                .for_each(|(object_id, object_name)| {
+                    let mut linked = objects.contains(object_id);
+                    if ui.checkbox(&mut linked, object_name).changed() {
+                        if linked {
+                            objects.insert(*object_id);
+                        } else {
+                            objects.remove(object_id);
+                        }
+                    }
+                });
+        }
+    }
+}
+
+/// Type of the light along with the settings specific to the type.
+///
+/// Lights are oriented along the -Z axis of their transform (same as
+/// Blender), area lights lie on the XY plane and emit towards -Z.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LightType {
+    /// Light emitted equally in all directions from a sphere of the
+    /// given radius, a radius of 0.0 makes it a point.
+    Point { radius: f64 },
+    /// Point light restricted to a cone, `cone_angle` is the full
+    /// angle (in radians) of the cone and `blend` (0.0 to 1.0) is the
+    /// fraction of the cone over which the light fades out.
+    Spot {
+        radius: f64,
+        cone_angle: f64,
+        blend: f64,
+    },
+    /// Light from infinitely far away, `angular_diameter` (in
+    /// radians) is the angle subtended by the light, 0.0 makes it
+    /// perfectly parallel.
+    Directional { angular_diameter: f64 },
+    /// Rectangle of the given size on the XY plane.
+    Rectangle { size_x: f64, size_y: f64 },
+    /// Disk of the given radius on the XY plane.
+    Disk { radius: f64 },
+}
+
+impl LightType {
+    pub fn all_names() -> impl Iterator<Item = &'static str> {
+        ["Point", "Spot", "Directional", "Rectangle", "Disk"]
+            .iter()
+            .copied()
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LightType::Point { .. } => "Point",
+            LightType::Spot { .. } => "Spot",
+            LightType::Directional { .. } => "Directional",
+            LightType::Rectangle { .. } => "Rectangle",
+            LightType::Disk { .. } => "Disk",
+        }
+    }
+
+    /// Create the light type with default settings from the given
+    /// name, see [`Self::all_names()`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Point" => Some(Self::Point { radius: 0.1 }),
+            "Spot" => Some(Self::Spot {
+                radius: 0.1,
+                cone_angle: 45.0_f64.to_radians(),
+                blend: 0.15,
+            }),
+            "Directional" => Some(Self::Directional {
+                angular_diameter: 0.526_f64.to_radians(),
+            }),
+            "Rectangle" => Some(Self::Rectangle {
+                size_x: 1.0,
+                size_y: 1.0,
+            }),
+            "Disk" => Some(Self::Disk { radius: 0.5 }),
+            _ => None,
+        }
+    }
+}
+
+/// Light sampled from a point by [`Light::sample()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    /// direction from the point towards the light (normalized)
+    direction: glm::DVec3,
+    /// distance to the light along `direction`, infinite for
+    /// directional lights
+    distance: f64,
+    /// radiance arriving at the point, for delta lights (point
+    /// without radius, directional without angular diameter) it is
+    /// the irradiance (perpendicular to `direction`)
+    radiance: glm::DVec3,
+    /// probability density (with respect to solid angle) of sampling
+    /// `direction`, 1.0 for delta lights
+    pdf: f64,
+    /// true if the light can only be reached by sampling it, so
+    /// multiple importance sampling must not be done
+    is_delta: bool,
+}
+
+impl LightSample {
+    /// Get light sample's direction.
+    pub fn get_direction(&self) -> &glm::DVec3 {
+        &self.direction
+    }
+
+    /// Get light sample's distance.
+    pub fn get_distance(&self) -> f64 {
+        self.distance
+    }
+
+    /// Get light sample's radiance.
+    pub fn get_radiance(&self) -> &glm::DVec3 {
+        &self.radiance
+    }
+
+    /// Get light sample's pdf.
+    pub fn get_pdf(&self) -> f64 {
+        self.pdf
+    }
+
+    /// Get light sample's is delta.
+    pub fn get_is_delta(&self) -> bool {
+        self.is_delta
+    }
+}
+
+/// Analytic light source, unlike objects with an emissive shader it
+/// has no geometry in the scene, it is sampled directly by the
+/// integrator and never occludes other rays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Light {
+    name: String,
+    light_type: LightType,
+    /// colour of the light, scaled by `power`
+    color: glm::DVec3,
+    /// total power emitted by the light (in Watts), for directional
+    /// lights it is the irradiance (in Watts per square meter)
+    /// perpendicular to the light
+    power: f64,
+    /// true if the light is seen by the rays from the camera
+    #[serde(default = "default_visible_to_camera")]
+    visible_to_camera: bool,
+    /// objects illuminated by the light
+    #[serde(default)]
+    light_linking: LightLinking,
+    /// shapes the emission of the light, the profile points along
+    /// the direction of the light, ignored by directional lights
+    #[serde(default)]
+    ies_profile: Option<IESProfile>,
+    transform: Transform,
+}
+
+fn default_visible_to_camera() -> bool {
+    true
+}
+
+impl Light {
+    pub fn new(
+        name: String,
+        light_type: LightType,
+        color: glm::DVec3,
+        power: f64,
+        transform: Transform,
+    ) -> Self {
+        Self {
+            name,
+            light_type,
+            color,
+            power,
+            visible_to_camera: default_visible_to_camera(),
+            light_linking: LightLinking::default(),
+            ies_profile: None,
+            transform,
+        }
+    }
+
+    /// Create new light from the Light Object of a Blend file
+    ///
+    /// If the given object is not a light, [`None`] is returned.
+    pub fn from_blend(object: &blend::object::Object) -> Option<Self> {
+        let light_data = match object.get_data()? {
+            blend::id::IDObject::Light(data) => data,
+            _ => return None,
+        };
+
+        let obmat: glm::DMat4 = glm::convert(glm::make_mat4(object.get_obmat()));
+        let obmat = util::axis_conversion_matrix_from_blender() * obmat;
+
+        let light_type = match light_data.get_light_type() {
+            blend::light::Type::Point => LightType::Point {
+                radius: light_data.get_radius().into(),
+            },
+            blend::light::Type::Spot => LightType::Spot {
+                radius: light_data.get_radius().into(),
+                cone_angle: light_data.get_spotsize().into(),
+                blend: light_data.get_spotblend().into(),
+            },
+            blend::light::Type::Sun => LightType::Directional {
+                angular_diameter: light_data.get_sun_angle().into(),
+            },
+            blend::light::Type::Area => match light_data.get_area_shape() {
+                blend::light::AreaShape::Square => LightType::Rectangle {
+                    size_x: light_data.get_area_size().into(),
+                    size_y: light_data.get_area_size().into(),
+                },
+                blend::light::AreaShape::Rectangle => LightType::Rectangle {
+                    size_x: light_data.get_area_size().into(),
+                    size_y: light_data.get_area_sizey().into(),
+                },
+                blend::light::AreaShape::Disk => LightType::Disk {
+                    radius: f64::from(light_data.get_area_size()) * 0.5,
+                },
+                // ellipses are approximated by a disk of the larger
+                // of the two sizes
+                blend::light::AreaShape::Ellipse => LightType::Disk {
+                    radius: f64::from(light_data.get_area_size().max(light_data.get_area_sizey()))
+                        * 0.5,
+                },
+            },
+        };
+
+        let color = light_data.get_color();
+        let mut light = Self::new(
+            object.get_id().get_name()[2..].to_string(),
+            light_type,
+            glm::vec3(color[0].into(), color[1].into(), color[2].into()),
+            light_data.get_energy().into(),
+            Transform::from_matrix(&obmat),
+        );
+        // the light is visible to the camera in Blender only if it has
+        // a surface
+        light.set_visible_to_camera(!matches!(
+            light_type,
+            LightType::Point { .. } | LightType::Spot { .. } | LightType::Directional { .. }
+        ));
+        Some(light)
+    }
+
+    /// Get a reference to the light's name.
+    pub fn get_name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Get a mutable reference to the light's name.
+    pub fn get_name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+
+    /// Get a reference to the light's light type.
+    pub fn get_light_type(&self) -> &LightType {
+        &self.light_type
+    }
+
+    /// Set the light's light type.
+    pub fn set_light_type(&mut self, light_type: LightType) {
+        self.light_type = light_type;
+    }
+
+    /// Get a reference to the light's color.
+    pub fn get_color(&self) -> &glm::DVec3 {
+        &self.color
+    }
+
+    /// Set the light's color.
+    pub fn set_color(&mut self, color: glm::DVec3) {
+        self.color = color;
+    }
+
+    /// Get light's power.
+    pub fn get_power(&self) -> f64 {
+        self.power
+    }
+
+    /// Set the light's power.
+    pub fn set_power(&mut self, power: f64) {
+        self.power = power;
+    }
+
+    /// Get light's visible to camera.
+    pub fn get_visible_to_camera(&self) -> bool {
+        self.visible_to_camera
+    }
+
+    /// Set the light's visible to camera.
+    pub fn set_visible_to_camera(&mut self, visible_to_camera: bool) {
+        self.visible_to_camera = visible_to_camera;
+    }
+
+    /// Get a reference to the light's light linking.
+    pub fn get_light_linking(&self) -> &LightLinking {
+        &self.light_linking
+    }
+
+    /// Get a mutable reference to the light's light linking.
+    pub fn get_light_linking_mut(&mut self) -> &mut LightLinking {
+        &mut self.light_linking
+    }
+
+    /// Get a reference to the light's transform.
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Get a mutable reference to the light's transform.
+    pub fn get_transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    /// Get a reference to the light's ies profile.
+    pub fn get_ies_profile(&self) -> Option<&IESProfile> {
+        self.ies_profile.as_ref()
+    }
+
+    /// Set the light's ies profile.
+    pub fn set_ies_profile(&mut self, ies_profile: Option<IESProfile>) {
+        self.ies_profile = ies_profile;
+    }
+
+    /// Get the position of the light.
+    pub fn get_position(&self) -> glm::DVec3 {
+        self.transform.location
+    }
+
+    /// Get the direction the light is pointing towards, -Z axis of
+    /// the transform.
+    pub fn get_direction(&self) -> glm::DVec3 {
+        (glm::mat4_to_mat3(&self.transform.get_matrix()) * glm::vec3(0.0, 0.0, -1.0)).normalize()
+    }
+
+    /// Get the half extents (the X and Y axes of the transform scaled
+    /// by half the size) of area lights.
+    fn get_area_axes(&self) -> Option<(glm::DVec3, glm::DVec3)> {
+        let (half_x, half_y) = match self.light_type {
+            LightType::Rectangle { size_x, size_y } => (size_x * 0.5, size_y * 0.5),
+            LightType::Disk { radius } => (radius, radius),
+            _ => return None,
+        };
+        let linear = glm::mat4_to_mat3(&self.transform.get_matrix());
+        Some((
+            linear * glm::vec3(half_x, 0.0, 0.0),
+            linear * glm::vec3(0.0, half_y, 0.0),
+        ))
+    }
+
+    /// Get the area of area lights.
+    fn get_area(&self, axis_x: &glm::DVec3, axis_y: &glm::DVec3) -> f64 {
+        let parallelogram_area = glm::cross(axis_x, axis_y).norm();
+        match self.light_type {
+            LightType::Rectangle { .. } => 4.0 * parallelogram_area,
+            LightType::Disk { .. } => std::f64::consts::PI * parallelogram_area,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Attenuation of spot lights for the given direction from the
+    /// light, 1.0 for other lights.
+    fn get_spot_attenuation(&self, direction_from_light: &glm::DVec3) -> f64 {
+        match self.light_type {
+            LightType::Spot {
+                cone_angle, blend, ..
+            } => {
+                let cos_half_angle = (cone_angle * 0.5).cos();
+                let cos_theta = glm::dot(direction_from_light, &self.get_direction());
+                if cos_theta <= cos_half_angle {
+                    return 0.0;
+                }
+                let smooth = (1.0 - cos_half_angle) * blend;
+                let t = cos_theta - cos_half_angle;
+                if t < smooth && smooth > 0.0 {
+                    let t = t / smooth;
+                    t * t * (3.0 - 2.0 * t)
+                } else {
+                    1.0
+                }
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Intensity of the IES profile for the given direction from the
+    /// light, 1.0 if the light has no IES profile.
+    fn get_ies_attenuation(&self, direction_from_light: &glm::DVec3) -> f64 {
+        self.ies_profile.as_ref().map_or(1.0, |ies_profile| {
+            let axis_x = (glm::mat4_to_mat3(&self.transform.get_matrix())
+                * glm::vec3(1.0, 0.0, 0.0))
+            .normalize();
+            ies_profile.eval_direction(direction_from_light, &self.get_direction(), &axis_x)
+        })
+    }
+
+    /// Sample the light from the given point.
+    ///
+    /// Returns `None` if the light cannot illuminate the point.
+    pub fn sample(&self, point: &glm::DVec3) -> Option<LightSample> {
+        let color = self.color * self.power;
+        match self.light_type {
+            LightType::Point { radius } | LightType::Spot { radius, .. } => {
+                let center = self.get_position();
+                let to_center = center - point;
+                let distance = to_center.norm();
+                if distance <= radius || distance == 0.0 {
+                    return None;
+                }
+                let axis = to_center / distance;
+                let attenuation =
+                    self.get_spot_attenuation(&-axis) * self.get_ies_attenuation(&-axis);
+                if attenuation == 0.0 {
+                    return None;
+                }
+
+                if radius <= 0.0 {
+                    // intensity of an isotropic point light
+                    return Some(LightSample {
+                        direction: axis,
+                        distance,
+                        radiance: color * attenuation
+                            / (4.0 * std::f64::consts::PI * distance * distance),
+                        pdf: 1.0,
+                        is_delta: true,
+                    });
+                }
+
+                // uniformly sample the cone subtended by the sphere
+                let (_, one_minus_cos_max) = sphere_cone(radius, distance);
+                let cos_theta = 1.0 - rand::random::<f64>() * one_minus_cos_max;
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+                let (tangent, bitangent) = util::orthonormal_basis(&axis);
+                let direction = (axis * cos_theta
+                    + (tangent * phi.cos() + bitangent * phi.sin()) * sin_theta)
+                    .normalize();
+
+                Some(LightSample {
+                    direction,
+                    distance: ray_sphere_distance(point, &direction, &center, radius)
+                        .unwrap_or(distance - radius),
+                    radiance: color * attenuation * self.get_sphere_radiance_scale(radius),
+                    pdf: 1.0 / (2.0 * std::f64::consts::PI * one_minus_cos_max),
+                    is_delta: false,
+                })
+            }
+            LightType::Directional { angular_diameter } => {
+                let axis = -self.get_direction();
+                if angular_diameter <= 0.0 {
+                    return Some(LightSample {
+                        direction: axis,
+                        distance: f64::INFINITY,
+                        radiance: color,
+                        pdf: 1.0,
+                        is_delta: true,
+                    });
+                }
+
+                let one_minus_cos_max = directional_one_minus_cos_max(angular_diameter);
+                let cos_theta = 1.0 - rand::random::<f64>() * one_minus_cos_max;
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+                let (tangent, bitangent) = util::orthonormal_basis(&axis);
+                let direction = (axis * cos_theta
+                    + (tangent * phi.cos() + bitangent * phi.sin()) * sin_theta)
+                    .normalize();
+                let pdf = 1.0 / (2.0 * std::f64::consts::PI * one_minus_cos_max);
+
+                Some(LightSample {
+                    direction,
+                    distance: f64::INFINITY,
+                    // irradiance is spread over the solid angle of
+                    // the light
+                    radiance: color * pdf,
+                    pdf,
+                    is_delta: false,
+                })
+            }
+            LightType::Rectangle { .. } | LightType::Disk { .. } => {
+                let (axis_x, axis_y) = self.get_area_axes()?;
+                let area = self.get_area(&axis_x, &axis_y);
+                if area <= 0.0 {
+                    return None;
+                }
+                let local = match self.light_type {
+                    LightType::Rectangle { .. } => glm::vec2(
+                        rand::random::<f64>() * 2.0 - 1.0,
+                        rand::random::<f64>() * 2.0 - 1.0,
+                    ),
+                    _ => math::random_in_unit_disk(),
+                };
+                let light_point = self.get_position() + axis_x * local[0] + axis_y * local[1];
+
+                let to_light = light_point - point;
+                let distance = to_light.norm();
+                if distance == 0.0 {
+                    return None;
+                }
+                let direction = to_light / distance;
+                let cos_light = -glm::dot(&direction, &self.get_direction());
+                if cos_light <= 0.0 {
+                    return None;
+                }
+
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: color * self.get_ies_attenuation(&-direction)
+                        / (std::f64::consts::PI * area),
+                    pdf: distance * distance / (cos_light * area),
+                    is_delta: false,
+                })
+            }
+        }
+    }
+
+    /// Scale from power to radiance of a point (or spot) light with
+    /// the given radius. The sphere is treated as a disk facing the
+    /// point being lit so that its intensity matches that of an
+    /// isotropic point light of the same power.
+    fn get_sphere_radiance_scale(&self, radius: f64) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI * std::f64::consts::PI * radius * radius)
+    }
+
+    /// Intersect the given ray with the light (within `t_min` and
+    /// `t_max`), returns the `t` of the intersection and the radiance
+    /// of the light seen by the ray. Directional lights are hit only
+    /// if `t_max` is infinite.
+    ///
+    /// Lights with no area (point without radius, directional without
+    /// angular diameter) can never be hit.
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, glm::DVec3)> {
+        let color = self.color * self.power;
+        match self.light_type {
+            LightType::Point { radius } | LightType::Spot { radius, .. } => {
+                if radius <= 0.0 {
+                    return None;
+                }
+                let center = self.get_position();
+                let direction_length = ray.get_direction().norm();
+                let direction = ray.get_direction() / direction_length;
+                let t = ray_sphere_distance(ray.get_origin(), &direction, &center, radius)?
+                    / direction_length;
+                if t <= t_min || t >= t_max {
+                    return None;
+                }
+                let direction_from_light = (ray.get_origin() - center).normalize();
+                let attenuation = self.get_spot_attenuation(&direction_from_light)
+                    * self.get_ies_attenuation(&direction_from_light);
+                Some((
+                    t,
+                    color * attenuation * self.get_sphere_radiance_scale(radius),
+                ))
+            }
+            LightType::Directional { angular_diameter } => {
+                if angular_diameter <= 0.0 || t_max.is_finite() {
+                    return None;
+                }
+                let one_minus_cos_max = directional_one_minus_cos_max(angular_diameter);
+                let cos_theta = -glm::dot(&ray.get_direction().normalize(), &self.get_direction());
+                if 1.0 - cos_theta > one_minus_cos_max {
+                    return None;
+                }
+                Some((
+                    f64::INFINITY,
+                    color / (2.0 * std::f64::consts::PI * one_minus_cos_max),
+                ))
+            }
+            LightType::Rectangle { .. } | LightType::Disk { .. } => {
+                let (axis_x, axis_y) = self.get_area_axes()?;
+                let area = self.get_area(&axis_x, &axis_y);
+                let t = self.hit_area(ray.get_origin(), ray.get_direction(), &axis_x, &axis_y)?;
+                if area <= 0.0 || t <= t_min || t >= t_max {
+                    return None;
+                }
+                Some((
+                    t,
+                    color * self.get_ies_attenuation(&-ray.get_direction().normalize())
+                        / (std::f64::consts::PI * area),
+                ))
+            }
+        }
+    }
+
+    /// Intersect the ray with the front side of the area light,
+    /// returns the `t` of the intersection.
+    fn hit_area(
+        &self,
+        origin: &glm::DVec3,
+        direction: &glm::DVec3,
+        axis_x: &glm::DVec3,
+        axis_y: &glm::DVec3,
+    ) -> Option<f64> {
+        let normal = self.get_direction();
+        let denominator = glm::dot(direction, &normal);
+        // only the front side emits light
+        if denominator >= 0.0 {
+            return None;
+        }
+        let center = self.get_position();
+        let t = glm::dot(&(center - origin), &normal) / denominator;
+        if t <= 0.0 {
+            return None;
+        }
+        let offset = origin + direction * t - center;
+        let x = glm::dot(&offset, axis_x) / axis_x.norm_squared();
+        let y = glm::dot(&offset, axis_y) / axis_y.norm_squared();
+        let inside = match self.light_type {
+            LightType::Rectangle { .. } => x.abs() <= 1.0 && y.abs() <= 1.0,
+            _ => x * x + y * y <= 1.0,
+        };
+        if inside {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /// Get the probability density (with respect to solid angle) of
+    /// [`Self::sample()`] returning the given direction (normalized)
+    /// from the given point.
+    pub fn pdf(&self, point: &glm::DVec3, direction: &glm::DVec3) -> f64 {
+        match self.light_type {
+            LightType::Point { radius } | LightType::Spot { radius, .. } => {
+                let to_center = self.get_position() - point;
+                let distance = to_center.norm();
+                if radius <= 0.0 || distance <= radius {
+                    return 0.0;
+                }
+                let (cos_max, one_minus_cos_max) = sphere_cone(radius, distance);
+                if glm::dot(direction, &(to_center / distance)) < cos_max {
+                    return 0.0;
+                }
+                1.0 / (2.0 * std::f64::consts::PI * one_minus_cos_max)
+            }
+            LightType::Directional { angular_diameter } => {
+                if angular_diameter <= 0.0 {
+                    return 0.0;
+                }
+                let one_minus_cos_max = directional_one_minus_cos_max(angular_diameter);
+                let cos_theta = -glm::dot(direction, &self.get_direction());
+                if 1.0 - cos_theta > one_minus_cos_max {
+                    return 0.0;
+                }
+                1.0 / (2.0 * std::f64::consts::PI * one_minus_cos_max)
+            }
+            LightType::Rectangle { .. } | LightType::Disk { .. } => {
+                let (axis_x, axis_y) = match self.get_area_axes() {
+                    Some(axes) => axes,
+                    None => return 0.0,
+                };
+                let area = self.get_area(&axis_x, &axis_y);
+                let cos_light = -glm::dot(direction, &self.get_direction());
+                match self.hit_area(point, direction, &axis_x, &axis_y) {
+                    Some(distance) if area > 0.0 && cos_light > 0.0 => {
+                        distance * distance / (cos_light * area)
+                    }
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+
+    /// Get the line segments used to draw the light in the viewport.
+    fn get_draw_lines(&self) -> Vec<(glm::DVec3, glm::DVec3)> {
+        const SEGMENTS: usize = 32;
+        let position = self.get_position();
+        let direction = self.get_direction();
+        let (tangent, bitangent) = util::orthonormal_basis(&direction);
+
+        let circle = |center: glm::DVec3, axis_x: glm::DVec3, axis_y: glm::DVec3| {
+            (0..SEGMENTS).map(move |i| {
+                let angle = |i: usize| 2.0 * std::f64::consts::PI * i as f64 / SEGMENTS as f64;
+                let point = |angle: f64| center + axis_x * angle.cos() + axis_y * angle.sin();
+                (point(angle(i)), point(angle(i + 1)))
+            })
+        };
+
+        let mut lines = Vec::new();
+        match self.light_type {
+            LightType::Point { radius } => {
+                let radius = radius.max(0.1);
+                let (x, y, z) = (
+                    glm::vec3(radius, 0.0, 0.0),
+                    glm::vec3(0.0, radius, 0.0),
+                    glm::vec3(0.0, 0.0, radius),
+                );
+                lines.extend(circle(position, x, y));
+                lines.extend(circle(position, y, z));
+                lines.extend(circle(position, z, x));
+            }
+            LightType::Spot {
+                radius, cone_angle, ..
+            } => {
+                lines.extend(circle(
+                    position,
+                    tangent * radius.max(0.05),
+                    bitangent * radius.max(0.05),
+                ));
+                // cone of unit length
+                let cone_radius = (cone_angle * 0.5).min(1.5).tan();
+                let cone_center = position + direction;
+                lines.extend(circle(
+                    cone_center,
+                    tangent * cone_radius,
+                    bitangent * cone_radius,
+                ));
+                lines.extend(
+                    [tangent, -tangent, bitangent, -bitangent]
+                        .iter()
+                        .map(|axis| (position, cone_center + axis * cone_radius)),
+                );
+            }
+            LightType::Directional { .. } => {
+                lines.extend(circle(position, tangent * 0.2, bitangent * 0.2));
+                lines.extend((0..4).map(|i| {
+                    let angle = std::f64::consts::FRAC_PI_2 * i as f64;
+                    let offset = (tangent * angle.cos() + bitangent * angle.sin()) * 0.2;
+                    (position + offset, position + offset + direction)
+                }));
+            }
+            LightType::Rectangle { .. } => {
+                if let Some((axis_x, axis_y)) = self.get_area_axes() {
+                    let corners = [
+                        position - axis_x - axis_y,
+                        position + axis_x - axis_y,
+                        position + axis_x + axis_y,
+                        position - axis_x + axis_y,
+                    ];
+                    lines.extend((0..4).map(|i| (corners[i], corners[(i + 1) % 4])));
+                }
+                lines.push((position, position + direction * 0.5));
+            }
+            LightType::Disk { .. } => {
+                if let Some((axis_x, axis_y)) = self.get_area_axes() {
+                    lines.extend(circle(position, axis_x, axis_y));
+                }
+                lines.push((position, position + direction * 0.5));
+            }
+        }
+        lines
+    }
+}
+
+/// Get the cosine (and 1 - cosine, computed without cancellation) of
+/// the half angle of the cone subtended by a sphere of the given
+/// radius at the given distance from its center.
+fn sphere_cone(radius: f64, distance: f64) -> (f64, f64) {
+    let sin2_max = (radius / distance).powi(2);
+    let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+    (cos_max, sin2_max / (1.0 + cos_max))
+}
+
+/// Get 1 - cosine of the half angle of the cone subtended by a
+/// directional light with the given angular diameter.
+fn directional_one_minus_cos_max(angular_diameter: f64) -> f64 {
+    // 1 - cos(x) = 2 sin^2(x / 2)
+    2.0 * (angular_diameter * 0.25).sin().powi(2)
+}
+
+/// Distance along the (normalized) direction from the origin to the
+/// first intersection with the sphere.
+fn ray_sphere_distance(
+    origin: &glm::DVec3,
+    direction: &glm::DVec3,
+    center: &glm::DVec3,
+    radius: f64,
+) -> Option<f64> {
+    let offset = origin - center;
+    let b = glm::dot(direction, &offset);
+    let c = offset.norm_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    let t = -b - sqrt_discriminant;
+    if t > 0.0 {
+        Some(t)
+    } else {
+        let t = -b + sqrt_discriminant;
+        if t > 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct LightDrawData {
+    imm: Rc<RefCell<GPUImmediate>>,
+}
+
+impl LightDrawData {
+    pub fn new(imm: Rc<RefCell<GPUImmediate>>) -> Self {
+        Self { imm }
+    }
+}
+
+impl Drawable for Light {
+    type ExtraData = LightDrawData;
+    type Error = NoSpecificDrawError;
+
+    fn draw(&self, extra_data: &Self::ExtraData) -> Result<(), Self::Error> {
+        let mut imm = extra_data.imm.borrow_mut();
+
+        let smooth_color_3d_shader = shader::builtins::get_smooth_color_3d_shader()
+            .as_ref()
+            .unwrap();
+        smooth_color_3d_shader.use_shader();
+        smooth_color_3d_shader.set_mat4("model\0", &glm::identity());
+
+        let format = imm.get_cleared_vertex_format();
+        let pos_attr = format.add_attribute(
+            "in_pos\0".to_string(),
+            GPUVertCompType::F32,
+            3,
+            GPUVertFetchMode::Float,
+        );
+        let color_attr = format.add_attribute(
+            "in_color\0".to_string(),
+            GPUVertCompType::F32,
+            4,
+            GPUVertFetchMode::Float,
+        );
+
+        // colour of the light normalized so that it is visible
+        // irrespective of the power
+        let max_component = self.color.max();
+        let color: glm::Vec3 = if max_component > 0.0 {
+            glm::convert(self.color / max_component)
+        } else {
+            glm::zero()
+        };
+
+        let lines = self.get_draw_lines();
+        imm.begin(GPUPrimType::Lines, lines.len() * 2, smooth_color_3d_shader);
+
+        lines.iter().for_each(|(p1, p2)| {
+            let p1: glm::Vec3 = glm::convert(*p1);
+            let p2: glm::Vec3 = glm::convert(*p2);
+
+            imm.attr_4f(color_attr, color[0], color[1], color[2], 1.0);
+            imm.vertex_3f(pos_attr, p1[0], p1[1], p1[2]);
+
+            imm.attr_4f(color_attr, color[0], color[1], color[2], 1.0);
+            imm.vertex_3f(pos_attr, p2[0], p2[1], p2[2]);
+        });
+
+        imm.end();
+
+        Ok(())
+    }
+
+    fn draw_wireframe(&self, extra_data: &Self::ExtraData) -> Result<(), Self::Error> {
+        self.draw(extra_data)
+    }
+}
+
+impl DrawUI for Light {
+    type ExtraData = UiData;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        ui.text_edit_singleline(&mut self.name);
+
+        let mut light_type_name = self.light_type.get_name();
+        egui::ComboBox::from_id_source(ui.id().with("Light Type"))
+            .selected_text(format!("Type: {}", light_type_name))
+            .show_ui(ui, |ui| {
+                LightType::all_names().for_each(|name| {
+                    ui.selectable_value(&mut light_type_name, name, name);
+                });
+            });
+        if light_type_name != self.light_type.get_name() {
+            self.light_type = LightType::from_name(light_type_name).unwrap();
+        }
+
+        let angle_slider = |ui: &mut egui::Ui, angle: &mut f64, text: &str| {
+            let mut degrees = angle.to_degrees();
+            ui.add(egui::Slider::new(&mut degrees, 0.0..=180.0).text(text));
+            *angle = degrees.to_radians();
+        };
+
+        match &mut self.light_type {
+            LightType::Point { radius } => {
+                ui.add(
+                    egui::Slider::new(radius, 0.0..=1.0)
+                        .clamp_to_range(false)
+                        .text("Radius"),
+                );
+            }
+            LightType::Spot {
+                radius,
+                cone_angle,
+                blend,
+            } => {
+                ui.add(
+                    egui::Slider::new(radius, 0.0..=1.0)
+                        .clamp_to_range(false)
+                        .text("Radius"),
+                );
+                angle_slider(ui, cone_angle, "Cone Angle");
+                ui.add(egui::Slider::new(blend, 0.0..=1.0).text("Blend"));
+            }
+            LightType::Directional { angular_diameter } => {
+                angle_slider(ui, angular_diameter, "Angular Diameter");
+            }
+            LightType::Rectangle { size_x, size_y } => {
+                ui.add(
+                    egui::Slider::new(size_x, 0.0..=10.0)
+                        .clamp_to_range(false)
+                        .text("Size X"),
+                );
+                ui.add(
+                    egui::Slider::new(size_y, 0.0..=10.0)
+                        .clamp_to_range(false)
+                        .text("Size Y"),
+                );
+            }
+            LightType::Disk { radius } => {
+                ui.add(
+                    egui::Slider::new(radius, 0.0..=10.0)
+                        .clamp_to_range(false)
+                        .text("Radius"),
+                );
+            }
+        }
+
+        ui::color_edit_button_dvec3(ui, "Color", &mut self.color);
+        ui.add(
+            egui::Slider::new(&mut self.power, 0.0..=1000.0)
+                .clamp_to_range(false)
+                .text(match self.light_type {
+                    LightType::Directional { .. } => "Strength (W/m²)",
+                    _ => "Power (W)",
+                }),
+        );
+        ui.checkbox(&mut self.visible_to_camera, "Visible to Camera");
+        if !matches!(self.light_type, LightType::Directional { .. }) {
+            ies::ies_profile_ui(ui, &mut self.ies_profile);
+        }
+
+        ui.collapsing("Transform", |ui| {
+            self.transform.draw_ui(ui, extra_data);
+            self.transform.draw_ui_mut(ui, extra_data);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_light(light_type: LightType, power: f64, transform: Transform) -> Light {
+        Light::new(
+            "light".to_string(),
+            light_type,
+            glm::vec3(1.0, 1.0, 1.0),
+            power,
+            transform,
+        )
+    }
+
+    /// Rotated and scaled transform, the lights do not rely on
+    /// pointing along an axis.
+    fn get_transform() -> Transform {
+        Transform {
+            location: glm::vec3(0.5, -0.25, 1.0),
+            rotation: glm::vec3(30.0, -20.0, 45.0),
+            scale: glm::vec3(1.5, 0.5, 1.0),
+        }
+    }
+
+    /// The probability density of the sampled directions must be the
+    /// same as the one given by `pdf()` for the direction.
+    fn assert_sample_pdf(light: &Light, point: &glm::DVec3) {
+        (0..1000).for_each(|_| {
+            let sample = light.sample(point).unwrap();
+            assert!(!sample.get_is_delta());
+            let pdf = light.pdf(point, sample.get_direction());
+            assert!(
+                (pdf - sample.get_pdf()).abs() < 1e-6 * sample.get_pdf(),
+                "{}: {} != {}",
+                light.get_light_type().get_name(),
+                pdf,
+                sample.get_pdf()
+            );
+        });
+    }
+
+    /// Power of the light flowing out of a sphere of the given radius
+    /// about the light, estimated from the light sampled at uniformly
+    /// distributed points on the sphere.
+    fn get_power_through_sphere(light: &Light, radius: f64) -> f64 {
+        let num_points = 200000;
+        let irradiance = (0..num_points)
+            .filter_map(|_| {
+                let normal = math::random_unit_vector();
+                let point = light.get_position() + normal * radius;
+                let sample = light.sample(&point)?;
+                // cosine with the inward normal of the sphere
+                let cos_theta = -glm::dot(sample.get_direction(), &normal);
+                Some(sample.get_radiance()[0] * cos_theta.max(0.0) / sample.get_pdf())
+            })
+            .sum::<f64>()
+            / num_points as f64;
+        4.0 * std::f64::consts::PI * radius * radius * irradiance
+    }
+
+    #[test]
+    fn light_point_test_01() {
+        let light = new_light(LightType::Point { radius: 0.5 }, 100.0, get_transform());
+        assert_sample_pdf(&light, &glm::vec3(2.0, 3.0, -1.0));
+
+        let power = get_power_through_sphere(&light, 3.0);
+        assert!((power - 100.0).abs() < 1.0, "{} != 100.0", power);
+
+        // a point light without radius is a delta light
+        let light = new_light(LightType::Point { radius: 0.0 }, 100.0, get_transform());
+        let sample = light.sample(&glm::vec3(2.0, 3.0, -1.0)).unwrap();
+        assert!(sample.get_is_delta());
+        assert_eq!(
+            light.pdf(&glm::vec3(2.0, 3.0, -1.0), sample.get_direction()),
+            0.0
+        );
+        let power = get_power_through_sphere(&light, 3.0);
+        assert!((power - 100.0).abs() < 1e-6, "{} != 100.0", power);
+    }
+
+    #[test]
+    fn light_spot_test_01() {
+        let light = new_light(
+            LightType::Spot {
+                radius: 0.2,
+                cone_angle: 90.0_f64.to_radians(),
+                blend: 0.15,
+            },
+            100.0,
+            get_transform(),
+        );
+        let point = light.get_position() + light.get_direction() * 2.0;
+        assert_sample_pdf(&light, &point);
+
+        // nothing outside the cone
+        let point = light.get_position() - light.get_direction() * 2.0;
+        assert!(light.sample(&point).is_none());
+    }
+
+    #[test]
+    fn light_directional_test_01() {
+        let angular_diameter = 5.0_f64.to_radians();
+        let light = new_light(
+            LightType::Directional { angular_diameter },
+            3.0,
+            get_transform(),
+        );
+        let point = glm::vec3(2.0, 3.0, -1.0);
+        assert_sample_pdf(&light, &point);
+
+        // the irradiance perpendicular to the light is the power,
+        // apart from the small falloff of the cosine within the disk
+        // of the sun
+        let num_samples = 100000;
+        let axis = -light.get_direction();
+        let irradiance = (0..num_samples)
+            .map(|_| {
+                let sample = light.sample(&point).unwrap();
+                sample.get_radiance()[0] * glm::dot(sample.get_direction(), &axis)
+                    / sample.get_pdf()
+            })
+            .sum::<f64>()
+            / num_samples as f64;
+        // the cosine weighted solid angle of the cone divided by its
+        // solid angle, (1 + cos(half angle)) / 2
+        let expected = 3.0 * (1.0 + (angular_diameter * 0.5).cos()) * 0.5;
+        assert!(
+            (irradiance - expected).abs() < 1e-4,
+            "{} != {}",
+            irradiance,
+            expected
+        );
+
+        // a sun without angular diameter is a delta light with the
+        // power as the irradiance
+        let light = new_light(
+            LightType::Directional {
+                angular_diameter: 0.0,
+            },
+            3.0,
+            get_transform(),
+        );
+        let sample = light.sample(&point).unwrap();
+        assert!(sample.get_is_delta());
+        assert_eq!(sample.get_radiance()[0], 3.0);
+    }
+
+    #[test]
+    fn light_rectangle_test_01() {
+        let light = new_light(
+            LightType::Rectangle {
+                size_x: 1.0,
+                size_y: 2.0,
+            },
+            50.0,
+            get_transform(),
+        );
+        let point = light.get_position() + light.get_direction() * 2.0 + glm::vec3(0.3, 0.2, 0.1);
+        assert_sample_pdf(&light, &point);
+
+        let power = get_power_through_sphere(&light, 5.0);
+        assert!((power - 50.0).abs() < 1.0, "{} != 50.0", power);
+    }
+
+    #[test]
+    fn light_disk_test_01() {
+        let light = new_light(LightType::Disk { radius: 0.75 }, 50.0, get_transform());
+        let point = light.get_position() + light.get_direction() * 2.0 + glm::vec3(0.3, 0.2, 0.1);
+        assert_sample_pdf(&light, &point);
+
+        let power = get_power_through_sphere(&light, 5.0);
+        assert!((power - 50.0).abs() < 1.0, "{} != 50.0", power);
+    }
+}
@@ -11,3 +11,366 @@ pub fn random_in_unit_sphere() -> glm::DVec3 {
         return p;
     }
 }
+
+/// Uniformly sample a direction, a point on the surface of the unit
+/// sphere.
+pub fn random_unit_vector() -> glm::DVec3 {
+    loop {
+        let p = random_in_unit_sphere();
+        let length = glm::length(&p);
+        if length < 1e-8 {
+            continue;
+        }
+        return p / length;
+    }
+}
+
+/// Uniformly sample a point on the unit disk using the concentric
+/// mapping (Shirley and Chiu) of a uniform sample in the unit
+/// square.
+pub fn random_in_unit_disk() -> glm::DVec2 {
+    let offset: glm::DVec2 = glm::vec2(random(), random()) * 2.0 - glm::vec2(1.0, 1.0);
+    if offset[0] == 0.0 && offset[1] == 0.0 {
+        return glm::zero();
+    }
+
+    let (r, theta) = if offset[0].abs() > offset[1].abs() {
+        (
+            offset[0],
+            std::f64::consts::FRAC_PI_4 * (offset[1] / offset[0]),
+        )
+    } else {
+        (
+            offset[1],
+            std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (offset[0] / offset[1]),
+        )
+    };
+
+    glm::vec2(theta.cos(), theta.sin()) * r
+}
+
+/// Uniformly sample a point within a regular polygon with `sides`
+/// number of sides inscribed in the unit circle. The first vertex of
+/// the polygon is at `rotation` (in radians) from the x axis.
+///
+/// If `sides` is less than 3, it is treated as a circle, see
+/// [`random_in_unit_disk()`].
+pub fn random_in_regular_polygon(sides: usize, rotation: f64) -> glm::DVec2 {
+    if sides < 3 {
+        return random_in_unit_disk();
+    }
+
+    // pick a triangle (formed with the center) of the polygon and
+    // sample within the triangle uniformly
+    let segment_angle = 2.0 * std::f64::consts::PI / sides as f64;
+    let segment = (random::<f64>() * sides as f64) as usize % sides;
+    let angle_1 = rotation + segment as f64 * segment_angle;
+    let angle_2 = angle_1 + segment_angle;
+    let v1 = glm::vec2(angle_1.cos(), angle_1.sin());
+    let v2 = glm::vec2(angle_2.cos(), angle_2.sin());
+
+    let (u, v) = {
+        let u: f64 = random();
+        let v: f64 = random();
+        if u + v > 1.0 {
+            (1.0 - u, 1.0 - v)
+        } else {
+            (u, v)
+        }
+    };
+
+    v1 * u + v2 * v
+}
+
+/// Piecewise constant 1D distribution of the given function values
+/// over \[0, 1). Allows sampling proportional to the function.
+///
+/// Based on pbrt's `Distribution1D`.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    func_integral: f64,
+}
+
+impl Distribution1D {
+    /// Create the distribution from the function values, the values
+    /// must not be negative.
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty());
+        let n = func.len();
+
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for i in 0..n {
+            cdf.push(cdf[i] + func[i] / n as f64);
+        }
+
+        let func_integral = cdf[n];
+        if func_integral == 0.0 {
+            // function is zero everywhere, sample uniformly
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, value)| *value = i as f64 / n as f64);
+        } else {
+            cdf.iter_mut().for_each(|value| *value /= func_integral);
+        }
+
+        Self {
+            func,
+            cdf,
+            func_integral,
+        }
+    }
+
+    /// Get the number of function values.
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Get the integral of the function over \[0, 1).
+    pub fn get_func_integral(&self) -> f64 {
+        self.func_integral
+    }
+
+    /// Get the function values.
+    pub fn get_func(&self) -> &[f64] {
+        &self.func
+    }
+
+    /// Get the index of the function value whose cdf range contains
+    /// `u`.
+    fn find_offset(&self, u: f64) -> usize {
+        // largest index such that cdf[index] <= u
+        self.cdf
+            .partition_point(|value| *value <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1)
+    }
+
+    /// Sample the distribution given the uniform random number `u`
+    /// in \[0, 1). Returns the sampled value in \[0, 1), its
+    /// probability density and the index of the function value it
+    /// lies in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let offset = self.find_offset(u);
+
+        let mut du = u - self.cdf[offset];
+        let cdf_range = self.cdf[offset + 1] - self.cdf[offset];
+        if cdf_range > 0.0 {
+            du /= cdf_range;
+        }
+
+        let pdf = if self.func_integral > 0.0 {
+            self.func[offset] / self.func_integral
+        } else {
+            0.0
+        };
+
+        (
+            ((offset as f64 + du) / self.count() as f64).min(1.0 - f64::EPSILON),
+            pdf,
+            offset,
+        )
+    }
+
+    /// Sample an index of the function values given the uniform
+    /// random number `u` in \[0, 1). Returns the sampled index and
+    /// its probability.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let offset = self.find_offset(u);
+        (offset, self.discrete_pdf(offset))
+    }
+
+    /// Get the probability of sampling the given index with
+    /// [`Self::sample_discrete()`].
+    pub fn discrete_pdf(&self, index: usize) -> f64 {
+        if self.func_integral > 0.0 {
+            self.func[index] / (self.func_integral * self.count() as f64)
+        } else {
+            1.0 / self.count() as f64
+        }
+    }
+}
+
+/// Piecewise constant 2D distribution of the given function values
+/// over \[0, 1)^2. The function values are stored row wise, the
+/// first dimension is along a row and the second dimension is across
+/// the rows.
+///
+/// Based on pbrt's `Distribution2D`.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Create the distribution from the function values stored row
+    /// wise with `width` number of values in each row.
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<_> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(
+            conditional
+                .iter()
+                .map(|distribution| distribution.get_func_integral())
+                .collect(),
+        );
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Get the integral of the function over \[0, 1)^2.
+    pub fn get_func_integral(&self) -> f64 {
+        self.marginal.get_func_integral()
+    }
+
+    /// Sample the distribution given the uniform random numbers `u`
+    /// in \[0, 1)^2. Returns the sampled point in \[0, 1)^2 and its
+    /// probability density.
+    pub fn sample_continuous(&self, u: &glm::DVec2) -> (glm::DVec2, f64) {
+        let (v, pdf_marginal, row) = self.marginal.sample_continuous(u[1]);
+        let (u, pdf_conditional, _) = self.conditional[row].sample_continuous(u[0]);
+        (glm::vec2(u, v), pdf_marginal * pdf_conditional)
+    }
+
+    /// Get the probability density of sampling the given point in
+    /// \[0, 1)^2.
+    pub fn pdf(&self, p: &glm::DVec2) -> f64 {
+        let row = ((p[1] * self.marginal.count() as f64) as usize).min(self.marginal.count() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((p[0] * conditional.count() as f64) as usize).min(conditional.count() - 1);
+        if self.marginal.get_func_integral() == 0.0 {
+            return 0.0;
+        }
+        conditional.get_func()[column] / self.marginal.get_func_integral()
+    }
+}
+
+/// Machine epsilon used for floating point error bounds. It is half
+/// of [`f64::EPSILON`] since IEEE floating point rounds to the
+/// nearest representable value.
+///
+/// Based on pbrt's `MachineEpsilon`.
+pub const MACHINE_EPSILON: f64 = f64::EPSILON * 0.5;
+
+/// Conservative bound of the relative error introduced by `n`
+/// successive floating point operations, `(1 ± ε)^n` is bounded by
+/// `1 ± gamma(n)`.
+///
+/// Based on pbrt's `gamma()`.
+pub fn gamma(n: usize) -> f64 {
+    let n = n as f64;
+    (n * MACHINE_EPSILON) / (1.0 - n * MACHINE_EPSILON)
+}
+
+/// Same as [`gamma()`] but for computations that are done with `f32`,
+/// for example by Embree.
+pub fn gamma_f32(n: usize) -> f64 {
+    let n = n as f64;
+    let machine_epsilon = f32::EPSILON as f64 * 0.5;
+    (n * machine_epsilon) / (1.0 - n * machine_epsilon)
+}
+
+/// Get the next representable floating point value greater than `v`.
+///
+/// Based on pbrt's `NextFloatUp()`.
+pub fn next_float_up(v: f64) -> f64 {
+    if v.is_infinite() && v > 0.0 {
+        return v;
+    }
+    // -0.0 and 0.0 must both move to the smallest positive value
+    let v = if v == 0.0 { 0.0 } else { v };
+    let bits = v.to_bits();
+    let bits = if v >= 0.0 { bits + 1 } else { bits - 1 };
+    f64::from_bits(bits)
+}
+
+/// Get the next representable floating point value lesser than `v`.
+///
+/// Based on pbrt's `NextFloatDown()`.
+pub fn next_float_down(v: f64) -> f64 {
+    if v.is_infinite() && v < 0.0 {
+        return v;
+    }
+    // -0.0 and 0.0 must both move to the smallest negative value
+    let v = if v == 0.0 { -0.0 } else { v };
+    let bits = v.to_bits();
+    let bits = if v > 0.0 { bits - 1 } else { bits + 1 };
+    f64::from_bits(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_float_up_test_01() {
+        assert!(next_float_up(1.0) > 1.0);
+        assert_eq!(next_float_up(1.0), 1.0 + f64::EPSILON);
+        assert!(next_float_up(-0.0) > 0.0);
+        assert!(next_float_up(-1.0) > -1.0);
+        assert_eq!(next_float_up(f64::INFINITY), f64::INFINITY);
+    }
+
+    #[test]
+    fn next_float_down_test_01() {
+        assert!(next_float_down(1.0) < 1.0);
+        assert!(next_float_down(0.0) < 0.0);
+        assert!(next_float_down(-1.0) < -1.0);
+        assert_eq!(next_float_down(f64::NEG_INFINITY), f64::NEG_INFINITY);
+        assert_eq!(next_float_down(next_float_up(3.5)), 3.5);
+    }
+
+    #[test]
+    fn distribution_1d_test_01() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0, 0.0, 4.0]);
+        assert!((distribution.get_func_integral() - 2.0).abs() < 1e-12);
+
+        let (x, pdf, offset) = distribution.sample_continuous(0.0);
+        assert_eq!(x, 0.0);
+        assert_eq!(offset, 0);
+        assert!((pdf - 0.5).abs() < 1e-12);
+
+        // cdf is [0.0, 0.125, 0.5, 0.5, 1.0]
+        let (x, pdf, offset) = distribution.sample_continuous(0.3125);
+        assert_eq!(offset, 1);
+        assert!((x - 0.375).abs() < 1e-12);
+        assert!((pdf - 1.5).abs() < 1e-12);
+
+        // zero valued function values are never sampled
+        let (_, _, offset) = distribution.sample_continuous(0.5);
+        assert_eq!(offset, 3);
+
+        let (index, probability) = distribution.sample_discrete(0.9);
+        assert_eq!(index, 3);
+        assert!((probability - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn distribution_2d_test_01() {
+        let distribution = Distribution2D::new(&[1.0, 1.0, 0.0, 0.0, 2.0, 4.0], 3, 2);
+
+        // the pdf integrates to 1 over [0, 1)^2
+        let integral: f64 = (0..2)
+            .flat_map(|j| (0..3).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                distribution.pdf(&glm::vec2((i as f64 + 0.5) / 3.0, (j as f64 + 0.5) / 2.0))
+                    / (3.0 * 2.0)
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-12);
+
+        (0..100).for_each(|_| {
+            let (p, pdf) = distribution.sample_continuous(&glm::vec2(random(), random()));
+            assert!(pdf > 0.0);
+            assert!((pdf - distribution.pdf(&p)).abs() < 1e-12);
+        });
+    }
+}
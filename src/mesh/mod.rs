@@ -14,13 +14,14 @@ use serde::{Deserialize, Serialize};
 use std::{cell::RefCell, convert::TryInto, fmt::Display, path::Path, rc::Rc, sync::Mutex};
 
 use crate::{
-    glm,
+    glm, math,
     meshio::{self, MeshIO},
     path_trace::{
         intersectable::{IntersectInfo, Intersectable},
         ray::Ray,
     },
     rasterize::Rasterize,
+    transform,
     util::{self, normal_apply_model_matrix, vec3_apply_model_matrix},
 };
 
@@ -131,6 +132,12 @@ pub struct Mesh {
     vertices: Vec<Vertex>,
     faces: Vec<Vec<usize>>,
 
+    /// Positions of the vertices keyed at specific times as (time,
+    /// positions), sorted by time. Used for deformation motion blur,
+    /// empty if the mesh does not deform.
+    #[serde(default)]
+    motion_positions: Vec<(f64, Vec<glm::DVec3>)>,
+
     /// BVH that stores face indices
     bvh: Option<BVHTree<usize>>,
     /// OpenGL mesh for rendering, is cached upon first draw, if mesh
@@ -183,6 +190,7 @@ impl Mesh {
         Ok(Self {
             vertices,
             faces,
+            motion_positions: Vec::new(),
             bvh: None,
             gl_mesh: Mutex::new(None),
         })
@@ -312,10 +320,18 @@ impl Mesh {
         let mut bvh = BVHTree::new(self.faces.len(), epsilon, 4, 8);
 
         self.faces.iter().enumerate().for_each(|(f_index, face)| {
-            let co: Vec<_> = face
-                .iter()
-                .map(|v_index| *self.vertices[*v_index].get_pos())
-                .collect();
+            // for a deforming mesh, the bounds of the face must
+            // contain the face at all the keys
+            let co: Vec<_> = if self.is_deforming() {
+                self.motion_positions
+                    .iter()
+                    .flat_map(|(_, positions)| face.iter().map(move |v_index| positions[*v_index]))
+                    .collect()
+            } else {
+                face.iter()
+                    .map(|v_index| *self.vertices[*v_index].get_pos())
+                    .collect()
+            };
 
             bvh.insert(f_index, &co);
         });
@@ -348,28 +364,124 @@ impl Mesh {
         self.vertices.par_iter_mut().for_each(|vert| {
             vert.apply_model_matrix(model);
         });
+        self.motion_positions
+            .par_iter_mut()
+            .flat_map(|(_, positions)| positions.par_iter_mut())
+            .for_each(|pos| {
+                *pos = vec3_apply_model_matrix(pos, model);
+            });
     }
 
     pub fn get_min_max_bounds(&self) -> (glm::DVec3, glm::DVec3) {
-        self.vertices.iter().fold(
-            (*self.vertices[0].get_pos(), *self.vertices[0].get_pos()),
-            |acc, elem| {
-                (
-                    glm::vec3(
-                        acc.0[0].min(elem.get_pos()[0]),
-                        acc.0[1].min(elem.get_pos()[1]),
-                        acc.0[2].min(elem.get_pos()[2]),
-                    ),
-                    glm::vec3(
-                        acc.1[0].max(elem.get_pos()[0]),
-                        acc.1[1].max(elem.get_pos()[1]),
-                        acc.1[2].max(elem.get_pos()[2]),
-                    ),
-                )
+        self.vertices
+            .iter()
+            .map(|vert| vert.get_pos())
+            .chain(
+                self.motion_positions
+                    .iter()
+                    .flat_map(|(_, positions)| positions.iter()),
+            )
+            .fold(
+                (*self.vertices[0].get_pos(), *self.vertices[0].get_pos()),
+                |acc, pos| {
+                    (
+                        glm::vec3(
+                            acc.0[0].min(pos[0]),
+                            acc.0[1].min(pos[1]),
+                            acc.0[2].min(pos[2]),
+                        ),
+                        glm::vec3(
+                            acc.1[0].max(pos[0]),
+                            acc.1[1].max(pos[1]),
+                            acc.1[2].max(pos[2]),
+                        ),
+                    )
+                },
+            )
+    }
+
+    /// Set the positions of the vertices keyed at specific times as
+    /// (time, positions) for deformation motion blur. Each key must
+    /// have a finite time and a position for every vertex of the
+    /// mesh.
+    pub fn set_motion_positions(&mut self, mut motion_positions: Vec<(f64, Vec<glm::DVec3>)>) {
+        assert!(motion_positions
+            .iter()
+            .all(|(_, positions)| positions.len() == self.vertices.len()));
+        assert!(
+            motion_positions.iter().all(|(time, _)| time.is_finite()),
+            "time of the motion keys must be finite"
+        );
+        motion_positions.sort_by(|(time_1, _), (time_2, _)| time_1.partial_cmp(time_2).unwrap());
+        self.motion_positions = motion_positions;
+        // bounds of the faces have changed
+        self.delete_bvh();
+    }
+
+    /// Get a reference to the mesh's motion positions.
+    pub fn get_motion_positions(&self) -> &[(f64, Vec<glm::DVec3>)] {
+        self.motion_positions.as_slice()
+    }
+
+    /// Check if the mesh deforms over time, needs at least 2 keys.
+    pub fn is_deforming(&self) -> bool {
+        self.motion_positions.len() > 1
+    }
+
+    /// Get the position of the vertex at the given time, the
+    /// position is linearly interpolated between the keys if the mesh
+    /// is deforming. The rest position is used if the mesh is not
+    /// deforming or no keys can be found for the time (when it is
+    /// NaN).
+    pub fn get_vertex_pos_at_time(&self, v_index: usize, time: f64) -> glm::DVec3 {
+        if !self.is_deforming() {
+            return *self.vertices[v_index].get_pos();
+        }
+        transform::find_motion_keys(&self.motion_positions, time).map_or_else(
+            || *self.vertices[v_index].get_pos(),
+            |(positions_1, positions_2, factor)| {
+                glm::lerp(&positions_1[v_index], &positions_2[v_index], factor)
             },
         )
     }
 
+    /// Get the shading normal of the triangle (by its vertex indices)
+    /// at the given time, `normal` is the shading normal at the rest
+    /// state of the mesh. The normal of a deforming mesh is rotated
+    /// along with the triangle, by the rotation that takes the
+    /// geometric normal of the triangle at the rest state to its
+    /// geometric normal at the given time.
+    pub fn get_deformed_normal(
+        &self,
+        triangle: [usize; 3],
+        normal: &glm::DVec3,
+        time: f64,
+    ) -> glm::DVec3 {
+        if !self.is_deforming() {
+            return *normal;
+        }
+        let geometric_normal = |p1: &glm::DVec3, p2: &glm::DVec3, p3: &glm::DVec3| {
+            glm::cross(&(p2 - p1), &(p3 - p1)).try_normalize(0.0)
+        };
+        let rest_normal = geometric_normal(
+            self.vertices[triangle[0]].get_pos(),
+            self.vertices[triangle[1]].get_pos(),
+            self.vertices[triangle[2]].get_pos(),
+        );
+        let deformed_normal = geometric_normal(
+            &self.get_vertex_pos_at_time(triangle[0], time),
+            &self.get_vertex_pos_at_time(triangle[1], time),
+            &self.get_vertex_pos_at_time(triangle[2], time),
+        );
+        match (rest_normal, deformed_normal) {
+            (Some(rest_normal), Some(deformed_normal)) => {
+                util::rotate_between(normal, &rest_normal, &deformed_normal)
+            }
+            // degenerate triangles have no rotation
+            _ => *normal,
+        }
+    }
+
     /// Get a reference to the mesh's vertices.
     pub fn get_vertices(&self) -> &[Vertex] {
         self.vertices.as_slice()
@@ -379,6 +491,26 @@ impl Mesh {
     pub fn get_faces(&self) -> &[Vec<usize>] {
         self.faces.as_slice()
     }
+
+    /// Get the total area of the faces of the mesh, faces are
+    /// triangulated as a fan around their first vertex.
+    pub fn get_surface_area(&self) -> f64 {
+        self.faces
+            .par_iter()
+            .map(|face| {
+                let p1 = self.vertices[face[0]].get_pos();
+                face.iter()
+                    .skip(1)
+                    .tuple_windows()
+                    .map(|(&v2_index, &v3_index)| {
+                        let p2 = self.vertices[v2_index].get_pos();
+                        let p3 = self.vertices[v3_index].get_pos();
+                        glm::cross(&(p2 - p1), &(p3 - p1)).norm() * 0.5
+                    })
+                    .sum::<f64>()
+            })
+            .sum()
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -562,6 +694,9 @@ impl Intersectable for Mesh {
             struct MeshRayCastData {
                 uv: glm::DVec2,
                 bary_coords: glm::DVec3,
+                point_error: glm::DVec3,
+                geometric_normal: glm::DVec3,
+                uv_derivatives: Option<(glm::DVec3, glm::DVec3)>,
             }
 
             let mesh_ray_cast_callback =
@@ -571,16 +706,16 @@ impl Intersectable for Mesh {
                     let face = &self.faces[face_index];
                     let v1_index = face[0];
                     let v1 = &self.vertices[v1_index];
+                    let p1 = &self.get_vertex_pos_at_time(v1_index, ray.get_time());
                     for (v2_index, v3_index) in face.iter().skip(1).tuple_windows() {
                         let v2 = &self.vertices[*v2_index];
                         let v3 = &self.vertices[*v3_index];
+                        let p2 = &self.get_vertex_pos_at_time(*v2_index, ray.get_time());
+                        let p3 = &self.get_vertex_pos_at_time(*v3_index, ray.get_time());
 
-                        if let Some((dist, bary_coords)) = ray.intersect_triangle(
-                            v1.get_pos(),
-                            v2.get_pos(),
-                            v3.get_pos(),
-                            f64::EPSILON,
-                        ) {
+                        if let Some((dist, bary_coords)) =
+                            ray.intersect_triangle(p1, p2, p3, f64::EPSILON)
+                        {
                             if dist > t_min && dist < t_max {
                                 let uv1 = v1.get_uv().as_ref().unwrap();
                                 let uv2 = v2.get_uv().as_ref().unwrap();
@@ -588,14 +723,31 @@ impl Intersectable for Mesh {
                                 let n1 = v1.get_normal().as_ref().unwrap();
                                 let n2 = v2.get_normal().as_ref().unwrap();
                                 let n3 = v3.get_normal().as_ref().unwrap();
+                                // the point is computed from the
+                                // barycentric coords instead of the
+                                // ray since its error bounds are far
+                                // smaller
+                                let point = util::vec3_apply_bary_coord(p1, p2, p3, &bary_coords);
+                                let point_error = math::gamma(7)
+                                    * (glm::abs(&(p1 * bary_coords[0]))
+                                        + glm::abs(&(p2 * bary_coords[1]))
+                                        + glm::abs(&(p3 * bary_coords[2])));
                                 let mut hit_data = RayHitData::new(dist);
-                                hit_data.normal =
-                                    Some(util::vec3_apply_bary_coord(n1, n2, n3, &bary_coords));
-                                hit_data
-                                    .set_data(RayHitOptionalData::new(face_index, ray.at(dist)));
+                                hit_data.normal = Some(self.get_deformed_normal(
+                                    [v1_index, *v2_index, *v3_index],
+                                    &util::vec3_apply_bary_coord(n1, n2, n3, &bary_coords),
+                                    ray.get_time(),
+                                ));
+                                hit_data.set_data(RayHitOptionalData::new(face_index, point));
                                 hit_data.set_extra_data(MeshRayCastData {
                                     uv: util::vec2_apply_bary_coord(uv1, uv2, uv3, &bary_coords),
                                     bary_coords,
+                                    point_error,
+                                    geometric_normal: glm::cross(&(p2 - p1), &(p3 - p1))
+                                        .normalize(),
+                                    uv_derivatives: util::triangle_uv_derivatives(
+                                        p1, p2, p3, uv1, uv2, uv3,
+                                    ),
                                 });
                                 return Some(hit_data);
                             }
@@ -619,7 +771,11 @@ impl Intersectable for Mesh {
                         hit_data.extra_data.unwrap().bary_coords,
                     );
                     intersect_info.set_uv(hit_data.extra_data.unwrap().uv);
+                    intersect_info.set_point_error(hit_data.extra_data.unwrap().point_error);
+                    intersect_info
+                        .set_geometric_normal(hit_data.extra_data.unwrap().geometric_normal);
                     intersect_info.set_normal(ray, &hit_data.normal.unwrap());
+                    intersect_info.set_uv_derivatives(hit_data.extra_data.unwrap().uv_derivatives);
                     intersect_info
                 })
         }
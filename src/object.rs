@@ -1,3 +1,4 @@
+use bitflags::bitflags;
 use lazy_static::lazy_static;
 use quick_renderer::{
     drawable::{Drawable, NoSpecificDrawError},
@@ -16,10 +17,19 @@ use std::{
 #[cfg(feature = "use_embree")]
 use crate::embree::Embree;
 use crate::{
-    glm,
+    egui, glm,
+    light::LightLinking,
     mesh::MeshDrawError,
     namegen::NameGen,
-    path_trace::{intersectable::Intersectable, shader_list::ShaderID as PathTraceShaderID},
+    path_trace::{
+        bsdf::SamplingTypes,
+        intersectable::{IntersectInfo, Intersectable},
+        ray::Ray,
+        shader_list::ShaderID as PathTraceShaderID,
+    },
+    transform::MotionTransform,
+    ui::DrawUI,
+    util,
 };
 
 lazy_static! {
@@ -43,10 +53,68 @@ impl ObjectID {
     }
 }
 
+bitflags! {
+    /// Types of rays, an object is hit only by the types of rays it
+    /// is visible to, see [`Object::get_visibility()`].
+    #[derive(Serialize, Deserialize)]
+    pub struct RayVisibility: u8 {
+        const CAMERA       = 0b00000001;
+        const SHADOW       = 0b00000010;
+        const DIFFUSE      = 0b00000100;
+        const GLOSSY       = 0b00001000;
+        const TRANSMISSION = 0b00010000;
+    }
+}
+
+impl Default for RayVisibility {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl RayVisibility {
+    /// Get the type of the ray generated by the given type of
+    /// sampling of the `BSDF`.
+    pub fn from_sampling_type(sampling_type: SamplingTypes) -> Self {
+        match sampling_type {
+            SamplingTypes::Diffuse => Self::DIFFUSE,
+            SamplingTypes::Glossy | SamplingTypes::Reflection => Self::GLOSSY,
+            SamplingTypes::Transmission => Self::TRANSMISSION,
+        }
+    }
+}
+
+impl DrawUI for RayVisibility {
+    type ExtraData = ();
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label("Visibility");
+        ui.horizontal_wrapped(|ui| {
+            [
+                (Self::CAMERA, "Camera"),
+                (Self::SHADOW, "Shadow"),
+                (Self::DIFFUSE, "Diffuse"),
+                (Self::GLOSSY, "Glossy"),
+                (Self::TRANSMISSION, "Transmission"),
+            ]
+            .iter()
+            .for_each(|(flag, text)| {
+                let mut visible = self.contains(*flag);
+                if ui.checkbox(&mut visible, *text).changed() {
+                    self.set(*flag, visible);
+                }
+            });
+        });
+    }
+}
+
 #[derive(Debug)]
 pub enum DrawError {
     Mesh(MeshDrawError),
     Sphere(NoSpecificDrawError),
+    Light(NoSpecificDrawError),
 }
 
 impl Display for DrawError {
@@ -54,6 +122,7 @@ impl Display for DrawError {
         match self {
             DrawError::Mesh(err) => write!(f, "DrawError: Mesh: {}", err),
             DrawError::Sphere(err) => write!(f, "DrawError: Sphere: {}", err),
+            DrawError::Light(err) => write!(f, "DrawError: Light: {}", err),
         }
     }
 }
@@ -135,6 +204,36 @@ impl DataForInterpolation {
     }
 }
 
+/// Intersect the ray with the given data of an object taking into
+/// account the motion of the object. `motion_matrix` is the matrix
+/// that transforms the data to its state at the time of the ray, see
+/// [`Object::get_motion_matrix()`].
+fn hit_with_motion(
+    data: &dyn Intersectable,
+    motion_matrix: Option<glm::DMat4>,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<IntersectInfo> {
+    match motion_matrix {
+        Some(motion_matrix) => {
+            // intersect in the space of the data, `t` is the same in
+            // both the spaces since the ray direction is not
+            // normalized
+            let data_ray = ray.apply_model_matrix(&glm::inverse(&motion_matrix));
+            data.hit(&data_ray, t_min, t_max).map(|mut info| {
+                info.apply_model_matrix(&motion_matrix);
+                info
+            })
+        }
+        None => data.hit(ray, t_min, t_max),
+    }
+    .map(|mut info| {
+        info.set_time(ray.get_time());
+        info
+    })
+}
+
 #[typetag::serde(tag = "type")]
 pub trait Object:
     Debug
@@ -168,6 +267,80 @@ pub trait Object:
 
     fn get_min_max_bounds(&self) -> (glm::DVec3, glm::DVec3);
 
+    /// Get the surface area of the object (in its current state,
+    /// with or without the model matrix applied).
+    fn get_surface_area(&self) -> f64;
+
+    /// Set the types of rays that the object is visible to.
+    fn set_visibility(&mut self, visibility: RayVisibility);
+    /// Get the types of rays that the object is visible to, rays of
+    /// other types pass through the object.
+    fn get_visibility(&self) -> RayVisibility;
+
+    /// Get a reference to the light linking of the object, the set of
+    /// objects that are illuminated by the object if it is an
+    /// emitter.
+    fn get_light_linking(&self) -> &LightLinking;
+    /// Get a mutable reference to the light linking of the object,
+    /// see [`Object::get_light_linking()`].
+    fn get_light_linking_mut(&mut self) -> &mut LightLinking;
+
+    /// Set the keyed model matrices of the object used for
+    /// transformation motion blur.
+    fn set_motion_transform(&mut self, motion_transform: MotionTransform);
+    /// Get a reference to the keyed model matrices of the object used
+    /// for transformation motion blur.
+    fn get_motion_transform(&self) -> &MotionTransform;
+
+    /// Get the matrix that transforms the object (with the model
+    /// matrix applied) to its state at the given time. Returns None
+    /// if the object does not move.
+    fn get_motion_matrix(&self, time: f64) -> Option<glm::DMat4> {
+        if !self.get_motion_transform().is_animated() {
+            return None;
+        }
+        let model = self.get_model_matrix().unwrap_or_else(glm::identity);
+        self.get_motion_transform()
+            .get_matrix(time)
+            .map(|motion_model| motion_model * glm::inverse(&model))
+    }
+
+    /// Check if the object moves over time, through its motion
+    /// transform or by deforming.
+    fn is_moving(&self) -> bool {
+        self.get_motion_transform().is_animated()
+    }
+
+    /// Get the bounds of the object that contain the object
+    /// throughout its motion. Same as
+    /// [`Object::get_min_max_bounds()`] if the object does not move.
+    fn get_motion_min_max_bounds(&self) -> (glm::DVec3, glm::DVec3) {
+        let bounds = self.get_min_max_bounds();
+        if !self.get_motion_transform().is_animated() {
+            return bounds;
+        }
+
+        // the rotation in between the keys is not linear, so the
+        // bounds are computed at multiple steps in between the keys
+        const STEPS_BETWEEN_KEYS: usize = 8;
+        self.get_motion_transform()
+            .get_keys()
+            .windows(2)
+            .flat_map(|keys| {
+                let (time_1, time_2) = (keys[0].0, keys[1].0);
+                (0..=STEPS_BETWEEN_KEYS).map(move |step| {
+                    time_1 + (time_2 - time_1) * step as f64 / STEPS_BETWEEN_KEYS as f64
+                })
+            })
+            .filter_map(|time| self.get_motion_matrix(time))
+            .fold(bounds, |acc, motion_matrix| {
+                util::min_max_bounds_union(
+                    &acc,
+                    &util::min_max_bounds_apply_model_matrix(&bounds, &motion_matrix),
+                )
+            })
+    }
+
     #[cfg(feature = "use_embree")]
     fn add_object_to_embree(&self, embree: &mut Embree);
 
@@ -177,6 +350,20 @@ pub trait Object:
     /// Get normal of the object with the given data
     fn get_normal(&self, data: &DataForInterpolation) -> glm::DVec3;
 
+    /// Get normal of the object with the given data at the given
+    /// time, differs from [`Object::get_normal()`] only for objects
+    /// that deform over time. The motion of the transform is not
+    /// taken into account, see [`Object::get_motion_matrix()`].
+    fn get_normal_at_time(&self, data: &DataForInterpolation, _time: f64) -> glm::DVec3 {
+        self.get_normal(data)
+    }
+
+    /// Get the partial derivatives of the position with respect to
+    /// the UVs (dp/du, dp/dv) of the object with the given data,
+    /// used to compute the footprint of the ray differentials on the
+    /// textures. Returns `None` if they cannot be computed.
+    fn get_uv_derivatives(&self, data: &DataForInterpolation) -> Option<(glm::DVec3, glm::DVec3)>;
+
     /// Must set any data that must be cached
     fn set_cached_data(&mut self);
 
@@ -226,7 +413,8 @@ pub mod objects {
         use crate::embree::Embree;
         use crate::{
             glm,
-            object::{DataForInterpolation, ObjectID},
+            light::LightLinking,
+            object::{DataForInterpolation, ObjectID, RayVisibility},
             path_trace::{
                 self,
                 intersectable::{IntersectInfo, Intersectable},
@@ -234,9 +422,10 @@ pub mod objects {
                 shader_list::ShaderID,
             },
             sphere::{Sphere as SphereData, SphereDrawData},
+            transform::MotionTransform,
         };
 
-        use super::super::{DrawError, Object, ObjectDrawData, SPHERE_NAME_GEN};
+        use super::super::{hit_with_motion, DrawError, Object, ObjectDrawData, SPHERE_NAME_GEN};
 
         #[derive(Debug, Serialize, Deserialize)]
         pub struct Sphere {
@@ -246,6 +435,12 @@ pub mod objects {
             #[serde(default = "default_object_name")]
             object_name: String,
             model_matrix: Option<glm::DMat4>,
+            #[serde(default)]
+            motion_transform: MotionTransform,
+            #[serde(default)]
+            visibility: RayVisibility,
+            #[serde(default)]
+            light_linking: LightLinking,
 
             // TODO: since this is a partial copy of SphereDrawData, it
             // might make sense to store this in a separate structure and
@@ -270,6 +465,9 @@ pub mod objects {
                     object_id: None,
                     object_name: SPHERE_NAME_GEN.lock().unwrap().next().unwrap(),
                     model_matrix: None,
+                    motion_transform: MotionTransform::new(),
+                    visibility: RayVisibility::all(),
+                    light_linking: LightLinking::default(),
                     outside_color,
                     inside_color,
                 }
@@ -278,7 +476,14 @@ pub mod objects {
 
         impl Intersectable for Sphere {
             fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<IntersectInfo> {
-                self.data.hit(ray, t_min, t_max).map(|mut info| {
+                hit_with_motion(
+                    &self.data,
+                    self.get_motion_matrix(ray.get_time()),
+                    ray,
+                    t_min,
+                    t_max,
+                )
+                .map(|mut info| {
                     info.set_shader_id(self.get_path_trace_shader_id());
                     info.set_object_id(self.get_object_id());
                     info
@@ -389,9 +594,37 @@ pub mod objects {
                 )
             }
 
+            fn get_surface_area(&self) -> f64 {
+                4.0 * std::f64::consts::PI * self.data.get_radius() * self.data.get_radius()
+            }
+
+            fn set_visibility(&mut self, visibility: RayVisibility) {
+                self.visibility = visibility;
+            }
+
+            fn get_visibility(&self) -> RayVisibility {
+                self.visibility
+            }
+
+            fn get_light_linking(&self) -> &LightLinking {
+                &self.light_linking
+            }
+
+            fn get_light_linking_mut(&mut self) -> &mut LightLinking {
+                &mut self.light_linking
+            }
+
+            fn set_motion_transform(&mut self, motion_transform: MotionTransform) {
+                self.motion_transform = motion_transform;
+            }
+
+            fn get_motion_transform(&self) -> &MotionTransform {
+                &self.motion_transform
+            }
+
             #[cfg(feature = "use_embree")]
             fn add_object_to_embree(&self, embree: &mut Embree) {
-                embree.add_sphere(&self.data, self.get_object_id());
+                embree.add_sphere(&self.data, self);
             }
 
             fn get_uv(&self, data: &DataForInterpolation) -> glm::DVec2 {
@@ -402,6 +635,29 @@ pub mod objects {
                 (data.position - self.data.get_center()) / self.data.get_radius()
             }
 
+            fn get_uv_derivatives(
+                &self,
+                data: &DataForInterpolation,
+            ) -> Option<(glm::DVec3, glm::DVec3)> {
+                // derivatives of the equirectangular mapping, see
+                // `path_trace::equirectangular_to_direction()`, u
+                // covers 2pi of the azimuth and v covers pi from -Y
+                // to +Y
+                let direction = (data.position - self.data.get_center()).normalize();
+                let theta = direction[1].clamp(-1.0, 1.0).acos();
+                let phi = direction[2].atan2(direction[0]);
+                let radius = self.data.get_radius();
+                Some((
+                    glm::vec3(-phi.sin(), 0.0, phi.cos())
+                        * (radius * theta.sin() * std::f64::consts::TAU),
+                    glm::vec3(
+                        theta.cos() * phi.cos(),
+                        -theta.sin(),
+                        theta.cos() * phi.sin(),
+                    ) * (-radius * std::f64::consts::PI),
+                ))
+            }
+
             fn set_cached_data(&mut self) {
                 // no caching for sphere
             }
@@ -423,17 +679,19 @@ pub mod objects {
         use crate::embree::Embree;
         use crate::{
             glm,
+            light::LightLinking,
             mesh::{Mesh as MeshData, MeshBVHDrawData, MeshDrawData, MeshUseShader},
-            object::{DataForInterpolation, ObjectID, PrimitiveType},
+            object::{DataForInterpolation, ObjectID, PrimitiveType, RayVisibility},
             path_trace::{
                 intersectable::{IntersectInfo, Intersectable},
                 ray::Ray,
                 shader_list::ShaderID,
             },
+            transform::MotionTransform,
             util,
         };
 
-        use super::super::{DrawError, Object, ObjectDrawData, MESH_NAME_GEN};
+        use super::super::{hit_with_motion, DrawError, Object, ObjectDrawData, MESH_NAME_GEN};
 
         use itertools::Itertools;
         use serde::{Deserialize, Serialize};
@@ -458,11 +716,22 @@ pub mod objects {
             /// until mesh structure remains the same
             #[serde(skip)]
             triangles: Option<Vec<Triangle>>,
+            /// surface area of the mesh in its current state, cached
+            /// since it is needed by emitters that are normalized by
+            /// area
+            #[serde(skip)]
+            surface_area: Option<f64>,
             shader_id: Option<ShaderID>,
             object_id: Option<ObjectID>,
             #[serde(default = "default_object_name")]
             object_name: String,
             model_matrix: Option<glm::DMat4>,
+            #[serde(default)]
+            motion_transform: MotionTransform,
+            #[serde(default)]
+            visibility: RayVisibility,
+            #[serde(default)]
+            light_linking: LightLinking,
 
             // TODO: since this is a partial copy of MeshDrawData, it
             // might make sense to store this in a separate structure and
@@ -481,13 +750,18 @@ pub mod objects {
                 use_shader: MeshUseShader,
                 bvh_draw_data: Option<MeshBVHDrawData>,
             ) -> Self {
+                let surface_area = Some(data.get_surface_area());
                 Self {
                     data,
                     triangles: None,
+                    surface_area,
                     shader_id: None,
                     object_id: None,
                     object_name: MESH_NAME_GEN.lock().unwrap().next().unwrap(),
                     model_matrix: None,
+                    motion_transform: MotionTransform::new(),
+                    visibility: RayVisibility::all(),
+                    light_linking: LightLinking::default(),
 
                     use_shader,
                     bvh_draw_data,
@@ -498,11 +772,23 @@ pub mod objects {
             pub fn get_data(&self) -> &MeshData {
                 &self.data
             }
+
+            /// Get a mutable reference to the mesh's data.
+            pub fn get_data_mut(&mut self) -> &mut MeshData {
+                &mut self.data
+            }
         }
 
         impl Intersectable for Mesh {
             fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<IntersectInfo> {
-                self.data.hit(ray, t_min, t_max).map(|mut info| {
+                hit_with_motion(
+                    &self.data,
+                    self.get_motion_matrix(ray.get_time()),
+                    ray,
+                    t_min,
+                    t_max,
+                )
+                .map(|mut info| {
                     info.set_shader_id(self.get_path_trace_shader_id());
                     info.set_object_id(self.get_object_id());
                     info
@@ -569,6 +855,7 @@ pub mod objects {
             fn apply_model_matrix(&mut self) {
                 let model = self.get_model_matrix().unwrap();
                 self.data.apply_model_matrix(&model);
+                self.surface_area = Some(self.data.get_surface_area());
             }
 
             fn set_path_trace_shader_id(&mut self, shader_id: ShaderID) {
@@ -599,9 +886,42 @@ pub mod objects {
                 self.data.get_min_max_bounds()
             }
 
+            fn get_surface_area(&self) -> f64 {
+                self.surface_area
+                    .unwrap_or_else(|| self.data.get_surface_area())
+            }
+
+            fn set_visibility(&mut self, visibility: RayVisibility) {
+                self.visibility = visibility;
+            }
+
+            fn get_visibility(&self) -> RayVisibility {
+                self.visibility
+            }
+
+            fn get_light_linking(&self) -> &LightLinking {
+                &self.light_linking
+            }
+
+            fn get_light_linking_mut(&mut self) -> &mut LightLinking {
+                &mut self.light_linking
+            }
+
+            fn set_motion_transform(&mut self, motion_transform: MotionTransform) {
+                self.motion_transform = motion_transform;
+            }
+
+            fn get_motion_transform(&self) -> &MotionTransform {
+                &self.motion_transform
+            }
+
+            fn is_moving(&self) -> bool {
+                self.get_motion_transform().is_animated() || self.data.is_deforming()
+            }
+
             #[cfg(feature = "use_embree")]
             fn add_object_to_embree(&self, embree: &mut Embree) {
-                embree.add_mesh(&self.data, self.get_object_id());
+                embree.add_mesh(&self.data, self);
             }
 
             fn get_uv(&self, data: &DataForInterpolation) -> glm::DVec2 {
@@ -640,6 +960,44 @@ pub mod objects {
                 }
             }
 
+            fn get_normal_at_time(&self, data: &DataForInterpolation, time: f64) -> glm::DVec3 {
+                match data.primitive_type {
+                    PrimitiveType::Triangle => {
+                        let triangle = &self.triangles.as_ref().expect("not cached yet")
+                            [data.primitive_index.unwrap()];
+                        self.data.get_deformed_normal(
+                            [triangle.i1, triangle.i2, triangle.i3],
+                            &self.get_normal(data),
+                            time,
+                        )
+                    }
+                }
+            }
+
+            fn get_uv_derivatives(
+                &self,
+                data: &DataForInterpolation,
+            ) -> Option<(glm::DVec3, glm::DVec3)> {
+                match data.primitive_type {
+                    PrimitiveType::Triangle => {
+                        let triangle = &self.triangles.as_ref().expect("not cached yet")
+                            [data.primitive_index.unwrap()];
+                        let v1 = &self.data.get_vertices()[triangle.i1];
+                        let v2 = &self.data.get_vertices()[triangle.i2];
+                        let v3 = &self.data.get_vertices()[triangle.i3];
+
+                        util::triangle_uv_derivatives(
+                            v1.get_pos(),
+                            v2.get_pos(),
+                            v3.get_pos(),
+                            v1.get_uv().as_ref()?,
+                            v2.get_uv().as_ref()?,
+                            v3.get_uv().as_ref()?,
+                        )
+                    }
+                }
+            }
+
             fn set_cached_data(&mut self) {
                 if self.triangles.is_none() {
                     self.triangles = Some(
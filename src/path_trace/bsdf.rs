@@ -2,9 +2,12 @@ use super::{
     bsdfs::{utils::ColorPicker, BSDFUiData},
     intersectable::IntersectInfo,
     medium::Mediums,
+    shader_list::{ShaderID, ShaderList},
     texture_list::TextureList,
 };
-use crate::{glm, ui::DrawUI};
+use crate::{glm, ui::DrawUI, util};
+
+use std::collections::HashMap;
 
 use enumflags2::{bitflags, BitFlags};
 
@@ -15,6 +18,13 @@ pub enum SamplingTypes {
     Diffuse,
     Glossy,
     Reflection,
+    /// Light passing through the surface (refraction). Rays of this
+    /// type are treated as specular, they carry ray differentials,
+    /// see the environment as glossy rays and keep the path
+    /// transparent, so diffuse transmission (see
+    /// [`super::bsdfs::translucent::Translucent`]) is `Diffuse`
+    /// instead.
+    Transmission,
 }
 
 /// Stores information about the incoming ray direction (`wi`) and the
@@ -23,11 +33,28 @@ pub enum SamplingTypes {
 pub struct SampleData {
     wi: glm::DVec3,
     sampling_type: SamplingTypes,
+    /// Probability density (with respect to solid angle) of sampling
+    /// `wi`, it is `None` if `wi` was sampled from a distribution
+    /// that cannot be evaluated for arbitrary directions (for
+    /// example perfect reflection), see [`BSDF::eval_with_pdf()`].
+    pdf: Option<f64>,
 }
 
 impl SampleData {
     pub fn new(wi: glm::DVec3, sampling_type: SamplingTypes) -> Self {
-        Self { wi, sampling_type }
+        Self {
+            wi,
+            sampling_type,
+            pdf: None,
+        }
+    }
+
+    pub fn new_with_pdf(wi: glm::DVec3, sampling_type: SamplingTypes, pdf: f64) -> Self {
+        Self {
+            wi,
+            sampling_type,
+            pdf: Some(pdf),
+        }
     }
 
     pub fn get_wi(&self) -> &glm::DVec3 {
@@ -37,6 +64,25 @@ impl SampleData {
     pub fn get_sampling_type(&self) -> SamplingTypes {
         self.sampling_type
     }
+
+    pub fn get_pdf(&self) -> Option<f64> {
+        self.pdf
+    }
+}
+
+/// How the surface must be treated by the camera rays so that the
+/// render can be composited over other images (for example
+/// photographed plates), see [`BSDF::get_compositing_type()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompositingType {
+    /// The surface is black and cuts out the alpha of the image, even
+    /// if the background is not transparent.
+    Holdout,
+    /// The surface is not seen, only the shadows cast on to it darken
+    /// (and the objects reflected by it cover) whatever is behind
+    /// it. `reflectivity` is the fraction of light reflected by the
+    /// surface.
+    ShadowCatcher { reflectivity: f64 },
 }
 
 #[typetag::serde(tag = "type")]
@@ -56,6 +102,9 @@ pub trait BSDF: DrawUI<ExtraData = BSDFUiData> {
     ///
     /// `intersect_info`: information at the point of intersection
     ///
+    /// `texture_list`: textures that may drive the parameters that
+    /// guide the sampling
+    ///
     /// `sampling_types`: the current sampling types that are possible
     ///
     /// Need to calculate the incoming ray direction since in ray
@@ -71,6 +120,7 @@ pub trait BSDF: DrawUI<ExtraData = BSDFUiData> {
         wo: &glm::DVec3,
         mediums: &mut Mediums,
         intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData>;
 
@@ -92,6 +142,49 @@ pub trait BSDF: DrawUI<ExtraData = BSDFUiData> {
         texture_list: &TextureList,
     ) -> glm::DVec3;
 
+    /// Calculates the colour/intensity of light that moves from `wi`
+    /// towards `wo` for any `wi`, not only the ones returned by
+    /// [`BSDF::sample()`], along with the probability density (with
+    /// respect to solid angle) of [`BSDF::sample()`] returning `wi`.
+    ///
+    /// Unlike [`BSDF::eval()`], the colour is not divided by the
+    /// probability density, it is the BSDF multiplied by the cosine
+    /// of the angle between `wi` and the normal. Only the parts of
+    /// the BSDF that can be evaluated for arbitrary directions must
+    /// be considered, perfect reflection or refraction must be
+    /// skipped.
+    ///
+    /// `mediums` are the mediums the ray is in at the point of
+    /// intersection, before [`BSDF::sample()`] transmits the ray
+    /// through the surface.
+    ///
+    /// Used when light is sampled directly, returns `None` if the
+    /// BSDF cannot be evaluated for arbitrary directions, light is
+    /// then only found by following [`BSDF::sample()`].
+    fn eval_with_pdf(
+        &self,
+        _wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        _mediums: &Mediums,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        None
+    }
+
+    /// Get the sampling types of the lobes that are evaluated by
+    /// [`BSDF::eval_with_pdf()`], directions sampled by
+    /// [`BSDF::sample()`] with a probability density must be of one
+    /// of these types.
+    ///
+    /// Light that is sampled directly is weighted against all these
+    /// lobes together, so it must be seen the same way by the rays of
+    /// each of them, see
+    /// [`super::environment::EnvironmentShadingData::should_sample()`].
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::empty()
+    }
+
     /// Calculates the colour/intensity of light produced by the object the point of intersection
     fn emission(
         &self,
@@ -103,6 +196,15 @@ pub trait BSDF: DrawUI<ExtraData = BSDFUiData> {
         None
     }
 
+    /// Returns true if the emission (see [`BSDF::emission()`]) is
+    /// emitted by the entire surface of the object instead of by a
+    /// unit area of it. The emission is then divided by the surface
+    /// area of the object so that scaling the object does not change
+    /// its total output.
+    fn is_emission_normalized_by_area(&self) -> bool {
+        false
+    }
+
     fn get_bsdf_name(&self) -> &str;
 
     fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3>;
@@ -112,4 +214,524 @@ pub trait BSDF: DrawUI<ExtraData = BSDFUiData> {
     fn get_ior(&self) -> f64 {
         1.0
     }
+
+    /// Get how the surface must be treated by the camera rays (and
+    /// rays that continue them only through transmission) for
+    /// compositing. For all other rays the surface is shaded using
+    /// [`BSDF::sample()`] and [`BSDF::eval()`] as usual. Within a
+    /// mixture, see [`BSDFMixture::choose_compositing_type()`].
+    fn get_compositing_type(&self) -> Option<CompositingType> {
+        None
+    }
+
+    /// Get the shaders combined by the BSDF along with their weights
+    /// at the point of intersection. Returns `None` if the BSDF does
+    /// not combine other shaders, such BSDFs are never sampled or
+    /// evaluated directly, see [`BSDFMixture`].
+    ///
+    /// A shader that is not set (`None`) or is not in the shader list
+    /// is the default shader, like it is for objects.
+    fn get_weighted_shaders(
+        &self,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+    ) -> Option<Vec<(Option<ShaderID>, f64)>> {
+        None
+    }
+
+    /// Get the shaders referred to by the BSDF, used to detect cycles
+    /// in the references, see [`BSDF::get_weighted_shaders()`].
+    fn get_referenced_shaders(&self) -> Vec<ShaderID> {
+        Vec::new()
+    }
+
+    /// Replace the references to the given shader by the default
+    /// shader (`None`), used to break cycles in the references, see
+    /// [`ShaderList::remove_cycles()`].
+    fn remove_referenced_shader(&mut self, _shader_id: ShaderID) {}
+
+    /// Build the `BSDF` at the point of intersection for `BSDF`s
+    /// whose parameters are computed at the point of intersection,
+    /// the built `BSDF` is used in place of this one at the point of
+    /// intersection. Returns `None` if this `BSDF` is used as is.
+    ///
+    /// [`BSDFMixture`] builds the `BSDF`s once per point of
+    /// intersection.
+    fn build_at(
+        &self,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+    ) -> Option<Box<dyn BSDF>> {
+        None
+    }
+}
+
+/// `BSDF` of a [`BSDFMixture`], either the `BSDF` of the shader or
+/// the `BSDF` built from it at the point of intersection, see
+/// [`BSDF::build_at()`].
+enum MixtureBSDF<'a> {
+    Shader(&'a dyn BSDF),
+    Built(Box<dyn BSDF>),
+}
+
+impl<'a> MixtureBSDF<'a> {
+    fn new(bsdf: &'a dyn BSDF, intersect_info: &IntersectInfo, texture_list: &TextureList) -> Self {
+        match bsdf.build_at(intersect_info, texture_list) {
+            Some(built) => MixtureBSDF::Built(built),
+            None => MixtureBSDF::Shader(bsdf),
+        }
+    }
+
+    fn as_bsdf(&self) -> &dyn BSDF {
+        match self {
+            MixtureBSDF::Shader(bsdf) => *bsdf,
+            MixtureBSDF::Built(bsdf) => &**bsdf,
+        }
+    }
+}
+
+/// Shader reached while flattening a [`BSDFMixture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MixtureNode {
+    Shader(ShaderID),
+    /// The default shader, used for missing shaders and for
+    /// references that would create a cycle.
+    Default,
+}
+
+/// The `BSDF`s that make up a shader at the point of intersection
+/// along with their weights, shaders that combine other shaders (see
+/// [`BSDF::get_weighted_shaders()`]) are flattened.
+///
+/// A single `BSDF` is chosen for sampling with a probability
+/// proportional to its weight, the directions that the other
+/// `BSDF`s can evaluate are weighted by all of them.
+///
+/// The mixture is meant to be created once per point of
+/// intersection, `BSDF`s whose parameters are computed at the point
+/// of intersection are built when it is created (see
+/// [`BSDF::build_at()`]).
+///
+/// Cycles are removed when the shader list is loaded (see
+/// [`ShaderList::remove_cycles()`]) and cannot be created through
+/// the UI (see [`ShaderList::get_referenceable_shaders()`]), a
+/// reference that still creates a cycle is the default shader.
+pub struct BSDFMixture<'a> {
+    bsdfs: Vec<(MixtureBSDF<'a>, f64)>,
+    /// sum of the weights of `bsdfs`
+    total_weight: f64,
+}
+
+impl<'a> BSDFMixture<'a> {
+    /// Create the mixture for the shader, `default_bsdf` is used if
+    /// the shader is not set or is not in the shader list.
+    pub fn new(
+        shader_id: Option<ShaderID>,
+        shader_list: &'a ShaderList,
+        default_bsdf: &'a dyn BSDF,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Self {
+        let bsdfs: Vec<_> = Self::flatten(
+            shader_id,
+            shader_list,
+            default_bsdf,
+            intersect_info,
+            texture_list,
+        )
+        .into_iter()
+        .map(|(bsdf, weight)| (MixtureBSDF::new(bsdf, intersect_info, texture_list), weight))
+        .collect();
+        let total_weight = bsdfs.iter().map(|(_, weight)| weight).sum();
+        Self {
+            bsdfs,
+            total_weight,
+        }
+    }
+
+    /// Get the `BSDF`s that make up the shader along with their
+    /// weights. The weighted shaders of each shader are found only
+    /// once, the weights of a shader reached through multiple paths
+    /// are added together.
+    fn flatten(
+        shader_id: Option<ShaderID>,
+        shader_list: &'a ShaderList,
+        default_bsdf: &'a dyn BSDF,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Vec<(&'a dyn BSDF, f64)> {
+        let to_node = move |shader_id: Option<ShaderID>| match shader_id {
+            Some(shader_id) if shader_list.get_shader(shader_id).is_some() => {
+                MixtureNode::Shader(shader_id)
+            }
+            _ => MixtureNode::Default,
+        };
+        let get_node_bsdf = move |node: MixtureNode| match node {
+            MixtureNode::Shader(shader_id) => shader_list.get_shader(shader_id).unwrap().get_bsdf(),
+            MixtureNode::Default => default_bsdf,
+        };
+
+        let get_children = |node: MixtureNode| {
+            get_node_bsdf(node)
+                .get_weighted_shaders(intersect_info, texture_list)
+                .map(|weighted_shaders| {
+                    weighted_shaders
+                        .into_iter()
+                        .filter(|(_, weight)| *weight > 0.0)
+                        .map(|(shader_id, weight)| (to_node(shader_id), weight))
+                        .collect()
+                })
+        };
+
+        let root = to_node(shader_id);
+        // weighted shaders of the shaders that combine other shaders
+        let mut children: HashMap<MixtureNode, Vec<(MixtureNode, f64)>> = HashMap::new();
+        let search =
+            util::depth_first_search(std::iter::once(root), |node| match get_children(node) {
+                Some(node_children) => {
+                    let edges = node_children
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (child, _))| (index, *child))
+                        .collect();
+                    children.insert(node, node_children);
+                    edges
+                }
+                None => Vec::new(),
+            });
+
+        // the references to the shaders on the current path would
+        // create a cycle so they are replaced by the default shader,
+        // which combines no shaders so it can be first in the post
+        // order
+        let mut post_order = search.get_post_order().to_vec();
+        search.get_back_edges().iter().for_each(|(node, index)| {
+            children.get_mut(node).unwrap()[*index].0 = MixtureNode::Default;
+            if !post_order.contains(&MixtureNode::Default) {
+                post_order.insert(0, MixtureNode::Default);
+            }
+        });
+
+        // reverse post order is a topological order, the weight of
+        // each shader is final before it is passed on
+        let mut weights: HashMap<MixtureNode, f64> = HashMap::new();
+        weights.insert(root, 1.0);
+        let mut bsdfs = Vec::new();
+        post_order.iter().rev().for_each(|node| {
+            let weight = weights.get(node).copied().unwrap_or(0.0);
+            match children.get(node) {
+                Some(node_children) => node_children.iter().for_each(|(child, child_weight)| {
+                    *weights.entry(*child).or_insert(0.0) += weight * child_weight;
+                }),
+                None => {
+                    if weight > 0.0 {
+                        bsdfs.push((get_node_bsdf(*node), weight));
+                    }
+                }
+            }
+        });
+        bsdfs
+    }
+
+    /// Sample one of the `BSDF`s, see [`BSDF::sample()`]. Returns the
+    /// sample along with the colour/intensity of light that moves
+    /// from `wi` towards `wo` (see [`BSDF::eval()`]) since it depends
+    /// on the `BSDF` that was chosen.
+    pub fn sample(
+        &self,
+        wo: &glm::DVec3,
+        mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<(SampleData, glm::DVec3)> {
+        if self.bsdfs.len() == 1 {
+            let (bsdf, weight) = &self.bsdfs[0];
+            let bsdf = bsdf.as_bsdf();
+            let sample_data =
+                bsdf.sample(wo, mediums, intersect_info, texture_list, sampling_types)?;
+            let wi = sample_data.get_wi().normalize();
+            let color = bsdf.eval(&wi, wo, intersect_info, texture_list) * *weight;
+            return Some((sample_data, color));
+        }
+
+        let mut choice = rand::random::<f64>() * self.total_weight;
+        let (bsdf, _) = self
+            .bsdfs
+            .iter()
+            .find(|(_, weight)| {
+                choice -= weight;
+                choice < 0.0
+            })
+            .or_else(|| self.bsdfs.last())?;
+        let bsdf = bsdf.as_bsdf();
+
+        // sampling can transmit the ray into another medium, the
+        // direction is evaluated with the mediums at the hitpoint
+        let hit_mediums = mediums.clone();
+        let sample_data = bsdf.sample(wo, mediums, intersect_info, texture_list, sampling_types)?;
+        let wi = sample_data.get_wi().normalize();
+        match sample_data.get_pdf() {
+            // the direction cannot be evaluated by the other `BSDF`s,
+            // the weight divided by the probability of choosing the
+            // `BSDF` is the total weight
+            None => {
+                let color = bsdf.eval(&wi, wo, intersect_info, texture_list) * self.total_weight;
+                Some((sample_data, color))
+            }
+            Some(_) => {
+                let (color, pdf) =
+                    self.eval_with_pdf(&wi, wo, &hit_mediums, intersect_info, texture_list)?;
+                if pdf <= 0.0 {
+                    return None;
+                }
+                Some((
+                    SampleData::new_with_pdf(
+                        *sample_data.get_wi(),
+                        sample_data.get_sampling_type(),
+                        pdf,
+                    ),
+                    color / pdf,
+                ))
+            }
+        }
+    }
+
+    /// See [`BSDF::eval_with_pdf()`], the pdf includes the
+    /// probability of choosing each `BSDF`. Returns `None` if none of
+    /// the `BSDF`s can evaluate the direction.
+    pub fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        self.bsdfs
+            .iter()
+            .filter_map(|(bsdf, weight)| {
+                bsdf.as_bsdf()
+                    .eval_with_pdf(wi, wo, mediums, intersect_info, texture_list)
+                    .map(|(color, pdf)| (color * *weight, pdf * weight / self.total_weight))
+            })
+            .reduce(|(color_a, pdf_a), (color_b, pdf_b)| (color_a + color_b, pdf_a + pdf_b))
+    }
+
+    /// See [`BSDF::get_evaluated_sampling_types()`], the lobes of
+    /// all the `BSDF`s are evaluated together.
+    pub fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        self.bsdfs.iter().fold(BitFlags::empty(), |acc, (bsdf, _)| {
+            acc | bsdf.as_bsdf().get_evaluated_sampling_types()
+        })
+    }
+
+    /// See [`BSDF::emission()`].
+    pub fn emission(
+        &self,
+        wo: &glm::DVec3,
+        mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<glm::DVec3> {
+        self.bsdfs
+            .iter()
+            .filter_map(|(bsdf, weight)| {
+                bsdf.as_bsdf()
+                    .emission(wo, mediums, intersect_info, texture_list)
+                    .map(|emission| emission * *weight)
+            })
+            .reduce(|emission_a, emission_b| emission_a + emission_b)
+    }
+
+    /// See [`BSDF::is_emission_normalized_by_area()`], true if any of
+    /// the `BSDF`s is normalized.
+    pub fn is_emission_normalized_by_area(&self) -> bool {
+        self.bsdfs
+            .iter()
+            .any(|(bsdf, _)| bsdf.as_bsdf().is_emission_normalized_by_area())
+    }
+
+    /// Choose how the surface is treated by the camera rays, see
+    /// [`BSDF::get_compositing_type()`]. The fraction of the surface
+    /// used for compositing is the weight of the `BSDF`s used for
+    /// compositing over the total weight, so mixing them with other
+    /// `BSDF`s partially holds out (or catches shadows on) the
+    /// surface. The choice is random, proportional to the weights.
+    ///
+    /// Returns the compositing type of the chosen `BSDF`. If a `BSDF`
+    /// not used for compositing is chosen, returns `None` and removes
+    /// the `BSDF`s used for compositing, the weights of the others
+    /// are divided by the probability of the choice.
+    pub fn choose_compositing_type(&mut self) -> Option<CompositingType> {
+        let compositing: Vec<_> = self
+            .bsdfs
+            .iter()
+            .filter_map(|(bsdf, weight)| {
+                bsdf.as_bsdf()
+                    .get_compositing_type()
+                    .map(|compositing_type| (compositing_type, *weight))
+            })
+            .collect();
+        let compositing_weight: f64 = compositing.iter().map(|(_, weight)| weight).sum();
+        if compositing.is_empty() {
+            return None;
+        }
+
+        let mut choice = rand::random::<f64>() * self.total_weight;
+        if choice < compositing_weight || compositing_weight >= self.total_weight {
+            return compositing
+                .iter()
+                .find(|(_, weight)| {
+                    choice -= weight;
+                    choice < 0.0
+                })
+                .or_else(|| compositing.last())
+                .map(|(compositing_type, _)| *compositing_type);
+        }
+
+        let scale = self.total_weight / (self.total_weight - compositing_weight);
+        self.bsdfs
+            .retain(|(bsdf, _)| bsdf.as_bsdf().get_compositing_type().is_none());
+        self.bsdfs
+            .iter_mut()
+            .for_each(|(_, weight)| *weight *= scale);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::{bsdfs, shader_list::Shader, shaders};
+
+    #[test]
+    fn bsdf_mixture_flatten_test_01() {
+        let mut shader_list = ShaderList::new();
+        let lambert = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.5, 0.5, 0.5)),
+        )));
+        let add = shader_list.add_shader(Box::new(shaders::Add::new(Default::default())));
+        let mut shader = shaders::Add::new(bsdfs::add::Add::new(Some(add), Some(lambert)));
+        shader.set_shader_id(add);
+        *shader_list.get_shader_mut(add).unwrap() = Box::new(shader);
+
+        let default_bsdf = bsdfs::lambert::Lambert::new(glm::zero());
+        let texture_list = TextureList::new();
+        let intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        let mixture = BSDFMixture::new(
+            Some(add),
+            &shader_list,
+            &default_bsdf,
+            &intersect_info,
+            &texture_list,
+        );
+
+        // the reference of the add to itself is the default shader
+        // (black)
+        let mut weighted_colors: Vec<_> = mixture
+            .bsdfs
+            .iter()
+            .map(|(bsdf, weight)| {
+                (
+                    bsdf.as_bsdf().get_base_color(&texture_list).unwrap()[0],
+                    *weight,
+                )
+            })
+            .collect();
+        weighted_colors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(weighted_colors, vec![(0.0, 1.0), (0.5, 1.0)]);
+        assert_eq!(mixture.total_weight, 2.0);
+    }
+
+    #[test]
+    fn bsdf_mixture_flatten_test_02() {
+        // each add refers to the previous add twice, the shared
+        // shaders must be flattened only once
+        let mut shader_list = ShaderList::new();
+        let lambert = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.5, 0.5, 0.5)),
+        )));
+        let last = (0..32).fold(lambert, |previous, _| {
+            shader_list.add_shader(Box::new(shaders::Add::new(bsdfs::add::Add::new(
+                Some(previous),
+                Some(previous),
+            ))))
+        });
+
+        let default_bsdf = bsdfs::lambert::Lambert::new(glm::zero());
+        let texture_list = TextureList::new();
+        let intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        let mixture = BSDFMixture::new(
+            Some(last),
+            &shader_list,
+            &default_bsdf,
+            &intersect_info,
+            &texture_list,
+        );
+
+        assert_eq!(mixture.bsdfs.len(), 1);
+        assert_eq!(mixture.total_weight, 2.0_f64.powi(32));
+    }
+
+    #[test]
+    fn bsdf_mixture_choose_compositing_type_test_01() {
+        let mut shader_list = ShaderList::new();
+        let holdout = shader_list.add_shader(Box::new(shaders::Holdout::new(
+            bsdfs::holdout::Holdout::new(),
+        )));
+        let lambert = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.5, 0.5, 0.5)),
+        )));
+        let mix = shader_list.add_shader(Box::new(shaders::Mix::new(bsdfs::mix::Mix::new(
+            0.75,
+            Some(holdout),
+            Some(lambert),
+        ))));
+
+        let default_bsdf = bsdfs::lambert::Lambert::new(glm::zero());
+        let texture_list = TextureList::new();
+        let intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        let new_mixture = |shader_id| {
+            BSDFMixture::new(
+                Some(shader_id),
+                &shader_list,
+                &default_bsdf,
+                &intersect_info,
+                &texture_list,
+            )
+        };
+
+        // a quarter of the surface is held out
+        let num_samples = 10000;
+        let mut num_holdouts = 0;
+        (0..num_samples).for_each(|_| {
+            let mut mixture = new_mixture(mix);
+            match mixture.choose_compositing_type() {
+                Some(compositing_type) => {
+                    assert_eq!(compositing_type, CompositingType::Holdout);
+                    assert_eq!(mixture.bsdfs.len(), 2);
+                    num_holdouts += 1;
+                }
+                None => {
+                    // the lambert is divided by the probability of
+                    // choosing it
+                    assert_eq!(mixture.bsdfs.len(), 1);
+                    assert!((mixture.bsdfs[0].1 - 1.0).abs() < 1e-12);
+                    assert_eq!(mixture.total_weight, 1.0);
+                }
+            }
+        });
+        assert!((num_holdouts as f64 / num_samples as f64 - 0.25).abs() < 0.02);
+
+        // a single compositing `BSDF` is always chosen, others never
+        assert_eq!(
+            new_mixture(holdout).choose_compositing_type(),
+            Some(CompositingType::Holdout)
+        );
+        let mut mixture = new_mixture(lambert);
+        assert_eq!(mixture.choose_compositing_type(), None);
+        assert_eq!(mixture.bsdfs.len(), 1);
+        assert_eq!(mixture.bsdfs[0].1, 1.0);
+    }
 }
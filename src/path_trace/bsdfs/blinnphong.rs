@@ -42,6 +42,7 @@ impl BSDF for Blinnphong {
         _wo: &glm::DVec3,
         _mediums: &mut Mediums,
         intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
         // TODO: Need to figure out proper sampling for this
@@ -80,7 +81,7 @@ impl BSDF for Blinnphong {
         };
 
         self.color
-            .get_color(intersect_info.get_uv().as_ref().unwrap(), texture_list)
+            .get_color_at(intersect_info, texture_list)
             .component_mul(&glm::vec3(val, val, val))
     }
 
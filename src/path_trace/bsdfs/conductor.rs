@@ -0,0 +1,475 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::microfacet;
+use super::utils::{ColorPicker, ColorPickerUiData};
+use super::BSDFUiData;
+use crate::egui;
+use crate::glm;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+
+/// Measured metals, the complex ior is sampled at roughly 650nm,
+/// 550nm and 450nm for the red, green and blue channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConductorPreset {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+    Chromium,
+    Titanium,
+}
+
+impl ConductorPreset {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use ConductorPreset::*;
+        [Gold, Silver, Copper, Aluminium, Chromium, Titanium]
+            .iter()
+            .copied()
+    }
+
+    /// Get the complex ior `(eta, k)` of the metal.
+    pub fn get_complex_ior(&self) -> (glm::DVec3, glm::DVec3) {
+        match self {
+            ConductorPreset::Gold => (
+                glm::vec3(0.143, 0.374, 1.442),
+                glm::vec3(3.983, 2.385, 1.603),
+            ),
+            ConductorPreset::Silver => (
+                glm::vec3(0.155, 0.117, 0.138),
+                glm::vec3(4.828, 3.122, 2.147),
+            ),
+            ConductorPreset::Copper => (
+                glm::vec3(0.200, 0.924, 1.102),
+                glm::vec3(3.912, 2.452, 2.142),
+            ),
+            ConductorPreset::Aluminium => (
+                glm::vec3(1.657, 0.880, 0.521),
+                glm::vec3(9.224, 6.270, 4.837),
+            ),
+            ConductorPreset::Chromium => (
+                glm::vec3(3.180, 3.180, 2.010),
+                glm::vec3(3.300, 3.330, 3.040),
+            ),
+            ConductorPreset::Titanium => (
+                glm::vec3(2.745, 2.541, 2.267),
+                glm::vec3(3.814, 3.435, 3.039),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ConductorPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConductorPreset::Gold => write!(f, "Gold"),
+            ConductorPreset::Silver => write!(f, "Silver"),
+            ConductorPreset::Copper => write!(f, "Copper"),
+            ConductorPreset::Aluminium => write!(f, "Aluminium"),
+            ConductorPreset::Chromium => write!(f, "Chromium"),
+            ConductorPreset::Titanium => write!(f, "Titanium"),
+        }
+    }
+}
+
+/// How the Fresnel reflectance of the [`Conductor`] is defined.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConductorFresnel {
+    /// Complex ior `eta + i * k` per colour channel.
+    ComplexIor { eta: glm::DVec3, k: glm::DVec3 },
+    /// Artist friendly reflectivity (colour at normal incidence) and
+    /// edge tint (colour towards grazing angles), see
+    /// [`microfacet::artistic_to_complex_ior()`].
+    Artistic {
+        reflectivity: ColorPicker,
+        edge_tint: ColorPicker,
+    },
+}
+
+impl std::fmt::Display for ConductorFresnel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConductorFresnel::ComplexIor { .. } => write!(f, "Complex IOR"),
+            ConductorFresnel::Artistic { .. } => write!(f, "Artistic"),
+        }
+    }
+}
+
+impl ConductorFresnel {
+    pub fn from_preset(preset: ConductorPreset) -> Self {
+        let (eta, k) = preset.get_complex_ior();
+        Self::ComplexIor { eta, k }
+    }
+
+    /// Get the preset that the complex ior matches, if any.
+    pub fn get_preset(&self) -> Option<ConductorPreset> {
+        ConductorPreset::all().find(|preset| *self == Self::from_preset(*preset))
+    }
+
+    /// Get the Fresnel reflectance, `cos_theta` is the cosine of the
+    /// angle between the direction and the microfacet normal.
+    pub fn eval(
+        &self,
+        cos_theta: f64,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        match self {
+            ConductorFresnel::ComplexIor { eta, k } => {
+                microfacet::fresnel_conductor(cos_theta, eta, k)
+            }
+            ConductorFresnel::Artistic {
+                reflectivity,
+                edge_tint,
+            } => {
+                let (eta, k) = microfacet::artistic_to_complex_ior(
+                    &reflectivity.get_color_at(intersect_info, texture_list),
+                    &edge_tint.get_color_at(intersect_info, texture_list),
+                );
+                microfacet::fresnel_conductor(cos_theta, &eta, &k)
+            }
+        }
+    }
+}
+
+/// Rough conductor with the exact Fresnel reflectance of a metal,
+/// reflection off a GGX (Trowbridge-Reitz) microfacet surface with
+/// a Smith masking-shadowing term, see
+/// [`super::glossy::Glossy`] for the Schlick approximation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conductor {
+    fresnel: ConductorFresnel,
+
+    /// alpha of the GGX distribution, the square of the roughness,
+    /// see [`microfacet::roughness_to_alpha()`]
+    alpha: f64,
+}
+
+impl Default for Conductor {
+    fn default() -> Self {
+        Self::new(ConductorFresnel::from_preset(ConductorPreset::Gold), 0.2)
+    }
+}
+
+impl Conductor {
+    pub fn new(fresnel: ConductorFresnel, roughness: f64) -> Self {
+        Self {
+            fresnel,
+            alpha: microfacet::roughness_to_alpha(roughness),
+        }
+    }
+
+    /// Get a reference to the conductor's fresnel.
+    pub fn get_fresnel(&self) -> &ConductorFresnel {
+        &self.fresnel
+    }
+
+    /// Set the conductor's fresnel.
+    pub fn set_fresnel(&mut self, fresnel: ConductorFresnel) {
+        self.fresnel = fresnel;
+    }
+
+    /// Get the conductor's roughness.
+    pub fn get_roughness(&self) -> f64 {
+        self.alpha.sqrt()
+    }
+
+    /// Set the conductor's roughness.
+    pub fn set_roughness(&mut self, roughness: f64) {
+        self.alpha = microfacet::roughness_to_alpha(roughness);
+    }
+
+    fn get_alpha(&self) -> f64 {
+        self.alpha
+    }
+}
+
+#[typetag::serde]
+impl BSDF for Conductor {
+    fn sample(
+        &self,
+        wo: &glm::DVec3,
+        _mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        microfacet::sample_ggx_reflection(
+            wo,
+            intersect_info.get_normal().as_ref().unwrap(),
+            self.get_alpha(),
+            sampling_types,
+        )
+    }
+
+    fn eval(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        microfacet::eval_ggx_reflection(
+            wi,
+            wo,
+            intersect_info.get_normal().as_ref().unwrap(),
+            self.get_alpha(),
+            |cos_theta| self.fresnel.eval(cos_theta, intersect_info, texture_list),
+        )
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        microfacet::eval_ggx_reflection_with_pdf(
+            wi,
+            wo,
+            intersect_info.get_normal().as_ref().unwrap(),
+            self.get_alpha(),
+            |cos_theta| self.fresnel.eval(cos_theta, intersect_info, texture_list),
+        )
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::from(SamplingTypes::Glossy)
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Conductor"
+    }
+
+    fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3> {
+        match &self.fresnel {
+            ConductorFresnel::ComplexIor { eta, k } => {
+                Some(microfacet::fresnel_conductor(1.0, eta, k))
+            }
+            ConductorFresnel::Artistic { reflectivity, .. } => {
+                Some(reflectivity.get_color(&glm::zero(), texture_list))
+            }
+        }
+    }
+
+    fn set_base_color(&mut self, color: ColorPicker) {
+        // the base colour is the reflectivity, the complex ior
+        // cannot be derived from it without an edge tint
+        match &mut self.fresnel {
+            ConductorFresnel::ComplexIor { .. } => {
+                self.fresnel = ConductorFresnel::Artistic {
+                    reflectivity: color,
+                    edge_tint: ColorPicker::Color(glm::vec3(1.0, 1.0, 1.0)),
+                };
+            }
+            ConductorFresnel::Artistic { reflectivity, .. } => {
+                *reflectivity = color;
+            }
+        }
+    }
+}
+
+fn complex_ior_channels_ui(ui: &mut egui::Ui, text: &str, value: &mut glm::DVec3) {
+    ui.horizontal(|ui| {
+        ui.label(text);
+        ui.add(
+            egui::DragValue::new(&mut value[0])
+                .speed(0.01)
+                .clamp_range(0.0..=f64::MAX)
+                .prefix("R: "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut value[1])
+                .speed(0.01)
+                .clamp_range(0.0..=f64::MAX)
+                .prefix("G: "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut value[2])
+                .speed(0.01)
+                .clamp_range(0.0..=f64::MAX)
+                .prefix("B: "),
+        );
+    });
+}
+
+impl DrawUI for Conductor {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        let mut is_complex_ior = matches!(self.fresnel, ConductorFresnel::ComplexIor { .. });
+        egui::ComboBox::from_id_source(extra_data.get_shader_egui_id().with("Fresnel"))
+            .selected_text(format!("{}", self.fresnel))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut is_complex_ior, true, "Complex IOR");
+                ui.selectable_value(&mut is_complex_ior, false, "Artistic");
+            });
+        match (is_complex_ior, &self.fresnel) {
+            (true, ConductorFresnel::Artistic { .. }) => {
+                self.fresnel = ConductorFresnel::from_preset(ConductorPreset::Gold);
+            }
+            (false, ConductorFresnel::ComplexIor { eta, k }) => {
+                // start with the look of the complex ior
+                let reflectivity = microfacet::fresnel_conductor(1.0, eta, k);
+                let edge_tint = microfacet::fresnel_conductor(0.2, eta, k);
+                self.fresnel = ConductorFresnel::Artistic {
+                    reflectivity: ColorPicker::Color(reflectivity),
+                    edge_tint: ColorPicker::Color(edge_tint),
+                };
+            }
+            _ => {}
+        }
+
+        let preset = self.fresnel.get_preset();
+        match &mut self.fresnel {
+            ConductorFresnel::ComplexIor { eta, k } => {
+                let mut selected_preset = preset;
+                egui::ComboBox::from_id_source(extra_data.get_shader_egui_id().with("Preset"))
+                    .selected_text(
+                        preset.map_or_else(|| "Custom".to_string(), |preset| preset.to_string()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ConductorPreset::all().for_each(|preset| {
+                            ui.selectable_value(
+                                &mut selected_preset,
+                                Some(preset),
+                                format!("{}", preset),
+                            );
+                        });
+                    });
+                if let Some(selected_preset) = selected_preset {
+                    if Some(selected_preset) != preset {
+                        let (preset_eta, preset_k) = selected_preset.get_complex_ior();
+                        *eta = preset_eta;
+                        *k = preset_k;
+                    }
+                }
+
+                complex_ior_channels_ui(ui, "n", eta);
+                complex_ior_channels_ui(ui, "k", k);
+            }
+            ConductorFresnel::Artistic {
+                reflectivity,
+                edge_tint,
+            } => {
+                ui.horizontal(|ui| {
+                    ui.label("Reflectivity");
+                    reflectivity.draw_ui_mut(
+                        ui,
+                        &ColorPickerUiData::new(
+                            extra_data.get_texture_list().clone(),
+                            extra_data.get_shader_egui_id().with("Reflectivity"),
+                        ),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Edge Tint");
+                    edge_tint.draw_ui_mut(
+                        ui,
+                        &ColorPickerUiData::new(
+                            extra_data.get_texture_list().clone(),
+                            extra_data.get_shader_egui_id().with("Edge Tint"),
+                        ),
+                    );
+                });
+            }
+        }
+
+        let mut roughness = self.get_roughness();
+        if ui
+            .add(
+                egui::Slider::new(&mut roughness, 0.0..=1.0)
+                    .clamp_to_range(false)
+                    .text("Roughness"),
+            )
+            .changed()
+        {
+            self.set_roughness(roughness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reflectance at normal incidence of the complex ior, the Fresnel
+    /// equations reduce to `((eta - 1)^2 + k^2) / ((eta + 1)^2 + k^2)`.
+    fn get_f0(eta: &glm::DVec3, k: &glm::DVec3) -> glm::DVec3 {
+        glm::vec3(
+            ((eta[0] - 1.0).powi(2) + k[0] * k[0]) / ((eta[0] + 1.0).powi(2) + k[0] * k[0]),
+            ((eta[1] - 1.0).powi(2) + k[1] * k[1]) / ((eta[1] + 1.0).powi(2) + k[1] * k[1]),
+            ((eta[2] - 1.0).powi(2) + k[2] * k[2]) / ((eta[2] + 1.0).powi(2) + k[2] * k[2]),
+        )
+    }
+
+    #[test]
+    fn conductor_fresnel_normal_incidence_test_01() {
+        let texture_list = TextureList::new();
+        let intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+
+        ConductorPreset::all().for_each(|preset| {
+            let (eta, k) = preset.get_complex_ior();
+            let f0 = get_f0(&eta, &k);
+
+            let fresnel = ConductorFresnel::from_preset(preset);
+            assert_eq!(fresnel.get_preset(), Some(preset));
+            let reflectance = fresnel.eval(1.0, &intersect_info, &texture_list);
+            assert!(
+                glm::distance(&reflectance, &f0) < 1e-6,
+                "{}: {} != {}",
+                preset,
+                reflectance,
+                f0
+            );
+
+            // the base colour of the conductor is the reflectance at
+            // normal incidence
+            let conductor = Conductor::new(fresnel, 0.2);
+            assert!(glm::distance(&conductor.get_base_color(&texture_list).unwrap(), &f0) < 1e-6);
+
+            // the artistic reflectivity is the reflectance at normal
+            // incidence
+            let artistic = ConductorFresnel::Artistic {
+                reflectivity: ColorPicker::Color(f0),
+                edge_tint: ColorPicker::Color(glm::vec3(0.5, 0.5, 0.5)),
+            };
+            let reflectance = artistic.eval(1.0, &intersect_info, &texture_list);
+            assert!(
+                glm::distance(&reflectance, &f0) < 1e-6,
+                "{} (artistic): {} != {}",
+                preset,
+                reflectance,
+                f0
+            );
+        });
+
+        // measured reflectance of gold
+        let (eta, k) = ConductorPreset::Gold.get_complex_ior();
+        let reflectance = microfacet::fresnel_conductor(1.0, &eta, &k);
+        assert!(glm::distance(&reflectance, &glm::vec3(0.9667, 0.8025, 0.324)) < 1e-3);
+    }
+
+    #[test]
+    fn conductor_serialize_round_trip_test_01() {
+        let conductor = Conductor::new(ConductorFresnel::from_preset(ConductorPreset::Gold), 0.5);
+        let loaded: Conductor =
+            serde_json::from_str(&serde_json::to_string(&conductor).unwrap()).unwrap();
+        assert!((loaded.get_alpha() - 0.25).abs() < 1e-12);
+        assert!((loaded.get_roughness() - 0.5).abs() < 1e-12);
+        assert_eq!(
+            loaded.get_fresnel().get_preset(),
+            Some(ConductorPreset::Gold)
+        );
+    }
+}
@@ -152,6 +152,7 @@ impl BSDF for DebugBSDF {
         _wo: &glm::DVec3,
         _mediums: &mut Mediums,
         _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         _sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
         None
@@ -3,18 +3,38 @@ use serde::{Deserialize, Serialize};
 
 use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
 use super::super::intersectable::IntersectInfo;
+use super::microfacet::ShadingFrame;
 use super::utils::{ColorPicker, ColorPickerUiData};
 use super::BSDFUiData;
 use crate::egui;
 use crate::glm;
+use crate::ies::{self, IESProfile};
 use crate::path_trace::medium::Mediums;
 use crate::path_trace::texture_list::TextureList;
 use crate::ui::DrawUI;
+use crate::util;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Emissive {
     color: ColorPicker,
     power: f64,
+    /// temperature (in kelvin) of the black body whose colour (see
+    /// [`util::blackbody_to_linear_rgb()`]) tints `color`
+    #[serde(default)]
+    blackbody_temperature: Option<f64>,
+    /// the luminance of the colour (usually a texture) scales `power`
+    #[serde(default)]
+    strength: Option<ColorPicker>,
+    /// shapes the emission, the profile points along the (outward)
+    /// normal of the surface and the horizontal angle 0 is along the
+    /// U direction of the uvs
+    #[serde(default)]
+    ies_profile: Option<IESProfile>,
+    /// `power` is emitted by the entire surface of the object instead
+    /// of by a unit area of it, see
+    /// [`BSDF::is_emission_normalized_by_area()`]
+    #[serde(default)]
+    normalize_by_area: bool,
 }
 
 impl Default for Emissive {
@@ -28,8 +48,55 @@ impl Emissive {
         Self {
             color: ColorPicker::Color(color),
             power,
+            blackbody_temperature: None,
+            strength: None,
+            ies_profile: None,
+            normalize_by_area: false,
         }
     }
+
+    /// Get emissive's blackbody temperature.
+    pub fn get_blackbody_temperature(&self) -> Option<f64> {
+        self.blackbody_temperature
+    }
+
+    /// Set emissive's blackbody temperature.
+    pub fn set_blackbody_temperature(&mut self, blackbody_temperature: Option<f64>) {
+        self.blackbody_temperature = blackbody_temperature;
+    }
+
+    /// Get a reference to the emissive's strength.
+    pub fn get_strength(&self) -> Option<&ColorPicker> {
+        self.strength.as_ref()
+    }
+
+    /// Set emissive's strength.
+    pub fn set_strength(&mut self, strength: Option<ColorPicker>) {
+        self.strength = strength;
+    }
+
+    /// Get a reference to the emissive's ies profile.
+    pub fn get_ies_profile(&self) -> Option<&IESProfile> {
+        self.ies_profile.as_ref()
+    }
+
+    /// Set emissive's ies profile.
+    pub fn set_ies_profile(&mut self, ies_profile: Option<IESProfile>) {
+        self.ies_profile = ies_profile;
+    }
+
+    /// Set emissive's normalize by area.
+    pub fn set_normalize_by_area(&mut self, normalize_by_area: bool) {
+        self.normalize_by_area = normalize_by_area;
+    }
+
+    /// Tint the colour with the colour of the black body if
+    /// available.
+    fn apply_blackbody(&self, color: glm::DVec3) -> glm::DVec3 {
+        self.blackbody_temperature.map_or(color, |temperature| {
+            color.component_mul(&util::blackbody_to_linear_rgb(temperature))
+        })
+    }
 }
 
 #[typetag::serde]
@@ -39,6 +106,7 @@ impl BSDF for Emissive {
         _wo: &glm::DVec3,
         _mediums: &mut Mediums,
         _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         _sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
         None
@@ -56,17 +124,40 @@ impl BSDF for Emissive {
 
     fn emission(
         &self,
-        _wo: &glm::DVec3,
+        wo: &glm::DVec3,
         _mediums: &Mediums,
         intersect_info: &IntersectInfo,
         texture_list: &TextureList,
     ) -> Option<glm::DVec3> {
-        Some(
-            self.power
-                * self
-                    .color
-                    .get_color(intersect_info.get_uv().as_ref().unwrap(), texture_list),
-        )
+        let color = self.apply_blackbody(self.color.get_color_at(intersect_info, texture_list));
+
+        let strength = self.strength.as_ref().map_or(1.0, |strength| {
+            util::luminance(&strength.get_color_at(intersect_info, texture_list)).max(0.0)
+        });
+
+        let ies_intensity = self.ies_profile.as_ref().map_or(1.0, |ies_profile| {
+            // the stored normal faces `wo`, the profile must be
+            // oriented with the outward normal so that the back of
+            // the surface gets the back of the profile
+            let normal = intersect_info.get_normal().unwrap();
+            let outward_normal = if intersect_info.get_front_face() {
+                normal
+            } else {
+                -normal
+            };
+            let frame = intersect_info.get_uv_derivatives().map_or_else(
+                || ShadingFrame::new(&outward_normal),
+                |(dpdu, _)| ShadingFrame::with_tangent(&outward_normal, dpdu),
+            );
+            let axis_x = frame.to_world(&glm::vec3(1.0, 0.0, 0.0));
+            ies_profile.eval_direction(wo, &outward_normal, &axis_x)
+        });
+
+        Some(self.power * strength * ies_intensity * color)
+    }
+
+    fn is_emission_normalized_by_area(&self) -> bool {
+        self.normalize_by_area
     }
 
     fn get_bsdf_name(&self) -> &str {
@@ -74,7 +165,7 @@ impl BSDF for Emissive {
     }
 
     fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3> {
-        Some(self.color.get_color(&glm::zero(), texture_list))
+        Some(self.apply_blackbody(self.color.get_color(&glm::zero(), texture_list)))
     }
 
     fn set_base_color(&mut self, color: ColorPicker) {
@@ -105,5 +196,145 @@ impl DrawUI for Emissive {
                 .clamp_to_range(false)
                 .text("Power"),
         );
+
+        let mut use_blackbody = self.blackbody_temperature.is_some();
+        ui.checkbox(&mut use_blackbody, "Blackbody");
+        match (use_blackbody, &mut self.blackbody_temperature) {
+            (true, Some(temperature)) => {
+                // colours below 1667K are the same, see
+                // `util::planckian_locus_xy()`
+                ui.add(egui::Slider::new(temperature, 1667.0..=12000.0).text("Temperature (K)"));
+            }
+            (true, None) => self.blackbody_temperature = Some(6500.0),
+            (false, _) => self.blackbody_temperature = None,
+        }
+
+        let mut use_strength = self.strength.is_some();
+        ui.checkbox(&mut use_strength, "Strength Texture");
+        match (use_strength, &mut self.strength) {
+            (true, Some(strength)) => {
+                ui.horizontal(|ui| {
+                    ui.label("Strength");
+                    strength.draw_ui_mut(
+                        ui,
+                        &ColorPickerUiData::new(
+                            extra_data.get_texture_list().clone(),
+                            extra_data.get_shader_egui_id().with("Strength"),
+                        ),
+                    );
+                });
+            }
+            (true, None) => self.strength = Some(ColorPicker::Texture(Default::default())),
+            (false, _) => self.strength = None,
+        }
+
+        ies::ies_profile_ui(ui, &mut self.ies_profile);
+
+        ui.checkbox(&mut self.normalize_by_area, "Normalize Power by Area");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::ray::Ray;
+
+    /// Profile that is not symmetric about any plane, each
+    /// horizontal angle has a different intensity away from nadir.
+    const IES_ASYMMETRIC: &str = "IESNA:LM-63-2002
+[TEST] asymmetric
+TILT=NONE
+1 1000 1 3 5 1 2 0 0 0
+1 1 100
+0 45 90
+0 90 180 270 360
+100 100 100
+100 50 50
+100 20 20
+100 80 80
+100 100 100
+";
+
+    fn get_ies_intensity(
+        emissive: &Emissive,
+        dpdu: glm::DVec3,
+        horizontal_direction: glm::DVec3,
+    ) -> f64 {
+        // the emitter lies on the XY plane facing +Z
+        let normal = glm::vec3(0.0, 0.0, 1.0);
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 1.0), -normal);
+        let mut intersect_info = IntersectInfo::new(1.0, glm::zero(), glm::zero());
+        intersect_info.set_normal(&ray, &normal);
+        intersect_info.set_uv_derivatives(Some((dpdu, glm::cross(&normal, &dpdu))));
+
+        // 45 degrees away from nadir
+        let wo = (horizontal_direction + normal).normalize();
+        emissive
+            .emission(
+                &wo,
+                &Mediums::with_air(),
+                &intersect_info,
+                &TextureList::new(),
+            )
+            .unwrap()[0]
+    }
+
+    #[test]
+    fn emissive_ies_profile_test_01() {
+        let mut emissive = Emissive::new(glm::vec3(1.0, 1.0, 1.0), 1.0);
+        emissive.set_ies_profile(Some(
+            IESProfile::parse(IES_ASYMMETRIC, "asymmetric".to_string()).unwrap(),
+        ));
+
+        // the profile points along the outward normal
+        let normal = glm::vec3(0.0, 0.0, 1.0);
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 1.0), -normal);
+        let mut intersect_info = IntersectInfo::new(1.0, glm::zero(), glm::zero());
+        intersect_info.set_normal(&ray, &normal);
+        let emission = emissive
+            .emission(
+                &normal,
+                &Mediums::with_air(),
+                &intersect_info,
+                &TextureList::new(),
+            )
+            .unwrap();
+        assert!((emission[0] - 1.0).abs() < 1e-6);
+
+        // the horizontal angle 0 is along the U direction of the
+        // uvs, the horizontal angle 90 is 90 degrees clockwise about
+        // the outward normal, the same as for lights which point
+        // along their -Z axis
+        [
+            (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+            (glm::vec3(0.0, 1.0, 0.0), glm::vec3(1.0, 0.0, 0.0)),
+            (
+                glm::vec3(-1.0, 1.0, 0.0).normalize(),
+                glm::vec3(1.0, 1.0, 0.0).normalize(),
+            ),
+        ]
+        .iter()
+        .for_each(|(dpdu, axis_y)| {
+            [(*dpdu, 1.0), (*axis_y, 0.5), (-dpdu, 0.2), (-axis_y, 0.8)]
+                .iter()
+                .for_each(|(horizontal_direction, expected)| {
+                    let intensity = get_ies_intensity(&emissive, *dpdu, *horizontal_direction);
+                    assert!(
+                        (intensity - expected).abs() < 1e-6,
+                        "dpdu {}: {} != {}",
+                        dpdu,
+                        intensity,
+                        expected
+                    );
+                });
+        });
+
+        // the U direction does not need to lie on the surface
+        let intensity = get_ies_intensity(
+            &emissive,
+            glm::vec3(0.0, 2.0, 0.5),
+            glm::vec3(-1.0, 0.0, 0.0),
+        );
+        assert!((intensity - 0.8).abs() < 1e-6);
     }
 }
@@ -3,24 +3,73 @@ use serde::{Deserialize, Serialize};
 
 use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
 use super::super::intersectable::IntersectInfo;
+use super::microfacet::{self, ShadingFrame};
 use super::utils::{self, ColorPicker, ColorPickerUiData};
 use super::BSDFUiData;
 use crate::egui;
 use crate::glm;
-use crate::path_trace::medium::{Medium, Mediums};
+use crate::path_trace::medium::Mediums;
 use crate::path_trace::texture_list::TextureList;
 use crate::ui::DrawUI;
 
+/// Rough dielectric, reflection and refraction through a GGX
+/// (Trowbridge-Reitz) microfacet surface. Reflection or refraction
+/// is chosen based on the Fresnel reflectance of the sampled
+/// microfacet.
+///
+/// reference: Walter et al. "Microfacet Models for Refraction
+/// through Rough Surfaces" (2007)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "GlassIO")]
 pub struct Glass {
     color: ColorPicker,
     ior: f64,
-    roughness: f64,
+
+    /// alpha of the GGX distribution, the square of the roughness,
+    /// see [`microfacet::roughness_to_alpha()`]
+    alpha: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum GlassIO {
+    // latest version must be tried first, untagged enums are tried
+    // in order
+    V1 {
+        color: ColorPicker,
+        ior: f64,
+        alpha: f64,
+    },
+    /// Stored the roughness, it was used as the probability of
+    /// scattering diffusely, it is now read as the roughness of the
+    /// microfacets.
+    V0 {
+        color: ColorPicker,
+        ior: f64,
+        roughness: f64,
+    },
+}
+
+impl From<GlassIO> for Glass {
+    fn from(io: GlassIO) -> Self {
+        match io {
+            GlassIO::V1 { color, ior, alpha } => Self { color, ior, alpha },
+            GlassIO::V0 {
+                color,
+                ior,
+                roughness,
+            } => Self {
+                color,
+                ior,
+                alpha: microfacet::roughness_to_alpha(roughness),
+            },
+        }
+    }
 }
 
 impl Default for Glass {
     fn default() -> Self {
-        Self::new(glm::vec3(1.0, 1.0, 1.0), 1.5, 0.4)
+        Self::new(glm::vec3(1.0, 1.0, 1.0), 1.5, 0.0)
     }
 }
 
@@ -29,98 +78,111 @@ impl Glass {
         Self {
             color: ColorPicker::Color(color),
             ior,
-            roughness,
+            alpha: microfacet::roughness_to_alpha(roughness),
         }
     }
 
+    /// Get the glass's roughness.
+    pub fn get_roughness(&self) -> f64 {
+        self.alpha.sqrt()
+    }
+
+    /// Set the glass's roughness.
+    pub fn set_roughness(&mut self, roughness: f64) {
+        self.alpha = microfacet::roughness_to_alpha(roughness);
+    }
+
+    fn get_alpha(&self) -> f64 {
+        self.alpha
+    }
+
     fn handle_reflection(
-        wo: &glm::DVec3,
-        intersect_info: &IntersectInfo,
+        &self,
+        wi: glm::DVec3,
+        pdf: f64,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
-        if sampling_types.contains(SamplingTypes::Reflection) {
-            Some(SampleData::new(
-                glm::reflect_vec(wo, intersect_info.get_normal().as_ref().unwrap()),
-                SamplingTypes::Reflection,
-            ))
+        if microfacet::is_smooth(self.get_alpha()) {
+            if sampling_types.contains(SamplingTypes::Reflection) {
+                Some(SampleData::new(wi, SamplingTypes::Reflection))
+            } else {
+                None
+            }
+        } else if sampling_types.contains(SamplingTypes::Glossy) {
+            Some(SampleData::new_with_pdf(wi, SamplingTypes::Glossy, pdf))
         } else {
             None
         }
     }
 
-    fn handle_refraction_and_reflection(
+    fn handle_refraction(
         &self,
-        wo: &glm::DVec3,
+        wi: glm::DVec3,
+        pdf: f64,
         mediums: &mut Mediums,
-        intersect_info: &IntersectInfo,
+        entering: bool,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
-        // TODO: need to figure out which sampling type it would be,
-        // both diffuse and reflection seem to make sense
-        if sampling_types.contains(SamplingTypes::Diffuse | SamplingTypes::Reflection) {
-            let entering = intersect_info.get_front_face();
-
-            let ior = if entering {
-                let ior = mediums.get_lastest_medium().unwrap().get_ior() / self.get_ior();
-                ior
-            } else {
-                // need to check second latest medium since the
-                // lastest medium would be the same as the medium of
-                // wi
-                //
-                // if there is no more mediums, the ray must have had
-                // more exits than entries and thus must not
-                // sample. This can happen because of non manifold
-                // meshes. If there exists a medium, calculate the
-                // ior.
-                self.get_ior() / mediums.get_second_lastest_medium()?.get_ior()
-            };
+        if sampling_types.contains(SamplingTypes::Transmission) {
+            utils::transmit_through_dielectric(self.get_ior(), mediums, entering);
 
-            let refracted_wi =
-                -glm::refract_vec(&-wo, intersect_info.get_normal().as_ref().unwrap(), ior);
-
-            // if refraction can take place. It may not be possible
-            // when `wi` is at an angle (with the normal) greater than
-            // critical angle and `wi` would be in a denser medium
-            // than `wo`. In such a case total internal reflection
-            // will take place.
-            if refracted_wi != glm::DVec3::zeros() {
-                if sampling_types.contains(SamplingTypes::Diffuse) {
-                    // add `wi` medium if entering the medium
-                    if entering {
-                        mediums.add_medium(Medium::new(self.get_ior()));
-                    } else {
-                        // must remove the latest medium
-                        mediums.remove_medium().unwrap();
-                    }
-
-                    Some(SampleData::new(refracted_wi, SamplingTypes::Diffuse))
-                } else {
-                    None
-                }
+            if microfacet::is_smooth(self.get_alpha()) {
+                Some(SampleData::new(wi, SamplingTypes::Transmission))
             } else {
-                // total internal reflection (TIR)
-                Self::handle_reflection(wo, intersect_info, sampling_types)
+                Some(SampleData::new_with_pdf(
+                    wi,
+                    SamplingTypes::Transmission,
+                    pdf,
+                ))
             }
         } else {
             None
         }
     }
 
-    fn handle_diffuse(
-        &self,
-        _intersect_info: &IntersectInfo,
-        sampling_types: BitFlags<SamplingTypes>,
-    ) -> Option<SampleData> {
-        if sampling_types.contains(SamplingTypes::Diffuse) {
-            Some(SampleData::new(
-                crate::math::random_in_unit_sphere(),
-                SamplingTypes::Diffuse,
-            ))
-        } else {
+    /// Get the microfacet normal that refracts `wo` into `light`
+    /// (both in the local space of the shading frame, on opposite
+    /// sides of the surface), `eta` is the ratio of the ior of the
+    /// side of `light` to the ior of the side of `wo`.
+    ///
+    /// Returns `None` if no microfacet normal visible from both
+    /// directions refracts one into the other.
+    fn get_refraction_half_vector(
+        wo: &glm::DVec3,
+        light: &glm::DVec3,
+        eta: f64,
+    ) -> Option<glm::DVec3> {
+        let m = -(wo + light * eta);
+        if m.norm() < 1e-12 {
+            return None;
+        }
+        let m = m.normalize();
+        let m = if m[2] < 0.0 { -m } else { m };
+        if glm::dot(wo, &m) <= 0.0 || glm::dot(light, &m) >= 0.0 {
             None
+        } else {
+            Some(m)
         }
     }
+
+    /// Get the probability density of [`microfacet::refract()`]
+    /// about a visible microfacet normal `m` giving `light`, without
+    /// the probability of choosing refraction.
+    ///
+    /// reference: Walter et al. "Microfacet Models for Refraction
+    /// through Rough Surfaces" (2007), equation 17
+    fn refraction_pdf(
+        wo: &glm::DVec3,
+        light: &glm::DVec3,
+        m: &glm::DVec3,
+        eta: f64,
+        alpha: f64,
+    ) -> f64 {
+        let light_dot_m = glm::dot(light, m);
+        let denominator = glm::dot(wo, m) + eta * light_dot_m;
+        microfacet::ggx_vndf_pdf(wo, m, alpha) * eta * eta * light_dot_m.abs()
+            / (denominator * denominator)
+    }
 }
 
 #[typetag::serde]
@@ -130,48 +192,153 @@ impl BSDF for Glass {
         wo: &glm::DVec3,
         mediums: &mut Mediums,
         intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
-        // TODO(ish): need to handle roughness accurately, using
-        // something like GGX microfacet model. Right now it cannot
-        // give physically accurate results.
+        let entering = intersect_info.get_front_face();
+        let eta = utils::dielectric_eta(self.get_ior(), mediums, entering)?;
+
+        let alpha = self.get_alpha();
+        let frame = ShadingFrame::new(intersect_info.get_normal().as_ref().unwrap());
+        let wo_local = frame.to_local(&wo.normalize());
+        if wo_local[2] <= 0.0 {
+            return None;
+        }
 
-        if rand::random::<f64>() < self.roughness {
-            // sample diffuse
-            self.handle_diffuse(intersect_info, sampling_types)
+        let m = if microfacet::is_smooth(alpha) {
+            glm::vec3(0.0, 0.0, 1.0)
         } else {
-            // sample refraction and reflection
-            let entering = intersect_info.get_front_face();
-            let (n1, n2) = if entering {
-                (
-                    self.get_ior(),
-                    mediums.get_lastest_medium().unwrap().get_ior(),
-                )
-            } else {
-                (
-                    mediums.get_lastest_medium().unwrap().get_ior(),
-                    self.get_ior(),
-                )
-            };
-            let fresnel = utils::fresnel(intersect_info.get_normal().as_ref().unwrap(), wo, n1, n2);
+            microfacet::sample_ggx_vndf(
+                &wo_local,
+                alpha,
+                rand::random::<f64>(),
+                rand::random::<f64>(),
+            )
+        };
+        let wo_dot_m = glm::dot(&wo_local, &m);
+        if wo_dot_m <= 0.0 {
+            return None;
+        }
 
-            if rand::random::<f64>() < fresnel {
-                Self::handle_reflection(wo, intersect_info, sampling_types)
-            } else {
-                self.handle_refraction_and_reflection(wo, mediums, intersect_info, sampling_types)
+        // fresnel is 1.0 on total internal reflection, so reflection
+        // is always chosen then
+        let fresnel = microfacet::fresnel_dielectric(wo_dot_m, eta);
+
+        if rand::random::<f64>() < fresnel {
+            let light_local = m * (2.0 * wo_dot_m) - wo_local;
+            if light_local[2] <= 0.0 {
+                return None;
+            }
+            let pdf = fresnel * microfacet::ggx_vndf_pdf(&wo_local, &m, alpha) / (4.0 * wo_dot_m);
+
+            // need to return `wi` which should point towards the
+            // hitpoint
+            self.handle_reflection(-frame.to_world(&light_local), pdf, sampling_types)
+        } else {
+            let light_local = microfacet::refract(&wo_local, &m, eta)?;
+            if light_local[2] >= 0.0 {
+                return None;
             }
+            let pdf =
+                (1.0 - fresnel) * Self::refraction_pdf(&wo_local, &light_local, &m, eta, alpha);
+            self.handle_refraction(
+                -frame.to_world(&light_local),
+                pdf,
+                mediums,
+                entering,
+                sampling_types,
+            )
         }
     }
 
     fn eval(
         &self,
-        _wi: &glm::DVec3,
-        _wo: &glm::DVec3,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
         intersect_info: &IntersectInfo,
         texture_list: &TextureList,
     ) -> glm::DVec3 {
-        self.color
-            .get_color(intersect_info.get_uv().as_ref().unwrap(), texture_list)
+        let color = self.color.get_color_at(intersect_info, texture_list);
+        let alpha = self.get_alpha();
+        if microfacet::is_smooth(alpha) {
+            return color;
+        }
+
+        let frame = ShadingFrame::new(intersect_info.get_normal().as_ref().unwrap());
+        let wo_local = frame.to_local(&wo.normalize());
+        let light_local = frame.to_local(&-wi.normalize());
+        if wo_local[2] <= 0.0 || light_local[2] == 0.0 {
+            return glm::zero();
+        }
+
+        // with visible normal sampling, the fresnel (used to choose
+        // between reflection and refraction), the distribution and
+        // the jacobians cancel out for both reflection and
+        // refraction, only the masking-shadowing remains
+        color * microfacet::smith_g2(&wo_local, &light_local, alpha)
+            / microfacet::smith_g1(&wo_local, alpha)
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        // perfect reflection and refraction cannot be evaluated
+        let alpha = self.get_alpha();
+        if microfacet::is_smooth(alpha) {
+            return None;
+        }
+
+        // same as `sample()`, there is nothing to scatter if there is
+        // no medium on the other side
+        let eta =
+            match utils::dielectric_eta(self.get_ior(), mediums, intersect_info.get_front_face()) {
+                Some(eta) => eta,
+                None => return Some((glm::zero(), 0.0)),
+            };
+
+        let frame = ShadingFrame::new(intersect_info.get_normal().as_ref().unwrap());
+        let wo_local = frame.to_local(&wo.normalize());
+        let light_local = frame.to_local(&-wi.normalize());
+        if wo_local[2] <= 0.0 || light_local[2] == 0.0 {
+            return Some((glm::zero(), 0.0));
+        }
+        let color = self.color.get_color_at(intersect_info, texture_list);
+        let g2 = microfacet::smith_g2(&wo_local, &light_local, alpha);
+
+        if light_local[2] > 0.0 {
+            let m = (wo_local + light_local).normalize();
+            let fresnel = microfacet::fresnel_dielectric(glm::dot(&wo_local, &m), eta);
+            let d = microfacet::ggx_d(&m, alpha);
+
+            let pdf = fresnel * microfacet::smith_g1(&wo_local, alpha) * d / (4.0 * wo_local[2]);
+            Some((color * (fresnel * d * g2 / (4.0 * wo_local[2])), pdf))
+        } else {
+            let m = match Self::get_refraction_half_vector(&wo_local, &light_local, eta) {
+                Some(m) => m,
+                None => return Some((glm::zero(), 0.0)),
+            };
+            let fresnel = microfacet::fresnel_dielectric(glm::dot(&wo_local, &m), eta);
+            let pdf =
+                (1.0 - fresnel) * Self::refraction_pdf(&wo_local, &light_local, &m, eta, alpha);
+
+            // the BSDF times the cosine term, the scaling of the
+            // radiance by eta^2 is not applied, the same as `eval()`
+            // so that the light found by `sample()` and by
+            // `eval_with_pdf()` agree
+            Some((
+                color * (pdf * g2 / microfacet::smith_g1(&wo_local, alpha)),
+                pdf,
+            ))
+        }
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        SamplingTypes::Glossy | SamplingTypes::Transmission
     }
 
     fn get_bsdf_name(&self) -> &str {
@@ -214,10 +381,113 @@ impl DrawUI for Glass {
                 .clamp_to_range(false)
                 .text("ior"),
         );
-        ui.add(
-            egui::Slider::new(&mut self.roughness, 0.0..=1.0)
-                .clamp_to_range(false)
-                .text("Roughness"),
-        );
+        let mut roughness = self.get_roughness();
+        if ui
+            .add(
+                egui::Slider::new(&mut roughness, 0.0..=1.0)
+                    .clamp_to_range(false)
+                    .text("Roughness"),
+            )
+            .changed()
+        {
+            self.set_roughness(roughness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::medium::Medium;
+    use crate::path_trace::ray::Ray;
+
+    /// The probability density and the colour of the directions
+    /// sampled by `sample()` must be the same as the ones given by
+    /// `eval_with_pdf()` for reflection and refraction, with the ior
+    /// outside the glass taken from the mediums.
+    #[test]
+    fn glass_eval_with_pdf_matches_sample_test_01() {
+        let glass = Glass::new(glm::vec3(0.9, 0.8, 0.7), 1.5, 0.5);
+        let texture_list = TextureList::new();
+
+        let mut water = Mediums::with_air();
+        water.add_medium(Medium::new(1.33));
+        let mut in_glass = Mediums::with_air();
+        in_glass.add_medium(Medium::glass());
+
+        [
+            // entering the glass from air and from water
+            (Mediums::with_air(), glm::vec3(0.3, 0.1, -1.0)),
+            (water, glm::vec3(0.3, 0.1, -1.0)),
+            // exiting the glass
+            (in_glass, glm::vec3(0.3, 0.1, 1.0)),
+        ]
+        .iter()
+        .for_each(|(mediums, direction)| {
+            let ray = Ray::new(glm::zero(), direction.normalize());
+            let mut intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+            intersect_info.set_normal(&ray, &glm::vec3(0.0, 0.0, 1.0));
+            let wo = -ray.get_direction();
+
+            let mut num_refractions = 0;
+            (0..1000).for_each(|_| {
+                let mut sample_mediums = mediums.clone();
+                let sample_data = match glass.sample(
+                    &wo,
+                    &mut sample_mediums,
+                    &intersect_info,
+                    &texture_list,
+                    BitFlags::all(),
+                ) {
+                    Some(sample_data) => sample_data,
+                    None => return,
+                };
+                if sample_data.get_sampling_type() == SamplingTypes::Transmission {
+                    num_refractions += 1;
+                }
+                let sample_pdf = sample_data.get_pdf().unwrap();
+                let wi = sample_data.get_wi();
+
+                let (color, pdf) = glass
+                    .eval_with_pdf(wi, &wo, mediums, &intersect_info, &texture_list)
+                    .unwrap();
+                assert!(
+                    (pdf - sample_pdf).abs() < 1e-6 * sample_pdf.max(1.0),
+                    "{} != {}",
+                    pdf,
+                    sample_pdf
+                );
+                assert!(
+                    glm::distance(
+                        &(color / pdf),
+                        &glass.eval(wi, &wo, &intersect_info, &texture_list)
+                    ) < 1e-6
+                );
+            });
+            assert!(num_refractions > 0);
+        });
+    }
+
+    #[test]
+    fn glass_serialize_round_trip_test_01() {
+        let glass = Glass::new(glm::vec3(0.2, 0.4, 0.6), 1.33, 0.5);
+        let loaded: Glass = serde_json::from_str(&serde_json::to_string(&glass).unwrap()).unwrap();
+        assert!((loaded.get_alpha() - 0.25).abs() < 1e-12);
+        assert!((loaded.get_roughness() - 0.5).abs() < 1e-12);
+        assert_eq!(loaded.get_ior(), 1.33);
+    }
+
+    #[test]
+    fn glass_deserialize_v0_test_01() {
+        // previous files stored the roughness instead of the alpha
+        let mut value =
+            serde_json::to_value(&Glass::new(glm::vec3(0.2, 0.4, 0.6), 1.33, 0.0)).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("alpha").unwrap();
+        object.insert("roughness".to_string(), serde_json::json!(0.5));
+        let loaded: Glass = serde_json::from_value(value).unwrap();
+        assert!((loaded.get_alpha() - 0.25).abs() < 1e-12);
+        assert!((loaded.get_roughness() - 0.5).abs() < 1e-12);
+        assert_eq!(loaded.get_ior(), 1.33);
     }
 }
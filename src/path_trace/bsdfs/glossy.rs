@@ -3,7 +3,8 @@ use serde::{Deserialize, Serialize};
 
 use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
 use super::super::intersectable::IntersectInfo;
-use super::utils::{self, ColorPicker, ColorPickerUiData};
+use super::microfacet;
+use super::utils::{ColorPicker, ColorPickerUiData};
 use super::BSDFUiData;
 use crate::egui;
 use crate::glm;
@@ -11,12 +12,37 @@ use crate::path_trace::medium::Mediums;
 use crate::path_trace::texture_list::TextureList;
 use crate::ui::DrawUI;
 
+/// Rough conductor, reflection off a GGX (Trowbridge-Reitz)
+/// microfacet surface with a Smith masking-shadowing term. The
+/// colour is the reflectance at normal incidence, it tends towards
+/// white at grazing angles (Schlick's Fresnel approximation).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "GlossyIO")]
 pub struct Glossy {
     color: ColorPicker,
 
-    #[serde(default = "default_roughness")]
-    roughness: f64,
+    /// alpha of the GGX distribution, the square of the roughness,
+    /// see [`microfacet::roughness_to_alpha()`]
+    alpha: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum GlossyIO {
+    // latest version must be tried first, untagged enums are tried
+    // in order
+    V1 {
+        color: ColorPicker,
+        alpha: f64,
+    },
+    /// Stored the roughness, it was used as the probability of
+    /// scattering diffusely, it is now read as the roughness of the
+    /// microfacets.
+    V0 {
+        color: ColorPicker,
+        #[serde(default = "default_roughness")]
+        roughness: f64,
+    },
 }
 
 fn default_roughness() -> f64 {
@@ -24,6 +50,18 @@ fn default_roughness() -> f64 {
     0.0
 }
 
+impl From<GlossyIO> for Glossy {
+    fn from(io: GlossyIO) -> Self {
+        match io {
+            GlossyIO::V1 { color, alpha } => Self { color, alpha },
+            GlossyIO::V0 { color, roughness } => Self {
+                color,
+                alpha: microfacet::roughness_to_alpha(roughness),
+            },
+        }
+    }
+}
+
 impl Default for Glossy {
     fn default() -> Self {
         Self::new(glm::vec3(1.0, 1.0, 1.0), 0.4)
@@ -34,39 +72,22 @@ impl Glossy {
     pub fn new(color: glm::DVec3, roughness: f64) -> Self {
         Self {
             color: ColorPicker::Color(color),
-            roughness,
+            alpha: microfacet::roughness_to_alpha(roughness),
         }
     }
 
-    fn handle_reflection(
-        &self,
-        wo: &glm::DVec3,
-        intersect_info: &IntersectInfo,
-        sampling_types: BitFlags<SamplingTypes>,
-    ) -> Option<SampleData> {
-        if sampling_types.contains(SamplingTypes::Reflection) {
-            Some(SampleData::new(
-                glm::reflect_vec(wo, intersect_info.get_normal().as_ref().unwrap()),
-                SamplingTypes::Reflection,
-            ))
-        } else {
-            None
-        }
+    /// Get the glossy's roughness.
+    pub fn get_roughness(&self) -> f64 {
+        self.alpha.sqrt()
     }
 
-    fn handle_diffuse(
-        &self,
-        intersect_info: &IntersectInfo,
-        sampling_types: BitFlags<SamplingTypes>,
-    ) -> Option<SampleData> {
-        if sampling_types.contains(SamplingTypes::Diffuse) {
-            Some(SampleData::new(
-                utils::wi_diffuse(intersect_info.get_normal().as_ref().unwrap()),
-                SamplingTypes::Diffuse,
-            ))
-        } else {
-            None
-        }
+    /// Set the glossy's roughness.
+    pub fn set_roughness(&mut self, roughness: f64) {
+        self.alpha = microfacet::roughness_to_alpha(roughness);
+    }
+
+    fn get_alpha(&self) -> f64 {
+        self.alpha
     }
 }
 
@@ -77,29 +98,54 @@ impl BSDF for Glossy {
         wo: &glm::DVec3,
         _mediums: &mut Mediums,
         intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
-        // TODO(ish): need to handle roughness accurately, using
-        // something like GGX microfacet model. Right now it cannot
-        // give physically accurate results.
-        if rand::random::<f64>() < self.roughness {
-            // sample diffuse
-            self.handle_diffuse(intersect_info, sampling_types)
-        } else {
-            // sample pure reflection
-            self.handle_reflection(wo, intersect_info, sampling_types)
-        }
+        microfacet::sample_ggx_reflection(
+            wo,
+            intersect_info.get_normal().as_ref().unwrap(),
+            self.get_alpha(),
+            sampling_types,
+        )
     }
 
     fn eval(
         &self,
-        _wi: &glm::DVec3,
-        _wo: &glm::DVec3,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
         intersect_info: &IntersectInfo,
         texture_list: &TextureList,
     ) -> glm::DVec3 {
-        self.color
-            .get_color(intersect_info.get_uv().as_ref().unwrap(), texture_list)
+        let color = self.color.get_color_at(intersect_info, texture_list);
+        microfacet::eval_ggx_reflection(
+            wi,
+            wo,
+            intersect_info.get_normal().as_ref().unwrap(),
+            self.get_alpha(),
+            |cos_theta| microfacet::fresnel_schlick(&color, cos_theta),
+        )
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        let color = self.color.get_color_at(intersect_info, texture_list);
+        microfacet::eval_ggx_reflection_with_pdf(
+            wi,
+            wo,
+            intersect_info.get_normal().as_ref().unwrap(),
+            self.get_alpha(),
+            |cos_theta| microfacet::fresnel_schlick(&color, cos_theta),
+        )
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::from(SamplingTypes::Glossy)
     }
 
     fn get_bsdf_name(&self) -> &str {
@@ -133,10 +179,108 @@ impl DrawUI for Glossy {
                 ),
             );
         });
-        ui.add(
-            egui::Slider::new(&mut self.roughness, 0.0..=1.0)
-                .clamp_to_range(false)
-                .text("Roughness"),
-        );
+        let mut roughness = self.get_roughness();
+        if ui
+            .add(
+                egui::Slider::new(&mut roughness, 0.0..=1.0)
+                    .clamp_to_range(false)
+                    .text("Roughness"),
+            )
+            .changed()
+        {
+            self.set_roughness(roughness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::ray::Ray;
+
+    /// The probability density and the colour of the directions
+    /// sampled by `sample()` must be the same as the ones given by
+    /// `eval_with_pdf()` and `eval()`.
+    #[test]
+    fn glossy_eval_with_pdf_matches_sample_test_01() {
+        let texture_list = TextureList::new();
+
+        [0.2, 0.5, 1.0].iter().for_each(|roughness| {
+            let glossy = Glossy::new(glm::vec3(0.9, 0.6, 0.3), *roughness);
+            [0.0_f64, 45.0, 80.0].iter().for_each(|theta| {
+                let theta = theta.to_radians();
+                let ray = Ray::new(glm::zero(), glm::vec3(theta.sin(), 0.0, -theta.cos()));
+                let mut intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+                intersect_info.set_normal(&ray, &glm::vec3(0.0, 0.0, 1.0));
+                let wo = -ray.get_direction();
+
+                let mut num_samples = 0;
+                (0..1000).for_each(|_| {
+                    let sample_data = match glossy.sample(
+                        &wo,
+                        &mut Mediums::with_air(),
+                        &intersect_info,
+                        &texture_list,
+                        BitFlags::all(),
+                    ) {
+                        Some(sample_data) => sample_data,
+                        None => return,
+                    };
+                    num_samples += 1;
+                    assert_eq!(sample_data.get_sampling_type(), SamplingTypes::Glossy);
+                    let sample_pdf = sample_data.get_pdf().unwrap();
+                    let wi = sample_data.get_wi();
+
+                    let (color, pdf) = glossy
+                        .eval_with_pdf(
+                            wi,
+                            &wo,
+                            &Mediums::with_air(),
+                            &intersect_info,
+                            &texture_list,
+                        )
+                        .unwrap();
+                    assert!(
+                        (pdf - sample_pdf).abs() < 1e-6 * sample_pdf.max(1.0),
+                        "{} != {}",
+                        pdf,
+                        sample_pdf
+                    );
+                    assert!(
+                        glm::distance(
+                            &(color / pdf),
+                            &glossy.eval(wi, &wo, &intersect_info, &texture_list)
+                        ) < 1e-6
+                    );
+                });
+                assert!(num_samples > 0);
+            });
+        });
+    }
+
+    #[test]
+    fn glossy_serialize_round_trip_test_01() {
+        let glossy = Glossy::new(glm::vec3(0.2, 0.4, 0.6), 0.5);
+        let loaded: Glossy =
+            serde_json::from_str(&serde_json::to_string(&glossy).unwrap()).unwrap();
+        assert!((loaded.get_alpha() - 0.25).abs() < 1e-12);
+        assert!((loaded.get_roughness() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn glossy_deserialize_v0_test_01() {
+        // previous files stored the roughness instead of the alpha
+        let mut value = serde_json::to_value(&Glossy::new(glm::vec3(0.2, 0.4, 0.6), 0.0)).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("alpha").unwrap();
+        object.insert("roughness".to_string(), serde_json::json!(0.5));
+        let loaded: Glossy = serde_json::from_value(value.clone()).unwrap();
+        assert!((loaded.get_alpha() - 0.25).abs() < 1e-12);
+        assert!((loaded.get_roughness() - 0.5).abs() < 1e-12);
+
+        // even older files did not store the roughness
+        value.as_object_mut().unwrap().remove("roughness").unwrap();
+        let loaded: Glossy = serde_json::from_value(value).unwrap();
+        assert_eq!(loaded.get_alpha(), 0.0);
     }
 }
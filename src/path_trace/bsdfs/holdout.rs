@@ -0,0 +1,72 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{CompositingType, SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::utils::ColorPicker;
+use super::BSDFUiData;
+use crate::egui;
+use crate::glm;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+
+/// Cuts out the alpha of the render where it is seen by the camera,
+/// it is black and absorbs all light for every other ray.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Holdout {}
+
+impl Holdout {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[typetag::serde]
+impl BSDF for Holdout {
+    fn sample(
+        &self,
+        _wo: &glm::DVec3,
+        _mediums: &mut Mediums,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+        _sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        None
+    }
+
+    fn eval(
+        &self,
+        _wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        // absorbs all light
+        glm::zero()
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Holdout"
+    }
+
+    fn get_base_color(&self, _texture_list: &TextureList) -> Option<glm::DVec3> {
+        None
+    }
+
+    fn set_base_color(&mut self, _color: ColorPicker) {}
+
+    fn get_compositing_type(&self) -> Option<CompositingType> {
+        Some(CompositingType::Holdout)
+    }
+}
+
+impl DrawUI for Holdout {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+}
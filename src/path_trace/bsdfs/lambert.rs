@@ -37,12 +37,16 @@ impl BSDF for Lambert {
         _wo: &glm::DVec3,
         _mediums: &mut Mediums,
         intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
         if sampling_types.contains(SamplingTypes::Diffuse) {
-            Some(SampleData::new(
-                utils::wi_diffuse(intersect_info.get_normal().as_ref().unwrap()),
+            let normal = intersect_info.get_normal().as_ref().unwrap();
+            let wi = utils::wi_diffuse(normal);
+            Some(SampleData::new_with_pdf(
+                wi,
                 SamplingTypes::Diffuse,
+                utils::pdf_diffuse(normal, &wi),
             ))
         } else {
             None
@@ -56,8 +60,28 @@ impl BSDF for Lambert {
         intersect_info: &IntersectInfo,
         texture_list: &TextureList,
     ) -> glm::DVec3 {
-        self.color
-            .get_color(intersect_info.get_uv().as_ref().unwrap(), texture_list)
+        self.color.get_color_at(intersect_info, texture_list)
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        // the BSDF is color / pi and the cosine term cos / pi is the
+        // same as the pdf
+        let pdf = utils::pdf_diffuse(intersect_info.get_normal().as_ref().unwrap(), wi);
+        Some((
+            self.color.get_color_at(intersect_info, texture_list) * pdf,
+            pdf,
+        ))
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::from(SamplingTypes::Diffuse)
     }
 
     fn get_bsdf_name(&self) -> &str {
@@ -0,0 +1,485 @@
+use enumflags2::BitFlags;
+
+use super::super::bsdf::{SampleData, SamplingTypes};
+use crate::{glm, util};
+
+/// Surfaces with an alpha (see [`roughness_to_alpha()`]) smaller
+/// than this are treated as perfectly smooth, the microfacet
+/// distribution is too sharp to be sampled or evaluated reliably.
+pub const SMOOTH_ALPHA: f64 = 1e-3;
+
+/// Convert the (perceptually linear) roughness to the alpha of the
+/// GGX distribution.
+pub fn roughness_to_alpha(roughness: f64) -> f64 {
+    let roughness = roughness.clamp(0.0, 1.0);
+    roughness * roughness
+}
+
+/// Check if the surface with the given alpha must be treated as
+/// perfectly smooth, see [`SMOOTH_ALPHA`].
+pub fn is_smooth(alpha: f64) -> bool {
+    alpha < SMOOTH_ALPHA
+}
+
+/// Orthonormal frame about the shading normal. In the local space of
+/// the frame the normal is the +Z axis.
+pub struct ShadingFrame {
+    tangent: glm::DVec3,
+    bitangent: glm::DVec3,
+    normal: glm::DVec3,
+}
+
+impl ShadingFrame {
+    /// Create the frame about the given (normalized) normal.
+    pub fn new(normal: &glm::DVec3) -> Self {
+        let (tangent, bitangent) = util::orthonormal_basis(normal);
+        Self {
+            tangent,
+            bitangent,
+            normal: *normal,
+        }
+    }
+
+    /// Create the frame about the given (normalized) normal with the
+    /// X axis along the given tangent (projected on to the plane of
+    /// the normal). Falls back to [`Self::new()`] if the tangent is
+    /// parallel to the normal.
+    pub fn with_tangent(normal: &glm::DVec3, tangent: &glm::DVec3) -> Self {
+        let tangent = tangent - normal * glm::dot(normal, tangent);
+        let length = tangent.norm();
+        if length < 1e-12 {
+            return Self::new(normal);
+        }
+        let tangent = tangent / length;
+        Self {
+            tangent,
+            bitangent: glm::cross(normal, &tangent),
+            normal: *normal,
+        }
+    }
+
+    /// Transform the given vector from world space to the local
+    /// space of the frame.
+    pub fn to_local(&self, v: &glm::DVec3) -> glm::DVec3 {
+        glm::vec3(
+            glm::dot(v, &self.tangent),
+            glm::dot(v, &self.bitangent),
+            glm::dot(v, &self.normal),
+        )
+    }
+
+    /// Transform the given vector from the local space of the frame
+    /// to world space.
+    pub fn to_world(&self, v: &glm::DVec3) -> glm::DVec3 {
+        self.tangent * v[0] + self.bitangent * v[1] + self.normal * v[2]
+    }
+}
+
+/// GGX (Trowbridge-Reitz) distribution of the microfacet normals,
+/// `m` is in the local space of the shading frame.
+pub fn ggx_d(m: &glm::DVec3, alpha: f64) -> f64 {
+    ggx_d_anisotropic(m, alpha, alpha)
+}
+
+/// Anisotropic GGX distribution of the microfacet normals with
+/// different alphas along the X and Y axes of the shading frame, `m`
+/// is in the local space of the shading frame.
+pub fn ggx_d_anisotropic(m: &glm::DVec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    if m[2] <= 0.0 {
+        return 0.0;
+    }
+    let denominator = (m[0] / alpha_x).powi(2) + (m[1] / alpha_y).powi(2) + m[2] * m[2];
+    1.0 / (std::f64::consts::PI * alpha_x * alpha_y * denominator * denominator)
+}
+
+/// Smith's lambda function of the GGX distribution, `v` is in the
+/// local space of the shading frame.
+pub fn ggx_lambda(v: &glm::DVec3, alpha: f64) -> f64 {
+    ggx_lambda_anisotropic(v, alpha, alpha)
+}
+
+/// Smith's lambda function of the anisotropic GGX distribution, see
+/// [`ggx_d_anisotropic()`].
+pub fn ggx_lambda_anisotropic(v: &glm::DVec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    let cos2 = v[2] * v[2];
+    if cos2 <= 0.0 {
+        return f64::INFINITY;
+    }
+    // alpha^2 * tan^2 with the alpha projected on to the direction
+    let alpha2_tan2 = ((v[0] * alpha_x).powi(2) + (v[1] * alpha_y).powi(2)) / cos2;
+    0.5 * (-1.0 + (1.0 + alpha2_tan2).sqrt())
+}
+
+/// Smith masking function of the GGX distribution for the given
+/// direction (in the local space of the shading frame).
+pub fn smith_g1(v: &glm::DVec3, alpha: f64) -> f64 {
+    smith_g1_anisotropic(v, alpha, alpha)
+}
+
+/// Smith masking function of the anisotropic GGX distribution, see
+/// [`ggx_d_anisotropic()`].
+pub fn smith_g1_anisotropic(v: &glm::DVec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    1.0 / (1.0 + ggx_lambda_anisotropic(v, alpha_x, alpha_y))
+}
+
+/// Height correlated Smith masking-shadowing function of the GGX
+/// distribution for the given directions (in the local space of the
+/// shading frame).
+pub fn smith_g2(wo: &glm::DVec3, wi: &glm::DVec3, alpha: f64) -> f64 {
+    smith_g2_anisotropic(wo, wi, alpha, alpha)
+}
+
+/// Height correlated Smith masking-shadowing function of the
+/// anisotropic GGX distribution, see [`ggx_d_anisotropic()`].
+pub fn smith_g2_anisotropic(wo: &glm::DVec3, wi: &glm::DVec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    1.0 / (1.0
+        + ggx_lambda_anisotropic(wo, alpha_x, alpha_y)
+        + ggx_lambda_anisotropic(wi, alpha_x, alpha_y))
+}
+
+/// Sample a microfacet normal visible from `wo` (in the local space
+/// of the shading frame, must be above the surface) given two
+/// uniform random numbers.
+///
+/// reference: Heitz, "Sampling the GGX Distribution of Visible
+/// Normals" (2018)
+pub fn sample_ggx_vndf(wo: &glm::DVec3, alpha: f64, u1: f64, u2: f64) -> glm::DVec3 {
+    sample_ggx_vndf_anisotropic(wo, alpha, alpha, u1, u2)
+}
+
+/// Sample a microfacet normal of the anisotropic GGX distribution
+/// (see [`ggx_d_anisotropic()`]) visible from `wo`, see
+/// [`sample_ggx_vndf()`].
+pub fn sample_ggx_vndf_anisotropic(
+    wo: &glm::DVec3,
+    alpha_x: f64,
+    alpha_y: f64,
+    u1: f64,
+    u2: f64,
+) -> glm::DVec3 {
+    // stretch the view direction so that the distribution is a
+    // hemisphere
+    let vh = glm::vec3(alpha_x * wo[0], alpha_y * wo[1], wo[2]).normalize();
+
+    let length2 = vh[0] * vh[0] + vh[1] * vh[1];
+    let t1 = if length2 > 0.0 {
+        glm::vec3(-vh[1], vh[0], 0.0) / length2.sqrt()
+    } else {
+        glm::vec3(1.0, 0.0, 0.0)
+    };
+    let t2 = glm::cross(&vh, &t1);
+
+    // sample the projected area of the visible hemisphere
+    let r = u1.sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u2;
+    let p1 = r * phi.cos();
+    let p2 = r * phi.sin();
+    let s = 0.5 * (1.0 + vh[2]);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * p2;
+
+    let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+    // unstretch the normal
+    glm::vec3(alpha_x * nh[0], alpha_y * nh[1], nh[2].max(0.0)).normalize()
+}
+
+/// Probability density of [`sample_ggx_vndf()`] returning the
+/// microfacet normal `m` for the given `wo` (both in the local space
+/// of the shading frame).
+pub fn ggx_vndf_pdf(wo: &glm::DVec3, m: &glm::DVec3, alpha: f64) -> f64 {
+    ggx_vndf_pdf_anisotropic(wo, m, alpha, alpha)
+}
+
+/// Probability density of [`sample_ggx_vndf_anisotropic()`]
+/// returning the microfacet normal `m` for the given `wo`.
+pub fn ggx_vndf_pdf_anisotropic(
+    wo: &glm::DVec3,
+    m: &glm::DVec3,
+    alpha_x: f64,
+    alpha_y: f64,
+) -> f64 {
+    if wo[2] <= 0.0 {
+        return 0.0;
+    }
+    smith_g1_anisotropic(wo, alpha_x, alpha_y)
+        * glm::dot(wo, m).max(0.0)
+        * ggx_d_anisotropic(m, alpha_x, alpha_y)
+        / wo[2]
+}
+
+/// Sample the reflection off a GGX microfacet surface with the given
+/// alpha about the given shading normal. A smooth surface (see
+/// [`is_smooth()`]) is sampled as perfect reflection, otherwise the
+/// microfacet normals visible from `wo` are sampled.
+///
+/// Shared by the conductor like `BSDF`s that differ only in their
+/// Fresnel term, see [`eval_ggx_reflection_with_pdf()`].
+pub fn sample_ggx_reflection(
+    wo: &glm::DVec3,
+    normal: &glm::DVec3,
+    alpha: f64,
+    sampling_types: BitFlags<SamplingTypes>,
+) -> Option<SampleData> {
+    if is_smooth(alpha) {
+        if !sampling_types.contains(SamplingTypes::Reflection) {
+            return None;
+        }
+        return Some(SampleData::new(
+            glm::reflect_vec(wo, normal),
+            SamplingTypes::Reflection,
+        ));
+    }
+
+    if !sampling_types.contains(SamplingTypes::Glossy) {
+        return None;
+    }
+
+    let frame = ShadingFrame::new(normal);
+    let wo_local = frame.to_local(&wo.normalize());
+    if wo_local[2] <= 0.0 {
+        return None;
+    }
+
+    let m = sample_ggx_vndf(
+        &wo_local,
+        alpha,
+        rand::random::<f64>(),
+        rand::random::<f64>(),
+    );
+    let wo_dot_m = glm::dot(&wo_local, &m);
+    // direction of the reflected light (away from the surface)
+    let light_local = m * (2.0 * wo_dot_m) - wo_local;
+    if light_local[2] <= 0.0 || wo_dot_m <= 0.0 {
+        return None;
+    }
+
+    let pdf = ggx_vndf_pdf(&wo_local, &m, alpha) / (4.0 * wo_dot_m);
+
+    // need to return `wi` which should point towards the hitpoint
+    Some(SampleData::new_with_pdf(
+        -frame.to_world(&light_local),
+        SamplingTypes::Glossy,
+        pdf,
+    ))
+}
+
+/// Evaluate the reflection off a GGX microfacet surface, see
+/// [`sample_ggx_reflection()`]. `fresnel` gives the Fresnel
+/// reflectance for the cosine of the angle between the direction and
+/// the microfacet normal.
+///
+/// Returns the colour (multiplied by the cosine of the light) along
+/// with the probability density of sampling `wi`, `None` if the
+/// surface is smooth since perfect reflection cannot be evaluated.
+pub fn eval_ggx_reflection_with_pdf(
+    wi: &glm::DVec3,
+    wo: &glm::DVec3,
+    normal: &glm::DVec3,
+    alpha: f64,
+    fresnel: impl Fn(f64) -> glm::DVec3,
+) -> Option<(glm::DVec3, f64)> {
+    if is_smooth(alpha) {
+        return None;
+    }
+
+    let frame = ShadingFrame::new(normal);
+    let wo_local = frame.to_local(&wo.normalize());
+    let light_local = frame.to_local(&-wi.normalize());
+    if wo_local[2] <= 0.0 || light_local[2] <= 0.0 {
+        return Some((glm::zero(), 0.0));
+    }
+    let m = (wo_local + light_local).normalize();
+
+    let d = ggx_d(&m, alpha);
+    let fresnel = fresnel(glm::dot(&wo_local, &m));
+
+    // f * cos(theta_i) = F * D * G2 / (4 * cos(theta_o)), the
+    // cosine of the light cancels out
+    let color = fresnel * d * smith_g2(&wo_local, &light_local, alpha) / (4.0 * wo_local[2]);
+    let pdf = smith_g1(&wo_local, alpha) * d / (4.0 * wo_local[2]);
+
+    Some((color, pdf))
+}
+
+/// Evaluate the reflection off a GGX microfacet surface divided by
+/// the probability density of sampling `wi`, see
+/// [`eval_ggx_reflection_with_pdf()`]. For a smooth surface, it is
+/// the Fresnel reflectance of perfect reflection.
+pub fn eval_ggx_reflection(
+    wi: &glm::DVec3,
+    wo: &glm::DVec3,
+    normal: &glm::DVec3,
+    alpha: f64,
+    fresnel: impl Fn(f64) -> glm::DVec3,
+) -> glm::DVec3 {
+    if is_smooth(alpha) {
+        return fresnel(glm::dot(normal, &wo.normalize()));
+    }
+
+    match eval_ggx_reflection_with_pdf(wi, wo, normal, alpha, fresnel) {
+        Some((color, pdf)) if pdf > 0.0 => color / pdf,
+        _ => glm::zero(),
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance with the given
+/// reflectance at normal incidence, `cos_theta` is the cosine of the
+/// angle between the direction and the microfacet normal.
+pub fn fresnel_schlick(f0: &glm::DVec3, cos_theta: f64) -> glm::DVec3 {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + (glm::vec3(1.0, 1.0, 1.0) - f0) * weight
+}
+
+/// Exact Fresnel reflectance of a dielectric interface for
+/// unpolarized light. `cos_theta_i` is the cosine of the angle
+/// between the incident direction and the normal (on the incident
+/// side) and `eta` is the ratio of the ior of the transmitted side
+/// to the ior of the incident side. Returns 1.0 for total internal
+/// reflection.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    let r_s = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    let r_p = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    0.5 * (r_s * r_s + r_p * r_p)
+}
+
+/// Exact Fresnel reflectance of a conductor for unpolarized light
+/// with the complex ior `eta + i * k` (per colour channel) relative
+/// to the ior of the incident side. `cos_theta_i` is the cosine of
+/// the angle between the incident direction and the normal.
+///
+/// reference: Lagarde, "Memo on Fresnel equations" (2013)
+pub fn fresnel_conductor(cos_theta_i: f64, eta: &glm::DVec3, k: &glm::DVec3) -> glm::DVec3 {
+    glm::vec3(
+        fresnel_conductor_channel(cos_theta_i, eta[0], k[0]),
+        fresnel_conductor_channel(cos_theta_i, eta[1], k[1]),
+        fresnel_conductor_channel(cos_theta_i, eta[2], k[2]),
+    )
+}
+
+/// See [`fresnel_conductor()`].
+fn fresnel_conductor_channel(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos2.sqrt() * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    0.5 * (r_s + r_p)
+}
+
+/// Convert the artist friendly reflectivity (reflectance at normal
+/// incidence) and edge tint (colour towards grazing angles) of a
+/// conductor to its complex ior `(eta, k)`, see
+/// [`fresnel_conductor()`].
+///
+/// reference: Gulbrandsen, "Artist Friendly Metallic Fresnel" (2014)
+pub fn artistic_to_complex_ior(
+    reflectivity: &glm::DVec3,
+    edge_tint: &glm::DVec3,
+) -> (glm::DVec3, glm::DVec3) {
+    let channel = |r: f64, g: f64| {
+        // a reflectivity of 1.0 needs an infinite k
+        let r = r.clamp(0.0, 0.99);
+        let g = g.clamp(0.0, 1.0);
+        let r_sqrt = r.sqrt();
+        let n_min = (1.0 - r) / (1.0 + r);
+        let n_max = (1.0 + r_sqrt) / (1.0 - r_sqrt);
+        let eta = g * n_min + (1.0 - g) * n_max;
+        let k2 = (r * (eta + 1.0).powi(2) - (eta - 1.0).powi(2)) / (1.0 - r);
+        (eta, k2.max(0.0).sqrt())
+    };
+    let (eta_r, k_r) = channel(reflectivity[0], edge_tint[0]);
+    let (eta_g, k_g) = channel(reflectivity[1], edge_tint[1]);
+    let (eta_b, k_b) = channel(reflectivity[2], edge_tint[2]);
+    (glm::vec3(eta_r, eta_g, eta_b), glm::vec3(k_r, k_g, k_b))
+}
+
+/// Refract `wo` about the microfacet normal `m` (both in the local
+/// space of the shading frame), `eta` is the ratio of the ior of the
+/// transmitted side to the ior of the side of `wo`. Returns the
+/// direction of the transmitted light (away from the surface) or
+/// `None` on total internal reflection.
+pub fn refract(wo: &glm::DVec3, m: &glm::DVec3, eta: f64) -> Option<glm::DVec3> {
+    let cos_theta_i = glm::dot(wo, m);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(m * (cos_theta_i / eta - cos_theta_t) - wo / eta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integrate the given function over the hemisphere about +Z
+    /// with the midpoint rule.
+    fn integrate_hemisphere(f: impl Fn(&glm::DVec3) -> f64) -> f64 {
+        let num_theta = 1000;
+        let num_phi = 128;
+        let d_theta = std::f64::consts::FRAC_PI_2 / num_theta as f64;
+        let d_phi = 2.0 * std::f64::consts::PI / num_phi as f64;
+        (0..num_theta)
+            .map(|i| {
+                let theta = (i as f64 + 0.5) * d_theta;
+                (0..num_phi)
+                    .map(|j| {
+                        let phi = (j as f64 + 0.5) * d_phi;
+                        let v = glm::vec3(
+                            theta.sin() * phi.cos(),
+                            theta.sin() * phi.sin(),
+                            theta.cos(),
+                        );
+                        f(&v) * theta.sin() * d_theta * d_phi
+                    })
+                    .sum::<f64>()
+            })
+            .sum()
+    }
+
+    /// Alphas (along X and Y) of isotropic and anisotropic
+    /// distributions.
+    fn get_alphas() -> [(f64, f64); 4] {
+        [(0.1, 0.1), (0.5, 0.5), (1.0, 1.0), (0.2, 0.6)]
+    }
+
+    /// The projected area of the microfacets is the area of the
+    /// surface, `D(m) * cos(theta_m)` integrates to 1.
+    #[test]
+    fn ggx_d_test_01() {
+        get_alphas().iter().for_each(|(alpha_x, alpha_y)| {
+            let integral =
+                integrate_hemisphere(|m| ggx_d_anisotropic(m, *alpha_x, *alpha_y) * m[2]);
+            assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+        });
+    }
+
+    #[test]
+    fn ggx_vndf_pdf_test_01() {
+        get_alphas().iter().for_each(|(alpha_x, alpha_y)| {
+            [0.0_f64, 45.0, 80.0].iter().for_each(|theta| {
+                let theta = theta.to_radians();
+                let wo = glm::vec3(0.6 * theta.sin(), 0.8 * theta.sin(), theta.cos());
+                let integral =
+                    integrate_hemisphere(|m| ggx_vndf_pdf_anisotropic(&wo, m, *alpha_x, *alpha_y));
+                assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+            });
+        });
+    }
+}
@@ -0,0 +1,178 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::super::shader_list::ShaderID;
+use super::utils::{self, ColorPicker, ValuePicker, ValuePickerUiData};
+use super::BSDFUiData;
+use crate::egui;
+use crate::glm;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+
+/// Blend of two shaders of the shader list, the factor is the
+/// weight of the second shader and one minus the factor is the
+/// weight of the first. A missing shader is the default shader
+/// (black).
+///
+/// It is never sampled or evaluated directly, see
+/// [`super::super::bsdf::BSDFMixture`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mix {
+    factor: ValuePicker,
+    shader_a: Option<ShaderID>,
+    shader_b: Option<ShaderID>,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self::new(0.5, None, None)
+    }
+}
+
+impl Mix {
+    pub fn new(factor: f64, shader_a: Option<ShaderID>, shader_b: Option<ShaderID>) -> Self {
+        Self {
+            factor: ValuePicker::Value(factor),
+            shader_a,
+            shader_b,
+        }
+    }
+
+    /// Get a reference to the mix's factor.
+    pub fn get_factor(&self) -> &ValuePicker {
+        &self.factor
+    }
+
+    /// Set the mix's factor.
+    pub fn set_factor(&mut self, factor: ValuePicker) {
+        self.factor = factor;
+    }
+
+    /// Get the mix's first shader.
+    pub fn get_shader_a(&self) -> Option<ShaderID> {
+        self.shader_a
+    }
+
+    /// Set the mix's first shader, the caller must ensure that it
+    /// does not create a cycle, see
+    /// [`super::super::shader_list::ShaderList::get_referenceable_shaders()`].
+    pub fn set_shader_a(&mut self, shader_a: Option<ShaderID>) {
+        self.shader_a = shader_a;
+    }
+
+    /// Get the mix's second shader.
+    pub fn get_shader_b(&self) -> Option<ShaderID> {
+        self.shader_b
+    }
+
+    /// Set the mix's second shader, see [`Self::set_shader_a()`].
+    pub fn set_shader_b(&mut self, shader_b: Option<ShaderID>) {
+        self.shader_b = shader_b;
+    }
+}
+
+#[typetag::serde]
+impl BSDF for Mix {
+    fn sample(
+        &self,
+        _wo: &glm::DVec3,
+        _mediums: &mut Mediums,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+        _sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        None
+    }
+
+    fn eval(
+        &self,
+        _wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        _intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        glm::zero()
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Mix"
+    }
+
+    fn get_base_color(&self, _texture_list: &TextureList) -> Option<glm::DVec3> {
+        None
+    }
+
+    fn set_base_color(&mut self, _color: ColorPicker) {}
+
+    fn get_weighted_shaders(
+        &self,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<Vec<(Option<ShaderID>, f64)>> {
+        let factor = self
+            .factor
+            .get_value_at(intersect_info, texture_list)
+            .clamp(0.0, 1.0);
+        Some(vec![(self.shader_a, 1.0 - factor), (self.shader_b, factor)])
+    }
+
+    fn get_referenced_shaders(&self) -> Vec<ShaderID> {
+        self.shader_a
+            .iter()
+            .chain(self.shader_b.iter())
+            .copied()
+            .collect()
+    }
+
+    fn remove_referenced_shader(&mut self, shader_id: ShaderID) {
+        if self.shader_a == Some(shader_id) {
+            self.shader_a = None;
+        }
+        if self.shader_b == Some(shader_id) {
+            self.shader_b = None;
+        }
+    }
+}
+
+impl DrawUI for Mix {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        ui.horizontal(|ui| {
+            ui.label("Factor");
+            self.factor.draw_ui_mut(
+                ui,
+                &ValuePickerUiData::new(
+                    extra_data.get_texture_list().clone(),
+                    extra_data.get_shader_egui_id().with("Factor"),
+                    0.0..=1.0,
+                ),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Shader A");
+            utils::shader_reference_ui(
+                ui,
+                extra_data.get_shader_egui_id().with("Shader A"),
+                &mut self.shader_a,
+                extra_data.get_shader_choices(),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Shader B");
+            utils::shader_reference_ui(
+                ui,
+                extra_data.get_shader_egui_id().with("Shader B"),
+                &mut self.shader_b,
+                extra_data.get_shader_choices(),
+            );
+        });
+    }
+}
@@ -1,27 +1,46 @@
+pub mod add;
 pub mod blinnphong;
+pub mod conductor;
 pub mod debug_bsdf;
 pub mod emissive;
 pub mod glass;
 pub mod glossy;
+pub mod holdout;
 pub mod lambert;
+pub mod microfacet;
+pub mod mix;
+pub mod oren_nayar;
+pub mod principled;
 pub mod refraction;
+pub mod shader_graph;
+pub mod shadow_catcher;
+pub mod translucent;
 pub mod utils;
 
 use std::sync::{Arc, RwLock};
 
+use super::shader_list::ShaderID;
 use super::texture_list::TextureList;
 use crate::egui;
 
 pub struct BSDFUiData {
     texture_list: Arc<RwLock<TextureList>>,
     shader_egui_id: egui::Id,
+    /// shaders (along with their names) that the `BSDF` can refer
+    /// to, see [`super::shader_list::ShaderList::get_referenceable_shaders()`]
+    shader_choices: Vec<(ShaderID, String)>,
 }
 
 impl BSDFUiData {
-    pub fn new(texture_list: Arc<RwLock<TextureList>>, shader_egui_id: egui::Id) -> Self {
+    pub fn new(
+        texture_list: Arc<RwLock<TextureList>>,
+        shader_egui_id: egui::Id,
+        shader_choices: Vec<(ShaderID, String)>,
+    ) -> Self {
         Self {
             texture_list,
             shader_egui_id,
+            shader_choices,
         }
     }
 
@@ -34,4 +53,9 @@ impl BSDFUiData {
     pub fn get_shader_egui_id(&self) -> &egui::Id {
         &self.shader_egui_id
     }
+
+    /// Get a reference to the shader choices.
+    pub fn get_shader_choices(&self) -> &[(ShaderID, String)] {
+        &self.shader_choices
+    }
 }
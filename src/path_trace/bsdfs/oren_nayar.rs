@@ -0,0 +1,235 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::utils::{self, ColorPicker, ColorPickerUiData};
+use super::BSDFUiData;
+use crate::egui;
+use crate::glm;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+
+/// Rough diffuse surface made of Lambertian V-cavities, reflects
+/// more light back towards the light at grazing angles than
+/// [`super::lambert::Lambert`]. It is the same as `Lambert` with a
+/// roughness of 0.0.
+///
+/// reference: Oren and Nayar, "Generalization of Lambert's
+/// Reflectance Model" (1994), the qualitative model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrenNayar {
+    color: ColorPicker,
+    /// standard deviation of the angle of the facets (in radians)
+    roughness: f64,
+}
+
+impl Default for OrenNayar {
+    fn default() -> Self {
+        Self::new(glm::vec3(1.0, 1.0, 1.0), 0.5)
+    }
+}
+
+impl OrenNayar {
+    pub fn new(color: glm::DVec3, roughness: f64) -> Self {
+        Self {
+            color: ColorPicker::Color(color),
+            roughness,
+        }
+    }
+
+    /// Get the BSDF divided by the BSDF of a Lambertian surface of
+    /// the same colour, `wi` points towards the hitpoint and `wo`
+    /// away from it.
+    fn get_lambert_scale(&self, wi: &glm::DVec3, wo: &glm::DVec3, normal: &glm::DVec3) -> f64 {
+        let sigma2 = self.roughness.max(0.0).powi(2);
+        let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        let light = -wi.normalize();
+        let view = wo.normalize();
+        let cos_l = glm::dot(&light, normal).clamp(-1.0, 1.0);
+        let cos_v = glm::dot(&view, normal).clamp(-1.0, 1.0);
+        let sin_l = (1.0 - cos_l * cos_l).sqrt();
+        let sin_v = (1.0 - cos_v * cos_v).sqrt();
+
+        // cosine of the difference of the azimuthal angles
+        let cos_phi = if sin_l > 1e-4 && sin_v > 1e-4 {
+            let light_tangent = (light - normal * cos_l) / sin_l;
+            let view_tangent = (view - normal * cos_v) / sin_v;
+            glm::dot(&light_tangent, &view_tangent).max(0.0)
+        } else {
+            0.0
+        };
+
+        // sin(alpha) * tan(beta), alpha is the larger of the two
+        // angles and beta the smaller
+        let (sin_alpha, tan_beta) = if cos_l.abs() > cos_v.abs() {
+            (sin_v, sin_l / cos_l.abs().max(1e-8))
+        } else {
+            (sin_l, sin_v / cos_v.abs().max(1e-8))
+        };
+
+        a + b * cos_phi * sin_alpha * tan_beta
+    }
+}
+
+#[typetag::serde]
+impl BSDF for OrenNayar {
+    fn sample(
+        &self,
+        _wo: &glm::DVec3,
+        _mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        if sampling_types.contains(SamplingTypes::Diffuse) {
+            let normal = intersect_info.get_normal().as_ref().unwrap();
+            let wi = utils::wi_diffuse(normal);
+            Some(SampleData::new_with_pdf(
+                wi,
+                SamplingTypes::Diffuse,
+                utils::pdf_diffuse(normal, &wi),
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn eval(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        // the directions are cosine weighted, so only the scale of
+        // the Lambertian BSDF remains
+        let normal = intersect_info.get_normal().as_ref().unwrap();
+        self.color.get_color_at(intersect_info, texture_list)
+            * self.get_lambert_scale(wi, wo, normal)
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        // the Lambertian part of the BSDF times the cosine term is
+        // the same as the pdf, see `Lambert::eval_with_pdf()`
+        let normal = intersect_info.get_normal().as_ref().unwrap();
+        let pdf = utils::pdf_diffuse(normal, wi);
+        if pdf <= 0.0 {
+            return Some((glm::zero(), 0.0));
+        }
+        Some((
+            self.color.get_color_at(intersect_info, texture_list)
+                * (self.get_lambert_scale(wi, wo, normal) * pdf),
+            pdf,
+        ))
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::from(SamplingTypes::Diffuse)
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Oren Nayar"
+    }
+
+    fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3> {
+        Some(self.color.get_color(&glm::zero(), texture_list))
+    }
+
+    fn set_base_color(&mut self, color: ColorPicker) {
+        self.color = color;
+    }
+}
+
+impl DrawUI for OrenNayar {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        ui.horizontal(|ui| {
+            ui.label("Base Color");
+            self.color.draw_ui_mut(
+                ui,
+                &ColorPickerUiData::new(
+                    extra_data.get_texture_list().clone(),
+                    extra_data.get_shader_egui_id().with("Base Color"),
+                ),
+            );
+        });
+        ui.add(
+            egui::Slider::new(&mut self.roughness, 0.0..=1.0)
+                .clamp_to_range(false)
+                .text("Roughness"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::bsdfs::lambert::Lambert;
+
+    #[test]
+    fn oren_nayar_zero_roughness_is_lambert_test_01() {
+        let color = glm::vec3(0.8, 0.5, 0.2);
+        let oren_nayar = OrenNayar::new(color, 0.0);
+        let lambert = Lambert::new(color);
+        let texture_list = TextureList::new();
+        let mediums = Mediums::with_air();
+        let mut intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        intersect_info.set_normal(
+            &crate::path_trace::ray::Ray::new(glm::zero(), glm::vec3(0.0, 0.0, -1.0)),
+            &glm::vec3(0.0, 0.0, 1.0),
+        );
+
+        let directions = [
+            glm::vec3(0.0, 0.0, 1.0),
+            glm::vec3(0.5, 0.2, 0.8),
+            glm::vec3(-0.7, 0.1, 0.3),
+            glm::vec3(0.9, -0.4, 0.05),
+        ];
+        directions.iter().for_each(|light| {
+            directions.iter().for_each(|view| {
+                // `wi` points towards the hitpoint
+                let wi = -light.normalize();
+                let wo = view.normalize();
+
+                let oren_nayar_color = oren_nayar.eval(&wi, &wo, &intersect_info, &texture_list);
+                let lambert_color = lambert.eval(&wi, &wo, &intersect_info, &texture_list);
+                assert!(glm::distance(&oren_nayar_color, &lambert_color) < 1e-12);
+
+                let (oren_nayar_color, oren_nayar_pdf) = oren_nayar
+                    .eval_with_pdf(&wi, &wo, &mediums, &intersect_info, &texture_list)
+                    .unwrap();
+                let (lambert_color, lambert_pdf) = lambert
+                    .eval_with_pdf(&wi, &wo, &mediums, &intersect_info, &texture_list)
+                    .unwrap();
+                assert!(glm::distance(&oren_nayar_color, &lambert_color) < 1e-12);
+                assert!((oren_nayar_pdf - lambert_pdf).abs() < 1e-12);
+            });
+        });
+
+        // rough surfaces reflect more light back towards grazing
+        // lights
+        let rough = OrenNayar::new(color, 0.5);
+        let wi = -glm::vec3(0.9, 0.0, 0.2).normalize();
+        let wo = glm::vec3(0.9, 0.0, 0.2).normalize();
+        assert!(
+            rough.eval(&wi, &wo, &intersect_info, &texture_list)[0]
+                > oren_nayar.eval(&wi, &wo, &intersect_info, &texture_list)[0]
+        );
+    }
+}
@@ -0,0 +1,685 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::microfacet::{self, ShadingFrame};
+use super::utils::{self, ColorPicker, ColorPickerUiData, ValuePicker, ValuePickerUiData};
+use super::BSDFUiData;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+use crate::{egui, glm, util};
+
+/// Layered uber BSDF modelled after the Principled BSDF of Blender
+/// (Disney BSDF). From the top, the layers are a clearcoat, the
+/// specular reflection (metallic or dielectric), the transmission
+/// and the diffuse with sheen.
+///
+/// reference: Burley, "Physically Based Shading at Disney" (2012)
+/// and "Extending the Disney BRDF to a BSDF with Integrated
+/// Subsurface Scattering" (2015)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Principled {
+    base_color: ColorPicker,
+    metallic: ValuePicker,
+    /// amount of dielectric specular reflection, 0.5 is a
+    /// reflectance of 4% at normal incidence
+    specular: ValuePicker,
+    roughness: ValuePicker,
+    /// amount of anisotropy of the specular reflection, it is
+    /// stretched along the U direction of the uvs
+    anisotropic: ValuePicker,
+    /// rotation of the direction of the anisotropy, 1.0 is a full
+    /// rotation
+    anisotropic_rotation: ValuePicker,
+    sheen: ValuePicker,
+    /// tint of the sheen towards the base colour
+    sheen_tint: ValuePicker,
+    clearcoat: ValuePicker,
+    clearcoat_roughness: ValuePicker,
+    transmission: ValuePicker,
+    /// ior of the object for transmission, it cannot be textured
+    /// since the medium inside the object has a single ior
+    ior: f64,
+    emission: ColorPicker,
+    emission_strength: ValuePicker,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self::new(glm::vec3(0.8, 0.8, 0.8), 0.0, 0.5)
+    }
+}
+
+/// Parameters of [`Principled`] evaluated at the point of
+/// intersection.
+struct PrincipledParams {
+    base_color: glm::DVec3,
+    metallic: f64,
+    specular: f64,
+    roughness: f64,
+    /// alpha of the specular along the X axis of `frame`
+    alpha_x: f64,
+    /// alpha of the specular along the Y axis of `frame`
+    alpha_y: f64,
+    /// colour of the sheen scaled by the amount of sheen
+    sheen_color: glm::DVec3,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    transmission: f64,
+    frame: ShadingFrame,
+}
+
+impl PrincipledParams {
+    /// Weight of the diffuse (and sheen) layer.
+    fn get_diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    /// Weight of the transmission layer.
+    fn get_transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    /// Reflectance of the specular layer at normal incidence.
+    fn get_specular_f0(&self) -> glm::DVec3 {
+        let dielectric = glm::vec3(1.0, 1.0, 1.0) * (0.08 * self.specular);
+        glm::lerp(&dielectric, &self.base_color, self.metallic)
+    }
+
+    /// Get the probabilities of sampling the diffuse, specular,
+    /// clearcoat and transmission layers, roughly proportional to
+    /// their contribution as seen from `wo` (in the local space of
+    /// the frame). Returns `None` if nothing is reflected or
+    /// transmitted.
+    fn get_layer_probabilities(&self, wo: &glm::DVec3) -> Option<[f64; 4]> {
+        let specular =
+            util::luminance(&microfacet::fresnel_schlick(&self.get_specular_f0(), wo[2]));
+        let diffuse = self.get_diffuse_weight()
+            * util::luminance(&(self.base_color + self.sheen_color)).max(0.0);
+        let clearcoat = 0.25 * self.clearcoat * (0.04 + 0.96 * schlick_weight(wo[2]));
+        let transmission = self.get_transmission_weight()
+            * (1.0 - specular).max(0.0)
+            * util::luminance(&self.base_color).max(0.0);
+
+        let total = diffuse + specular + clearcoat + transmission;
+        if total <= 0.0 {
+            return None;
+        }
+        Some([
+            diffuse / total,
+            specular / total,
+            clearcoat / total,
+            transmission / total,
+        ])
+    }
+
+    /// Get the BSDF multiplied by the cosine of `light` along with the
+    /// probability density of sampling `light` for the layers that
+    /// reflect light. `wo` and `light` (direction towards the light)
+    /// are in the local space of the frame and must be above the
+    /// surface.
+    fn eval_reflection(
+        &self,
+        wo: &glm::DVec3,
+        light: &glm::DVec3,
+        probabilities: &[f64; 4],
+    ) -> (glm::DVec3, f64) {
+        let cos_o = wo[2];
+        let cos_l = light[2];
+        let m = (wo + light).normalize();
+        let cos_d = glm::dot(light, &m);
+        let wo_dot_m = glm::dot(wo, &m);
+
+        // diffuse with retro-reflection at grazing angles and sheen
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let diffuse = self.base_color
+            * (std::f64::consts::FRAC_1_PI
+                * (1.0 + (fd90 - 1.0) * schlick_weight(cos_l))
+                * (1.0 + (fd90 - 1.0) * schlick_weight(cos_o)));
+        let sheen = self.sheen_color * schlick_weight(cos_d);
+        let diffuse = (diffuse + sheen) * self.get_diffuse_weight();
+
+        // specular
+        let d = microfacet::ggx_d_anisotropic(&m, self.alpha_x, self.alpha_y);
+        let specular = microfacet::fresnel_schlick(&self.get_specular_f0(), cos_d)
+            * (d * microfacet::smith_g2_anisotropic(wo, light, self.alpha_x, self.alpha_y)
+                / (4.0 * cos_o * cos_l));
+
+        // clearcoat, fixed ior of 1.5
+        let clearcoat_d = microfacet::ggx_d(&m, self.clearcoat_alpha);
+        let clearcoat = 0.25
+            * self.clearcoat
+            * (0.04 + 0.96 * schlick_weight(cos_d))
+            * clearcoat_d
+            * microfacet::smith_g2(wo, light, self.clearcoat_alpha)
+            / (4.0 * cos_o * cos_l);
+
+        let color = (diffuse + specular + glm::vec3(clearcoat, clearcoat, clearcoat)) * cos_l;
+
+        let pdf = if wo_dot_m > 0.0 {
+            probabilities[0] * cos_l * std::f64::consts::FRAC_1_PI
+                + probabilities[1]
+                    * microfacet::ggx_vndf_pdf_anisotropic(wo, &m, self.alpha_x, self.alpha_y)
+                    / (4.0 * wo_dot_m)
+                + probabilities[2] * microfacet::ggx_vndf_pdf(wo, &m, self.clearcoat_alpha)
+                    / (4.0 * wo_dot_m)
+        } else {
+            probabilities[0] * cos_l * std::f64::consts::FRAC_1_PI
+        };
+
+        (color, pdf)
+    }
+
+    /// Get the colour of the light transmitted from `light` (below
+    /// the surface) towards `wo` divided by the probability of
+    /// sampling it when the transmission layer is chosen, both in the
+    /// local space of the frame.
+    ///
+    /// With visible normal sampling only the masking-shadowing
+    /// remains (see `Glass::eval()`), the transmitted fraction is
+    /// taken to be whatever is not reflected by the specular layer
+    /// at `wo` so that it does not depend on the mediums.
+    fn eval_transmission(&self, wo: &glm::DVec3, light: &glm::DVec3) -> glm::DVec3 {
+        let specular =
+            util::luminance(&microfacet::fresnel_schlick(&self.get_specular_f0(), wo[2]));
+        self.base_color
+            * (self.get_transmission_weight()
+                * (1.0 - specular).max(0.0)
+                * microfacet::smith_g2_anisotropic(wo, light, self.alpha_x, self.alpha_y)
+                / microfacet::smith_g1_anisotropic(wo, self.alpha_x, self.alpha_y))
+    }
+}
+
+/// Schlick's Fresnel weight, (1 - cos)^5.
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+impl Principled {
+    pub fn new(base_color: glm::DVec3, metallic: f64, roughness: f64) -> Self {
+        Self {
+            base_color: ColorPicker::Color(base_color),
+            metallic: ValuePicker::Value(metallic),
+            specular: ValuePicker::Value(0.5),
+            roughness: ValuePicker::Value(roughness),
+            anisotropic: ValuePicker::Value(0.0),
+            anisotropic_rotation: ValuePicker::Value(0.0),
+            sheen: ValuePicker::Value(0.0),
+            sheen_tint: ValuePicker::Value(0.5),
+            clearcoat: ValuePicker::Value(0.0),
+            clearcoat_roughness: ValuePicker::Value(0.03),
+            transmission: ValuePicker::Value(0.0),
+            ior: 1.45,
+            emission: ColorPicker::Color(glm::zero()),
+            emission_strength: ValuePicker::Value(1.0),
+        }
+    }
+
+    /// Set the principled's specular.
+    pub fn set_specular(&mut self, specular: ValuePicker) {
+        self.specular = specular;
+    }
+
+    /// Set the principled's anisotropic.
+    pub fn set_anisotropic(&mut self, anisotropic: ValuePicker) {
+        self.anisotropic = anisotropic;
+    }
+
+    /// Set the principled's anisotropic rotation.
+    pub fn set_anisotropic_rotation(&mut self, anisotropic_rotation: ValuePicker) {
+        self.anisotropic_rotation = anisotropic_rotation;
+    }
+
+    /// Set the principled's sheen.
+    pub fn set_sheen(&mut self, sheen: ValuePicker) {
+        self.sheen = sheen;
+    }
+
+    /// Set the principled's sheen tint.
+    pub fn set_sheen_tint(&mut self, sheen_tint: ValuePicker) {
+        self.sheen_tint = sheen_tint;
+    }
+
+    /// Set the principled's clearcoat.
+    pub fn set_clearcoat(&mut self, clearcoat: ValuePicker) {
+        self.clearcoat = clearcoat;
+    }
+
+    /// Set the principled's clearcoat roughness.
+    pub fn set_clearcoat_roughness(&mut self, clearcoat_roughness: ValuePicker) {
+        self.clearcoat_roughness = clearcoat_roughness;
+    }
+
+    /// Set the principled's transmission.
+    pub fn set_transmission(&mut self, transmission: ValuePicker) {
+        self.transmission = transmission;
+    }
+
+    /// Set the principled's ior.
+    pub fn set_ior(&mut self, ior: f64) {
+        self.ior = ior;
+    }
+
+    /// Set the principled's emission.
+    pub fn set_emission(&mut self, emission: ColorPicker) {
+        self.emission = emission;
+    }
+
+    /// Set the principled's emission strength.
+    pub fn set_emission_strength(&mut self, emission_strength: ValuePicker) {
+        self.emission_strength = emission_strength;
+    }
+
+    fn get_params(
+        &self,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> PrincipledParams {
+        let value = |value_picker: &ValuePicker| {
+            value_picker
+                .get_value_at(intersect_info, texture_list)
+                .clamp(0.0, 1.0)
+        };
+
+        let base_color = self.base_color.get_color_at(intersect_info, texture_list);
+        let roughness = value(&self.roughness);
+
+        // the roughness is clamped so that the layers can always be
+        // evaluated
+        let alpha = microfacet::roughness_to_alpha(roughness);
+        let aspect = (1.0 - 0.9 * value(&self.anisotropic)).sqrt();
+        let alpha_x = (alpha / aspect).max(microfacet::SMOOTH_ALPHA);
+        let alpha_y = (alpha * aspect).max(microfacet::SMOOTH_ALPHA);
+
+        // anisotropy is along the U direction of the uvs, rotated
+        // about the normal
+        let normal = intersect_info.get_normal().unwrap();
+        let frame = intersect_info.get_uv_derivatives().map_or_else(
+            || ShadingFrame::new(&normal),
+            |(dpdu, _)| ShadingFrame::with_tangent(&normal, dpdu),
+        );
+        let rotation = 2.0 * std::f64::consts::PI * value(&self.anisotropic_rotation);
+        let frame = if rotation != 0.0 {
+            ShadingFrame::with_tangent(
+                &normal,
+                &frame.to_world(&glm::vec3(rotation.cos(), rotation.sin(), 0.0)),
+            )
+        } else {
+            frame
+        };
+
+        let tint = if util::luminance(&base_color) > 0.0 {
+            base_color / util::luminance(&base_color)
+        } else {
+            glm::vec3(1.0, 1.0, 1.0)
+        };
+        let sheen_color = glm::lerp(&glm::vec3(1.0, 1.0, 1.0), &tint, value(&self.sheen_tint))
+            * value(&self.sheen);
+
+        PrincipledParams {
+            base_color,
+            metallic: value(&self.metallic),
+            specular: value_picker_max(&self.specular, intersect_info, texture_list),
+            roughness,
+            alpha_x,
+            alpha_y,
+            sheen_color,
+            clearcoat: value(&self.clearcoat),
+            clearcoat_alpha: microfacet::roughness_to_alpha(value(&self.clearcoat_roughness))
+                .max(microfacet::SMOOTH_ALPHA),
+            transmission: value(&self.transmission),
+            frame,
+        }
+    }
+}
+
+/// Specular can be larger than 1.0 (up to a reflectance of 8% at
+/// normal incidence at 1.0), it is only clamped to be positive.
+fn value_picker_max(
+    value_picker: &ValuePicker,
+    intersect_info: &IntersectInfo,
+    texture_list: &TextureList,
+) -> f64 {
+    value_picker
+        .get_value_at(intersect_info, texture_list)
+        .max(0.0)
+}
+
+#[typetag::serde]
+impl BSDF for Principled {
+    fn sample(
+        &self,
+        wo: &glm::DVec3,
+        mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        let params = self.get_params(intersect_info, texture_list);
+
+        let wo_local = params.frame.to_local(&wo.normalize());
+        if wo_local[2] <= 0.0 {
+            return None;
+        }
+        let probabilities = params.get_layer_probabilities(&wo_local)?;
+
+        let u = rand::random::<f64>();
+        let (light_local, sampling_type) = if u < probabilities[0] {
+            if !sampling_types.contains(SamplingTypes::Diffuse) {
+                return None;
+            }
+            let normal = intersect_info.get_normal().unwrap();
+            (
+                params.frame.to_local(&-utils::wi_diffuse(&normal)),
+                SamplingTypes::Diffuse,
+            )
+        } else if u < probabilities[0] + probabilities[1] + probabilities[2] {
+            if !sampling_types.contains(SamplingTypes::Glossy) {
+                return None;
+            }
+            let m = if u < probabilities[0] + probabilities[1] {
+                microfacet::sample_ggx_vndf_anisotropic(
+                    &wo_local,
+                    params.alpha_x,
+                    params.alpha_y,
+                    rand::random::<f64>(),
+                    rand::random::<f64>(),
+                )
+            } else {
+                microfacet::sample_ggx_vndf(
+                    &wo_local,
+                    params.clearcoat_alpha,
+                    rand::random::<f64>(),
+                    rand::random::<f64>(),
+                )
+            };
+            (
+                m * (2.0 * glm::dot(&wo_local, &m)) - wo_local,
+                SamplingTypes::Glossy,
+            )
+        } else {
+            if !sampling_types.contains(SamplingTypes::Transmission) {
+                return None;
+            }
+            let entering = intersect_info.get_front_face();
+            let eta = utils::dielectric_eta(self.get_ior(), mediums, entering)?;
+            let m = microfacet::sample_ggx_vndf_anisotropic(
+                &wo_local,
+                params.alpha_x,
+                params.alpha_y,
+                rand::random::<f64>(),
+                rand::random::<f64>(),
+            );
+            // total internal reflection terminates the ray
+            let light_local = microfacet::refract(&wo_local, &m, eta)?;
+            if light_local[2] >= 0.0 {
+                return None;
+            }
+            utils::transmit_through_dielectric(self.get_ior(), mediums, entering);
+
+            // refraction is not evaluated by `eval_with_pdf()`, so
+            // the pdf must not be given, see `Glass`
+            return Some(SampleData::new(
+                -params.frame.to_world(&light_local),
+                SamplingTypes::Transmission,
+            ));
+        };
+
+        if light_local[2] <= 0.0 {
+            return None;
+        }
+        let (_, pdf) = params.eval_reflection(&wo_local, &light_local, &probabilities);
+
+        // need to return `wi` which should point towards the hitpoint
+        Some(SampleData::new_with_pdf(
+            -params.frame.to_world(&light_local),
+            sampling_type,
+            pdf,
+        ))
+    }
+
+    fn eval(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        let params = self.get_params(intersect_info, texture_list);
+        let wo_local = params.frame.to_local(&wo.normalize());
+        let light_local = params.frame.to_local(&-wi.normalize());
+        if wo_local[2] <= 0.0 {
+            return glm::zero();
+        }
+        let probabilities = match params.get_layer_probabilities(&wo_local) {
+            Some(probabilities) => probabilities,
+            None => return glm::zero(),
+        };
+
+        if light_local[2] > 0.0 {
+            match params.eval_reflection(&wo_local, &light_local, &probabilities) {
+                (color, pdf) if pdf > 0.0 => color / pdf,
+                _ => glm::zero(),
+            }
+        } else if light_local[2] < 0.0 && probabilities[3] > 0.0 {
+            params.eval_transmission(&wo_local, &light_local) / probabilities[3]
+        } else {
+            glm::zero()
+        }
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        // only the reflecting layers are evaluated, see `sample()`
+        let params = self.get_params(intersect_info, texture_list);
+        let wo_local = params.frame.to_local(&wo.normalize());
+        let light_local = params.frame.to_local(&-wi.normalize());
+        if wo_local[2] <= 0.0 || light_local[2] <= 0.0 {
+            return Some((glm::zero(), 0.0));
+        }
+        let probabilities = match params.get_layer_probabilities(&wo_local) {
+            Some(probabilities) => probabilities,
+            None => return Some((glm::zero(), 0.0)),
+        };
+
+        Some(params.eval_reflection(&wo_local, &light_local, &probabilities))
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        SamplingTypes::Diffuse | SamplingTypes::Glossy
+    }
+
+    fn emission(
+        &self,
+        _wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<glm::DVec3> {
+        if self.emission == ColorPicker::Color(glm::zero()) {
+            return None;
+        }
+        Some(
+            self.emission.get_color_at(intersect_info, texture_list)
+                * self
+                    .emission_strength
+                    .get_value_at(intersect_info, texture_list),
+        )
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Principled"
+    }
+
+    fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3> {
+        Some(self.base_color.get_color(&glm::zero(), texture_list))
+    }
+
+    fn set_base_color(&mut self, color: ColorPicker) {
+        self.base_color = color;
+    }
+
+    fn get_ior(&self) -> f64 {
+        self.ior
+    }
+}
+
+impl DrawUI for Principled {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        let color_picker_ui = |ui: &mut egui::Ui, color_picker: &mut ColorPicker, text: &str| {
+            ui.horizontal(|ui| {
+                ui.label(text);
+                color_picker.draw_ui_mut(
+                    ui,
+                    &ColorPickerUiData::new(
+                        extra_data.get_texture_list().clone(),
+                        extra_data.get_shader_egui_id().with(text),
+                    ),
+                );
+            });
+        };
+        let value_picker_ui = |ui: &mut egui::Ui, value_picker: &mut ValuePicker, text: &str| {
+            ui.horizontal(|ui| {
+                ui.label(text);
+                value_picker.draw_ui_mut(
+                    ui,
+                    &ValuePickerUiData::new(
+                        extra_data.get_texture_list().clone(),
+                        extra_data.get_shader_egui_id().with(text),
+                        0.0..=1.0,
+                    ),
+                );
+            });
+        };
+
+        color_picker_ui(ui, &mut self.base_color, "Base Color");
+        value_picker_ui(ui, &mut self.metallic, "Metallic");
+        value_picker_ui(ui, &mut self.specular, "Specular");
+        value_picker_ui(ui, &mut self.roughness, "Roughness");
+        value_picker_ui(ui, &mut self.anisotropic, "Anisotropic");
+        value_picker_ui(ui, &mut self.anisotropic_rotation, "Anisotropic Rotation");
+        value_picker_ui(ui, &mut self.sheen, "Sheen");
+        value_picker_ui(ui, &mut self.sheen_tint, "Sheen Tint");
+        value_picker_ui(ui, &mut self.clearcoat, "Clearcoat");
+        value_picker_ui(ui, &mut self.clearcoat_roughness, "Clearcoat Roughness");
+        value_picker_ui(ui, &mut self.transmission, "Transmission");
+        ui.add(
+            egui::Slider::new(&mut self.ior, 1.0..=2.0)
+                .clamp_to_range(false)
+                .text("IOR"),
+        );
+        color_picker_ui(ui, &mut self.emission, "Emission");
+        value_picker_ui(ui, &mut self.emission_strength, "Emission Strength");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::ray::Ray;
+
+    /// The directions sampled from the GGX distribution of visible
+    /// normals must have the probability density given by
+    /// `eval_with_pdf()`, which must integrate (over the hemisphere)
+    /// to the fraction of the directions that `sample()` returns.
+    #[test]
+    fn principled_ggx_vndf_pdf_test_01() {
+        let texture_list = TextureList::new();
+        let ray = Ray::new(
+            glm::zero(),
+            glm::vec3(
+                60.0_f64.to_radians().sin(),
+                0.0,
+                -60.0_f64.to_radians().cos(),
+            ),
+        );
+        let mut intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        intersect_info.set_normal(&ray, &glm::vec3(0.0, 0.0, 1.0));
+        let wo = -ray.get_direction();
+
+        [0.0, 0.8].iter().for_each(|anisotropic| {
+            // fully metallic, only the specular layer is sampled
+            let mut principled = Principled::new(glm::vec3(0.9, 0.6, 0.3), 1.0, 0.5);
+            principled.set_anisotropic(ValuePicker::Value(*anisotropic));
+
+            let num_samples = 20000;
+            let samples: Vec<_> = (0..num_samples)
+                .filter_map(|_| {
+                    principled.sample(
+                        &wo,
+                        &mut Mediums::with_air(),
+                        &intersect_info,
+                        &texture_list,
+                        BitFlags::all(),
+                    )
+                })
+                .collect();
+            samples.iter().for_each(|sample_data| {
+                let sample_pdf = sample_data.get_pdf().unwrap();
+                let wi = sample_data.get_wi();
+                let (color, pdf) = principled
+                    .eval_with_pdf(
+                        wi,
+                        &wo,
+                        &Mediums::with_air(),
+                        &intersect_info,
+                        &texture_list,
+                    )
+                    .unwrap();
+                assert!((pdf - sample_pdf).abs() < 1e-6 * sample_pdf.max(1.0));
+                assert!(
+                    glm::distance(
+                        &(color / pdf),
+                        &principled.eval(wi, &wo, &intersect_info, &texture_list)
+                    ) < 1e-6
+                );
+            });
+
+            // integrate the pdf over the hemisphere with uniformly
+            // distributed directions
+            let num_directions = 200000;
+            let integral = (0..num_directions)
+                .map(|_| {
+                    let z = rand::random::<f64>();
+                    let phi = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+                    let r = (1.0 - z * z).max(0.0).sqrt();
+                    let light = glm::vec3(r * phi.cos(), r * phi.sin(), z);
+                    let (_, pdf) = principled
+                        .eval_with_pdf(
+                            &-light,
+                            &wo,
+                            &Mediums::with_air(),
+                            &intersect_info,
+                            &texture_list,
+                        )
+                        .unwrap();
+                    pdf * 2.0 * std::f64::consts::PI
+                })
+                .sum::<f64>()
+                / num_directions as f64;
+
+            let sampled_fraction = samples.len() as f64 / num_samples as f64;
+            assert!(
+                (integral - sampled_fraction).abs() < 0.03,
+                "anisotropic {}: {} != {}",
+                anisotropic,
+                integral,
+                sampled_fraction
+            );
+        });
+    }
+}
@@ -3,21 +3,48 @@ use serde::{Deserialize, Serialize};
 
 use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
 use super::super::intersectable::IntersectInfo;
+use super::microfacet::{self, ShadingFrame};
 use super::utils::{self, ColorPicker, ColorPickerUiData};
 use super::BSDFUiData;
 use crate::egui;
 use crate::glm;
-use crate::path_trace::medium::{Medium, Mediums};
+use crate::path_trace::medium::Mediums;
 use crate::path_trace::texture_list::TextureList;
 use crate::ui::DrawUI;
 
+/// Rough refraction only dielectric, refraction through a GGX
+/// (Trowbridge-Reitz) microfacet surface without the reflection of
+/// [`super::glass::Glass`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RefractionIO")]
 pub struct Refraction {
     color: ColorPicker,
     ior: f64,
 
-    #[serde(default = "default_roughness")]
-    roughness: f64,
+    /// alpha of the GGX distribution, the square of the roughness,
+    /// see [`microfacet::roughness_to_alpha()`]
+    alpha: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RefractionIO {
+    // latest version must be tried first, untagged enums are tried
+    // in order
+    V1 {
+        color: ColorPicker,
+        ior: f64,
+        alpha: f64,
+    },
+    /// Stored the roughness, it was used as the probability of
+    /// scattering diffusely, it is now read as the roughness of the
+    /// microfacets.
+    V0 {
+        color: ColorPicker,
+        ior: f64,
+        #[serde(default = "default_roughness")]
+        roughness: f64,
+    },
 }
 
 fn default_roughness() -> f64 {
@@ -25,9 +52,26 @@ fn default_roughness() -> f64 {
     0.0
 }
 
+impl From<RefractionIO> for Refraction {
+    fn from(io: RefractionIO) -> Self {
+        match io {
+            RefractionIO::V1 { color, ior, alpha } => Self { color, ior, alpha },
+            RefractionIO::V0 {
+                color,
+                ior,
+                roughness,
+            } => Self {
+                color,
+                ior,
+                alpha: microfacet::roughness_to_alpha(roughness),
+            },
+        }
+    }
+}
+
 impl Default for Refraction {
     fn default() -> Self {
-        Self::new(glm::vec3(1.0, 1.0, 1.0), 1.5, 0.4)
+        Self::new(glm::vec3(1.0, 1.0, 1.0), 1.5, 0.0)
     }
 }
 
@@ -36,75 +80,22 @@ impl Refraction {
         Self {
             color: ColorPicker::Color(color),
             ior,
-            roughness,
+            alpha: microfacet::roughness_to_alpha(roughness),
         }
     }
 
-    fn handle_refraction(
-        &self,
-        wo: &glm::DVec3,
-        mediums: &mut Mediums,
-        intersect_info: &IntersectInfo,
-        sampling_types: BitFlags<SamplingTypes>,
-    ) -> Option<SampleData> {
-        // TODO: need to figure out which sampling type it would be,
-        // both diffuse and reflection seem to make sense
-        if sampling_types.contains(SamplingTypes::Diffuse) {
-            let entering = intersect_info.get_front_face();
-
-            let ior = if entering {
-                let ior = mediums.get_lastest_medium().unwrap().get_ior() / self.get_ior();
-                ior
-            } else {
-                // need to remove the lastest medium since it would be
-                // the same as the medium of wi
-                mediums.remove_medium().unwrap();
-
-                // if there is no more mediums, the ray must have had
-                // more exits than entries and thus must not
-                // sample. This can happen because of non manifold
-                // meshes. If there exists a medium, calculate the
-                // ior.
-                self.get_ior() / mediums.get_lastest_medium()?.get_ior()
-            };
-
-            let output =
-                -glm::refract_vec(&-wo, intersect_info.get_normal().as_ref().unwrap(), ior);
-
-            // if refraction can take place. It may not be possible
-            // when `wi` is at an angle (with the normal) greater than
-            // critical angle and `wi` would be in a denser medium
-            // than `wo`. In such a case total internal reflection
-            // will take place but in refraction only bsdf, this isn't
-            // considered
-            if output != glm::DVec3::zeros() {
-                // add `wi` medium if entering the medium
-                if entering {
-                    mediums.add_medium(Medium::new(self.get_ior()));
-                }
-
-                Some(SampleData::new(output, SamplingTypes::Diffuse))
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    /// Get the refraction's roughness.
+    pub fn get_roughness(&self) -> f64 {
+        self.alpha.sqrt()
     }
 
-    fn handle_diffuse(
-        &self,
-        intersect_info: &IntersectInfo,
-        sampling_types: BitFlags<SamplingTypes>,
-    ) -> Option<SampleData> {
-        if sampling_types.contains(SamplingTypes::Diffuse) {
-            Some(SampleData::new(
-                utils::wi_diffuse(intersect_info.get_normal().as_ref().unwrap()),
-                SamplingTypes::Diffuse,
-            ))
-        } else {
-            None
-        }
+    /// Set the refraction's roughness.
+    pub fn set_roughness(&mut self, roughness: f64) {
+        self.alpha = microfacet::roughness_to_alpha(roughness);
+    }
+
+    fn get_alpha(&self) -> f64 {
+        self.alpha
     }
 }
 
@@ -115,29 +106,74 @@ impl BSDF for Refraction {
         wo: &glm::DVec3,
         mediums: &mut Mediums,
         intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
         sampling_types: BitFlags<SamplingTypes>,
     ) -> Option<SampleData> {
-        // TODO(ish): need to handle roughness accurately, using
-        // something like GGX microfacet model. Right now it cannot
-        // give physically accurate results.
-        if rand::random::<f64>() < self.roughness {
-            // sample diffuse
-            self.handle_diffuse(intersect_info, sampling_types)
+        if !sampling_types.contains(SamplingTypes::Transmission) {
+            return None;
+        }
+
+        let entering = intersect_info.get_front_face();
+        let eta = utils::dielectric_eta(self.get_ior(), mediums, entering)?;
+
+        let alpha = self.get_alpha();
+        let frame = ShadingFrame::new(intersect_info.get_normal().as_ref().unwrap());
+        let wo_local = frame.to_local(&wo.normalize());
+        if wo_local[2] <= 0.0 {
+            return None;
+        }
+
+        let m = if microfacet::is_smooth(alpha) {
+            glm::vec3(0.0, 0.0, 1.0)
         } else {
-            // sample pure refraction
-            self.handle_refraction(wo, mediums, intersect_info, sampling_types)
+            microfacet::sample_ggx_vndf(
+                &wo_local,
+                alpha,
+                rand::random::<f64>(),
+                rand::random::<f64>(),
+            )
+        };
+
+        // total internal reflection is not considered in a
+        // refraction only bsdf, the ray is terminated
+        let light_local = microfacet::refract(&wo_local, &m, eta)?;
+        if light_local[2] >= 0.0 {
+            return None;
         }
+
+        utils::transmit_through_dielectric(self.get_ior(), mediums, entering);
+
+        // need to return `wi` which should point towards the hitpoint
+        Some(SampleData::new(
+            -frame.to_world(&light_local),
+            SamplingTypes::Transmission,
+        ))
     }
 
     fn eval(
         &self,
-        _wi: &glm::DVec3,
-        _wo: &glm::DVec3,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
         intersect_info: &IntersectInfo,
         texture_list: &TextureList,
     ) -> glm::DVec3 {
-        self.color
-            .get_color(intersect_info.get_uv().as_ref().unwrap(), texture_list)
+        let color = self.color.get_color_at(intersect_info, texture_list);
+        let alpha = self.get_alpha();
+        if microfacet::is_smooth(alpha) {
+            return color;
+        }
+
+        let frame = ShadingFrame::new(intersect_info.get_normal().as_ref().unwrap());
+        let wo_local = frame.to_local(&wo.normalize());
+        let light_local = frame.to_local(&-wi.normalize());
+        if wo_local[2] <= 0.0 || light_local[2] >= 0.0 {
+            return glm::zero();
+        }
+
+        // with visible normal sampling, only the masking-shadowing
+        // remains, see `Glass::eval()`
+        color * microfacet::smith_g2(&wo_local, &light_local, alpha)
+            / microfacet::smith_g1(&wo_local, alpha)
     }
 
     fn get_bsdf_name(&self) -> &str {
@@ -180,10 +216,16 @@ impl DrawUI for Refraction {
                 .clamp_to_range(false)
                 .text("ior"),
         );
-        ui.add(
-            egui::Slider::new(&mut self.roughness, 0.0..=1.0)
-                .clamp_to_range(false)
-                .text("Roughness"),
-        );
+        let mut roughness = self.get_roughness();
+        if ui
+            .add(
+                egui::Slider::new(&mut roughness, 0.0..=1.0)
+                    .clamp_to_range(false)
+                    .text("Roughness"),
+            )
+            .changed()
+        {
+            self.set_roughness(roughness);
+        }
     }
 }
@@ -0,0 +1,1766 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::emissive::Emissive;
+use super::glossy::Glossy;
+use super::lambert::Lambert;
+use super::principled::Principled;
+use super::utils::{ColorPicker, TextureSlot, TextureSlotUiData, ValuePicker};
+use super::BSDFUiData;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+use crate::{egui, glm, ui, util};
+
+/// A unique identifier given to each [`Node`] of a [`ShaderGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NodeID(usize);
+
+/// Type of the value of a socket, decides how an input that is not
+/// linked is edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Float,
+    Color,
+    Vector,
+}
+
+/// Value passed along the links of the graph. Values are converted
+/// as needed, a vector becomes its luminance and a float is used for
+/// all the components.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NodeValue {
+    Float(f64),
+    Vector(glm::DVec3),
+}
+
+impl NodeValue {
+    pub fn as_float(&self) -> f64 {
+        match self {
+            NodeValue::Float(value) => *value,
+            NodeValue::Vector(vector) => util::luminance(vector),
+        }
+    }
+
+    pub fn as_vector(&self) -> glm::DVec3 {
+        match self {
+            NodeValue::Float(value) => glm::vec3(*value, *value, *value),
+            NodeValue::Vector(vector) => *vector,
+        }
+    }
+}
+
+/// Input socket of a [`Node`], the value is used only if it is not
+/// linked to the output of another node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInput {
+    value: NodeValue,
+    /// node and the index of its output that the input is linked to
+    link: Option<(NodeID, usize)>,
+}
+
+impl NodeInput {
+    pub fn new(value: NodeValue) -> Self {
+        Self { value, link: None }
+    }
+
+    /// Get a reference to the node input's value.
+    pub fn get_value(&self) -> &NodeValue {
+        &self.value
+    }
+
+    /// Get the node input's link.
+    pub fn get_link(&self) -> Option<(NodeID, usize)> {
+        self.link
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MathOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Minimum,
+    Maximum,
+    LessThan,
+    GreaterThan,
+}
+
+impl MathOperation {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use MathOperation::*;
+        [
+            Add,
+            Subtract,
+            Multiply,
+            Divide,
+            Power,
+            Minimum,
+            Maximum,
+            LessThan,
+            GreaterThan,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            MathOperation::Add => a + b,
+            MathOperation::Subtract => a - b,
+            MathOperation::Multiply => a * b,
+            MathOperation::Divide => {
+                if b == 0.0 {
+                    0.0
+                } else {
+                    a / b
+                }
+            }
+            MathOperation::Power => {
+                let result = a.powf(b);
+                if result.is_finite() {
+                    result
+                } else {
+                    0.0
+                }
+            }
+            MathOperation::Minimum => a.min(b),
+            MathOperation::Maximum => a.max(b),
+            MathOperation::LessThan => (a < b) as u8 as f64,
+            MathOperation::GreaterThan => (a > b) as u8 as f64,
+        }
+    }
+}
+
+impl Display for MathOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MathOperation::Add => write!(f, "Add"),
+            MathOperation::Subtract => write!(f, "Subtract"),
+            MathOperation::Multiply => write!(f, "Multiply"),
+            MathOperation::Divide => write!(f, "Divide"),
+            MathOperation::Power => write!(f, "Power"),
+            MathOperation::Minimum => write!(f, "Minimum"),
+            MathOperation::Maximum => write!(f, "Maximum"),
+            MathOperation::LessThan => write!(f, "Less Than"),
+            MathOperation::GreaterThan => write!(f, "Greater Than"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MixBlend {
+    Mix,
+    Add,
+    Multiply,
+    Subtract,
+    Screen,
+}
+
+impl MixBlend {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use MixBlend::*;
+        [Mix, Add, Multiply, Subtract, Screen].iter().copied()
+    }
+
+    /// Blend `b` over `a` by the given factor.
+    pub fn apply(&self, factor: f64, a: &glm::DVec3, b: &glm::DVec3) -> glm::DVec3 {
+        let factor = factor.clamp(0.0, 1.0);
+        match self {
+            MixBlend::Mix => glm::lerp(a, b, factor),
+            MixBlend::Add => a + b * factor,
+            MixBlend::Multiply => glm::lerp(a, &a.component_mul(b), factor),
+            MixBlend::Subtract => a - b * factor,
+            MixBlend::Screen => {
+                let one = glm::vec3(1.0, 1.0, 1.0);
+                one - (one * (1.0 - factor) + (one - b) * factor).component_mul(&(one - a))
+            }
+        }
+    }
+}
+
+impl Display for MixBlend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MixBlend::Mix => write!(f, "Mix"),
+            MixBlend::Add => write!(f, "Add"),
+            MixBlend::Multiply => write!(f, "Multiply"),
+            MixBlend::Subtract => write!(f, "Subtract"),
+            MixBlend::Screen => write!(f, "Screen"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorRampInterpolation {
+    Linear,
+    Constant,
+}
+
+impl Display for ColorRampInterpolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorRampInterpolation::Linear => write!(f, "Linear"),
+            ColorRampInterpolation::Constant => write!(f, "Constant"),
+        }
+    }
+}
+
+/// Colour of a colour ramp at the given position.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    position: f64,
+    color: glm::DVec3,
+}
+
+impl ColorStop {
+    pub fn new(position: f64, color: glm::DVec3) -> Self {
+        Self { position, color }
+    }
+}
+
+/// Maps a factor to a colour, `stops` are sorted by their position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorRamp {
+    stops: Vec<ColorStop>,
+    interpolation: ColorRampInterpolation,
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        Self::new(
+            vec![
+                ColorStop::new(0.0, glm::vec3(0.0, 0.0, 0.0)),
+                ColorStop::new(1.0, glm::vec3(1.0, 1.0, 1.0)),
+            ],
+            ColorRampInterpolation::Linear,
+        )
+    }
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<ColorStop>, interpolation: ColorRampInterpolation) -> Self {
+        stops.sort_by(|a, b| {
+            a.position
+                .partial_cmp(&b.position)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Self {
+            stops,
+            interpolation,
+        }
+    }
+
+    pub fn eval(&self, factor: f64) -> glm::DVec3 {
+        let upper = self.stops.iter().position(|stop| stop.position > factor);
+        match upper {
+            None => self.stops.last().map_or_else(glm::zero, |stop| stop.color),
+            Some(0) => self.stops[0].color,
+            Some(upper) => {
+                let lower = &self.stops[upper - 1];
+                let upper = &self.stops[upper];
+                match self.interpolation {
+                    ColorRampInterpolation::Linear => glm::lerp(
+                        &lower.color,
+                        &upper.color,
+                        (factor - lower.position) / (upper.position - lower.position),
+                    ),
+                    ColorRampInterpolation::Constant => lower.color,
+                }
+            }
+        }
+    }
+}
+
+/// `BSDF` that the output of the graph is, its inputs are the inputs
+/// of the output node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputBSDFType {
+    Lambert,
+    Glossy,
+    Principled,
+    Emission,
+}
+
+impl OutputBSDFType {
+    pub fn all() -> impl Iterator<Item = Self> {
+        use OutputBSDFType::*;
+        [Lambert, Glossy, Principled, Emission].iter().copied()
+    }
+}
+
+impl Display for OutputBSDFType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputBSDFType::Lambert => write!(f, "Lambert"),
+            OutputBSDFType::Glossy => write!(f, "Glossy"),
+            OutputBSDFType::Principled => write!(f, "Principled"),
+            OutputBSDFType::Emission => write!(f, "Emission"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+    /// Colour and alpha of a texture at the UV coordinates of the
+    /// vector input, the UV coordinates of the point of intersection
+    /// if it is not linked.
+    Texture(TextureSlot),
+    Math(MathOperation),
+    ColorRamp(ColorRamp),
+    Mix(MixBlend),
+    /// UV coordinates of the point of intersection.
+    UV,
+    /// Position, normal and backfacing of the point of intersection.
+    Geometry,
+    Output(OutputBSDFType),
+}
+
+impl NodeKind {
+    /// Node kinds that can be added to the graph, the output node
+    /// exists from the start.
+    pub fn all_addable() -> impl Iterator<Item = Self> {
+        vec![
+            NodeKind::Texture(TextureSlot::default()),
+            NodeKind::Math(MathOperation::Add),
+            NodeKind::ColorRamp(ColorRamp::default()),
+            NodeKind::Mix(MixBlend::Mix),
+            NodeKind::UV,
+            NodeKind::Geometry,
+        ]
+        .into_iter()
+    }
+
+    /// Get the names and types of the inputs of the node along with
+    /// their default values.
+    pub fn get_inputs(&self) -> Vec<(&'static str, SocketType, NodeValue)> {
+        let white = NodeValue::Vector(glm::vec3(0.8, 0.8, 0.8));
+        match self {
+            NodeKind::Texture(_) => {
+                vec![("Vector", SocketType::Vector, NodeValue::Vector(glm::zero()))]
+            }
+            NodeKind::Math(_) => vec![
+                ("A", SocketType::Float, NodeValue::Float(0.5)),
+                ("B", SocketType::Float, NodeValue::Float(0.5)),
+            ],
+            NodeKind::ColorRamp(_) => vec![("Factor", SocketType::Float, NodeValue::Float(0.5))],
+            NodeKind::Mix(_) => vec![
+                ("Factor", SocketType::Float, NodeValue::Float(0.5)),
+                (
+                    "A",
+                    SocketType::Color,
+                    NodeValue::Vector(glm::vec3(0.5, 0.5, 0.5)),
+                ),
+                (
+                    "B",
+                    SocketType::Color,
+                    NodeValue::Vector(glm::vec3(0.5, 0.5, 0.5)),
+                ),
+            ],
+            NodeKind::UV | NodeKind::Geometry => Vec::new(),
+            NodeKind::Output(OutputBSDFType::Lambert) => vec![("Color", SocketType::Color, white)],
+            NodeKind::Output(OutputBSDFType::Glossy) => vec![
+                ("Color", SocketType::Color, white),
+                ("Roughness", SocketType::Float, NodeValue::Float(0.4)),
+            ],
+            // the inputs of `Principled`, a link to the IOR is
+            // ignored since the medium inside the object has a
+            // single ior
+            NodeKind::Output(OutputBSDFType::Principled) => vec![
+                ("Base Color", SocketType::Color, white),
+                ("Metallic", SocketType::Float, NodeValue::Float(0.0)),
+                ("Roughness", SocketType::Float, NodeValue::Float(0.5)),
+                ("Specular", SocketType::Float, NodeValue::Float(0.5)),
+                ("Anisotropic", SocketType::Float, NodeValue::Float(0.0)),
+                (
+                    "Anisotropic Rotation",
+                    SocketType::Float,
+                    NodeValue::Float(0.0),
+                ),
+                ("Sheen", SocketType::Float, NodeValue::Float(0.0)),
+                ("Sheen Tint", SocketType::Float, NodeValue::Float(0.5)),
+                ("Clearcoat", SocketType::Float, NodeValue::Float(0.0)),
+                (
+                    "Clearcoat Roughness",
+                    SocketType::Float,
+                    NodeValue::Float(0.03),
+                ),
+                ("Transmission", SocketType::Float, NodeValue::Float(0.0)),
+                ("IOR", SocketType::Float, NodeValue::Float(1.45)),
+                (
+                    "Emission",
+                    SocketType::Color,
+                    NodeValue::Vector(glm::zero()),
+                ),
+                (
+                    "Emission Strength",
+                    SocketType::Float,
+                    NodeValue::Float(1.0),
+                ),
+            ],
+            NodeKind::Output(OutputBSDFType::Emission) => vec![
+                (
+                    "Color",
+                    SocketType::Color,
+                    NodeValue::Vector(glm::vec3(1.0, 1.0, 1.0)),
+                ),
+                ("Strength", SocketType::Float, NodeValue::Float(1.0)),
+            ],
+        }
+    }
+
+    /// Get the index of the input of the node with the given name,
+    /// see [`Self::get_inputs()`].
+    pub fn get_input_index(&self, name: &str) -> Option<usize> {
+        self.get_inputs()
+            .iter()
+            .position(|(input_name, _, _)| *input_name == name)
+    }
+
+    /// Get the names of the outputs of the node.
+    pub fn get_outputs(&self) -> &'static [&'static str] {
+        match self {
+            NodeKind::Texture(_) => &["Color", "Alpha"],
+            NodeKind::Math(_) => &["Value"],
+            NodeKind::ColorRamp(_) => &["Color"],
+            NodeKind::Mix(_) => &["Color"],
+            NodeKind::UV => &["UV"],
+            NodeKind::Geometry => &["Position", "Normal", "Backfacing"],
+            NodeKind::Output(_) => &[],
+        }
+    }
+}
+
+impl Display for NodeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeKind::Texture(_) => write!(f, "Texture"),
+            NodeKind::Math(operation) => write!(f, "Math ({})", operation),
+            NodeKind::ColorRamp(_) => write!(f, "Color Ramp"),
+            NodeKind::Mix(blend) => write!(f, "Mix ({})", blend),
+            NodeKind::UV => write!(f, "UV"),
+            NodeKind::Geometry => write!(f, "Geometry"),
+            NodeKind::Output(bsdf_type) => write!(f, "Output ({})", bsdf_type),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    kind: NodeKind,
+    inputs: Vec<NodeInput>,
+    /// position of the node in the node editor
+    position: [f32; 2],
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, position: [f32; 2]) -> Self {
+        let inputs = kind
+            .get_inputs()
+            .into_iter()
+            .map(|(_, _, value)| NodeInput::new(value))
+            .collect();
+        Self {
+            kind,
+            inputs,
+            position,
+        }
+    }
+
+    /// Get a reference to the node's kind.
+    pub fn get_kind(&self) -> &NodeKind {
+        &self.kind
+    }
+
+    /// Get a reference to the node's inputs.
+    pub fn get_inputs(&self) -> &[NodeInput] {
+        &self.inputs
+    }
+}
+
+/// Graph of nodes that computes the inputs of a `BSDF` at the point
+/// of intersection, see [`NodeKind`]. The output node always exists
+/// and decides the `BSDF`.
+///
+/// The `BSDF` is built once per point of intersection (see
+/// [`BSDF::build_at()`]), the methods of [`BSDF`] build it on every
+/// call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "ShaderGraphIO")]
+pub struct ShaderGraph {
+    /// nodes of the graph indexed by their NodeID
+    nodes: HashMap<NodeID, Node>,
+    /// node ids in the order of addition of the nodes
+    node_ids: Vec<NodeID>,
+    output_node: NodeID,
+
+    /// node selected in the node editor
+    #[serde(skip)]
+    selected_node: Option<NodeID>,
+    /// node kind for adding a new node in the node editor
+    #[serde(skip)]
+    node_kind_for_add: Option<NodeKind>,
+}
+
+/// [`ShaderGraph`] as stored in files, the graph is validated once
+/// loaded, see [`ShaderGraph::validate()`].
+#[derive(Deserialize)]
+struct ShaderGraphIO {
+    nodes: HashMap<NodeID, Node>,
+    node_ids: Vec<NodeID>,
+    output_node: NodeID,
+}
+
+impl From<ShaderGraphIO> for ShaderGraph {
+    fn from(io: ShaderGraphIO) -> Self {
+        let mut shader_graph = Self {
+            nodes: io.nodes,
+            node_ids: io.node_ids,
+            output_node: io.output_node,
+            selected_node: None,
+            node_kind_for_add: None,
+        };
+        shader_graph.validate();
+        shader_graph
+    }
+}
+
+impl Default for ShaderGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderGraph {
+    pub fn new() -> Self {
+        let output_node = NodeID(rand::random());
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            output_node,
+            Node::new(NodeKind::Output(OutputBSDFType::Principled), [320.0, 20.0]),
+        );
+        Self {
+            nodes,
+            node_ids: vec![output_node],
+            output_node,
+            selected_node: None,
+            node_kind_for_add: None,
+        }
+    }
+
+    pub fn get_node(&self, node_id: NodeID) -> Option<&Node> {
+        self.nodes.get(&node_id)
+    }
+
+    /// Get the id of the output node.
+    pub fn get_output_node(&self) -> NodeID {
+        self.output_node
+    }
+
+    pub fn add_node(&mut self, node: Node) -> NodeID {
+        let node_id = NodeID(rand::random());
+        self.nodes.insert(node_id, node);
+        self.node_ids.push(node_id);
+        node_id
+    }
+
+    /// Delete the node and the links to its outputs, the output node
+    /// cannot be deleted.
+    pub fn delete_node(&mut self, node_id: NodeID) {
+        if node_id == self.output_node || self.nodes.remove(&node_id).is_none() {
+            return;
+        }
+        self.node_ids.retain(|id| *id != node_id);
+        self.nodes.values_mut().for_each(|node| {
+            node.inputs
+                .iter_mut()
+                .filter(|input| matches!(input.link, Some((id, _)) if id == node_id))
+                .for_each(|input| input.link = None);
+        });
+        if self.selected_node == Some(node_id) {
+            self.selected_node = None;
+        }
+    }
+
+    /// Check if the outputs of `node_id` depend on the outputs of
+    /// `target` by following the links of the inputs.
+    pub fn depends_on(&self, node_id: NodeID, target: NodeID) -> bool {
+        util::depth_first_search(std::iter::once(node_id), |node_id| self.get_links(node_id))
+            .get_post_order()
+            .contains(&target)
+    }
+
+    /// Get the links (input index, linked node) of the inputs of the
+    /// node.
+    fn get_links(&self, node_id: NodeID) -> Vec<(usize, NodeID)> {
+        self.nodes.get(&node_id).map_or_else(Vec::new, |node| {
+            node.inputs
+                .iter()
+                .enumerate()
+                .filter_map(|(input_index, input)| {
+                    input.link.map(|(from_node, _)| (input_index, from_node))
+                })
+                .collect()
+        })
+    }
+
+    /// Link the input of `node_id` to the output of `from`, rejected
+    /// (returns false) if the link would create a cycle or the
+    /// sockets do not exist. `None` removes the link.
+    pub fn set_link(
+        &mut self,
+        node_id: NodeID,
+        input_index: usize,
+        from: Option<(NodeID, usize)>,
+    ) -> bool {
+        if let Some((from_node, output_index)) = from {
+            let valid_output = self
+                .nodes
+                .get(&from_node)
+                .map_or(false, |node| output_index < node.kind.get_outputs().len());
+            if !valid_output || self.depends_on(from_node, node_id) {
+                return false;
+            }
+        }
+        match self
+            .nodes
+            .get_mut(&node_id)
+            .and_then(|node| node.inputs.get_mut(input_index))
+        {
+            Some(input) => {
+                input.link = from;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Make the graph consistent, needed for graphs loaded from
+    /// files. The output node must exist and be of output kind, the
+    /// inputs of the nodes must match their kinds and links to
+    /// outputs that do not exist are removed. The links that create
+    /// a cycle are removed, see [`util::depth_first_search()`].
+    ///
+    /// Returns the number of links that were removed.
+    pub fn validate(&mut self) -> usize {
+        let nodes = &self.nodes;
+        let mut listed = HashSet::new();
+        self.node_ids
+            .retain(|node_id| nodes.contains_key(node_id) && listed.insert(*node_id));
+        let mut unlisted: Vec<_> = nodes
+            .keys()
+            .filter(|node_id| !listed.contains(node_id))
+            .copied()
+            .collect();
+        unlisted.sort();
+        self.node_ids.append(&mut unlisted);
+
+        if !matches!(
+            self.nodes.get(&self.output_node).map(|node| &node.kind),
+            Some(NodeKind::Output(_))
+        ) {
+            eprintln!("warning: shader graph has no output node, a new output node is added");
+            self.output_node = self.add_node(Node::new(
+                NodeKind::Output(OutputBSDFType::Principled),
+                [320.0, 20.0],
+            ));
+        }
+
+        self.nodes.values_mut().for_each(|node| {
+            let defaults = node.kind.get_inputs();
+            node.inputs.truncate(defaults.len());
+            let num_inputs = node.inputs.len();
+            node.inputs.extend(
+                defaults
+                    .into_iter()
+                    .skip(num_inputs)
+                    .map(|(_, _, value)| NodeInput::new(value)),
+            );
+        });
+
+        let num_outputs: HashMap<_, _> = self
+            .nodes
+            .iter()
+            .map(|(node_id, node)| (*node_id, node.kind.get_outputs().len()))
+            .collect();
+        let mut removed_links = 0;
+        self.nodes.values_mut().for_each(|node| {
+            node.inputs.iter_mut().for_each(|input| {
+                if let Some((from_node, output_index)) = input.link {
+                    if num_outputs
+                        .get(&from_node)
+                        .map_or(true, |num_outputs| output_index >= *num_outputs)
+                    {
+                        input.link = None;
+                        removed_links += 1;
+                    }
+                }
+            });
+        });
+
+        let cyclic_links = util::depth_first_search(self.node_ids.iter().copied(), |node_id| {
+            self.get_links(node_id)
+        })
+        .get_back_edges()
+        .to_vec();
+        cyclic_links.iter().for_each(|(node_id, input_index)| {
+            let node = self.nodes.get_mut(node_id).unwrap();
+            eprintln!(
+                "warning: input {} of node {} creates a cycle in the shader graph, the link is removed",
+                input_index, node.kind
+            );
+            node.inputs[*input_index].link = None;
+        });
+
+        removed_links + cyclic_links.len()
+    }
+
+    /// Get the nodes that the output node depends on in a
+    /// topological order, a node comes after all the nodes linked to
+    /// its inputs.
+    fn get_evaluation_order(&self) -> Vec<NodeID> {
+        util::depth_first_search(std::iter::once(self.output_node), |node_id| {
+            self.get_links(node_id)
+        })
+        .get_post_order()
+        .to_vec()
+    }
+
+    /// Evaluate the outputs of the nodes that the output node
+    /// depends on at the point of intersection. Each node is
+    /// evaluated once, after the nodes linked to its inputs.
+    fn eval_nodes(
+        &self,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> HashMap<NodeID, Vec<NodeValue>> {
+        let mut values = HashMap::new();
+        self.get_evaluation_order().into_iter().for_each(|node_id| {
+            if let Some(node) = self.nodes.get(&node_id) {
+                let outputs = Self::eval_node(node, &values, intersect_info, texture_list);
+                values.insert(node_id, outputs);
+            }
+        });
+        values
+    }
+
+    /// Get the value of the input of the node, the value of the
+    /// output it is linked to if that is evaluated (see
+    /// [`Self::eval_nodes()`]).
+    fn get_input_value(
+        node: &Node,
+        input_index: usize,
+        values: &HashMap<NodeID, Vec<NodeValue>>,
+    ) -> NodeValue {
+        match node.inputs.get(input_index) {
+            Some(input) => input
+                .link
+                .and_then(|(node_id, output_index)| {
+                    values
+                        .get(&node_id)
+                        .and_then(|outputs| outputs.get(output_index))
+                })
+                .copied()
+                .unwrap_or(input.value),
+            None => node
+                .kind
+                .get_inputs()
+                .get(input_index)
+                .map_or(NodeValue::Float(0.0), |(_, _, value)| *value),
+        }
+    }
+
+    /// Get the value of the input of the node with the given name,
+    /// see [`Self::get_input_value()`].
+    fn get_named_input_value(
+        node: &Node,
+        input_name: &str,
+        values: &HashMap<NodeID, Vec<NodeValue>>,
+    ) -> NodeValue {
+        node.kind
+            .get_input_index(input_name)
+            .map_or(NodeValue::Float(0.0), |input_index| {
+                Self::get_input_value(node, input_index, values)
+            })
+    }
+
+    /// Evaluate the outputs of the node at the point of intersection,
+    /// `values` are the outputs of the nodes linked to its inputs.
+    fn eval_node(
+        node: &Node,
+        values: &HashMap<NodeID, Vec<NodeValue>>,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Vec<NodeValue> {
+        let input = |index| Self::get_input_value(node, index, values);
+
+        match &node.kind {
+            NodeKind::Texture(texture_slot) => {
+                let vector_linked = node
+                    .inputs
+                    .first()
+                    .map_or(false, |input| input.link.is_some());
+                let uv = if vector_linked {
+                    let vector = input(0).as_vector();
+                    Some(glm::vec2(vector[0], vector[1]))
+                } else {
+                    *intersect_info.get_uv()
+                };
+                let pixel = uv
+                    .zip(texture_slot.get_texture_id())
+                    .and_then(|(uv, texture_id)| {
+                        texture_list.sample_texture(
+                            texture_id,
+                            &uv,
+                            intersect_info.get_uv_footprint(),
+                            texture_slot.get_sampler(),
+                        )
+                    })
+                    .unwrap_or_else(|| glm::vec4(1.0, 0.0, 1.0, 1.0));
+                vec![
+                    NodeValue::Vector(glm::vec4_to_vec3(&pixel)),
+                    NodeValue::Float(pixel[3]),
+                ]
+            }
+            NodeKind::Math(operation) => vec![NodeValue::Float(
+                operation.apply(input(0).as_float(), input(1).as_float()),
+            )],
+            NodeKind::ColorRamp(color_ramp) => {
+                vec![NodeValue::Vector(color_ramp.eval(input(0).as_float()))]
+            }
+            NodeKind::Mix(blend) => vec![NodeValue::Vector(blend.apply(
+                input(0).as_float(),
+                &input(1).as_vector(),
+                &input(2).as_vector(),
+            ))],
+            NodeKind::UV => {
+                let uv = intersect_info.get_uv().unwrap_or_else(glm::zero);
+                vec![NodeValue::Vector(glm::vec3(uv[0], uv[1], 0.0))]
+            }
+            NodeKind::Geometry => vec![
+                NodeValue::Vector(*intersect_info.get_point()),
+                NodeValue::Vector(intersect_info.get_normal().unwrap_or_else(glm::zero)),
+                NodeValue::Float(if intersect_info.get_front_face() {
+                    0.0
+                } else {
+                    1.0
+                }),
+            ],
+            NodeKind::Output(_) => Vec::new(),
+        }
+    }
+
+    /// Build the `BSDF` of the output node at the point of
+    /// intersection.
+    fn build_bsdf(
+        &self,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Box<dyn BSDF> {
+        let node = match self.nodes.get(&self.output_node) {
+            Some(node) => node,
+            None => return Box::new(Principled::default()),
+        };
+        let values = self.eval_nodes(intersect_info, texture_list);
+        let input = |index| Self::get_input_value(node, index, &values);
+        match self.get_output_bsdf_type() {
+            OutputBSDFType::Lambert => Box::new(Lambert::new(input(0).as_vector())),
+            OutputBSDFType::Glossy => {
+                Box::new(Glossy::new(input(0).as_vector(), input(1).as_float()))
+            }
+            OutputBSDFType::Principled => {
+                // the inputs are looked up by name, see
+                // `NodeKind::get_inputs()`
+                let input = |name| Self::get_named_input_value(node, name, &values);
+                let value = |name| ValuePicker::Value(input(name).as_float());
+                let mut principled = Principled::new(
+                    input("Base Color").as_vector(),
+                    input("Metallic").as_float(),
+                    input("Roughness").as_float(),
+                );
+                principled.set_specular(value("Specular"));
+                principled.set_anisotropic(value("Anisotropic"));
+                principled.set_anisotropic_rotation(value("Anisotropic Rotation"));
+                principled.set_sheen(value("Sheen"));
+                principled.set_sheen_tint(value("Sheen Tint"));
+                principled.set_clearcoat(value("Clearcoat"));
+                principled.set_clearcoat_roughness(value("Clearcoat Roughness"));
+                principled.set_transmission(value("Transmission"));
+                principled.set_ior(self.get_ior());
+                principled.set_emission(ColorPicker::Color(input("Emission").as_vector()));
+                principled.set_emission_strength(value("Emission Strength"));
+                Box::new(principled)
+            }
+            OutputBSDFType::Emission => {
+                Box::new(Emissive::new(input(0).as_vector(), input(1).as_float()))
+            }
+        }
+    }
+
+    /// Get the `BSDF` of the output node, the output node is always
+    /// of output kind once the graph is validated (see
+    /// [`Self::validate()`]).
+    fn get_output_bsdf_type(&self) -> OutputBSDFType {
+        match self.nodes.get(&self.output_node).map(|node| &node.kind) {
+            Some(NodeKind::Output(bsdf_type)) => *bsdf_type,
+            _ => OutputBSDFType::Principled,
+        }
+    }
+}
+
+#[typetag::serde]
+impl BSDF for ShaderGraph {
+    fn sample(
+        &self,
+        wo: &glm::DVec3,
+        mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        self.build_bsdf(intersect_info, texture_list).sample(
+            wo,
+            mediums,
+            intersect_info,
+            texture_list,
+            sampling_types,
+        )
+    }
+
+    fn eval(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        self.build_bsdf(intersect_info, texture_list)
+            .eval(wi, wo, intersect_info, texture_list)
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        wo: &glm::DVec3,
+        mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        self.build_bsdf(intersect_info, texture_list).eval_with_pdf(
+            wi,
+            wo,
+            mediums,
+            intersect_info,
+            texture_list,
+        )
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        match self.get_output_bsdf_type() {
+            OutputBSDFType::Lambert => SamplingTypes::Diffuse.into(),
+            OutputBSDFType::Glossy => SamplingTypes::Glossy.into(),
+            OutputBSDFType::Principled => SamplingTypes::Diffuse | SamplingTypes::Glossy,
+            OutputBSDFType::Emission => BitFlags::empty(),
+        }
+    }
+
+    fn emission(
+        &self,
+        wo: &glm::DVec3,
+        mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<glm::DVec3> {
+        self.build_bsdf(intersect_info, texture_list).emission(
+            wo,
+            mediums,
+            intersect_info,
+            texture_list,
+        )
+    }
+
+    fn build_at(
+        &self,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<Box<dyn BSDF>> {
+        Some(self.build_bsdf(intersect_info, texture_list))
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Shader Graph"
+    }
+
+    fn get_base_color(&self, _texture_list: &TextureList) -> Option<glm::DVec3> {
+        // only the colour of an output that is not linked is known
+        // without a point of intersection
+        self.nodes
+            .get(&self.output_node)
+            .and_then(|node| node.inputs.first())
+            .filter(|input| input.link.is_none())
+            .map(|input| input.value.as_vector())
+    }
+
+    fn set_base_color(&mut self, color: ColorPicker) {
+        let texture_slot = match color {
+            ColorPicker::Color(color) => {
+                if let Some(input) = self
+                    .nodes
+                    .get_mut(&self.output_node)
+                    .and_then(|node| node.inputs.first_mut())
+                {
+                    input.value = NodeValue::Vector(color);
+                    input.link = None;
+                }
+                return;
+            }
+            ColorPicker::Texture(texture_slot) => texture_slot,
+        };
+
+        // a texture needs a texture node linked to the colour, the
+        // texture node already linked to it is reused
+        let linked_node = self
+            .nodes
+            .get(&self.output_node)
+            .and_then(|node| node.inputs.first())
+            .and_then(|input| input.link);
+        if let Some((node_id, 0)) = linked_node {
+            if let Some(NodeKind::Texture(linked_texture_slot)) =
+                self.nodes.get_mut(&node_id).map(|node| &mut node.kind)
+            {
+                *linked_texture_slot = texture_slot;
+                return;
+            }
+        }
+        let texture_node = self.add_node(Node::new(NodeKind::Texture(texture_slot), [20.0, 20.0]));
+        self.set_link(self.output_node, 0, Some((texture_node, 0)));
+    }
+
+    fn get_ior(&self) -> f64 {
+        match self.get_output_bsdf_type() {
+            // the value of the input, see `NodeKind::get_inputs()`
+            OutputBSDFType::Principled => self
+                .nodes
+                .get(&self.output_node)
+                .and_then(|node| {
+                    node.kind
+                        .get_input_index("IOR")
+                        .and_then(|input_index| node.inputs.get(input_index))
+                })
+                .map_or_else(
+                    || Principled::default().get_ior(),
+                    |input| input.value.as_float(),
+                ),
+            _ => 1.0,
+        }
+    }
+}
+
+/// Width of a node in the node editor.
+const NODE_WIDTH: f32 = 150.0;
+/// Height of the title and of each socket of a node in the node
+/// editor.
+const NODE_ROW_HEIGHT: f32 = 20.0;
+/// Height of the node editor canvas.
+const NODE_EDITOR_HEIGHT: f32 = 300.0;
+
+impl ShaderGraph {
+    /// Get the rectangle of the node in the node editor, outputs are
+    /// listed below the title followed by the inputs.
+    fn get_node_rect(node: &Node, origin: egui::Pos2) -> egui::Rect {
+        let rows = 1 + node.kind.get_outputs().len() + node.inputs.len();
+        egui::Rect::from_min_size(
+            origin + egui::vec2(node.position[0], node.position[1]),
+            egui::vec2(NODE_WIDTH, rows as f32 * NODE_ROW_HEIGHT),
+        )
+    }
+
+    /// Get the position of the output socket of the node in the node
+    /// editor.
+    fn get_output_socket_pos(node: &Node, origin: egui::Pos2, output_index: usize) -> egui::Pos2 {
+        let rect = Self::get_node_rect(node, origin);
+        egui::pos2(
+            rect.max.x,
+            rect.min.y + (output_index as f32 + 1.5) * NODE_ROW_HEIGHT,
+        )
+    }
+
+    /// Get the position of the input socket of the node in the node
+    /// editor.
+    fn get_input_socket_pos(node: &Node, origin: egui::Pos2, input_index: usize) -> egui::Pos2 {
+        let rect = Self::get_node_rect(node, origin);
+        let row = 1 + node.kind.get_outputs().len() + input_index;
+        egui::pos2(
+            rect.min.x,
+            rect.min.y + (row as f32 + 0.5) * NODE_ROW_HEIGHT,
+        )
+    }
+
+    /// Draw the nodes and the links of the graph, nodes can be
+    /// dragged around and selected by clicking on them.
+    fn draw_node_editor(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), NODE_EDITOR_HEIGHT),
+            egui::Sense::hover(),
+        );
+        let origin = response.rect.min;
+        painter.rect_filled(response.rect, 0.0, egui::Color32::from_gray(30));
+
+        let link_stroke = egui::Stroke::new(2.0, egui::Color32::from_gray(180));
+        self.node_ids
+            .iter()
+            .filter_map(|node_id| self.nodes.get(node_id))
+            .for_each(|node| {
+                node.inputs
+                    .iter()
+                    .enumerate()
+                    .for_each(|(input_index, input)| {
+                        if let Some((from_node, output_index)) = input.link {
+                            if let Some(from_node) = self.nodes.get(&from_node) {
+                                painter.line_segment(
+                                    [
+                                        Self::get_output_socket_pos(
+                                            from_node,
+                                            origin,
+                                            output_index,
+                                        ),
+                                        Self::get_input_socket_pos(node, origin, input_index),
+                                    ],
+                                    link_stroke,
+                                );
+                            }
+                        }
+                    });
+            });
+
+        let selected_node = &mut self.selected_node;
+        let nodes = &mut self.nodes;
+        self.node_ids.iter().for_each(|node_id| {
+            let node = match nodes.get_mut(node_id) {
+                Some(node) => node,
+                None => return,
+            };
+            let rect = Self::get_node_rect(node, origin);
+
+            let node_response = ui.interact(rect, id.with(node_id), egui::Sense::click_and_drag());
+            if node_response.clicked() || node_response.dragged() {
+                *selected_node = Some(*node_id);
+            }
+            if node_response.dragged() {
+                let delta = node_response.drag_delta();
+                node.position[0] = (node.position[0] + delta.x).max(0.0);
+                node.position[1] = (node.position[1] + delta.y).max(0.0);
+            }
+
+            let fill = if *selected_node == Some(*node_id) {
+                egui::Color32::from_gray(90)
+            } else {
+                egui::Color32::from_gray(60)
+            };
+            painter.rect_filled(rect, 4.0, fill);
+            painter.rect_stroke(rect, 4.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+
+            let row_rect = |row: usize| {
+                egui::Rect::from_min_size(
+                    rect.min + egui::vec2(8.0, row as f32 * NODE_ROW_HEIGHT),
+                    egui::vec2(NODE_WIDTH - 16.0, NODE_ROW_HEIGHT),
+                )
+            };
+            ui.put(row_rect(0), egui::Label::new(format!("{}", node.kind)));
+            let outputs = node.kind.get_outputs();
+            outputs.iter().enumerate().for_each(|(output_index, name)| {
+                painter.circle_filled(
+                    Self::get_output_socket_pos(node, origin, output_index),
+                    4.0,
+                    egui::Color32::from_rgb(200, 200, 100),
+                );
+                ui.put(row_rect(1 + output_index), egui::Label::new(*name));
+            });
+            node.kind
+                .get_inputs()
+                .iter()
+                .enumerate()
+                .for_each(|(input_index, (name, _, _))| {
+                    painter.circle_filled(
+                        Self::get_input_socket_pos(node, origin, input_index),
+                        4.0,
+                        egui::Color32::from_rgb(100, 150, 200),
+                    );
+                    ui.put(
+                        row_rect(1 + outputs.len() + input_index),
+                        egui::Label::new(*name),
+                    );
+                });
+        });
+    }
+
+    /// Draw the settings and the inputs of the selected node, inputs
+    /// can be linked to the outputs of the other nodes that do not
+    /// create a cycle.
+    fn draw_selected_node_ui(&mut self, ui: &mut egui::Ui, extra_data: &BSDFUiData) {
+        let node_id = match self.selected_node.filter(|id| self.nodes.contains_key(id)) {
+            Some(node_id) => node_id,
+            None => {
+                ui.label("No node selected");
+                return;
+            }
+        };
+        let id = extra_data.get_shader_egui_id().with(node_id);
+
+        // outputs that the inputs of the node can be linked to
+        let link_choices: Vec<_> = self
+            .node_ids
+            .iter()
+            .filter(|from_node| !self.depends_on(**from_node, node_id))
+            .filter_map(|from_node| self.nodes.get(from_node).map(|node| (from_node, node)))
+            .flat_map(|(from_node, node)| {
+                node.kind
+                    .get_outputs()
+                    .iter()
+                    .enumerate()
+                    .map(move |(output_index, name)| {
+                        (
+                            (*from_node, output_index),
+                            format!("{}: {}", node.kind, name),
+                        )
+                    })
+            })
+            .collect();
+
+        let node = self.nodes.get_mut(&node_id).unwrap();
+        ui.label(format!("Selected Node: {}", node.kind));
+
+        let mut new_kind = None;
+        match &mut node.kind {
+            NodeKind::Texture(texture_slot) => texture_slot.draw_ui_mut(
+                ui,
+                &TextureSlotUiData::new(extra_data.get_texture_list().clone(), id.with("Texture")),
+            ),
+            NodeKind::Math(operation) => {
+                egui::ComboBox::from_id_source(id.with("Operation"))
+                    .selected_text(format!("{}", operation))
+                    .show_ui(ui, |ui| {
+                        MathOperation::all().for_each(|value| {
+                            ui.selectable_value(operation, value, format!("{}", value));
+                        });
+                    });
+            }
+            NodeKind::ColorRamp(color_ramp) => {
+                egui::ComboBox::from_id_source(id.with("Interpolation"))
+                    .selected_text(format!("{}", color_ramp.interpolation))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut color_ramp.interpolation,
+                            ColorRampInterpolation::Linear,
+                            "Linear",
+                        );
+                        ui.selectable_value(
+                            &mut color_ramp.interpolation,
+                            ColorRampInterpolation::Constant,
+                            "Constant",
+                        );
+                    });
+                let mut delete_stop = None;
+                color_ramp
+                    .stops
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(index, stop)| {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut stop.position)
+                                    .speed(0.01)
+                                    .clamp_range(0.0..=1.0),
+                            );
+                            ui::color_edit_button_dvec3(ui, "", &mut stop.color);
+                            if ui.button("X").clicked() {
+                                delete_stop = Some(index);
+                            }
+                        });
+                    });
+                if let Some(index) = delete_stop {
+                    color_ramp.stops.remove(index);
+                }
+                if ui.button("Add Stop").clicked() {
+                    color_ramp
+                        .stops
+                        .push(ColorStop::new(1.0, glm::vec3(1.0, 1.0, 1.0)));
+                }
+                color_ramp.stops.sort_by(|a, b| {
+                    a.position
+                        .partial_cmp(&b.position)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            }
+            NodeKind::Mix(blend) => {
+                egui::ComboBox::from_id_source(id.with("Blend"))
+                    .selected_text(format!("{}", blend))
+                    .show_ui(ui, |ui| {
+                        MixBlend::all().for_each(|value| {
+                            ui.selectable_value(blend, value, format!("{}", value));
+                        });
+                    });
+            }
+            NodeKind::UV | NodeKind::Geometry => {}
+            NodeKind::Output(bsdf_type) => {
+                let mut selected_bsdf_type = *bsdf_type;
+                egui::ComboBox::from_id_source(id.with("BSDF"))
+                    .selected_text(format!("{}", bsdf_type))
+                    .show_ui(ui, |ui| {
+                        OutputBSDFType::all().for_each(|value| {
+                            ui.selectable_value(
+                                &mut selected_bsdf_type,
+                                value,
+                                format!("{}", value),
+                            );
+                        });
+                    });
+                if selected_bsdf_type != *bsdf_type {
+                    new_kind = Some(NodeKind::Output(selected_bsdf_type));
+                }
+            }
+        }
+
+        // the inputs change with the `BSDF` of the output node
+        if let Some(new_kind) = new_kind {
+            *node = Node::new(new_kind, node.position);
+        }
+
+        let mut new_links = Vec::new();
+        node.kind
+            .get_inputs()
+            .iter()
+            .zip(node.inputs.iter_mut())
+            .enumerate()
+            .for_each(|(input_index, ((name, socket_type, _), input))| {
+                ui.horizontal(|ui| {
+                    ui.label(*name);
+
+                    let selected_text = input.link.map_or_else(
+                        || "Value".to_string(),
+                        |link| {
+                            link_choices
+                                .iter()
+                                .find(|(choice, _)| *choice == link)
+                                .map_or_else(|| "Invalid".to_string(), |(_, text)| text.clone())
+                        },
+                    );
+                    let mut link = input.link;
+                    egui::ComboBox::from_id_source(id.with(name))
+                        .selected_text(selected_text)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut link, None, "Value");
+                            link_choices.iter().for_each(|(choice, text)| {
+                                ui.selectable_value(&mut link, Some(*choice), text);
+                            });
+                        });
+                    if link != input.link {
+                        new_links.push((input_index, link));
+                    }
+
+                    if input.link.is_none() {
+                        match (socket_type, &mut input.value) {
+                            (SocketType::Float, NodeValue::Float(value)) => {
+                                ui.add(egui::DragValue::new(value).speed(0.01));
+                            }
+                            (SocketType::Color, NodeValue::Vector(color)) => {
+                                ui::color_edit_button_dvec3(ui, "", color);
+                            }
+                            (SocketType::Vector, NodeValue::Vector(vector)) => {
+                                ui.add(egui::DragValue::new(&mut vector[0]).speed(0.01));
+                                ui.add(egui::DragValue::new(&mut vector[1]).speed(0.01));
+                                ui.add(egui::DragValue::new(&mut vector[2]).speed(0.01));
+                            }
+                            _ => {}
+                        }
+                    }
+                });
+            });
+        new_links.into_iter().for_each(|(input_index, link)| {
+            self.set_link(node_id, input_index, link);
+        });
+
+        if node_id != self.output_node && ui.button("Delete Node").clicked() {
+            self.delete_node(node_id);
+        }
+    }
+}
+
+impl DrawUI for ShaderGraph {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        let id = extra_data.get_shader_egui_id().with("Shader Graph");
+        self.draw_node_editor(ui, id);
+
+        ui.horizontal(|ui| {
+            let node_kind_for_add = &mut self.node_kind_for_add;
+            egui::ComboBox::from_id_source(id.with("Node Kind"))
+                .selected_text(
+                    node_kind_for_add
+                        .as_ref()
+                        .map_or_else(|| "Node Kind".to_string(), |kind| kind.to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    NodeKind::all_addable().for_each(|kind| {
+                        let text = kind.to_string();
+                        ui.selectable_value(node_kind_for_add, Some(kind), text);
+                    });
+                });
+            if let Some(kind) = self.node_kind_for_add.clone() {
+                if ui.button("Add Node").clicked() {
+                    let node_id = self.add_node(Node::new(kind, [20.0, 20.0]));
+                    self.selected_node = Some(node_id);
+                }
+            }
+        });
+
+        self.draw_selected_node_ui(ui, extra_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a graph whose output node is of the given `BSDF`.
+    fn new_graph(bsdf_type: OutputBSDFType) -> ShaderGraph {
+        let mut shader_graph = ShaderGraph::new();
+        shader_graph.nodes.insert(
+            shader_graph.output_node,
+            Node::new(NodeKind::Output(bsdf_type), [0.0, 0.0]),
+        );
+        shader_graph
+    }
+
+    /// Set the value of the input of the node.
+    fn set_value(
+        shader_graph: &mut ShaderGraph,
+        node_id: NodeID,
+        input_index: usize,
+        value: NodeValue,
+    ) {
+        shader_graph.nodes.get_mut(&node_id).unwrap().inputs[input_index].value = value;
+    }
+
+    fn assert_color_eq(a: &glm::DVec3, b: &glm::DVec3) {
+        assert!(glm::distance(a, b) < 1e-9, "{} != {}", a, b);
+    }
+
+    /// Get the colour of the `BSDF` built by the graph.
+    fn get_built_color(shader_graph: &ShaderGraph) -> glm::DVec3 {
+        let texture_list = TextureList::new();
+        let intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        shader_graph
+            .build_bsdf(&intersect_info, &texture_list)
+            .get_base_color(&texture_list)
+            .unwrap()
+    }
+
+    #[test]
+    fn shader_graph_set_link_test_01() {
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let math_a =
+            shader_graph.add_node(Node::new(NodeKind::Math(MathOperation::Add), [0.0, 0.0]));
+        let math_b =
+            shader_graph.add_node(Node::new(NodeKind::Math(MathOperation::Add), [0.0, 0.0]));
+
+        assert!(shader_graph.set_link(math_b, 0, Some((math_a, 0))));
+        assert!(!shader_graph.set_link(math_a, 0, Some((math_b, 0))));
+        assert!(!shader_graph.set_link(math_a, 1, Some((math_a, 0))));
+        // sockets that do not exist
+        assert!(!shader_graph.set_link(math_a, 2, Some((math_b, 0))));
+        assert!(!shader_graph.set_link(math_b, 1, Some((math_a, 1))));
+
+        assert_eq!(shader_graph.nodes[&math_a].inputs[0].link, None);
+        assert_eq!(shader_graph.nodes[&math_a].inputs[1].link, None);
+        assert_eq!(
+            shader_graph.nodes[&math_b].inputs[0].link,
+            Some((math_a, 0))
+        );
+    }
+
+    #[test]
+    fn shader_graph_deserialize_test_01() {
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let math_a =
+            shader_graph.add_node(Node::new(NodeKind::Math(MathOperation::Add), [0.0, 0.0]));
+        let math_b =
+            shader_graph.add_node(Node::new(NodeKind::Math(MathOperation::Add), [0.0, 0.0]));
+        let output_node = shader_graph.output_node;
+        assert!(shader_graph.set_link(output_node, 0, Some((math_b, 0))));
+        assert!(shader_graph.set_link(math_b, 0, Some((math_a, 0))));
+        // cycle that cannot be made through `set_link()`
+        shader_graph.nodes.get_mut(&math_a).unwrap().inputs[0].link = Some((math_b, 0));
+
+        let shader_graph: ShaderGraph =
+            serde_json::from_str(&serde_json::to_string(&shader_graph).unwrap()).unwrap();
+
+        assert!(
+            !(shader_graph.depends_on(math_a, math_b) && shader_graph.depends_on(math_b, math_a))
+        );
+        assert_eq!(
+            shader_graph.nodes[&output_node].inputs[0].link,
+            Some((math_b, 0))
+        );
+        // evaluated without following the removed link
+        get_built_color(&shader_graph);
+    }
+
+    #[test]
+    fn shader_graph_validate_test_01() {
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let output_node = shader_graph.output_node;
+        shader_graph.nodes.remove(&output_node);
+        shader_graph.validate();
+
+        assert_ne!(shader_graph.output_node, output_node);
+        assert_eq!(
+            shader_graph.get_output_bsdf_type(),
+            OutputBSDFType::Principled
+        );
+        assert_eq!(shader_graph.node_ids, vec![shader_graph.output_node]);
+    }
+
+    #[test]
+    fn shader_graph_eval_test_01() {
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let math_a = shader_graph.add_node(Node::new(
+            NodeKind::Math(MathOperation::Multiply),
+            [0.0, 0.0],
+        ));
+        let math_b = shader_graph.add_node(Node::new(
+            NodeKind::Math(MathOperation::Subtract),
+            [0.0, 0.0],
+        ));
+        set_value(&mut shader_graph, math_a, 0, NodeValue::Float(0.5));
+        set_value(&mut shader_graph, math_a, 1, NodeValue::Float(4.0));
+        set_value(&mut shader_graph, math_b, 1, NodeValue::Float(0.5));
+        let output_node = shader_graph.output_node;
+        assert!(shader_graph.set_link(math_b, 0, Some((math_a, 0))));
+        assert!(shader_graph.set_link(output_node, 0, Some((math_b, 0))));
+
+        // (0.5 * 4.0) - 0.5
+        assert_eq!(get_built_color(&shader_graph), glm::vec3(1.5, 1.5, 1.5));
+
+        assert_eq!(MathOperation::Divide.apply(1.0, 0.0), 0.0);
+        assert_eq!(MathOperation::LessThan.apply(1.0, 2.0), 1.0);
+        assert_eq!(MathOperation::GreaterThan.apply(1.0, 2.0), 0.0);
+    }
+
+    #[test]
+    fn shader_graph_eval_test_02() {
+        // each math node adds the previous math node to itself, a
+        // node linked to multiple inputs is evaluated once
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let first =
+            shader_graph.add_node(Node::new(NodeKind::Math(MathOperation::Add), [0.0, 0.0]));
+        let last = (0..64).fold(first, |previous, _| {
+            let node_id =
+                shader_graph.add_node(Node::new(NodeKind::Math(MathOperation::Add), [0.0, 0.0]));
+            assert!(shader_graph.set_link(node_id, 0, Some((previous, 0))));
+            assert!(shader_graph.set_link(node_id, 1, Some((previous, 0))));
+            node_id
+        });
+        let output_node = shader_graph.output_node;
+        assert!(shader_graph.set_link(output_node, 0, Some((last, 0))));
+
+        assert_eq!(shader_graph.get_evaluation_order().len(), 66);
+        // (0.5 + 0.5) * 2^64
+        assert_eq!(get_built_color(&shader_graph)[0], 2.0_f64.powi(64));
+    }
+
+    #[test]
+    fn shader_graph_eval_test_03() {
+        let color_ramp = ColorRamp::new(
+            vec![
+                ColorStop::new(0.75, glm::vec3(1.0, 1.0, 1.0)),
+                ColorStop::new(0.25, glm::vec3(0.0, 0.0, 0.0)),
+            ],
+            ColorRampInterpolation::Linear,
+        );
+        assert_eq!(color_ramp.eval(0.0), glm::vec3(0.0, 0.0, 0.0));
+        assert_eq!(color_ramp.eval(0.5), glm::vec3(0.5, 0.5, 0.5));
+        assert_eq!(color_ramp.eval(1.0), glm::vec3(1.0, 1.0, 1.0));
+
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let color_ramp_node =
+            shader_graph.add_node(Node::new(NodeKind::ColorRamp(color_ramp), [0.0, 0.0]));
+        set_value(
+            &mut shader_graph,
+            color_ramp_node,
+            0,
+            NodeValue::Float(0.375),
+        );
+        let output_node = shader_graph.output_node;
+        assert!(shader_graph.set_link(output_node, 0, Some((color_ramp_node, 0))));
+        assert_eq!(get_built_color(&shader_graph), glm::vec3(0.25, 0.25, 0.25));
+
+        let constant = ColorRamp::new(
+            vec![
+                ColorStop::new(0.25, glm::vec3(0.0, 0.0, 0.0)),
+                ColorStop::new(0.75, glm::vec3(1.0, 1.0, 1.0)),
+            ],
+            ColorRampInterpolation::Constant,
+        );
+        assert_eq!(constant.eval(0.5), glm::vec3(0.0, 0.0, 0.0));
+        assert_eq!(constant.eval(0.8), glm::vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn shader_graph_eval_test_04() {
+        let a = glm::vec3(0.2, 0.4, 0.8);
+        let b = glm::vec3(0.5, 0.5, 0.5);
+        assert_color_eq(
+            &MixBlend::Mix.apply(0.5, &a, &b),
+            &glm::vec3(0.35, 0.45, 0.65),
+        );
+        assert_eq!(MixBlend::Add.apply(1.0, &a, &b), a + b);
+        assert_color_eq(
+            &MixBlend::Multiply.apply(1.0, &a, &b),
+            &glm::vec3(0.1, 0.2, 0.4),
+        );
+        assert_eq!(MixBlend::Subtract.apply(0.0, &a, &b), a);
+        // the factor is clamped
+        assert_color_eq(&MixBlend::Mix.apply(2.0, &a, &b), &b);
+
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        let mix_node = shader_graph.add_node(Node::new(NodeKind::Mix(MixBlend::Mix), [0.0, 0.0]));
+        let math_node = shader_graph.add_node(Node::new(
+            NodeKind::Math(MathOperation::Multiply),
+            [0.0, 0.0],
+        ));
+        set_value(&mut shader_graph, math_node, 0, NodeValue::Float(0.5));
+        set_value(&mut shader_graph, math_node, 1, NodeValue::Float(0.5));
+        set_value(
+            &mut shader_graph,
+            mix_node,
+            1,
+            NodeValue::Vector(glm::zero()),
+        );
+        set_value(
+            &mut shader_graph,
+            mix_node,
+            2,
+            NodeValue::Vector(glm::vec3(1.0, 2.0, 4.0)),
+        );
+        let output_node = shader_graph.output_node;
+        assert!(shader_graph.set_link(mix_node, 0, Some((math_node, 0))));
+        assert!(shader_graph.set_link(output_node, 0, Some((mix_node, 0))));
+        assert_eq!(get_built_color(&shader_graph), glm::vec3(0.25, 0.5, 1.0));
+    }
+
+    #[test]
+    fn shader_graph_output_bsdf_test_01() {
+        let texture_list = TextureList::new();
+        let intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        let expected = [
+            (OutputBSDFType::Lambert, "Lambert"),
+            (OutputBSDFType::Glossy, "Glossy"),
+            (OutputBSDFType::Principled, "Principled"),
+            (OutputBSDFType::Emission, "Emissive"),
+        ];
+        expected.iter().for_each(|(bsdf_type, bsdf_name)| {
+            let shader_graph = new_graph(*bsdf_type);
+            assert_eq!(
+                shader_graph
+                    .build_bsdf(&intersect_info, &texture_list)
+                    .get_bsdf_name(),
+                *bsdf_name
+            );
+            assert_eq!(
+                shader_graph
+                    .build_at(&intersect_info, &texture_list)
+                    .unwrap()
+                    .get_bsdf_name(),
+                *bsdf_name
+            );
+        });
+
+        let mut shader_graph = new_graph(OutputBSDFType::Principled);
+        assert_eq!(shader_graph.get_ior(), 1.45);
+        assert_eq!(
+            shader_graph
+                .build_bsdf(&intersect_info, &texture_list)
+                .get_ior(),
+            1.45
+        );
+        // no emission by default
+        assert!(shader_graph
+            .build_bsdf(&intersect_info, &texture_list)
+            .emission(
+                &glm::zero(),
+                &Mediums::new(),
+                &intersect_info,
+                &texture_list
+            )
+            .is_none());
+
+        let output_node = shader_graph.output_node;
+        let input_index = |name| {
+            NodeKind::Output(OutputBSDFType::Principled)
+                .get_input_index(name)
+                .unwrap()
+        };
+        set_value(
+            &mut shader_graph,
+            output_node,
+            input_index("IOR"),
+            NodeValue::Float(1.5),
+        );
+        set_value(
+            &mut shader_graph,
+            output_node,
+            input_index("Emission"),
+            NodeValue::Vector(glm::vec3(0.1, 0.2, 0.3)),
+        );
+        set_value(
+            &mut shader_graph,
+            output_node,
+            input_index("Emission Strength"),
+            NodeValue::Float(2.0),
+        );
+        assert_eq!(shader_graph.get_ior(), 1.5);
+        assert_eq!(
+            shader_graph
+                .build_bsdf(&intersect_info, &texture_list)
+                .get_ior(),
+            1.5
+        );
+        assert_color_eq(
+            &shader_graph
+                .build_bsdf(&intersect_info, &texture_list)
+                .emission(
+                    &glm::zero(),
+                    &Mediums::new(),
+                    &intersect_info,
+                    &texture_list,
+                )
+                .unwrap(),
+            &glm::vec3(0.2, 0.4, 0.6),
+        );
+        assert_eq!(new_graph(OutputBSDFType::Lambert).get_ior(), 1.0);
+    }
+
+    #[test]
+    fn shader_graph_set_base_color_test_01() {
+        let mut shader_graph = new_graph(OutputBSDFType::Lambert);
+        shader_graph.set_base_color(ColorPicker::Texture(TextureSlot::default()));
+        shader_graph.set_base_color(ColorPicker::Texture(TextureSlot::default()));
+        assert_eq!(shader_graph.nodes.len(), 2);
+
+        shader_graph.set_base_color(ColorPicker::Color(glm::vec3(0.1, 0.2, 0.3)));
+        assert_eq!(get_built_color(&shader_graph), glm::vec3(0.1, 0.2, 0.3));
+    }
+}
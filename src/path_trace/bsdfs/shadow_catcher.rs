@@ -0,0 +1,134 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{CompositingType, SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::utils::{self, ColorPicker, ColorPickerUiData};
+use super::BSDFUiData;
+use crate::egui;
+use crate::glm;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+
+/// Records only the shadows cast on to the surface (and the objects
+/// reflected by it) for the camera, used to composite objects on to
+/// photographed plates. For all other rays, it is a diffuse surface
+/// of the given colour so that the objects receive the light bounced
+/// off the surface.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowCatcher {
+    color: ColorPicker,
+    /// fraction of the light reflected (as a mirror) by the surface,
+    /// only the objects are reflected since the environment is
+    /// already part of the plate
+    reflectivity: f64,
+}
+
+impl Default for ShadowCatcher {
+    fn default() -> Self {
+        Self::new(glm::vec3(0.8, 0.8, 0.8), 0.0)
+    }
+}
+
+impl ShadowCatcher {
+    pub fn new(color: glm::DVec3, reflectivity: f64) -> Self {
+        Self {
+            color: ColorPicker::Color(color),
+            reflectivity,
+        }
+    }
+}
+
+#[typetag::serde]
+impl BSDF for ShadowCatcher {
+    fn sample(
+        &self,
+        _wo: &glm::DVec3,
+        _mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        if sampling_types.contains(SamplingTypes::Diffuse) {
+            let normal = intersect_info.get_normal().as_ref().unwrap();
+            let wi = utils::wi_diffuse(normal);
+            Some(SampleData::new_with_pdf(
+                wi,
+                SamplingTypes::Diffuse,
+                utils::pdf_diffuse(normal, &wi),
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn eval(
+        &self,
+        _wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        self.color.get_color_at(intersect_info, texture_list)
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        let pdf = utils::pdf_diffuse(intersect_info.get_normal().as_ref().unwrap(), wi);
+        Some((
+            self.color.get_color_at(intersect_info, texture_list) * pdf,
+            pdf,
+        ))
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::from(SamplingTypes::Diffuse)
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Shadow Catcher"
+    }
+
+    fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3> {
+        Some(self.color.get_color(&glm::zero(), texture_list))
+    }
+
+    fn set_base_color(&mut self, color: ColorPicker) {
+        self.color = color;
+    }
+
+    fn get_compositing_type(&self) -> Option<CompositingType> {
+        Some(CompositingType::ShadowCatcher {
+            reflectivity: self.reflectivity,
+        })
+    }
+}
+
+impl DrawUI for ShadowCatcher {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        ui.horizontal(|ui| {
+            ui.label("Base Color");
+            self.color.draw_ui_mut(
+                ui,
+                &ColorPickerUiData::new(
+                    extra_data.get_texture_list().clone(),
+                    extra_data.get_shader_egui_id().with("Base Color"),
+                ),
+            );
+        });
+        ui.add(egui::Slider::new(&mut self.reflectivity, 0.0..=1.0).text("Reflectivity"));
+    }
+}
@@ -0,0 +1,187 @@
+use enumflags2::BitFlags;
+use serde::{Deserialize, Serialize};
+
+use super::super::bsdf::{SampleData, SamplingTypes, BSDF};
+use super::super::intersectable::IntersectInfo;
+use super::utils::{self, ColorPicker, ColorPickerUiData};
+use super::BSDFUiData;
+use crate::egui;
+use crate::glm;
+use crate::path_trace::medium::Mediums;
+use crate::path_trace::texture_list::TextureList;
+use crate::ui::DrawUI;
+
+/// Diffuse transmission, scatters light into the hemisphere behind
+/// the surface like [`super::lambert::Lambert`] does in front of
+/// it. The object is treated as a thin sheet (leaves, paper,
+/// lampshades), the mediums are not changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Translucent {
+    color: ColorPicker,
+}
+
+impl Default for Translucent {
+    fn default() -> Self {
+        Self::new(glm::vec3(1.0, 1.0, 1.0))
+    }
+}
+
+impl Translucent {
+    pub fn new(color: glm::DVec3) -> Self {
+        Self {
+            color: ColorPicker::Color(color),
+        }
+    }
+}
+
+#[typetag::serde]
+impl BSDF for Translucent {
+    fn sample(
+        &self,
+        _wo: &glm::DVec3,
+        _mediums: &mut Mediums,
+        intersect_info: &IntersectInfo,
+        _texture_list: &TextureList,
+        sampling_types: BitFlags<SamplingTypes>,
+    ) -> Option<SampleData> {
+        // sampled as `Diffuse` not `Transmission` even though the
+        // light passes through the surface, see
+        // `SamplingTypes::Transmission`
+        if sampling_types.contains(SamplingTypes::Diffuse) {
+            // the normal faces `wo`, sample about the opposite normal
+            let normal = -intersect_info.get_normal().unwrap();
+            let wi = utils::wi_diffuse(&normal);
+            Some(SampleData::new_with_pdf(
+                wi,
+                SamplingTypes::Diffuse,
+                utils::pdf_diffuse(&normal, &wi),
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn eval(
+        &self,
+        _wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        self.color.get_color_at(intersect_info, texture_list)
+    }
+
+    fn eval_with_pdf(
+        &self,
+        wi: &glm::DVec3,
+        _wo: &glm::DVec3,
+        _mediums: &Mediums,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> Option<(glm::DVec3, f64)> {
+        // same as `Lambert::eval_with_pdf()` about the opposite
+        // normal, light in front of the surface gives a pdf of 0.0
+        let pdf = utils::pdf_diffuse(&-intersect_info.get_normal().unwrap(), wi);
+        Some((
+            self.color.get_color_at(intersect_info, texture_list) * pdf,
+            pdf,
+        ))
+    }
+
+    fn get_evaluated_sampling_types(&self) -> BitFlags<SamplingTypes> {
+        BitFlags::from(SamplingTypes::Diffuse)
+    }
+
+    fn get_bsdf_name(&self) -> &str {
+        "Translucent"
+    }
+
+    fn get_base_color(&self, texture_list: &TextureList) -> Option<glm::DVec3> {
+        Some(self.color.get_color(&glm::zero(), texture_list))
+    }
+
+    fn set_base_color(&mut self, color: ColorPicker) {
+        self.color = color;
+    }
+}
+
+impl DrawUI for Translucent {
+    type ExtraData = BSDFUiData;
+
+    fn draw_ui(&self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        ui.label(format!("BSDF: {}", self.get_bsdf_name()));
+    }
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        ui.horizontal(|ui| {
+            ui.label("Base Color");
+            self.color.draw_ui_mut(
+                ui,
+                &ColorPickerUiData::new(
+                    extra_data.get_texture_list().clone(),
+                    extra_data.get_shader_egui_id().with("Base Color"),
+                ),
+            );
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::ray::Ray;
+
+    #[test]
+    fn translucent_light_in_front_test_01() {
+        let translucent = Translucent::new(glm::vec3(0.8, 0.5, 0.2));
+        let texture_list = TextureList::new();
+        let mediums = Mediums::with_air();
+        let ray = Ray::new(glm::zero(), glm::vec3(0.3, 0.0, -1.0).normalize());
+        let mut intersect_info = IntersectInfo::new(0.0, glm::zero(), glm::zero());
+        intersect_info.set_normal(&ray, &glm::vec3(0.0, 0.0, 1.0));
+        let wo = -ray.get_direction();
+
+        // light arriving from in front of the surface, `wi` points
+        // towards the hitpoint
+        [
+            glm::vec3(0.0, 0.0, 1.0),
+            glm::vec3(0.5, 0.2, 0.8),
+            glm::vec3(-0.9, 0.1, 0.05),
+        ]
+        .iter()
+        .for_each(|light| {
+            let wi = -light.normalize();
+            let (color, pdf) = translucent
+                .eval_with_pdf(&wi, &wo, &mediums, &intersect_info, &texture_list)
+                .unwrap();
+            assert_eq!(pdf, 0.0);
+            assert_eq!(color, glm::zero());
+        });
+
+        // light arriving from behind the surface
+        let light = glm::vec3(0.36, 0.48, -0.8);
+        let (color, pdf) = translucent
+            .eval_with_pdf(&-light, &wo, &mediums, &intersect_info, &texture_list)
+            .unwrap();
+        assert!((pdf - 0.8 * std::f64::consts::FRAC_1_PI).abs() < 1e-12);
+        assert!(glm::distance(&(color / pdf), &glm::vec3(0.8, 0.5, 0.2)) < 1e-12);
+
+        // sampled directions are always behind the surface
+        (0..1000).for_each(|_| {
+            let sample_data = translucent
+                .sample(
+                    &wo,
+                    &mut Mediums::with_air(),
+                    &intersect_info,
+                    &texture_list,
+                    BitFlags::all(),
+                )
+                .unwrap();
+            assert!(glm::dot(sample_data.get_wi(), &glm::vec3(0.0, 0.0, 1.0)) >= 0.0);
+            assert_eq!(sample_data.get_sampling_type(), SamplingTypes::Diffuse);
+            assert!(translucent
+                .get_evaluated_sampling_types()
+                .contains(sample_data.get_sampling_type()));
+        });
+    }
+}
@@ -3,14 +3,87 @@ use std::sync::{Arc, RwLock};
 
 use serde::{Deserialize, Serialize};
 
+use crate::path_trace::intersectable::IntersectInfo;
+use crate::path_trace::medium::{Medium, Mediums};
+use crate::path_trace::shader_list::ShaderID;
 use crate::path_trace::texture_list::{TextureID, TextureList};
+use crate::texture::TextureSampler;
 use crate::ui::DrawUI;
-use crate::{egui, glm, math, ui};
+use crate::{egui, glm, math, ui, util};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ColorPicker {
     Color(glm::DVec3),
-    Texture(Option<TextureID>),
+    Texture(TextureSlot),
+}
+
+/// A texture along with the sampler used to sample it, see
+/// [`TextureSampler`].
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "TextureSlotIO")]
+pub struct TextureSlot {
+    texture_id: Option<TextureID>,
+    sampler: TextureSampler,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum TextureSlotIO {
+    // latest version must be tried first, untagged enums are tried
+    // in order
+    V1 {
+        texture_id: Option<TextureID>,
+        #[serde(default)]
+        sampler: TextureSampler,
+    },
+    V0(Option<TextureID>),
+}
+
+impl From<TextureSlotIO> for TextureSlot {
+    fn from(io: TextureSlotIO) -> Self {
+        match io {
+            TextureSlotIO::V0(texture_id) => Self::new(texture_id, TextureSampler::default()),
+            TextureSlotIO::V1 {
+                texture_id,
+                sampler,
+            } => Self::new(texture_id, sampler),
+        }
+    }
+}
+
+impl TextureSlot {
+    pub fn new(texture_id: Option<TextureID>, sampler: TextureSampler) -> Self {
+        Self {
+            texture_id,
+            sampler,
+        }
+    }
+
+    /// Create a texture slot for the given texture with the default
+    /// sampler.
+    pub fn with_texture(texture_id: TextureID) -> Self {
+        Self::new(Some(texture_id), TextureSampler::default())
+    }
+
+    /// Get texture slot's texture id.
+    pub fn get_texture_id(&self) -> Option<TextureID> {
+        self.texture_id
+    }
+
+    /// Set texture slot's texture id.
+    pub fn set_texture_id(&mut self, texture_id: Option<TextureID>) {
+        self.texture_id = texture_id;
+    }
+
+    /// Get a reference to the texture slot's sampler.
+    pub fn get_sampler(&self) -> &TextureSampler {
+        &self.sampler
+    }
+
+    /// Get a mutable reference to the texture slot's sampler.
+    pub fn get_sampler_mut(&mut self) -> &mut TextureSampler {
+        &mut self.sampler
+    }
 }
 
 impl Default for ColorPicker {
@@ -36,16 +109,31 @@ impl ColorPicker {
         &self,
         uv: &glm::DVec2,
         texture_list: &TextureList,
+    ) -> Option<glm::DVec3> {
+        self.get_color_with_footprint_checked(uv, None, texture_list)
+    }
+
+    /// Get color value from [`ColorPicker`] with the texture filtered
+    /// over the given footprint (du/dx and dv/dx, du/dy and dv/dy),
+    /// see [`TextureList::sample_texture()`]. Returns `None` if
+    /// texture id is None or texture is not available in the texture
+    /// list.
+    pub fn get_color_with_footprint_checked(
+        &self,
+        uv: &glm::DVec2,
+        footprint: Option<&(glm::DVec2, glm::DVec2)>,
+        texture_list: &TextureList,
     ) -> Option<glm::DVec3> {
         match self {
             ColorPicker::Color(color) => Some(*color),
-            ColorPicker::Texture(texture_id) => {
-                if let Some(texture_id) = texture_id {
-                    let pixel = texture_list.get_texture(*texture_id)?.get_pixel_uv(uv);
-                    Some(glm::vec3(pixel[0].into(), pixel[1].into(), pixel[2].into()))
-                } else {
-                    None
-                }
+            ColorPicker::Texture(texture_slot) => {
+                let pixel = texture_list.sample_texture(
+                    texture_slot.get_texture_id()?,
+                    uv,
+                    footprint,
+                    texture_slot.get_sampler(),
+                )?;
+                Some(glm::vec4_to_vec3(&pixel))
             }
         }
     }
@@ -62,6 +150,23 @@ impl ColorPicker {
         self.get_color_checked(uv, texture_list)
             .unwrap_or_else(|| glm::vec3(1.0, 0.0, 1.0))
     }
+
+    /// Get color value from [`ColorPicker`] at the point of
+    /// intersection, textures are filtered over the footprint of the
+    /// ray (see [`IntersectInfo::get_uv_footprint()`]). Return (1.0,
+    /// 0.0, 1.0) if unable to fetch the color.
+    pub fn get_color_at(
+        &self,
+        intersect_info: &IntersectInfo,
+        texture_list: &TextureList,
+    ) -> glm::DVec3 {
+        self.get_color_with_footprint_checked(
+            intersect_info.get_uv().as_ref().unwrap(),
+            intersect_info.get_uv_footprint(),
+            texture_list,
+        )
+        .unwrap_or_else(|| glm::vec3(1.0, 0.0, 1.0))
+    }
 }
 
 pub struct ColorPickerUiData {
@@ -88,68 +193,247 @@ impl DrawUI for ColorPicker {
             .selected_text(format!("{}", self))
             .show_ui(ui, |ui| {
                 ui.selectable_value(self, ColorPicker::Color(glm::vec3(1.0, 1.0, 1.0)), "Color");
-                ui.selectable_value(self, ColorPicker::Texture(None), "Texture");
+                ui.selectable_value(
+                    self,
+                    ColorPicker::Texture(TextureSlot::default()),
+                    "Texture",
+                );
             });
 
         match self {
             ColorPicker::Color(color) => ui::color_edit_button_dvec3(ui, "", color),
-            ColorPicker::Texture(selected_texture_id) => {
-                ui.vertical(|ui| {
-                    if let Ok(mut texture_list) = extra_data.texture_list.try_write() {
-                        if let Some(texture) = selected_texture_id
-                            .and_then(|texture_id| texture_list.get_texture_mut(texture_id))
-                        {
-                            ui.label("Selected Texture:");
-                            ui.image(
-                                egui::TextureId::User(texture.get_gl_tex().into()),
-                                &[
-                                    150.0,
-                                    150.0 * texture.get_height() as f32
-                                        / texture.get_width() as f32,
-                                ],
-                            );
-                        } else {
-                            ui.label("No Texture Selected");
-                        }
-                        egui::CollapsingHeader::new("Select Texture")
-                            .id_source(extra_data.color_picker_id.with("Select Texture"))
-                            .show(ui, |ui| {
-                                texture_list.get_textures_mut().for_each(
-                                    |(texture_id, texture)| {
-                                        ui.horizontal(|ui| {
-                                            if ui.button(".").clicked() {
-                                                *selected_texture_id = Some(*texture_id);
-                                            }
-                                            ui.image(
-                                                egui::TextureId::User(texture.get_gl_tex().into()),
-                                                &[
-                                                    100.0,
-                                                    100.0 * texture.get_height() as f32
-                                                        / texture.get_width() as f32,
-                                                ],
-                                            );
-                                        });
-                                    },
-                                );
-                            });
-                    } else {
-                        ui.label(
-                            "Textures not available right now, currently accessed by something",
-                        );
-                    }
-                });
+            ColorPicker::Texture(texture_slot) => texture_slot.draw_ui_mut(
+                ui,
+                &TextureSlotUiData::new(
+                    extra_data.texture_list.clone(),
+                    extra_data.color_picker_id,
+                ),
+            ),
+        }
+    }
+}
+
+/// A scalar value, either a constant or read from a texture, the
+/// luminance of the texture is used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ValuePicker {
+    Value(f64),
+    Texture(TextureSlot),
+}
+
+impl Display for ValuePicker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValuePicker::Value(_) => write!(f, "Value"),
+            ValuePicker::Texture(_) => write!(f, "Texture"),
+        }
+    }
+}
+
+impl ValuePicker {
+    /// Get the value from [`ValuePicker`] at the point of
+    /// intersection, textures are filtered over the footprint of the
+    /// ray (see [`IntersectInfo::get_uv_footprint()`]). Returns 0.0
+    /// if unable to fetch the value.
+    pub fn get_value_at(&self, intersect_info: &IntersectInfo, texture_list: &TextureList) -> f64 {
+        match self {
+            ValuePicker::Value(value) => *value,
+            ValuePicker::Texture(texture_slot) => texture_slot
+                .get_texture_id()
+                .and_then(|texture_id| {
+                    texture_list.sample_texture(
+                        texture_id,
+                        intersect_info.get_uv().as_ref().unwrap(),
+                        intersect_info.get_uv_footprint(),
+                        texture_slot.get_sampler(),
+                    )
+                })
+                .map_or(0.0, |pixel| util::luminance(&glm::vec4_to_vec3(&pixel))),
+        }
+    }
+}
+
+pub struct ValuePickerUiData {
+    texture_list: Arc<RwLock<TextureList>>,
+    value_picker_id: egui::Id,
+    /// range of the slider of the value, the value can be outside
+    /// the range
+    range: std::ops::RangeInclusive<f64>,
+}
+
+impl ValuePickerUiData {
+    pub fn new(
+        texture_list: Arc<RwLock<TextureList>>,
+        value_picker_id: egui::Id,
+        range: std::ops::RangeInclusive<f64>,
+    ) -> Self {
+        Self {
+            texture_list,
+            value_picker_id,
+            range,
+        }
+    }
+}
+
+impl DrawUI for ValuePicker {
+    type ExtraData = ValuePickerUiData;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        let mut is_value = matches!(self, ValuePicker::Value(_));
+        egui::ComboBox::from_id_source(extra_data.value_picker_id)
+            .selected_text(format!("{}", self))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut is_value, true, "Value");
+                ui.selectable_value(&mut is_value, false, "Texture");
+            });
+        match (is_value, &*self) {
+            (true, ValuePicker::Texture(_)) => {
+                *self = ValuePicker::Value(*extra_data.range.start());
+            }
+            (false, ValuePicker::Value(_)) => {
+                *self = ValuePicker::Texture(TextureSlot::default());
+            }
+            _ => {}
+        }
+
+        match self {
+            ValuePicker::Value(value) => {
+                ui.add(egui::Slider::new(value, extra_data.range.clone()).clamp_to_range(false));
             }
+            ValuePicker::Texture(texture_slot) => texture_slot.draw_ui_mut(
+                ui,
+                &TextureSlotUiData::new(
+                    extra_data.texture_list.clone(),
+                    extra_data.value_picker_id,
+                ),
+            ),
+        }
+    }
+}
+
+pub struct TextureSlotUiData {
+    texture_list: Arc<RwLock<TextureList>>,
+    texture_slot_id: egui::Id,
+}
+
+impl TextureSlotUiData {
+    pub fn new(texture_list: Arc<RwLock<TextureList>>, texture_slot_id: egui::Id) -> Self {
+        Self {
+            texture_list,
+            texture_slot_id,
         }
     }
 }
 
+impl DrawUI for TextureSlot {
+    type ExtraData = TextureSlotUiData;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        ui.vertical(|ui| {
+            self.get_sampler_mut()
+                .draw_ui_mut(ui, &extra_data.texture_slot_id.with("Texture Sampler"));
+
+            let selected_texture_id = &mut self.texture_id;
+            if let Ok(mut texture_list) = extra_data.texture_list.try_write() {
+                if let Some(texture) = selected_texture_id
+                    .and_then(|texture_id| texture_list.get_texture_mut(texture_id))
+                {
+                    ui.label("Selected Texture:");
+                    ui.image(
+                        egui::TextureId::User(texture.get_gl_tex().into()),
+                        &[
+                            150.0,
+                            150.0 * texture.get_height() as f32 / texture.get_width() as f32,
+                        ],
+                    );
+                } else {
+                    ui.label("No Texture Selected");
+                }
+                egui::CollapsingHeader::new("Select Texture")
+                    .id_source(extra_data.texture_slot_id.with("Select Texture"))
+                    .show(ui, |ui| {
+                        texture_list
+                            .get_textures_mut()
+                            .for_each(|(texture_id, texture)| {
+                                ui.horizontal(|ui| {
+                                    if ui.button(".").clicked() {
+                                        *selected_texture_id = Some(*texture_id);
+                                    }
+                                    ui.image(
+                                        egui::TextureId::User(texture.get_gl_tex().into()),
+                                        &[
+                                            100.0,
+                                            100.0 * texture.get_height() as f32
+                                                / texture.get_width() as f32,
+                                        ],
+                                    );
+                                });
+                            });
+                    });
+            } else {
+                ui.label("Textures not available right now, currently accessed by something");
+            }
+        });
+    }
+}
+
+/// Draw the UI to choose the shader referred to by a `BSDF` (see
+/// [`super::mix::Mix`]) from the given choices, see
+/// [`super::BSDFUiData::get_shader_choices()`]. A reference that is
+/// not one of the choices (deleted or forming a cycle) is shown as
+/// invalid.
+pub fn shader_reference_ui(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    shader_id: &mut Option<ShaderID>,
+    shader_choices: &[(ShaderID, String)],
+) {
+    let selected_text = match shader_id {
+        Some(shader_id) => shader_choices
+            .iter()
+            .find(|(id, _)| id == shader_id)
+            .map_or_else(|| "Invalid".to_string(), |(_, name)| name.clone()),
+        None => "None".to_string(),
+    };
+    egui::ComboBox::from_id_source(id)
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(shader_id, None, "None");
+            shader_choices.iter().for_each(|(id, name)| {
+                ui.selectable_value(shader_id, Some(*id), name);
+            });
+        });
+}
+
 /// Get `wi` when sampling pure diffuse
+///
+/// The directions are cosine weighted about the normal, see
+/// [`pdf_diffuse()`].
 pub fn wi_diffuse(normal: &glm::DVec3) -> glm::DVec3 {
-    // TODO: make this random in hemisphere instead of using a
-    // sphere for better performance
+    // offsetting the normal by a point on the unit sphere gives
+    // cosine weighted directions in the hemisphere
+    let direction = normal + math::random_unit_vector();
+    if direction.norm_squared() < 1e-12 {
+        return -normal;
+    }
 
     //need to return `wi` which should point towards the hitpoint
-    -(normal + math::random_in_unit_sphere())
+    -direction.normalize()
+}
+
+/// Get the probability density (with respect to solid angle) of
+/// [`wi_diffuse()`] returning `wi`.
+pub fn pdf_diffuse(normal: &glm::DVec3, wi: &glm::DVec3) -> f64 {
+    let cos_theta = -glm::dot(normal, &wi.normalize());
+    if cos_theta <= 0.0 {
+        0.0
+    } else {
+        cos_theta * std::f64::consts::FRAC_1_PI
+    }
 }
 
 /// TODO: documentation
@@ -163,3 +447,30 @@ pub fn fresnel(normal: &glm::DVec3, view: &glm::DVec3, n1: f64, n2: f64) -> f64
 
     r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
 }
+
+/// Get the ratio of the ior of the side that the light is
+/// transmitted to and the ior of the side of `wo` for a dielectric
+/// with the given ior, based on the mediums the ray is currently in.
+///
+/// Returns `None` if the ray is exiting the dielectric but there is
+/// no medium outside it. This can happen when the ray had more exits
+/// than entries because of non manifold meshes.
+pub fn dielectric_eta(ior: f64, mediums: &Mediums, entering: bool) -> Option<f64> {
+    if entering {
+        Some(ior / mediums.get_lastest_medium()?.get_ior())
+    } else {
+        // the lastest medium is the dielectric itself, the second
+        // lastest medium is the one outside it
+        Some(mediums.get_second_lastest_medium()?.get_ior() / ior)
+    }
+}
+
+/// Update the mediums after the ray is transmitted through (entering
+/// or exiting) a dielectric with the given ior.
+pub fn transmit_through_dielectric(ior: f64, mediums: &mut Mediums, entering: bool) {
+    if entering {
+        mediums.add_medium(Medium::new(ior));
+    } else {
+        mediums.remove_medium();
+    }
+}
@@ -1,7 +1,327 @@
+use enumflags2::BitFlags;
+use rayon::prelude::*;
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
 
-use crate::{egui, image::Image, transform::Transform, ui::DrawUI, UiData};
+use crate::{
+    camera::PathTraceCamera,
+    egui, glm,
+    image::Image,
+    math::Distribution2D,
+    texture::{TextureFilter, TextureSampler, TextureWrap},
+    transform::Transform,
+    ui,
+    ui::DrawUI,
+    util, UiData,
+};
+
+use super::{
+    bsdf::SamplingTypes,
+    direction_to_equirectangular, equirectangular_to_direction,
+    ray::Ray,
+    sky::{Sky, SunDisc},
+};
+
+/// Source of the light of the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnvironmentSource {
+    /// The hdr image of the environment.
+    Image,
+    /// Procedural physical sky and sun, see [`Sky`].
+    Sky,
+}
+
+impl Default for EnvironmentSource {
+    fn default() -> Self {
+        Self::Image
+    }
+}
+
+impl EnvironmentSource {
+    pub fn all() -> impl Iterator<Item = Self> {
+        [Self::Image, Self::Sky].iter().copied()
+    }
+}
+
+impl std::fmt::Display for EnvironmentSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvironmentSource::Image => write!(f, "Image"),
+            EnvironmentSource::Sky => write!(f, "Sky"),
+        }
+    }
+}
+
+/// Type of the ray that reaches the environment, the environment
+/// can be seen differently by each type of ray, see
+/// [`EnvironmentVisibility`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvironmentRayType {
+    /// Ray starting from the camera.
+    Camera,
+    /// Ray generated by reflection or refraction.
+    Glossy,
+    /// Ray generated by diffuse scattering.
+    Diffuse,
+}
+
+impl EnvironmentRayType {
+    /// Get the type of the ray generated by the given type of
+    /// sampling of the `BSDF`.
+    pub fn from_sampling_type(sampling_type: SamplingTypes) -> Self {
+        match sampling_type {
+            SamplingTypes::Diffuse => Self::Diffuse,
+            SamplingTypes::Glossy | SamplingTypes::Reflection | SamplingTypes::Transmission => {
+                Self::Glossy
+            }
+        }
+    }
+}
+
+/// Source used instead of the environment for a type of ray, see
+/// [`EnvironmentVisibility`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EnvironmentOverride {
+    /// Constant colour.
+    Color(glm::DVec3),
+    /// Gradient from the `bottom` colour (looking down, -Y) to the
+    /// `top` colour (looking up, +Y).
+    Gradient { top: glm::DVec3, bottom: glm::DVec3 },
+    /// Backplate image, it fills the view of the camera for camera
+    /// rays. For the other rays, it is mapped like the hdr of the
+    /// environment.
+    Backplate(Image),
+    /// Nothing is seen, no light is received from the environment.
+    /// For camera rays, the background of the rendered image becomes
+    /// transparent (also when seen through transmission).
+    Transparent,
+}
+
+impl EnvironmentOverride {
+    pub fn all_names() -> impl Iterator<Item = &'static str> {
+        ["Color", "Gradient", "Backplate", "Transparent"]
+            .iter()
+            .copied()
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            EnvironmentOverride::Color(_) => "Color",
+            EnvironmentOverride::Gradient { .. } => "Gradient",
+            EnvironmentOverride::Backplate(_) => "Backplate",
+            EnvironmentOverride::Transparent => "Transparent",
+        }
+    }
+
+    /// Create the override with default values given its name, see
+    /// [`Self::all_names()`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Color" => Some(Self::Color(glm::vec3(0.8, 0.8, 0.8))),
+            "Gradient" => Some(Self::Gradient {
+                top: glm::vec3(0.9, 0.9, 0.9),
+                bottom: glm::vec3(0.3, 0.3, 0.3),
+            }),
+            "Backplate" => Some(Self::Backplate(Image::new(1, 1))),
+            "Transparent" => Some(Self::Transparent),
+            _ => None,
+        }
+    }
+
+    /// Load the backplate image from disk given the path to the
+    /// image, the override becomes a backplate.
+    pub fn load_backplate<P>(&mut self, path: P)
+    where
+        P: AsRef<std::path::Path>,
+    {
+        *self = Self::Backplate(Image::load_from_disk(path).unwrap());
+    }
+
+    /// Load the backplate image from disk with file dialog to choose
+    /// the image.
+    pub fn load_backplate_file_dialog(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("png", &["png"])
+            .add_filter("jpg", &["jpg", "jpeg"])
+            .add_filter("tiff", &["tiff", "tif"])
+            .add_filter("OpenEXR", &["exr"])
+            .add_filter("HDR", &["hdr"])
+            .add_filter("PFM", &["pfm"])
+            .add_filter("Any", &["*"])
+            .set_directory(".")
+            .pick_file()
+        {
+            self.load_backplate(path);
+        }
+    }
+
+    /// Get the colour/intensity of light seen by the given ray.
+    ///
+    /// `hdr_sampler` and `hdr_matrix` are the sampler and the matrix
+    /// of the environment transform used to map the backplate for
+    /// rays that are not camera rays.
+    pub fn get_color(
+        &self,
+        ray: &Ray,
+        ray_type: EnvironmentRayType,
+        camera: &PathTraceCamera,
+        hdr_sampler: &TextureSampler,
+        hdr_matrix: &glm::DMat4,
+    ) -> glm::DVec3 {
+        match self {
+            EnvironmentOverride::Color(color) => *color,
+            EnvironmentOverride::Gradient { top, bottom } => {
+                let t = 0.5 * (ray.get_direction().normalize()[1] + 1.0);
+                glm::lerp(bottom, top, t)
+            }
+            EnvironmentOverride::Backplate(image) => match ray_type {
+                EnvironmentRayType::Camera => camera
+                    .get_sensor_uv(ray)
+                    .filter(|uv| uv[0].abs() <= 1.0 && uv[1].abs() <= 1.0)
+                    .map_or(glm::zero(), |uv| {
+                        // sensor uvs are from -1.0 to 1.0, the
+                        // backplate must not wrap around the edges
+                        // of the view
+                        image.sample_uv(
+                            &((uv + glm::vec2(1.0, 1.0)) * 0.5),
+                            &TextureSampler::new(
+                                hdr_sampler.get_filter(),
+                                TextureWrap::Clamp,
+                                TextureWrap::Clamp,
+                            ),
+                        )
+                    }),
+                EnvironmentRayType::Glossy | EnvironmentRayType::Diffuse => {
+                    hdr_color(image, hdr_sampler, hdr_matrix, ray.get_direction())
+                }
+            },
+            EnvironmentOverride::Transparent => glm::zero(),
+        }
+    }
+}
+
+impl DrawUI for EnvironmentOverride {
+    type ExtraData = ();
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        match self {
+            EnvironmentOverride::Color(color) => {
+                ui::color_edit_button_dvec3(ui, "Color", color);
+            }
+            EnvironmentOverride::Gradient { top, bottom } => {
+                ui::color_edit_button_dvec3(ui, "Top", top);
+                ui::color_edit_button_dvec3(ui, "Bottom", bottom);
+            }
+            EnvironmentOverride::Backplate(image) => {
+                ui.label(format!("Backplate: {}x{}", image.width(), image.height()));
+                if ui.button("Load Backplate Image").clicked() {
+                    self.load_backplate_file_dialog();
+                }
+            }
+            EnvironmentOverride::Transparent => {}
+        }
+    }
+}
+
+/// Visibility of the environment for the different types of rays,
+/// each type of ray sees the override instead of the environment if
+/// it is set. For example, the environment can light the scene while
+/// the camera sees a plain colour.
+///
+/// The environment is sampled directly (next event estimation) at a
+/// point only if the rays of all the lobes of the `BSDF` that are
+/// weighted against it see the environment, the overrides have no
+/// importance sampling. See
+/// [`EnvironmentShadingData::should_sample()`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvironmentVisibility {
+    camera: Option<EnvironmentOverride>,
+    glossy: Option<EnvironmentOverride>,
+    diffuse: Option<EnvironmentOverride>,
+}
+
+impl EnvironmentVisibility {
+    pub fn new(
+        camera: Option<EnvironmentOverride>,
+        glossy: Option<EnvironmentOverride>,
+        diffuse: Option<EnvironmentOverride>,
+    ) -> Self {
+        Self {
+            camera,
+            glossy,
+            diffuse,
+        }
+    }
+
+    /// Get a reference to the override for the given type of ray.
+    pub fn get_override(&self, ray_type: EnvironmentRayType) -> Option<&EnvironmentOverride> {
+        match ray_type {
+            EnvironmentRayType::Camera => self.camera.as_ref(),
+            EnvironmentRayType::Glossy => self.glossy.as_ref(),
+            EnvironmentRayType::Diffuse => self.diffuse.as_ref(),
+        }
+    }
+
+    /// Set the override for the given type of ray.
+    pub fn set_override(
+        &mut self,
+        ray_type: EnvironmentRayType,
+        environment_override: Option<EnvironmentOverride>,
+    ) {
+        match ray_type {
+            EnvironmentRayType::Camera => self.camera = environment_override,
+            EnvironmentRayType::Glossy => self.glossy = environment_override,
+            EnvironmentRayType::Diffuse => self.diffuse = environment_override,
+        }
+    }
+}
+
+impl DrawUI for EnvironmentVisibility {
+    type ExtraData = ();
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        [
+            ("Camera", &mut self.camera),
+            ("Reflection/Refraction", &mut self.glossy),
+            ("Diffuse", &mut self.diffuse),
+        ]
+        .iter_mut()
+        .for_each(|(label, environment_override)| {
+            let selected_name = environment_override
+                .as_ref()
+                .map_or("Environment", |environment_override| {
+                    environment_override.get_name()
+                });
+            ui.horizontal(|ui| {
+                ui.label(*label);
+                egui::ComboBox::from_id_source(format!("Environment Visibility {}", label))
+                    .selected_text(selected_name)
+                    .show_ui(ui, |ui| {
+                        if ui
+                            .selectable_label(environment_override.is_none(), "Environment")
+                            .clicked()
+                        {
+                            **environment_override = None;
+                        }
+                        EnvironmentOverride::all_names().for_each(|name| {
+                            if ui.selectable_label(selected_name == name, name).clicked()
+                                && selected_name != name
+                            {
+                                **environment_override = EnvironmentOverride::from_name(name);
+                            }
+                        });
+                    });
+            });
+            if let Some(environment_override) = environment_override {
+                environment_override.draw_ui_mut(ui, &());
+            }
+        });
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Environment {
@@ -10,12 +330,42 @@ pub struct Environment {
 
     #[serde(default = "default_transform")]
     transform: Transform,
+    #[serde(default = "default_sampler")]
+    sampler: TextureSampler,
+
+    #[serde(default)]
+    source: EnvironmentSource,
+    #[serde(default)]
+    sky: Sky,
+    /// The sky baked into an image along with the sky it was baked
+    /// from, see [`Environment::bake_sky_if_needed()`].
+    #[serde(skip)]
+    sky_image: Option<(Sky, Image)>,
+
+    #[serde(default)]
+    visibility: EnvironmentVisibility,
+
+    /// Data to importance sample the environment, must be rebuilt
+    /// when the hdr or the transform changes, see
+    /// [`Environment::build_sampling_data_if_needed()`].
+    #[serde(skip)]
+    sampling_data: Option<EnvironmentSamplingData>,
 }
 
 fn default_transform() -> Transform {
     Transform::default()
 }
 
+/// The hdr repeats along the azimuth (U) but must not wrap around the
+/// poles (V).
+fn default_sampler() -> TextureSampler {
+    TextureSampler::new(
+        TextureFilter::Bilinear,
+        TextureWrap::Repeat,
+        TextureWrap::Clamp,
+    )
+}
+
 impl Default for Environment {
     fn default() -> Self {
         Self::new(Image::new(4, 4), 1.0, Transform::default())
@@ -28,27 +378,36 @@ impl Environment {
             hdr,
             strength,
             transform,
+            sampler: default_sampler(),
+            source: EnvironmentSource::default(),
+            sky: Sky::default(),
+            sky_image: None,
+            visibility: EnvironmentVisibility::default(),
+            sampling_data: None,
         }
     }
 
-    /// Load hdr from disk given the path to the hdr
+    /// Create an environment lit by the given sky.
+    pub fn new_sky(sky: Sky, strength: f64, transform: Transform) -> Self {
+        let mut environment = Self::new(Image::new(4, 4), strength, transform);
+        environment.set_source(EnvironmentSource::Sky);
+        environment.set_sky(sky);
+        environment
+    }
+
+    /// Load hdr from disk given the path to the hdr, see
+    /// [`Image::load_from_disk()`] for the supported formats.
     pub fn load_hdr<P>(&mut self, path: P)
     where
         P: AsRef<std::path::Path>,
     {
-        let hdr = image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(
-            std::fs::File::open(path).unwrap(),
-        ))
-        .unwrap();
-        let width = hdr.metadata().width as _;
-        let height = hdr.metadata().height as _;
-        self.hdr = Image::from_vec_rgb_f32(&hdr.read_image_hdr().unwrap(), width, height);
+        self.hdr = Image::load_from_disk(path).unwrap();
     }
 
     /// Load hdr from disk with file dialog to choose the hdr image
     pub fn load_hdr_file_dialog(&mut self) {
         if let Some(path) = FileDialog::new()
-            .add_filter("HDR", &["hdr"])
+            .add_filter("HDR", &["hdr", "exr", "pfm"])
             .add_filter("Any", &["*"])
             .set_directory(".")
             .pick_file()
@@ -86,6 +445,284 @@ impl Environment {
     pub fn set_transform(&mut self, transform: Transform) {
         self.transform = transform;
     }
+
+    /// Get a reference to the environment's sampler.
+    pub fn get_sampler(&self) -> &TextureSampler {
+        &self.sampler
+    }
+
+    /// Set environment's sampler.
+    pub fn set_sampler(&mut self, sampler: TextureSampler) {
+        self.sampler = sampler;
+    }
+
+    /// Get environment's source.
+    pub fn get_source(&self) -> EnvironmentSource {
+        self.source
+    }
+
+    /// Set environment's source.
+    pub fn set_source(&mut self, source: EnvironmentSource) {
+        self.source = source;
+        self.bake_sky_if_needed();
+    }
+
+    /// Get a reference to the environment's sky.
+    pub fn get_sky(&self) -> &Sky {
+        &self.sky
+    }
+
+    /// Set environment's sky.
+    pub fn set_sky(&mut self, sky: Sky) {
+        self.sky = sky;
+        self.bake_sky_if_needed();
+    }
+
+    /// Get a reference to the environment's visibility.
+    pub fn get_visibility(&self) -> &EnvironmentVisibility {
+        &self.visibility
+    }
+
+    /// Get a mutable reference to the environment's visibility.
+    pub fn get_visibility_mut(&mut self) -> &mut EnvironmentVisibility {
+        &mut self.visibility
+    }
+
+    /// Set environment's visibility.
+    pub fn set_visibility(&mut self, visibility: EnvironmentVisibility) {
+        self.visibility = visibility;
+    }
+
+    /// Get the image that is used for the environment based on the
+    /// source of the environment.
+    ///
+    /// If the source is the sky but it has not been baked yet (see
+    /// [`Environment::bake_sky_if_needed()`]), the hdr is returned.
+    pub fn get_image(&self) -> &Image {
+        match self.source {
+            EnvironmentSource::Image => &self.hdr,
+            EnvironmentSource::Sky => self
+                .sky_image
+                .as_ref()
+                .map_or(&self.hdr, |(_, sky_image)| sky_image),
+        }
+    }
+
+    /// Get the sun disc of the environment, only available if the
+    /// source of the environment is the sky.
+    pub fn get_sun_disc(&self) -> Option<SunDisc> {
+        match self.source {
+            EnvironmentSource::Image => None,
+            EnvironmentSource::Sky => SunDisc::new(&self.sky, &self.transform.get_matrix()),
+        }
+    }
+
+    /// Bake the sky into an image if the source of the environment
+    /// is the sky and the sky has changed since it was last baked.
+    pub fn bake_sky_if_needed(&mut self) {
+        if self.source != EnvironmentSource::Sky {
+            return;
+        }
+        if let Some((sky, _)) = &self.sky_image {
+            if *sky == self.sky {
+                return;
+            }
+        }
+
+        let (width, height) = Sky::IMAGE_SIZE;
+        self.sky_image = Some((self.sky.clone(), self.sky.generate_image(width, height)));
+    }
+
+    /// Get a reference to the environment's sampling data, it is
+    /// `None` if it has not been built yet.
+    pub fn get_sampling_data(&self) -> Option<&EnvironmentSamplingData> {
+        self.sampling_data.as_ref()
+    }
+
+    /// Build the data needed to importance sample the environment if
+    /// the image (hdr or sky) or the transform has changed since it
+    /// was last built. The sky is baked first if needed.
+    pub fn build_sampling_data_if_needed(&mut self) {
+        self.bake_sky_if_needed();
+
+        let sun_disc = self.get_sun_disc();
+        if let Some(sampling_data) = &self.sampling_data {
+            if sampling_data.hdr_id == self.get_image().get_id()
+                && sampling_data.sampler == self.sampler
+                && sampling_data.transform == self.transform
+                && sampling_data.sun_disc == sun_disc
+            {
+                return;
+            }
+        }
+
+        self.sampling_data = Some(EnvironmentSamplingData::new(
+            self.get_image(),
+            &self.sampler,
+            &self.transform,
+            sun_disc,
+        ));
+    }
+}
+
+/// Get the colour of the hdr (without strength) in the given
+/// direction after applying the transform (as a matrix).
+fn hdr_color(
+    hdr: &Image,
+    sampler: &TextureSampler,
+    transform: &glm::DMat4,
+    direction: &glm::DVec3,
+) -> glm::DVec3 {
+    let transformed_direction = util::vec3_apply_model_matrix(direction, transform);
+
+    let uv = direction_to_equirectangular(&transformed_direction);
+    hdr.sample_uv(&uv, sampler)
+}
+
+/// Data needed to importance sample the environment.
+///
+/// A 2D distribution of the luminance of the environment over the
+/// directions (in the equirectangular mapping) is stored. The
+/// distribution is built over the world space directions with the
+/// transform of the environment applied when looking up the hdr, so
+/// it matches the shading of the environment exactly.
+///
+/// The sun disc (if available) is too small to be captured by the
+/// distribution, it is sampled separately, chosen based on its power
+/// relative to the rest of the environment.
+#[derive(Debug, Clone)]
+pub struct EnvironmentSamplingData {
+    distribution: Distribution2D,
+    /// id of the hdr the distribution was built from
+    hdr_id: usize,
+    /// sampler of the hdr the distribution was built with
+    sampler: TextureSampler,
+    /// transform the distribution was built with
+    transform: Transform,
+    sun_disc: Option<SunDisc>,
+    /// probability of sampling the sun disc instead of the
+    /// distribution
+    sun_probability: f64,
+}
+
+impl EnvironmentSamplingData {
+    /// Maximum resolution of the distribution, larger hdrs are
+    /// sampled at this resolution.
+    const MAX_RESOLUTION: (usize, usize) = (4096, 2048);
+
+    pub fn new(
+        hdr: &Image,
+        sampler: &TextureSampler,
+        transform: &Transform,
+        sun_disc: Option<SunDisc>,
+    ) -> Self {
+        let width = hdr.width().clamp(1, Self::MAX_RESOLUTION.0);
+        let height = hdr.height().clamp(1, Self::MAX_RESOLUTION.1);
+        let transform_matrix = transform.get_matrix();
+
+        // rows from +Y (theta of 0) to -Y (theta of pi), the
+        // luminance is weighted by sin(theta) since the rows near
+        // the poles cover a smaller solid angle
+        let func: Vec<f64> = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|j| {
+                let theta = std::f64::consts::PI * (j as f64 + 0.5) / height as f64;
+                let sin_theta = theta.sin();
+                let transform_matrix = &transform_matrix;
+                (0..width).map(move |i| {
+                    let u = (i as f64 + 0.5) / width as f64;
+                    let color = hdr_color(
+                        hdr,
+                        sampler,
+                        transform_matrix,
+                        &equirectangular_to_direction(u, theta),
+                    );
+                    util::luminance(&color).max(0.0) * sin_theta
+                })
+            })
+            .collect();
+
+        let distribution = Distribution2D::new(&func, width, height);
+
+        // power of the distribution is the integral of the luminance
+        // over the sphere, the integral over the unit square must be
+        // converted to solid angle
+        let sun_probability = sun_disc.as_ref().map_or(0.0, |sun_disc| {
+            let sun_power = sun_disc.get_power();
+            let distribution_power = distribution.get_func_integral()
+                * 2.0
+                * std::f64::consts::PI
+                * std::f64::consts::PI;
+            if sun_power + distribution_power > 0.0 {
+                sun_power / (sun_power + distribution_power)
+            } else {
+                0.0
+            }
+        });
+
+        Self {
+            distribution,
+            hdr_id: hdr.get_id(),
+            sampler: *sampler,
+            transform: transform.clone(),
+            sun_disc,
+            sun_probability,
+        }
+    }
+
+    /// Sample a direction towards the bright regions of the
+    /// environment. Returns the direction and its probability
+    /// density (with respect to solid angle), `None` if the direction
+    /// cannot be sampled.
+    pub fn sample(&self) -> Option<(glm::DVec3, f64)> {
+        let direction = match &self.sun_disc {
+            Some(sun_disc) if rand::random::<f64>() < self.sun_probability => sun_disc.sample(),
+            _ => {
+                let (uv, pdf) = self
+                    .distribution
+                    .sample_continuous(&glm::vec2(rand::random(), rand::random()));
+                if pdf == 0.0 {
+                    return None;
+                }
+                equirectangular_to_direction(uv[0], uv[1] * std::f64::consts::PI)
+            }
+        };
+
+        let pdf = self.pdf(&direction);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some((direction, pdf))
+    }
+
+    /// Get the probability density (with respect to solid angle) of
+    /// [`Self::sample()`] returning the given direction.
+    pub fn pdf(&self, direction: &glm::DVec3) -> f64 {
+        let sun_pdf = self
+            .sun_disc
+            .as_ref()
+            .map_or(0.0, |sun_disc| sun_disc.pdf(direction));
+
+        self.sun_probability * sun_pdf
+            + (1.0 - self.sun_probability) * self.distribution_pdf(direction)
+    }
+
+    /// Get the probability density (with respect to solid angle) of
+    /// sampling the given direction from the distribution.
+    fn distribution_pdf(&self, direction: &glm::DVec3) -> f64 {
+        let direction = direction.normalize();
+        let theta = direction[1].clamp(-1.0, 1.0).acos();
+        let sin_theta = theta.sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        let u = (direction[2].atan2(direction[0]) + std::f64::consts::PI) / std::f64::consts::TAU;
+
+        self.distribution.pdf(&glm::vec2(
+            u.clamp(0.0, 1.0),
+            (theta / std::f64::consts::PI).clamp(0.0, 1.0),
+        )) / (2.0 * std::f64::consts::PI * std::f64::consts::PI * sin_theta)
+    }
 }
 
 impl DrawUI for Environment {
@@ -100,14 +737,42 @@ impl DrawUI for Environment {
                 .text("Environment Strength"),
         );
 
-        if ui.button("Load Environment Image").clicked() {
-            self.load_hdr_file_dialog();
+        egui::ComboBox::from_id_source("Environment Source")
+            .selected_text(format!("{}", self.source))
+            .show_ui(ui, |ui| {
+                EnvironmentSource::all().for_each(|source| {
+                    ui.selectable_value(&mut self.source, source, format!("{}", source));
+                });
+            });
+
+        match self.source {
+            EnvironmentSource::Image => {
+                if ui.button("Load Environment Image").clicked() {
+                    self.load_hdr_file_dialog();
+                }
+            }
+            EnvironmentSource::Sky => {
+                ui.collapsing("Sky", |ui| {
+                    self.sky.draw_ui_mut(ui, &());
+                });
+            }
         }
 
+        ui.collapsing("Environment Visibility", |ui| {
+            self.visibility.draw_ui_mut(ui, &());
+        });
+
+        ui.collapsing("Environment Filtering", |ui| {
+            self.sampler
+                .draw_ui_mut(ui, &egui::Id::new("Environment Sampler"));
+        });
+
         ui.collapsing("Environment Transform", |ui| {
             self.transform.draw_ui(ui, extra_data);
             self.transform.draw_ui_mut(ui, extra_data);
         });
+
+        self.bake_sky_if_needed();
     }
 }
 
@@ -115,6 +780,10 @@ pub struct EnvironmentShadingData<'a> {
     hdr: &'a Image,
     strength: f64,
     transform: &'a Transform,
+    sampler: &'a TextureSampler,
+    sun_disc: Option<SunDisc>,
+    visibility: &'a EnvironmentVisibility,
+    sampling_data: Option<&'a EnvironmentSamplingData>,
 }
 
 impl<'a> EnvironmentShadingData<'a> {
@@ -124,11 +793,23 @@ impl<'a> EnvironmentShadingData<'a> {
     /// [`Environment`] instead of creating it manually. It is as
     /// simple as `environment.into()`.
     ///
-    pub unsafe fn new(hdr: &'a Image, strength: f64, transform: &'a Transform) -> Self {
+    pub unsafe fn new(
+        hdr: &'a Image,
+        strength: f64,
+        transform: &'a Transform,
+        sampler: &'a TextureSampler,
+        sun_disc: Option<SunDisc>,
+        visibility: &'a EnvironmentVisibility,
+        sampling_data: Option<&'a EnvironmentSamplingData>,
+    ) -> Self {
         Self {
             hdr,
             strength,
             transform,
+            sampler,
+            sun_disc,
+            visibility,
+            sampling_data,
         }
     }
 
@@ -146,10 +827,241 @@ impl<'a> EnvironmentShadingData<'a> {
     pub fn get_transform(&self) -> &'a Transform {
         self.transform
     }
+
+    /// Get environment sampler.
+    pub fn get_sampler(&self) -> &'a TextureSampler {
+        self.sampler
+    }
+
+    /// Get a reference to the environment sun disc.
+    pub fn get_sun_disc(&self) -> Option<&SunDisc> {
+        self.sun_disc.as_ref()
+    }
+
+    /// Get environment visibility.
+    pub fn get_visibility(&self) -> &'a EnvironmentVisibility {
+        self.visibility
+    }
+
+    /// Get environment sampling data.
+    pub fn get_sampling_data(&self) -> Option<&'a EnvironmentSamplingData> {
+        self.sampling_data
+    }
+
+    /// Get the colour/intensity of light from the environment in the
+    /// given direction.
+    pub fn get_color(&self, direction: &glm::DVec3) -> glm::DVec3 {
+        let sun_color = self
+            .sun_disc
+            .as_ref()
+            .map_or(glm::zero(), |sun_disc| sun_disc.get_color(direction));
+        (hdr_color(
+            self.hdr,
+            self.sampler,
+            &self.transform.get_matrix(),
+            direction,
+        ) + sun_color)
+            * self.strength
+    }
+
+    /// Get the colour/intensity of light seen by the given ray (that
+    /// did not hit any object) taking the visibility of the
+    /// environment for the type of the ray into account, see
+    /// [`EnvironmentVisibility`].
+    ///
+    /// Returns `None` if the environment is seen, the light should
+    /// then be computed with [`Self::get_color()`].
+    pub fn get_override_color(
+        &self,
+        ray: &Ray,
+        ray_type: EnvironmentRayType,
+        camera: &PathTraceCamera,
+    ) -> Option<glm::DVec3> {
+        self.visibility
+            .get_override(ray_type)
+            .map(|environment_override| {
+                environment_override.get_color(
+                    ray,
+                    ray_type,
+                    camera,
+                    self.sampler,
+                    &self.transform.get_matrix(),
+                )
+            })
+    }
+
+    /// Check if the background is transparent, it is if the camera
+    /// rays see the [`EnvironmentOverride::Transparent`] override.
+    pub fn is_transparent_background(&self) -> bool {
+        matches!(
+            self.visibility.get_override(EnvironmentRayType::Camera),
+            Some(EnvironmentOverride::Transparent)
+        )
+    }
+
+    /// Check if the environment must be sampled directly (next event
+    /// estimation) for a `BSDF` that evaluates the lobes of the given
+    /// sampling types (see
+    /// [`crate::path_trace::bsdf::BSDF::get_evaluated_sampling_types()`]).
+    ///
+    /// The direct samples are weighted against the directions
+    /// sampled by all these lobes using multiple importance sampling,
+    /// so the rays of each of the lobes must see the environment. If
+    /// any of them sees an override instead, the environment is only
+    /// found by sampling the `BSDF`.
+    pub fn should_sample(&self, sampling_types: BitFlags<SamplingTypes>) -> bool {
+        !sampling_types.is_empty()
+            && sampling_types.iter().all(|sampling_type| {
+                self.visibility
+                    .get_override(EnvironmentRayType::from_sampling_type(sampling_type))
+                    .is_none()
+            })
+    }
+
+    /// Sample a direction towards the bright regions of the
+    /// environment, see [`EnvironmentSamplingData::sample()`].
+    ///
+    /// Returns `None` if the sampling data is not available.
+    pub fn sample(&self) -> Option<(glm::DVec3, f64)> {
+        self.sampling_data?.sample()
+    }
+
+    /// Get the probability density of [`Self::sample()`] returning
+    /// the given direction, it is 0.0 if the sampling data is not
+    /// available.
+    pub fn pdf(&self, direction: &glm::DVec3) -> f64 {
+        self.sampling_data
+            .map_or(0.0, |sampling_data| sampling_data.pdf(direction))
+    }
 }
 
 impl<'a> From<&'a Environment> for EnvironmentShadingData<'a> {
     fn from(env: &'a Environment) -> Self {
-        unsafe { Self::new(env.get_hdr(), env.get_strength(), env.get_transform()) }
+        unsafe {
+            Self::new(
+                env.get_image(),
+                env.get_strength(),
+                env.get_transform(),
+                env.get_sampler(),
+                env.get_sun_disc(),
+                env.get_visibility(),
+                env.get_sampling_data(),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dim hdr with a bright block of pixels.
+    fn get_hdr() -> Image {
+        let (width, height) = (128, 64);
+        let pixels = (0..height)
+            .flat_map(|j| {
+                (0..width).map(move |i| {
+                    if (80..112).contains(&i) && (16..32).contains(&j) {
+                        glm::vec3(10.0, 10.0, 10.0)
+                    } else {
+                        glm::vec3(0.01, 0.01, 0.01)
+                    }
+                })
+            })
+            .collect();
+        Image::from_pixels(width, height, pixels)
+    }
+
+    fn get_sampler() -> TextureSampler {
+        TextureSampler::new(
+            TextureFilter::Nearest,
+            TextureWrap::Repeat,
+            TextureWrap::Clamp,
+        )
+    }
+
+    fn get_transforms() -> [Transform; 3] {
+        [
+            Transform::default(),
+            Transform {
+                rotation: glm::vec3(0.0, 180.0, 0.0),
+                ..Default::default()
+            },
+            Transform {
+                rotation: glm::vec3(60.0, 0.0, 45.0),
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// Check if the hdr is bright in the given direction with the
+    /// given transform applied.
+    fn is_bright(hdr: &Image, transform: &Transform, direction: &glm::DVec3) -> bool {
+        hdr_color(hdr, &get_sampler(), &transform.get_matrix(), direction)[0] > 1.0
+    }
+
+    #[test]
+    fn environment_sampling_data_sample_test_01() {
+        let hdr = get_hdr();
+        get_transforms().iter().for_each(|transform| {
+            let sampling_data = EnvironmentSamplingData::new(&hdr, &get_sampler(), transform, None);
+            let num_samples = 1000;
+            let directions: Vec<_> = (0..num_samples)
+                .map(|_| {
+                    let (direction, pdf) = sampling_data.sample().unwrap();
+                    assert!(
+                        (pdf - sampling_data.pdf(&direction)).abs() < 1e-9 * pdf,
+                        "{} != {}",
+                        pdf,
+                        sampling_data.pdf(&direction)
+                    );
+                    direction
+                })
+                .collect();
+
+            // nearly all the power is in the bright block, the
+            // samples must be where the block is after the transform
+            let num_bright = directions
+                .iter()
+                .filter(|direction| is_bright(&hdr, transform, direction))
+                .count();
+            assert!(num_bright > 900, "{}", num_bright);
+
+            if *transform != Transform::default() {
+                let num_bright = directions
+                    .iter()
+                    .filter(|direction| is_bright(&hdr, &Transform::default(), direction))
+                    .count();
+                assert!(num_bright < 100, "{}", num_bright);
+            }
+        });
+    }
+
+    /// The pdf integrates to 1 over the sphere of directions, which
+    /// needs the sin(theta) of the conversion from the unit square to
+    /// solid angle.
+    #[test]
+    fn environment_sampling_data_pdf_test_01() {
+        let hdr = get_hdr();
+        get_transforms().iter().for_each(|transform| {
+            let sampling_data = EnvironmentSamplingData::new(&hdr, &get_sampler(), transform, None);
+
+            // midpoints of a grid that is finer than the
+            // distribution
+            let (num_u, num_v) = (4 * hdr.width(), 4 * hdr.height());
+            let integral: f64 = (0..num_v)
+                .flat_map(|j| (0..num_u).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let u = (i as f64 + 0.5) / num_u as f64;
+                    let theta = std::f64::consts::PI * (j as f64 + 0.5) / num_v as f64;
+                    // solid angle of the cell
+                    let solid_angle = theta.sin() * std::f64::consts::TAU / num_u as f64
+                        * std::f64::consts::PI
+                        / num_v as f64;
+                    sampling_data.pdf(&equirectangular_to_direction(u, theta)) * solid_angle
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+        });
     }
 }
@@ -1,17 +1,38 @@
-use super::{ray::Ray, shader_list::ShaderID};
-use crate::{glm, object::ObjectID};
+use super::{
+    ray::{self, Ray, RayDifferential},
+    shader_list::ShaderID,
+};
+use crate::{glm, math, object::ObjectID, util};
 
 #[derive(Debug, Copy, Clone)]
 pub struct IntersectInfo {
     t: f64,
     point: glm::DVec3,
+    /// conservative bound of the absolute floating point error of
+    /// `point`
+    point_error: glm::DVec3,
     bary_coords: glm::DVec3,
     primitive_index: Option<usize>,
     object_id: Option<ObjectID>,
     shader_id: Option<ShaderID>,
     uv: Option<glm::DVec2>,
     normal: Option<glm::DVec3>,
+    /// normal of the underlying geometry (not interpolated), used
+    /// to offset the origin of rays spawned from the point
+    geometric_normal: Option<glm::DVec3>,
     front_face: bool,
+    /// time of the ray that generated the intersection
+    time: f64,
+    /// partial derivatives of the point with respect to the uv (dp/du,
+    /// dp/dv)
+    uv_derivatives: Option<(glm::DVec3, glm::DVec3)>,
+    /// offset of the point to the points hit by the differential rays
+    /// of the ray (dp/dx, dp/dy), see
+    /// [`Self::compute_differentials()`]
+    point_differentials: Option<(glm::DVec3, glm::DVec3)>,
+    /// footprint of the ray on the uv (du/dx and dv/dx, du/dy and
+    /// dv/dy), see [`Self::compute_differentials()`]
+    uv_footprint: Option<(glm::DVec2, glm::DVec2)>,
 }
 
 impl IntersectInfo {
@@ -19,13 +40,19 @@ impl IntersectInfo {
         Self {
             t,
             point,
+            point_error: glm::zero(),
             bary_coords,
             primitive_index: None,
             object_id: None,
             shader_id: None,
             uv: None,
             normal: None,
+            geometric_normal: None,
             front_face: false,
+            time: 0.0,
+            uv_derivatives: None,
+            point_differentials: None,
+            uv_footprint: None,
         }
     }
 
@@ -37,6 +64,16 @@ impl IntersectInfo {
         &self.point
     }
 
+    /// Get a reference to the intersect info's point error.
+    pub fn get_point_error(&self) -> &glm::DVec3 {
+        &self.point_error
+    }
+
+    /// Set the intersect info's point error.
+    pub fn set_point_error(&mut self, point_error: glm::DVec3) {
+        self.point_error = point_error;
+    }
+
     /// Get a reference uv.
     pub fn get_uv(&self) -> &Option<glm::DVec2> {
         &self.uv
@@ -82,6 +119,224 @@ impl IntersectInfo {
         }
     }
 
+    /// Get a reference to the intersect info's geometric normal.
+    pub fn get_geometric_normal(&self) -> &Option<glm::DVec3> {
+        &self.geometric_normal
+    }
+
+    /// Set the intersect info's geometric normal.
+    pub fn set_geometric_normal(&mut self, geometric_normal: glm::DVec3) {
+        self.geometric_normal = Some(geometric_normal);
+    }
+
+    /// Create a new ray starting at the point of intersection
+    /// travelling in the given direction. The origin of the ray is
+    /// offset based on the error bounds of the point so that the ray
+    /// does not intersect the same surface again, thus the ray can be
+    /// traced with a `t_min` of `0.0`.
+    ///
+    /// Based on pbrt's `Interaction::SpawnRay()`.
+    pub fn spawn_ray(&self, direction: glm::DVec3) -> Ray {
+        let normal = self
+            .geometric_normal
+            .or(self.normal)
+            .unwrap_or_else(glm::zero);
+        Ray::new_with_time(
+            ray::offset_ray_origin(&self.point, &self.point_error, &normal, &direction),
+            direction,
+            self.time,
+        )
+    }
+
+    /// Get intersect info's time.
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    /// Set the intersect info's time.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Transform the intersect info by the given model matrix. Useful
+    /// when the intersection is computed in a different space than
+    /// the ray, the ray must be transformed by the inverse of `model`
+    /// prior to the intersection.
+    ///
+    /// The error bounds of the point are propagated based on pbrt's
+    /// `Transform::operator()` for points with error.
+    pub fn apply_model_matrix(&mut self, model: &glm::DMat4) {
+        let linear = glm::mat4_to_mat3(model);
+        let abs_linear = glm::abs(&linear);
+        let translation = glm::vec3(model[(0, 3)], model[(1, 3)], model[(2, 3)]);
+        self.point_error = (math::gamma(3) + 1.0) * (abs_linear * self.point_error)
+            + math::gamma(3) * (abs_linear * glm::abs(&self.point) + glm::abs(&translation));
+        self.point = util::vec3_apply_model_matrix(&self.point, model);
+
+        let normal_matrix = glm::inverse_transpose(linear);
+        self.normal = self
+            .normal
+            .map(|normal| (normal_matrix * normal).normalize());
+        self.geometric_normal = self
+            .geometric_normal
+            .map(|normal| (normal_matrix * normal).normalize());
+        self.uv_derivatives = self
+            .uv_derivatives
+            .map(|(dpdu, dpdv)| (linear * dpdu, linear * dpdv));
+    }
+
+    /// Get intersect info's partial derivatives of the point with
+    /// respect to the uv (dp/du, dp/dv).
+    pub fn get_uv_derivatives(&self) -> Option<&(glm::DVec3, glm::DVec3)> {
+        self.uv_derivatives.as_ref()
+    }
+
+    /// Set the intersect info's partial derivatives of the point with
+    /// respect to the uv (dp/du, dp/dv).
+    pub fn set_uv_derivatives(&mut self, uv_derivatives: Option<(glm::DVec3, glm::DVec3)>) {
+        self.uv_derivatives = uv_derivatives;
+    }
+
+    /// Get intersect info's footprint on the uv (du/dx and dv/dx,
+    /// du/dy and dv/dy) of the ray that generated the intersection.
+    /// `None` if the ray did not have differentials.
+    pub fn get_uv_footprint(&self) -> Option<&(glm::DVec2, glm::DVec2)> {
+        self.uv_footprint.as_ref()
+    }
+
+    /// Compute the footprint of the given ray on the uv, the
+    /// differential rays of the ray are intersected with the tangent
+    /// plane at the point and the offsets of the points are projected
+    /// on to the uv derivatives. Must be called after the normal and
+    /// the uv derivatives are set.
+    ///
+    /// Based on pbrt's `SurfaceInteraction::ComputeDifferentials()`.
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        self.point_differentials = None;
+        self.uv_footprint = None;
+
+        let (differential, normal) = match (ray.get_differential(), self.normal) {
+            (Some(differential), Some(normal)) => (differential, normal),
+            _ => return,
+        };
+
+        let plane_distance = normal.dot(&self.point);
+        let intersect_plane = |origin: &glm::DVec3, direction: &glm::DVec3| {
+            let denominator = normal.dot(direction);
+            if denominator.abs() < 1e-12 {
+                return None;
+            }
+            let t = (plane_distance - normal.dot(origin)) / denominator;
+            Some(origin + t * direction - self.point)
+        };
+
+        let dpdx = intersect_plane(
+            differential.get_rx_origin(),
+            differential.get_rx_direction(),
+        );
+        let dpdy = intersect_plane(
+            differential.get_ry_origin(),
+            differential.get_ry_direction(),
+        );
+        let (dpdx, dpdy) = match (dpdx, dpdy) {
+            (Some(dpdx), Some(dpdy)) => (dpdx, dpdy),
+            _ => return,
+        };
+        self.point_differentials = Some((dpdx, dpdy));
+
+        if let Some((dpdu, dpdv)) = self.uv_derivatives {
+            // least squares solution of dp = dpdu * du + dpdv * dv
+            let a00 = dpdu.dot(&dpdu);
+            let a01 = dpdu.dot(&dpdv);
+            let a11 = dpdv.dot(&dpdv);
+            let determinant = a00 * a11 - a01 * a01;
+            if determinant.abs() < 1e-20 {
+                return;
+            }
+            let solve = |dp: &glm::DVec3| {
+                let b0 = dpdu.dot(dp);
+                let b1 = dpdv.dot(dp);
+                glm::vec2(
+                    (a11 * b0 - a01 * b1) / determinant,
+                    (a00 * b1 - a01 * b0) / determinant,
+                )
+            };
+            self.uv_footprint = Some((solve(&dpdx), solve(&dpdy)));
+        }
+    }
+
+    /// Create a new ray starting at the point of intersection
+    /// travelling in the given direction, see [`Self::spawn_ray()`],
+    /// with differentials computed for perfect specular reflection
+    /// (or transmission if `transmission` is true) of the
+    /// differentials of `ray`. The change of the normal across the
+    /// footprint is not taken into account.
+    ///
+    /// Based on pbrt's `SurfaceInteraction::SpecularReflect()` and
+    /// `SurfaceInteraction::SpecularTransmit()`.
+    pub fn spawn_specular_ray(&self, ray: &Ray, direction: glm::DVec3, transmission: bool) -> Ray {
+        let mut new_ray = self.spawn_ray(direction);
+
+        let (differential, (dpdx, dpdy), normal) = match (
+            ray.get_differential(),
+            self.point_differentials,
+            self.normal,
+        ) {
+            (Some(differential), Some(point_differentials), Some(normal)) => {
+                (differential, point_differentials, normal)
+            }
+            _ => return new_ray,
+        };
+
+        let wo = -ray.get_direction().normalize();
+        let wi = direction.normalize();
+        // normal facing the side of `wo`
+        let normal = if wo.dot(&normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+
+        let wo_dot_n = wo.dot(&normal);
+        let wi_dot_n = wi.dot(&normal);
+        // relative index of refraction, inferred from the tangential
+        // components of the directions (Snell's law)
+        let eta = if transmission {
+            let wo_tangent = (wo - wo_dot_n * normal).norm();
+            let wi_tangent = (wi - wi_dot_n * normal).norm();
+            if wo_tangent > 1e-6 {
+                wi_tangent / wo_tangent
+            } else {
+                1.0
+            }
+        } else {
+            1.0
+        };
+
+        let differential_direction = |offset_direction: &glm::DVec3| {
+            let dwo = -offset_direction.normalize() - wo;
+            let ddn = dwo.dot(&normal);
+            if transmission {
+                let dmu = if wi_dot_n.abs() > 1e-12 {
+                    (eta - (eta * eta * wo_dot_n) / wi_dot_n) * ddn
+                } else {
+                    0.0
+                };
+                wi - eta * dwo + dmu * normal
+            } else {
+                wi - dwo + 2.0 * ddn * normal
+            }
+        };
+
+        new_ray.set_differential(Some(RayDifferential::new(
+            new_ray.get_origin() + dpdx,
+            differential_direction(differential.get_rx_direction()),
+            new_ray.get_origin() + dpdy,
+            differential_direction(differential.get_ry_direction()),
+        )));
+        new_ray
+    }
+
     /// Get a reference to the intersect info's bary coords.
     pub fn get_bary_coords(&self) -> &glm::DVec3 {
         &self.bary_coords
@@ -101,3 +356,97 @@ impl IntersectInfo {
 pub trait Intersectable {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<IntersectInfo>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mesh::Mesh, sphere::Sphere};
+
+    /// Scales and translations of the scene, from very small to very
+    /// large.
+    fn get_scene_scales() -> [(f64, glm::DVec3); 4] {
+        [
+            (1.0, glm::zero()),
+            (1e-5, glm::zero()),
+            (1e-5, glm::vec3(1e-4, -2e-4, 3e-4)),
+            (1e5, glm::vec3(1e5, -2e5, 3e5)),
+        ]
+    }
+
+    #[test]
+    fn spawn_ray_sphere_test_01() {
+        get_scene_scales().iter().for_each(|(scale, translation)| {
+            let sphere = Sphere::new(*translation, 0.5 * scale);
+            (0..500).for_each(|_| {
+                let origin = sphere.get_center() + math::random_unit_vector() * 2.0 * scale;
+                let target = sphere.get_center() + math::random_unit_vector() * 0.25 * scale;
+                let info = sphere
+                    .hit(&Ray::new(origin, target - origin), 0.0, f64::INFINITY)
+                    .unwrap();
+                let normal = info.get_geometric_normal().unwrap();
+
+                (0..20).for_each(|_| {
+                    let direction = math::random_unit_vector();
+                    let hit = sphere.hit(&info.spawn_ray(direction), 0.0, f64::INFINITY);
+                    let cos_theta = direction.dot(&normal);
+                    if cos_theta > 0.0 {
+                        // leaves the sphere, must not hit it again
+                        assert!(hit.is_none(), "{:?}", hit.map(|hit| hit.get_t()));
+                    } else if cos_theta < -0.1 {
+                        // enters the sphere, must hit only the far side
+                        let t = hit.unwrap().get_t();
+                        assert!(t > 0.1 * sphere.get_radius(), "{}", t);
+                    }
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn spawn_ray_mesh_test_01() {
+        let cargo_manifest_dir = std::path::PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR").expect("could not find cargo manifest dir"),
+        );
+
+        get_scene_scales().iter().for_each(|(scale, translation)| {
+            // square from (-1, 0, -1) to (1, 0, 1) facing +Y
+            let mut mesh = Mesh::read_from_file(
+                cargo_manifest_dir
+                    .join("tests")
+                    .join("obj_test_05_square_no_uv.obj"),
+            )
+            .unwrap();
+            let model =
+                glm::translation(translation) * glm::scaling(&glm::vec3(*scale, *scale, *scale));
+            mesh.apply_model_matrix(&model);
+            mesh.build_bvh(0.01 * scale);
+
+            (0..500).for_each(|_| {
+                let target = util::vec3_apply_model_matrix(
+                    &glm::vec3(
+                        rand::random::<f64>() * 1.8 - 0.9,
+                        0.0,
+                        rand::random::<f64>() * 1.8 - 0.9,
+                    ),
+                    &model,
+                );
+                let origin =
+                    target + (math::random_unit_vector() + glm::vec3(0.0, 1.5, 0.0)) * *scale;
+                let info = mesh
+                    .hit(&Ray::new(origin, target - origin), 0.0, f64::INFINITY)
+                    .unwrap();
+
+                // the mesh is a plane, rays leaving it on either side
+                // must not hit it again
+                (0..20).for_each(|_| {
+                    let hit = mesh.hit(
+                        &info.spawn_ray(math::random_unit_vector()),
+                        0.0,
+                        f64::INFINITY,
+                    );
+                    assert!(hit.is_none(), "{:?}", hit.map(|hit| hit.get_t()));
+                });
+            });
+        });
+    }
+}
@@ -49,8 +49,10 @@ impl Mediums {
         self.mediums.pop()
     }
 
+    /// Get the medium that was added last, the medium the ray is
+    /// currently in.
     pub fn get_lastest_medium(&self) -> Option<&Medium> {
-        self.mediums.first()
+        self.mediums.last()
     }
 
     pub fn get_second_lastest_medium(&self) -> Option<&Medium> {
@@ -71,3 +73,32 @@ impl Default for Mediums {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mediums_get_lastest_medium_test_01() {
+        let mut mediums = Mediums::with_air();
+        assert_eq!(mediums.get_lastest_medium(), Some(&Medium::air()));
+        assert_eq!(mediums.get_second_lastest_medium(), None);
+
+        // entering glass, the ray is now in glass and was in air
+        mediums.add_medium(Medium::glass());
+        assert_eq!(mediums.get_lastest_medium(), Some(&Medium::glass()));
+        assert_eq!(mediums.get_second_lastest_medium(), Some(&Medium::air()));
+
+        // entering water within the glass
+        mediums.add_medium(Medium::new(1.33));
+        assert_eq!(mediums.get_lastest_medium(), Some(&Medium::new(1.33)));
+        assert_eq!(mediums.get_second_lastest_medium(), Some(&Medium::glass()));
+
+        // exiting the water and the glass
+        assert_eq!(mediums.remove_medium(), Some(Medium::new(1.33)));
+        assert_eq!(mediums.get_lastest_medium(), Some(&Medium::glass()));
+        assert_eq!(mediums.remove_medium(), Some(Medium::glass()));
+        assert_eq!(mediums.get_lastest_medium(), Some(&Medium::air()));
+        assert_eq!(mediums.get_number_of_mediums(), 1);
+    }
+}
@@ -6,13 +6,13 @@ pub mod medium;
 pub mod ray;
 pub mod shader_list;
 pub mod shaders;
+pub mod sky;
 pub mod texture_list;
 pub mod traversal_info;
 pub mod viewport_renderer;
 
 use enumflags2::BitFlags;
 use lazy_static::lazy_static;
-use quick_renderer::camera::Camera;
 use rayon::prelude::*;
 
 use std::{
@@ -26,11 +26,13 @@ use std::{
 };
 
 use crate::{
-    camera::CameraExtension,
+    camera::PathTraceCamera,
     glm,
     image::Image,
+    object::{ObjectID, RayVisibility},
     path_trace::{
-        bsdf::SamplingTypes,
+        bsdf::{BSDFMixture, CompositingType, SamplingTypes},
+        bsdfs::utils,
         intersectable::{IntersectInfo, Intersectable},
         ray::Ray,
     },
@@ -40,7 +42,7 @@ use crate::{
 };
 
 use self::{
-    environment::{Environment, EnvironmentShadingData},
+    environment::{Environment, EnvironmentRayType, EnvironmentShadingData},
     medium::Mediums,
     shader_list::{Shader, ShaderList},
     texture_list::TextureList,
@@ -72,7 +74,13 @@ pub struct RayTraceParams {
     /// operation. It is better store a clone of the camera since
     /// changes to the camera should anyway not be propagated while
     /// tracing the scene.
-    camera: Camera,
+    camera: PathTraceCamera,
+    /// time at which the shutter of the camera opens, used for motion
+    /// blur
+    shutter_open: f64,
+    /// time at which the shutter of the camera closes, used for
+    /// motion blur
+    shutter_close: f64,
     /// image to which the render (can be progressive) is updated
     rendered_image: Arc<RwLock<Image>>,
 }
@@ -83,7 +91,9 @@ impl RayTraceParams {
         height: usize,
         trace_max_depth: usize,
         samples_per_pixel: usize,
-        camera: Camera,
+        camera: PathTraceCamera,
+        shutter_open: f64,
+        shutter_close: f64,
         rendered_image: Arc<RwLock<Image>>,
     ) -> Self {
         Self {
@@ -92,6 +102,8 @@ impl RayTraceParams {
             trace_max_depth,
             samples_per_pixel,
             camera,
+            shutter_open,
+            shutter_close,
             rendered_image,
         }
     }
@@ -117,10 +129,20 @@ impl RayTraceParams {
     }
 
     /// Get a reference to ray trace params's camera.
-    pub fn get_camera(&self) -> &Camera {
+    pub fn get_camera(&self) -> &PathTraceCamera {
         &self.camera
     }
 
+    /// Get ray trace params's shutter open.
+    pub fn get_shutter_open(&self) -> f64 {
+        self.shutter_open
+    }
+
+    /// Get ray trace params's shutter close.
+    pub fn get_shutter_close(&self) -> f64 {
+        self.shutter_close
+    }
+
     /// Get ray trace params's rendered image.
     pub fn get_rendered_image(&self) -> Arc<RwLock<Image>> {
         self.rendered_image.clone()
@@ -138,15 +160,26 @@ pub fn ray_trace_scene(
     stop_render: Arc<RwLock<bool>>,
     stop_render_immediate: Arc<RwLock<bool>>,
 ) {
-    let mut image = Image::new(ray_trace_params.get_width(), ray_trace_params.get_height());
+    // camera is cloned since autofocus can modify it
+    let mut camera = ray_trace_params.get_camera().clone();
+
+    // width and height in the ray trace params are of the view of a
+    // single eye, the image contains the views of all the eyes
+    let (image_width, image_height) = camera
+        .get_stereo()
+        .get_image_size(ray_trace_params.get_width(), ray_trace_params.get_height());
+
+    let mut image = Image::new(image_width, image_height);
+    // alpha is accumulated along with the radiance
+    image
+        .get_alphas_mut()
+        .iter_mut()
+        .for_each(|alpha| *alpha = 0.0);
     progress.write().unwrap().reset();
 
-    let camera = ray_trace_params.get_camera();
-
     let update_often = Arc::new(RwLock::new(Instant::now()));
-    let total_number_of_samples = ray_trace_params.get_samples_per_pixel()
-        * ray_trace_params.get_width()
-        * ray_trace_params.get_height();
+    let total_number_of_samples =
+        ray_trace_params.get_samples_per_pixel() * image_width * image_height;
 
     // ray trace
     for processed_samples in 0..ray_trace_params.get_samples_per_pixel() {
@@ -159,77 +192,131 @@ pub fn ray_trace_scene(
 
         scene.write().unwrap().apply_model_matrices();
 
+        scene.write().unwrap().set_shutter(
+            ray_trace_params.get_shutter_open(),
+            ray_trace_params.get_shutter_close(),
+        );
+
         scene.write().unwrap().rebuild_bvh_if_needed(0.01);
 
+        environment.write().unwrap().build_sampling_data_if_needed();
+
+        texture_list.write().unwrap().build_mipmaps_if_needed();
+
         let scene = scene.read().unwrap();
+
+        if processed_samples == 0 {
+            camera.autofocus(&scene);
+        }
+        let camera = &camera;
+
         let shader_list = shader_list.read().unwrap();
         let texture_list = texture_list.read().unwrap();
         let environment: &Environment = &environment.read().unwrap();
         let environment = environment.into();
-        let image_width = image.width();
-        let maybe_exit = image
-            .get_pixels_mut()
+        let (pixels, alphas) = image.get_pixels_and_alphas_mut();
+        let maybe_exit = pixels
             .par_iter_mut()
+            .zip(alphas.par_iter_mut())
             .chunks(image_width)
             .enumerate()
             .try_for_each(|(j, mut row)| {
-                row.par_iter_mut().enumerate().try_for_each(|(i, pixel)| {
-                    let processed_pixels = processed_pixels.fetch_add(1, Ordering::SeqCst);
-
-                    {
-                        if update_often.read().unwrap().elapsed().as_secs_f64() > 0.03 {
-                            // calculate and set progress
-                            {
-                                let calculated_progress = (processed_samples
-                                    * ray_trace_params.get_width()
-                                    * ray_trace_params.get_height()
-                                    + processed_pixels)
-                                    as f64
-                                    / total_number_of_samples as f64;
-
-                                progress.write().unwrap().set_progress(calculated_progress);
+                row.par_iter_mut()
+                    .enumerate()
+                    .try_for_each(|(i, (pixel, alpha))| {
+                        let processed_pixels = processed_pixels.fetch_add(1, Ordering::SeqCst);
+
+                        {
+                            if update_often.read().unwrap().elapsed().as_secs_f64() > 0.03 {
+                                // calculate and set progress
+                                {
+                                    let calculated_progress =
+                                        (processed_samples * image_width * image_height
+                                            + processed_pixels)
+                                            as f64
+                                            / total_number_of_samples as f64;
+
+                                    progress.write().unwrap().set_progress(calculated_progress);
+                                }
+
+                                // check if render must be stopped immediately
+                                if *stop_render_immediate.read().unwrap() {
+                                    progress.write().unwrap().stop_progress();
+                                    return None;
+                                }
+
+                                *update_often.write().unwrap() = Instant::now();
                             }
-
-                            // check if render must be stopped immediately
-                            if *stop_render_immediate.read().unwrap() {
-                                progress.write().unwrap().stop_progress();
-                                return None;
-                            }
-
-                            *update_often.write().unwrap() = Instant::now();
                         }
-                    }
-
-                    let j = ray_trace_params.get_height() - j - 1;
-
-                    // use opengl coords, (0.0, 0.0) is center; (1.0, 1.0) is
-                    // top right; (-1.0, -1.0) is bottom left
-                    let u = (((i as f64 + rand::random::<f64>())
-                        / (ray_trace_params.get_width() - 1) as f64)
-                        - 0.5)
-                        * 2.0;
-                    let v = (((j as f64 + rand::random::<f64>())
-                        / (ray_trace_params.get_height() - 1) as f64)
-                        - 0.5)
-                        * 2.0;
-
-                    let ray = camera.get_ray(&glm::vec2(u, v)).unwrap();
 
-                    let (color, _traversal_info) = trace_ray(
-                        &ray,
-                        camera,
-                        &scene,
-                        ray_trace_params.get_trace_max_depth(),
-                        &shader_list,
-                        &texture_list,
-                        &environment,
-                        &mut Mediums::with_air(),
-                    );
-
-                    **pixel += color;
-
-                    Some(())
-                })?;
+                        let (eye, i, j) = camera.get_stereo().get_eye_pixel(
+                            i,
+                            j,
+                            ray_trace_params.get_width(),
+                            ray_trace_params.get_height(),
+                        );
+
+                        let j = ray_trace_params.get_height() - j - 1;
+
+                        // use opengl coords, (0.0, 0.0) is center; (1.0, 1.0) is
+                        // top right; (-1.0, -1.0) is bottom left
+                        let u = (((i as f64 + rand::random::<f64>())
+                            / (ray_trace_params.get_width() - 1) as f64)
+                            - 0.5)
+                            * 2.0;
+                        let v = (((j as f64 + rand::random::<f64>())
+                            / (ray_trace_params.get_height() - 1) as f64)
+                            - 0.5)
+                            * 2.0;
+
+                        let mut ray = match camera.get_eye_ray(&glm::vec2(u, v), eye) {
+                            Some(ray) => ray,
+                            // pixel is not covered by the camera projection
+                            None => return Some(()),
+                        };
+
+                        // differentials are used to filter the
+                        // textures, the footprint is shrunk as more
+                        // samples are taken per pixel
+                        ray.set_differential(camera.get_eye_ray_differential(
+                            &glm::vec2(u, v),
+                            &glm::vec2(
+                                2.0 / (ray_trace_params.get_width() - 1) as f64,
+                                2.0 / (ray_trace_params.get_height() - 1) as f64,
+                            ),
+                            eye,
+                        ));
+                        ray.scale_differential(
+                            (1.0 / (ray_trace_params.get_samples_per_pixel() as f64).sqrt())
+                                .max(0.125),
+                        );
+
+                        // time within the shutter for motion blur
+                        ray.set_time(
+                            ray_trace_params.get_shutter_open()
+                                + rand::random::<f64>()
+                                    * (ray_trace_params.get_shutter_close()
+                                        - ray_trace_params.get_shutter_open()),
+                        );
+
+                        let (color, transparency, _traversal_info) = trace_ray(
+                            &ray,
+                            camera,
+                            &scene,
+                            ray_trace_params.get_trace_max_depth(),
+                            &shader_list,
+                            &texture_list,
+                            &environment,
+                            &mut Mediums::with_air(),
+                        );
+
+                        **pixel += color;
+                        // alpha is the coverage of the pixel by the
+                        // camera rays, see `trace_ray()`
+                        **alpha += 1.0 - transparency;
+
+                        Some(())
+                    })?;
                 Some(())
             });
 
@@ -239,14 +326,20 @@ pub fn ray_trace_scene(
             return;
         }
 
+        // develop the film, the accumulated radiance and alpha are
+        // averaged and the exposure and white balance of the camera
+        // are applied
         {
+            let film_development_matrix = camera.get_film_development_matrix();
             let mut rendered_image = ray_trace_params.rendered_image.write().unwrap();
             *rendered_image = image.clone();
-            rendered_image
-                .get_pixels_mut()
+            let (pixels, alphas) = rendered_image.get_pixels_and_alphas_mut();
+            pixels
                 .par_iter_mut()
-                .for_each(|pixel| {
-                    *pixel /= (processed_samples + 1) as f64;
+                .zip(alphas.par_iter_mut())
+                .for_each(|(pixel, alpha)| {
+                    *pixel = film_development_matrix * (*pixel / (processed_samples + 1) as f64);
+                    *alpha /= (processed_samples + 1) as f64;
                 });
         }
 
@@ -358,14 +451,23 @@ pub struct ScatterHitData {
     /// type of sampling performed to generate the next ray by the
     /// `BSDF`
     sampling_type: SamplingTypes,
+    /// probability density of the `BSDF` sampling the direction of
+    /// the next ray, see [`bsdf::SampleData::get_pdf()`]
+    pdf: Option<f64>,
 }
 
 impl ScatterHitData {
-    pub fn new(color: glm::DVec3, next_ray: Ray, sampling_type: SamplingTypes) -> Self {
+    pub fn new(
+        color: glm::DVec3,
+        next_ray: Ray,
+        sampling_type: SamplingTypes,
+        pdf: Option<f64>,
+    ) -> Self {
         Self {
             color,
             next_ray,
             sampling_type,
+            pdf,
         }
     }
 
@@ -380,6 +482,10 @@ impl ScatterHitData {
     pub fn get_sampling_type(&self) -> SamplingTypes {
         self.sampling_type
     }
+
+    pub fn get_pdf(&self) -> Option<f64> {
+        self.pdf
+    }
 }
 
 /// Data returned during emission of light while shading of the
@@ -407,6 +513,18 @@ pub fn direction_to_equirectangular_range(dir: &glm::DVec3, range: &glm::DVec4)
     glm::vec2(u, v)
 }
 
+/// Get the direction given the equirectangular coordinates, `u` is
+/// along the azimuth and `theta` is the angle from +Y. Inverse of
+/// [`direction_to_equirectangular()`].
+pub fn equirectangular_to_direction(u: f64, theta: f64) -> glm::DVec3 {
+    let phi = u * std::f64::consts::TAU - std::f64::consts::PI;
+    glm::vec3(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        theta.sin() * phi.sin(),
+    )
+}
+
 pub fn direction_to_equirectangular(dir: &glm::DVec3) -> glm::DVec2 {
     direction_to_equirectangular_range(
         dir,
@@ -419,33 +537,334 @@ pub fn direction_to_equirectangular(dir: &glm::DVec3) -> glm::DVec2 {
     )
 }
 
-fn shade_environment(ray: &Ray, environment: &EnvironmentShadingData) -> glm::DVec3 {
-    let transformed_direction = util::vec3_apply_model_matrix(
-        ray.get_direction(),
-        &environment.get_transform().get_matrix(),
-    );
+/// Shade the environment for the ray that did not hit any object.
+///
+/// The environment may be overridden for the type of the ray, see
+/// [`environment::EnvironmentVisibility`].
+///
+/// `bsdf_pdf` is the probability density of the `BSDF` sampling the
+/// direction of the ray, it must be given only if the environment
+/// was also sampled directly at the previous hitpoint (see
+/// [`sample_environment_light()`]), the contribution is then weighted
+/// using multiple importance sampling. The environment is sampled
+/// directly only if the ray sees it, so an overridden environment is
+/// never weighted.
+fn shade_environment(
+    ray: &Ray,
+    ray_type: EnvironmentRayType,
+    camera: &PathTraceCamera,
+    environment: &EnvironmentShadingData,
+    bsdf_pdf: Option<f64>,
+) -> glm::DVec3 {
+    if let Some(color) = environment.get_override_color(ray, ray_type, camera) {
+        return color;
+    }
+
+    let color = environment.get_color(ray.get_direction());
 
-    let uv = direction_to_equirectangular(&transformed_direction);
-    *environment.get_hdr().get_pixel_uv(&uv) * environment.get_strength()
+    match bsdf_pdf {
+        Some(bsdf_pdf) => color * power_heuristic(bsdf_pdf, environment.pdf(ray.get_direction())),
+        None => color,
+    }
 }
 
-/// Shade the point of intersection when the ray hits an object
-fn shade_hit(
-    ray: &Ray,
+/// Power heuristic (with a power of 2) for multiple importance
+/// sampling, weight of the sample taken with the density `pdf_f`
+/// when the density of the other strategy is `pdf_g`.
+///
+/// reference: Veach, "Robust Monte Carlo Methods for Light Transport
+/// Simulation", section 9.2
+fn power_heuristic(pdf_f: f64, pdf_g: f64) -> f64 {
+    let f = pdf_f * pdf_f;
+    let g = pdf_g * pdf_g;
+    if f + g == 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+/// Get the `BSDF`s of the object at the point of intersection, see
+/// [`BSDFMixture`].
+fn get_bsdf<'a>(
     intersect_info: &IntersectInfo,
-    shader_list: &ShaderList,
+    shader_list: &'a ShaderList,
     texture_list: &TextureList,
-    mediums: &mut Mediums,
-) -> ShadeHitData {
+) -> BSDFMixture<'a> {
     // TODO: currently using a default shader only if the shader has
     // been deleted but there is no way to inform this to the user as
     // of now. Need to figure out a way to let the user know that the
     // object doesn't have a shader valid assigned.
-    let bsdf = intersect_info
-        .get_shader_id()
-        .and_then(|shader_id| shader_list.get_shader(shader_id))
-        .map_or(DEFAULT_SHADER.get_bsdf(), |shader| shader.get_bsdf());
+    BSDFMixture::new(
+        intersect_info.get_shader_id(),
+        shader_list,
+        DEFAULT_SHADER.get_bsdf(),
+        intersect_info,
+        texture_list,
+    )
+}
 
+/// Sample the environment directly (next event estimation) from the
+/// point of intersection, directions towards the bright regions of
+/// the environment are chosen. The contribution is weighted using
+/// multiple importance sampling with the sampling of the `BSDF`, see
+/// [`shade_environment()`].
+///
+/// Must be called only if the environment should be sampled for the
+/// `BSDF` at the point of intersection, see
+/// [`EnvironmentShadingData::should_sample()`].
+fn sample_environment_light(
+    ray: &Ray,
+    intersect_info: &IntersectInfo,
+    bsdf: &BSDFMixture,
+    scene: &Scene,
+    texture_list: &TextureList,
+    environment: &EnvironmentShadingData,
+    mediums: &Mediums,
+) -> glm::DVec3 {
+    let (direction, light_pdf) = match environment.sample() {
+        Some(sample) => sample,
+        None => return glm::zero(),
+    };
+    if light_pdf <= 0.0 {
+        return glm::zero();
+    }
+
+    let wo = -ray.get_direction();
+    // `wi` points towards the hitpoint, opposite to the direction
+    // of the light
+    let wi = -direction;
+    let (color, bsdf_pdf) =
+        match bsdf.eval_with_pdf(&wi, &wo, mediums, intersect_info, texture_list) {
+            Some(eval) => eval,
+            None => return glm::zero(),
+        };
+    if color == glm::zero() {
+        return glm::zero();
+    }
+
+    // the environment is visible only if nothing is hit
+    let mut shadow_ray = intersect_info.spawn_ray(direction);
+    shadow_ray.set_visibility(RayVisibility::SHADOW);
+    if scene
+        .hit(&shadow_ray, 0.0, scene.get_ray_t_max(&shadow_ray))
+        .is_some()
+    {
+        return glm::zero();
+    }
+
+    let light_color = environment.get_color(&direction);
+    color.component_mul(&light_color) * power_heuristic(light_pdf, bsdf_pdf) / light_pdf
+}
+
+/// Sample one of the analytic lights of the scene directly (next
+/// event estimation) from the point of intersection. The light is
+/// chosen uniformly, the contribution of lights that are not delta
+/// lights is weighted using multiple importance sampling with the
+/// sampling of the `BSDF`, see [`shade_lights()`].
+fn sample_scene_lights(
+    ray: &Ray,
+    intersect_info: &IntersectInfo,
+    bsdf: &BSDFMixture,
+    scene: &Scene,
+    texture_list: &TextureList,
+    mediums: &Mediums,
+) -> glm::DVec3 {
+    let light_ids = scene.get_light_ids();
+    if light_ids.is_empty() {
+        return glm::zero();
+    }
+    let light_select_pdf = 1.0 / light_ids.len() as f64;
+    let light_index =
+        ((rand::random::<f64>() * light_ids.len() as f64) as usize).min(light_ids.len() - 1);
+    let light = scene.get_light(light_ids[light_index]).unwrap();
+    if let Some(object_id) = intersect_info.get_object_id() {
+        if !light.get_light_linking().illuminates(object_id) {
+            return glm::zero();
+        }
+    }
+
+    let sample = match light.sample(intersect_info.get_point()) {
+        Some(sample) => sample,
+        None => return glm::zero(),
+    };
+    if sample.get_pdf() <= 0.0 || *sample.get_radiance() == glm::zero() {
+        return glm::zero();
+    }
+
+    let wo = -ray.get_direction();
+    // `wi` points towards the hitpoint, opposite to the direction
+    // of the light
+    let wi = -sample.get_direction();
+    let (color, bsdf_pdf) =
+        match bsdf.eval_with_pdf(&wi, &wo, mediums, intersect_info, texture_list) {
+            Some(eval) => eval,
+            None => return glm::zero(),
+        };
+    if color == glm::zero() {
+        return glm::zero();
+    }
+
+    // the light is visible only if nothing is hit before reaching
+    // it, the light itself is not part of the scene so the ray must
+    // stop just short of it
+    let mut shadow_ray = intersect_info.spawn_ray(*sample.get_direction());
+    shadow_ray.set_visibility(RayVisibility::SHADOW);
+    let shadow_t_max = if sample.get_distance().is_finite() {
+        sample.get_distance() * (1.0 - 1e-4)
+    } else {
+        scene.get_ray_t_max(&shadow_ray)
+    };
+    if scene.hit(&shadow_ray, 0.0, shadow_t_max).is_some() {
+        return glm::zero();
+    }
+
+    let light_pdf = sample.get_pdf() * light_select_pdf;
+    let weight = if sample.get_is_delta() {
+        1.0
+    } else {
+        power_heuristic(light_pdf, bsdf_pdf)
+    };
+    color.component_mul(sample.get_radiance()) * weight / light_pdf
+}
+
+/// Get the radiance of the analytic lights of the scene seen by the
+/// given ray within `t_min` and `t_max`. Lights do not occlude each
+/// other, so the radiance of all the lights hit is added.
+///
+/// Lights are also sampled directly at the previous hitpoint (see
+/// [`sample_scene_lights()`]), so when `bsdf_pdf` is available the
+/// contribution is weighted using multiple importance sampling.
+///
+/// `previous_object_id` is the object from which the ray starts,
+/// only lights that illuminate it are seen, see
+/// [`crate::light::LightLinking`].
+#[allow(clippy::too_many_arguments)]
+fn shade_lights(
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    ray_type: EnvironmentRayType,
+    bsdf_pdf: Option<f64>,
+    previous_object_id: Option<ObjectID>,
+    scene: &Scene,
+) -> glm::DVec3 {
+    let num_lights = scene.get_light_ids().len();
+    if num_lights == 0 {
+        return glm::zero();
+    }
+
+    let direction = ray.get_direction().normalize();
+    scene
+        .hit_lights(ray, t_min, t_max)
+        .iter()
+        .map(|(light_id, radiance)| (scene.get_light(*light_id).unwrap(), radiance))
+        .filter(|(light, _)| {
+            ray_type != EnvironmentRayType::Camera || light.get_visible_to_camera()
+        })
+        .filter(|(light, _)| {
+            previous_object_id.map_or(true, |object_id| {
+                light.get_light_linking().illuminates(object_id)
+            })
+        })
+        .fold(glm::zero(), |acc, (light, radiance)| {
+            let weight = bsdf_pdf.map_or(1.0, |bsdf_pdf| {
+                let light_pdf = light.pdf(ray.get_origin(), &direction) / num_lights as f64;
+                power_heuristic(bsdf_pdf, light_pdf)
+            });
+            acc + radiance * weight
+        })
+}
+
+/// Get the fraction of the direct light (from the environment and
+/// the analytic lights) reaching the point of intersection on a
+/// shadow catcher that is not blocked by other objects. It is 1.0 if
+/// no direct light reaches the point.
+///
+/// The shadow catcher is treated as a white diffuse surface, a
+/// single sample of the environment and of each light is used.
+fn shadow_catcher_visibility(
+    intersect_info: &IntersectInfo,
+    scene: &Scene,
+    environment: &EnvironmentShadingData,
+) -> f64 {
+    let normal = intersect_info.get_normal().unwrap();
+    let point = intersect_info.get_point();
+
+    // (unblocked, visible) contribution of the light arriving from
+    // the given direction
+    let light_contribution =
+        |direction: &glm::DVec3, radiance: &glm::DVec3, pdf: f64, distance: f64| {
+            let cos_theta = glm::dot(&normal, direction);
+            if cos_theta <= 0.0 || pdf <= 0.0 {
+                return (0.0, 0.0);
+            }
+            let contribution = util::luminance(radiance) * cos_theta / pdf;
+
+            let mut shadow_ray = intersect_info.spawn_ray(*direction);
+            shadow_ray.set_visibility(RayVisibility::SHADOW);
+            let t_max = if distance.is_finite() {
+                distance * (1.0 - 1e-4)
+            } else {
+                scene.get_ray_t_max(&shadow_ray)
+            };
+            if scene.hit(&shadow_ray, 0.0, t_max).is_some() {
+                (contribution, 0.0)
+            } else {
+                (contribution, contribution)
+            }
+        };
+
+    let environment_contribution = if environment.should_sample(SamplingTypes::Diffuse.into()) {
+        let (direction, pdf) = environment.sample().unwrap_or_else(|| {
+            // no sampling data, cosine weighted directions are used
+            let wi = utils::wi_diffuse(&normal);
+            (-wi, utils::pdf_diffuse(&normal, &wi))
+        });
+        light_contribution(
+            &direction,
+            &environment.get_color(&direction),
+            pdf,
+            f64::INFINITY,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+
+    let (unblocked, visible) = scene
+        .get_lights()
+        .filter(|light| {
+            intersect_info.get_object_id().map_or(true, |object_id| {
+                light.get_light_linking().illuminates(object_id)
+            })
+        })
+        .filter_map(|light| light.sample(point))
+        .map(|sample| {
+            light_contribution(
+                sample.get_direction(),
+                sample.get_radiance(),
+                sample.get_pdf(),
+                sample.get_distance(),
+            )
+        })
+        .fold(environment_contribution, |acc, contribution| {
+            (acc.0 + contribution.0, acc.1 + contribution.1)
+        });
+
+    if unblocked > 0.0 {
+        (visible / unblocked).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// Shade the point of intersection when the ray hits an object
+fn shade_hit(
+    ray: &Ray,
+    intersect_info: &IntersectInfo,
+    bsdf: &BSDFMixture,
+    texture_list: &TextureList,
+    mediums: &mut Mediums,
+) -> ShadeHitData {
     // wo: outgoing ray direction
     //
     // Outgoing ray direction must be the inverse of the current ray since
@@ -455,12 +874,11 @@ fn shade_hit(
     let wo = -ray.get_direction();
 
     let scattering_data = bsdf
-        .sample(&wo, mediums, intersect_info, BitFlags::all())
-        .map(|sample_data| {
+        .sample(&wo, mediums, intersect_info, texture_list, BitFlags::all())
+        .map(|(sample_data, color)| {
             // wi: incoming way direction
             let wi = sample_data.get_wi().normalize();
             let sampling_type = sample_data.get_sampling_type();
-            let color = bsdf.eval(&wi, &wo, intersect_info, texture_list);
 
             // BSDF returns the incoming ray direction at the point of
             // intersection but for the next ray that is shot in the opposite
@@ -468,11 +886,21 @@ fn shade_hit(
             // `wi`.
             let next_ray_dir = -wi;
 
-            ScatterHitData::new(
-                color,
-                Ray::new(*intersect_info.get_point(), next_ray_dir),
-                sampling_type,
-            )
+            // differentials are propagated only through specular
+            // bounces, the footprint of other bounces is too large to
+            // be useful
+            let mut next_ray = match sampling_type {
+                SamplingTypes::Reflection => {
+                    intersect_info.spawn_specular_ray(ray, next_ray_dir, false)
+                }
+                SamplingTypes::Transmission => {
+                    intersect_info.spawn_specular_ray(ray, next_ray_dir, true)
+                }
+                _ => intersect_info.spawn_ray(next_ray_dir),
+            };
+            next_ray.set_visibility(RayVisibility::from_sampling_type(sampling_type));
+
+            ScatterHitData::new(color, next_ray, sampling_type, sample_data.get_pdf())
         });
 
     let emission_data = bsdf
@@ -482,6 +910,29 @@ fn shade_hit(
     (scattering_data, emission_data)
 }
 
+/// Get the range of `t` within which the given ray (starting from
+/// the camera) must be traced, based on the clipping planes of the
+/// camera. The clipping planes are perpendicular to the front of the
+/// camera, so the distance along the ray is larger for rays away from
+/// the center of the view. For panoramic projections, the clipping
+/// planes are treated as distances from the camera.
+fn camera_ray_t_range(camera: &PathTraceCamera, ray: &Ray) -> (f64, f64) {
+    let projection = camera.get_projection();
+    let camera = camera.get_camera();
+    let cos_theta = glm::dot(ray.get_direction(), &camera.get_front());
+    let scale = if cos_theta > 0.0 && !projection.is_panoramic() {
+        cos_theta
+    } else {
+        // ray is not towards the front of the camera, the clipping
+        // planes can only be treated as distances from the camera
+        ray.get_direction().norm()
+    };
+    (
+        camera.get_near_plane() / scale,
+        camera.get_far_plane() / scale,
+    )
+}
+
 // x: current point
 // x_prime: previous point
 // x_prime_prime: previous's previous point
@@ -491,32 +942,267 @@ fn shade_hit(
 // p: intensity of light scattered from x_prime_prime to x by a patch on surface at x_prime
 /// Traces the given ray into the scene and returns the
 /// colour/intensity of light propagated by the given along with the
-/// path traced till that point
+/// transparency and the path traced till that point.
+///
+/// The transparency is the fraction of the ray that reaches the
+/// transparent background (see
+/// [`EnvironmentShadingData::is_transparent_background()`]) directly
+/// or only through transmission (for example through `Glass`), it is
+/// 0.0 if the background is not transparent. Holdouts (see
+/// [`CompositingType::Holdout`]) seen this way are always
+/// transparent, even if the background is not.
 #[allow(clippy::too_many_arguments)]
 pub fn trace_ray(
     ray: &Ray,
-    camera: &Camera,
+    camera: &PathTraceCamera,
+    scene: &Scene,
+    depth: usize,
+    shader_list: &ShaderList,
+    texture_list: &TextureList,
+    environment: &EnvironmentShadingData,
+    mediums: &mut Mediums,
+) -> (glm::DVec3, f64, TraversalInfo) {
+    let (t_min, t_max) = camera_ray_t_range(camera, ray);
+    let mut ray = *ray;
+    ray.set_visibility(RayVisibility::CAMERA);
+    trace_ray_internal(
+        &ray,
+        t_min,
+        t_max.min(scene.get_ray_t_max(&ray)),
+        EnvironmentRayType::Camera,
+        true,
+        None,
+        false,
+        None,
+        camera,
+        scene,
+        depth,
+        shader_list,
+        texture_list,
+        environment,
+        mediums,
+    )
+}
+
+/// Traces the given ray into the scene considering only the
+/// intersections within `t_min` and `t_max`. See [`trace_ray()`] for
+/// more details.
+///
+/// `ray_type` is the type of the ray, it decides how the environment
+/// is seen by the ray, see [`environment::EnvironmentVisibility`].
+///
+/// `transparent_path` is true if the ray is a camera ray or continues
+/// a camera ray only through transmission, the transparency is
+/// computed only for such rays.
+///
+/// `bsdf_pdf` is the probability density of the `BSDF` sampling the
+/// direction of the ray, `None` for rays that are not generated by
+/// sampling a `BSDF` that can be evaluated for arbitrary directions.
+///
+/// `environment_sampled` is true if the environment was sampled
+/// directly at the previous hitpoint (see
+/// [`sample_environment_light()`]), only then is the environment
+/// seen by the ray weighted using `bsdf_pdf`.
+///
+/// `previous_object_id` is the object from which the ray starts,
+/// `None` for camera rays. Emitters and lights contribute only if
+/// they illuminate it, see [`crate::light::LightLinking`].
+#[allow(clippy::too_many_arguments)]
+fn trace_ray_internal(
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    ray_type: EnvironmentRayType,
+    transparent_path: bool,
+    bsdf_pdf: Option<f64>,
+    environment_sampled: bool,
+    previous_object_id: Option<ObjectID>,
+    camera: &PathTraceCamera,
     scene: &Scene,
     depth: usize,
     shader_list: &ShaderList,
     texture_list: &TextureList,
     environment: &EnvironmentShadingData,
     mediums: &mut Mediums,
-) -> (glm::DVec3, TraversalInfo) {
+) -> (glm::DVec3, f64, TraversalInfo) {
     if depth == 0 {
-        return (glm::zero(), TraversalInfo::new());
+        return (glm::zero(), 0.0, TraversalInfo::new());
     }
 
     let mut traversal_info = TraversalInfo::new();
 
-    if let Some(info) = scene.hit(ray, 0.01, 1000.0) {
-        let (scattering_data, emission_data) =
-            shade_hit(ray, &info, shader_list, texture_list, mediums);
+    let hit_info = scene.hit(ray, t_min, t_max);
+
+    // analytic lights between the ray origin and the hitpoint, the
+    // lights are not part of the scene so they never occlude objects
+    let lights_intensity = shade_lights(
+        ray,
+        t_min,
+        hit_info.as_ref().map_or(f64::INFINITY, |info| info.get_t()),
+        ray_type,
+        bsdf_pdf,
+        previous_object_id,
+        scene,
+    );
+
+    if let Some(mut info) = hit_info {
+        // footprint of the ray on the textures
+        info.compute_differentials(ray);
+
+        // the `BSDF`s are built once for the point of intersection
+        let mut bsdf = get_bsdf(&info, shader_list, texture_list);
+
+        // surfaces used for compositing are treated differently by
+        // the camera rays, see `CompositingType`
+        let compositing_type = if transparent_path {
+            bsdf.choose_compositing_type()
+        } else {
+            None
+        };
+        match compositing_type {
+            Some(CompositingType::Holdout) => {
+                traversal_info.add_ray(SingleRayInfo::new(
+                    *ray,
+                    Some(*info.get_point()),
+                    lights_intensity,
+                    Some(info.get_normal().unwrap()),
+                ));
+
+                // transparent whether or not the background is
+                return (lights_intensity, 1.0, traversal_info);
+            }
+            Some(CompositingType::ShadowCatcher { reflectivity }) => {
+                // the shadow catcher is not seen, whatever is behind
+                // it is darkened by the shadows cast on to it
+                let mut continued_ray = info.spawn_ray(*ray.get_direction());
+                continued_ray.set_visibility(ray.get_visibility());
+                continued_ray.set_differential(ray.get_differential().copied());
+                let (behind_color, behind_transparency, behind_traversal_info) = trace_ray_internal(
+                    &continued_ray,
+                    0.0,
+                    scene.get_ray_t_max(&continued_ray),
+                    ray_type,
+                    transparent_path,
+                    bsdf_pdf,
+                    environment_sampled,
+                    previous_object_id,
+                    camera,
+                    scene,
+                    depth - 1,
+                    shader_list,
+                    texture_list,
+                    environment,
+                    mediums,
+                );
+                traversal_info.append_traversal(behind_traversal_info);
+
+                let shadow = shadow_catcher_visibility(&info, scene, environment);
+
+                // only the objects are reflected, the environment is
+                // already part of whatever is behind the shadow
+                // catcher
+                let (reflection_color, reflection_coverage) = if reflectivity > 0.0 {
+                    let direction = glm::reflect_vec(
+                        &ray.get_direction().normalize(),
+                        &info.get_normal().unwrap(),
+                    );
+                    let mut reflected_ray = info.spawn_specular_ray(ray, direction, false);
+                    reflected_ray.set_visibility(RayVisibility::GLOSSY);
+                    let reflected_t_max = scene.get_ray_t_max(&reflected_ray);
+                    if scene.hit(&reflected_ray, 0.0, reflected_t_max).is_some() {
+                        let (reflected_color, _, reflected_traversal_info) = trace_ray_internal(
+                            &reflected_ray,
+                            0.0,
+                            reflected_t_max,
+                            EnvironmentRayType::Glossy,
+                            false,
+                            None,
+                            false,
+                            info.get_object_id(),
+                            camera,
+                            scene,
+                            depth - 1,
+                            shader_list,
+                            texture_list,
+                            environment,
+                            mediums,
+                        );
+                        traversal_info.append_traversal(reflected_traversal_info);
+                        (reflected_color * reflectivity, reflectivity)
+                    } else {
+                        (glm::zero(), 0.0)
+                    }
+                } else {
+                    (glm::zero(), 0.0)
+                };
+
+                let behind_weight = shadow * (1.0 - reflection_coverage);
+                let resulting_intensity =
+                    behind_color * behind_weight + reflection_color + lights_intensity;
+
+                traversal_info.add_ray(SingleRayInfo::new(
+                    *ray,
+                    Some(*info.get_point()),
+                    resulting_intensity,
+                    Some(info.get_normal().unwrap()),
+                ));
+
+                return (
+                    resulting_intensity,
+                    behind_transparency * behind_weight,
+                    traversal_info,
+                );
+            }
+            None => {}
+        }
+
+        // sampling the `BSDF` can transmit the ray into another
+        // medium, lights are sampled directly with the mediums at the
+        // hitpoint
+        let hit_mediums = mediums.clone();
+        let (scattering_data, emission_data) = shade_hit(ray, &info, &bsdf, texture_list, mediums);
+
+        // light reaching the hitpoint directly from the environment,
+        // only if the hitpoint scatters light and the rays of all
+        // the evaluated lobes see the environment
+        let environment_sampled = scattering_data.is_some()
+            && environment.should_sample(bsdf.get_evaluated_sampling_types());
+        let environment_intensity = if environment_sampled {
+            sample_environment_light(
+                ray,
+                &info,
+                &bsdf,
+                scene,
+                texture_list,
+                environment,
+                &hit_mediums,
+            )
+        } else {
+            glm::zero()
+        };
+
+        // light reaching the hitpoint directly from the analytic
+        // lights, only if the hitpoint scatters light
+        let direct_lights_intensity = if scattering_data.is_some() {
+            sample_scene_lights(ray, &info, &bsdf, scene, texture_list, &hit_mediums)
+        } else {
+            glm::zero()
+        };
 
         // compute scattering of light
+        let mut transparency = 0.0;
         let scattering_intensity = scattering_data.map_or(glm::zero(), |scattering_data| {
-            let (traced_color, scatter_traversal_info) = trace_ray(
+            // the origin of the next ray is already offset to avoid
+            // self intersection, so `t_min` can be 0
+            let (traced_color, traced_transparency, scatter_traversal_info) = trace_ray_internal(
                 &scattering_data.next_ray,
+                0.0,
+                scene.get_ray_t_max(&scattering_data.next_ray),
+                EnvironmentRayType::from_sampling_type(scattering_data.sampling_type),
+                transparent_path && scattering_data.sampling_type == SamplingTypes::Transmission,
+                scattering_data.pdf,
+                environment_sampled,
+                info.get_object_id(),
                 camera,
                 scene,
                 depth - 1,
@@ -528,6 +1214,10 @@ pub fn trace_ray(
 
             traversal_info.append_traversal(scatter_traversal_info);
 
+            // the background is seen through the object attenuated
+            // by the colour of the transmission
+            transparency = traced_transparency * glm::comp_add(&scattering_data.color) / 3.0;
+
             glm::vec3(
                 scattering_data.color[0] * traced_color[0],
                 scattering_data.color[1] * traced_color[1],
@@ -535,12 +1225,35 @@ pub fn trace_ray(
             )
         });
 
-        // compute emission of light
-        let emission_intensity =
-            emission_data.map_or(glm::zero(), |emission_data| emission_data.emission_color);
+        // compute emission of light, the emitter must illuminate the
+        // object from which the ray started
+        let emitter = info
+            .get_object_id()
+            .and_then(|object_id| scene.get_object(object_id));
+        let emitter_illuminates_previous = previous_object_id.map_or(true, |previous_object_id| {
+            emitter.map_or(true, |object| {
+                object.get_light_linking().illuminates(previous_object_id)
+            })
+        });
+        let emission_intensity = match emission_data {
+            Some(emission_data) if emitter_illuminates_previous => {
+                let surface_area = emitter
+                    .filter(|_| bsdf.is_emission_normalized_by_area())
+                    .map(|object| object.get_surface_area())
+                    .filter(|surface_area| *surface_area > 0.0);
+                surface_area.map_or(emission_data.emission_color, |surface_area| {
+                    emission_data.emission_color / surface_area
+                })
+            }
+            _ => glm::zero(),
+        };
 
         // emission added to the scattered light
-        let resulting_intensity = emission_intensity + scattering_intensity;
+        let resulting_intensity = emission_intensity
+            + scattering_intensity
+            + environment_intensity
+            + direct_lights_intensity
+            + lights_intensity;
 
         // TODO: compute light fall off, it is not as simple as
         // resulting_intensity / (1.0 + info.get_t() * info.get_t())
@@ -568,12 +1281,407 @@ pub fn trace_ray(
             Some(info.get_normal().unwrap()),
         ));
 
-        (resulting_intensity, traversal_info)
+        (resulting_intensity, transparency, traversal_info)
     } else {
-        let final_intensity = shade_environment(ray, environment);
+        // the transparent background is not seen, only the
+        // transparency is propagated
+        let (final_intensity, transparency) =
+            if transparent_path && environment.is_transparent_background() {
+                (glm::zero(), 1.0)
+            } else {
+                (
+                    shade_environment(
+                        ray,
+                        ray_type,
+                        camera,
+                        environment,
+                        bsdf_pdf.filter(|_| environment_sampled),
+                    ),
+                    0.0,
+                )
+            };
+        let final_intensity = final_intensity + lights_intensity;
 
         traversal_info.add_ray(SingleRayInfo::new(*ray, None, final_intensity, None));
 
-        (final_intensity, traversal_info)
+        (final_intensity, transparency, traversal_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quick_renderer::camera::{self, Camera};
+
+    use super::*;
+    use crate::{
+        light::{Light, LightLinking, LightType},
+        mesh::{Mesh as MeshData, MeshUseShader},
+        object::{
+            objects::{Mesh as MeshObject, Sphere},
+            Object,
+        },
+        path_trace::environment::EnvironmentOverride,
+        path_trace::shader_list::ShaderID,
+        sphere::Sphere as SphereData,
+        transform::Transform,
+    };
+
+    fn get_camera() -> PathTraceCamera {
+        let mut camera = Camera::new(
+            glm::vec3(0.0, 0.0, 3.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            270.0,
+            0.0,
+            45.0,
+            Some(camera::Sensor::new(36.0, 36.0)),
+        );
+        camera.set_focal_length(50.0);
+        PathTraceCamera::new(camera)
+    }
+
+    /// Environment that is black for all rays apart from the camera
+    /// rays, which see `background` or a transparent background if
+    /// it is `None`.
+    fn get_environment(background: Option<glm::DVec3>) -> Environment {
+        let mut environment = Environment::new(
+            Image::from_pixels(4, 4, vec![glm::zero(); 16]),
+            1.0,
+            Transform::default(),
+        );
+        environment.get_visibility_mut().set_override(
+            EnvironmentRayType::Camera,
+            Some(background.map_or(EnvironmentOverride::Transparent, EnvironmentOverride::Color)),
+        );
+        environment
+    }
+
+    fn get_sphere(center: glm::DVec3, radius: f64, shader_id: ShaderID) -> Box<dyn Object> {
+        let mut sphere = Sphere::new(
+            SphereData::new(center, radius),
+            glm::vec4(0.0, 0.0, 0.0, 1.0),
+            glm::vec4(0.0, 0.0, 0.0, 1.0),
+        );
+        sphere.set_model_matrix(glm::identity());
+        sphere.set_path_trace_shader_id(shader_id);
+        Box::new(sphere)
+    }
+
+    /// Square from (-1, 0, -1) to (1, 0, 1) facing +Y.
+    fn get_plane(shader_id: ShaderID) -> Box<dyn Object> {
+        let cargo_manifest_dir = std::path::PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR").expect("could not find cargo manifest dir"),
+        );
+        let data = MeshData::read_from_file(
+            cargo_manifest_dir
+                .join("tests")
+                .join("obj_test_05_square_no_uv.obj"),
+        )
+        .unwrap();
+        let mut plane = MeshObject::new(data, MeshUseShader::FaceOrientation, None);
+        plane.set_model_matrix(glm::identity());
+        plane.set_path_trace_shader_id(shader_id);
+        Box::new(plane)
+    }
+
+    #[test]
+    fn camera_ray_t_range_test_01() {
+        let mut camera = get_camera();
+        camera.get_camera_mut().set_near_plane(0.5);
+        camera.get_camera_mut().set_far_plane(20.0);
+        let front = camera.get_camera().get_front();
+        let origin = glm::vec3(0.0, 0.0, 3.0);
+
+        // the clipping planes are perpendicular to the front of the
+        // camera, irrespective of the length of the direction
+        [
+            front,
+            front * 3.0,
+            (front + glm::vec3(0.3, -0.2, 0.0)) * 0.5,
+        ]
+        .iter()
+        .for_each(|direction| {
+            let ray = Ray::new(origin, *direction);
+            let (t_min, t_max) = camera_ray_t_range(&camera, &ray);
+            assert!(((ray.at(t_min) - origin).dot(&front) - 0.5).abs() < 1e-12);
+            assert!(((ray.at(t_max) - origin).dot(&front) - 20.0).abs() < 1e-12);
+        });
+
+        // rays away from the front of the camera and rays of panoramic
+        // projections are clipped by the distance from the camera
+        let ray = Ray::new(origin, glm::vec3(2.0, 0.0, 1.0));
+        let (t_min, t_max) = camera_ray_t_range(&camera, &ray);
+        assert!(((ray.at(t_min) - origin).norm() - 0.5).abs() < 1e-12);
+        assert!(((ray.at(t_max) - origin).norm() - 20.0).abs() < 1e-12);
+
+        camera.set_projection(crate::camera::Projection::Equirectangular);
+        let ray = Ray::new(origin, (front + glm::vec3(0.3, -0.2, 0.0)) * 0.5);
+        let (t_min, t_max) = camera_ray_t_range(&camera, &ray);
+        assert!(((ray.at(t_min) - origin).norm() - 0.5).abs() < 1e-12);
+        assert!(((ray.at(t_max) - origin).norm() - 20.0).abs() < 1e-12);
+    }
+
+    /// Trace the camera ray, returns the colour and transparency.
+    fn trace(
+        ray: &Ray,
+        scene: &Scene,
+        shader_list: &ShaderList,
+        environment: &Environment,
+    ) -> (glm::DVec3, f64) {
+        let (color, transparency, _) = trace_ray(
+            ray,
+            &get_camera(),
+            scene,
+            8,
+            shader_list,
+            &TextureList::new(),
+            &environment.into(),
+            &mut Mediums::with_air(),
+        );
+        (color, transparency)
+    }
+
+    /// The alpha of the pixel is 1.0 - transparency.
+    #[test]
+    fn trace_ray_transparency_test_01() {
+        let mut shader_list = ShaderList::new();
+        let lambert = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.5, 0.5, 0.5)),
+        )));
+        let glass = shader_list.add_shader(Box::new(shaders::Glass::new(
+            bsdfs::glass::Glass::new(glm::vec3(1.0, 1.0, 1.0), 1.5, 0.0),
+        )));
+        let mut scene = Scene::new();
+        scene.add_object(get_sphere(glm::vec3(-1.0, 0.0, 0.0), 0.5, lambert));
+        scene.add_object(get_sphere(glm::vec3(1.0, 0.0, 0.0), 0.5, glass));
+        scene.apply_model_matrices();
+        scene.build_bvh(0.01);
+
+        let background_ray = Ray::new(glm::vec3(0.0, 0.0, 3.0), glm::vec3(0.0, 0.0, -1.0));
+        let lambert_ray = Ray::new(glm::vec3(-1.0, 0.0, 3.0), glm::vec3(0.0, 0.0, -1.0));
+        let glass_ray = Ray::new(glm::vec3(1.0, 0.0, 3.0), glm::vec3(0.0, 0.0, -1.0));
+
+        // opaque background, everything is opaque
+        let environment = get_environment(Some(glm::vec3(0.2, 0.4, 0.6)));
+        [&background_ray, &lambert_ray, &glass_ray]
+            .iter()
+            .for_each(|ray| {
+                let (_, transparency) = trace(ray, &scene, &shader_list, &environment);
+                assert_eq!(transparency, 0.0);
+            });
+
+        // transparent background, alpha is 0.0 for the background and
+        // 1.0 for the opaque object
+        let environment = get_environment(None);
+        let (color, transparency) = trace(&background_ray, &scene, &shader_list, &environment);
+        assert_eq!(color, glm::zero());
+        assert_eq!(transparency, 1.0);
+        let (_, transparency) = trace(&lambert_ray, &scene, &shader_list, &environment);
+        assert_eq!(transparency, 0.0);
+
+        // the background is seen through the glass but not in its
+        // reflection, so the glass is partially transparent
+        let num_samples = 1000;
+        let transparency = (0..num_samples)
+            .map(|_| trace(&glass_ray, &scene, &shader_list, &environment).1)
+            .sum::<f64>()
+            / num_samples as f64;
+        assert!(transparency > 0.5 && transparency < 1.0, "{}", transparency);
+    }
+
+    #[test]
+    fn trace_ray_holdout_test_01() {
+        let mut shader_list = ShaderList::new();
+        let holdout = shader_list.add_shader(Box::new(shaders::Holdout::new(
+            bsdfs::holdout::Holdout::new(),
+        )));
+        let mut scene = Scene::new();
+        scene.add_object(get_sphere(glm::zero(), 0.5, holdout));
+        scene.apply_model_matrices();
+        scene.build_bvh(0.01);
+
+        let hit_ray = Ray::new(glm::vec3(0.0, 0.0, 3.0), glm::vec3(0.0, 0.0, -1.0));
+        let miss_ray = Ray::new(glm::vec3(2.0, 0.0, 3.0), glm::vec3(0.0, 0.0, -1.0));
+        [Some(glm::vec3(0.2, 0.4, 0.6)), None]
+            .iter()
+            .for_each(|background| {
+                let environment = get_environment(*background);
+
+                // black and cuts out the alpha, even if the background
+                // is not transparent
+                let (color, transparency) = trace(&hit_ray, &scene, &shader_list, &environment);
+                assert_eq!(color, glm::zero());
+                assert_eq!(transparency, 1.0);
+
+                let (color, transparency) = trace(&miss_ray, &scene, &shader_list, &environment);
+                assert_eq!(color, background.unwrap_or_else(glm::zero));
+                assert_eq!(transparency, if background.is_some() { 0.0 } else { 1.0 });
+            });
+    }
+
+    #[test]
+    fn trace_ray_shadow_catcher_test_01() {
+        let mut shader_list = ShaderList::new();
+        let shadow_catcher = shader_list.add_shader(Box::new(shaders::ShadowCatcher::new(
+            bsdfs::shadow_catcher::ShadowCatcher::new(glm::vec3(0.8, 0.8, 0.8), 0.0),
+        )));
+        let mut scene = Scene::new();
+        scene.add_object(get_plane(shadow_catcher));
+        scene.add_light(Light::new(
+            "light".to_string(),
+            LightType::Point { radius: 0.0 },
+            glm::vec3(1.0, 1.0, 1.0),
+            100.0,
+            Transform {
+                location: glm::vec3(0.0, 1.5, 0.0),
+                ..Default::default()
+            },
+        ));
+        scene.apply_model_matrices();
+        scene.build_bvh(0.01);
+
+        // hits the plane near the light, misses the plane
+        let hit_ray = Ray::new(
+            glm::vec3(1.1, 2.0, 0.2),
+            glm::vec3(-1.0, -2.0, 0.0).normalize(),
+        );
+        let miss_ray = Ray::new(glm::vec3(3.0, 2.0, 0.0), glm::vec3(0.0, -1.0, 0.0));
+
+        // without an occluder, the shadow catcher is not seen
+        [Some(glm::vec3(0.2, 0.4, 0.6)), None]
+            .iter()
+            .for_each(|background| {
+                let environment = get_environment(*background);
+                let (color, transparency) = trace(&hit_ray, &scene, &shader_list, &environment);
+                let (background_color, background_transparency) =
+                    trace(&miss_ray, &scene, &shader_list, &environment);
+                assert!(glm::distance(&color, &background_color) < 1e-12);
+                assert_eq!(transparency, background_transparency);
+            });
+
+        // the occluder (not seen by `hit_ray`) blocks the light, the
+        // shadow is black and opaque
+        let lambert = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.5, 0.5, 0.5)),
+        )));
+        scene.unapply_model_matrices();
+        scene.add_object(get_sphere(glm::vec3(0.0, 0.75, 0.0), 0.25, lambert));
+        scene.apply_model_matrices();
+        scene.build_bvh(0.01);
+        [Some(glm::vec3(0.2, 0.4, 0.6)), None]
+            .iter()
+            .for_each(|background| {
+                let environment = get_environment(*background);
+                let (color, transparency) = trace(&hit_ray, &scene, &shader_list, &environment);
+                assert_eq!(color, glm::zero());
+                assert_eq!(transparency, 0.0);
+            });
+    }
+
+    /// Lambert plane lit by a point light above it, along with a black
+    /// occluder in between them (if `occluder_visibility` is
+    /// available) that is visible to the given types of rays.
+    fn get_lit_plane_scene(
+        shader_list: &mut ShaderList,
+        occluder_visibility: Option<RayVisibility>,
+    ) -> Scene {
+        let white = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.8, 0.8, 0.8)),
+        )));
+        let black = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::zero()),
+        )));
+        let mut scene = Scene::new();
+        scene.add_object(get_plane(white));
+        if let Some(visibility) = occluder_visibility {
+            let mut occluder = get_sphere(glm::vec3(0.0, 0.75, 0.0), 0.25, black);
+            occluder.set_visibility(visibility);
+            scene.add_object(occluder);
+        }
+        scene.add_light(Light::new(
+            "light".to_string(),
+            LightType::Point { radius: 0.0 },
+            glm::vec3(1.0, 1.0, 1.0),
+            100.0,
+            Transform {
+                location: glm::vec3(0.0, 1.5, 0.0),
+                ..Default::default()
+            },
+        ));
+        scene.apply_model_matrices();
+        scene.build_bvh(0.01);
+        scene
+    }
+
+    #[test]
+    fn trace_ray_visibility_test_01() {
+        let environment = get_environment(None);
+        // hits the plane in the shadow of the occluder, hits only the
+        // occluder
+        let shadow_ray = Ray::new(
+            glm::vec3(1.1, 2.0, 0.2),
+            glm::vec3(-1.0, -2.0, 0.0).normalize(),
+        );
+        let occluder_ray = Ray::new(glm::vec3(0.0, 0.75, 3.0), glm::vec3(0.0, 0.0, -1.0));
+
+        // visible to all rays
+        let mut shader_list = ShaderList::new();
+        let scene = get_lit_plane_scene(&mut shader_list, Some(RayVisibility::all()));
+        let (color, _) = trace(&shadow_ray, &scene, &shader_list, &environment);
+        assert_eq!(color, glm::zero());
+        let (_, transparency) = trace(&occluder_ray, &scene, &shader_list, &environment);
+        assert_eq!(transparency, 0.0);
+
+        // not seen by the camera, still casts a shadow
+        let mut shader_list = ShaderList::new();
+        let scene = get_lit_plane_scene(
+            &mut shader_list,
+            Some(RayVisibility::all() - RayVisibility::CAMERA),
+        );
+        let (color, _) = trace(&shadow_ray, &scene, &shader_list, &environment);
+        assert_eq!(color, glm::zero());
+        let (_, transparency) = trace(&occluder_ray, &scene, &shader_list, &environment);
+        assert_eq!(transparency, 1.0);
+
+        // seen by the camera, does not cast a shadow
+        let mut shader_list = ShaderList::new();
+        let scene = get_lit_plane_scene(
+            &mut shader_list,
+            Some(RayVisibility::all() - RayVisibility::SHADOW),
+        );
+        let (color, _) = trace(&shadow_ray, &scene, &shader_list, &environment);
+        assert!(color.min() > 0.0);
+        let (_, transparency) = trace(&occluder_ray, &scene, &shader_list, &environment);
+        assert_eq!(transparency, 0.0);
+    }
+
+    #[test]
+    fn trace_ray_light_linking_test_01() {
+        let environment = get_environment(None);
+        let ray = Ray::new(
+            glm::vec3(1.1, 2.0, 0.2),
+            glm::vec3(-1.0, -2.0, 0.0).normalize(),
+        );
+
+        let mut shader_list = ShaderList::new();
+        let mut scene = get_lit_plane_scene(&mut shader_list, None);
+        let plane_id = scene.get_object_ids()[0];
+        let light_id = scene.get_light_ids()[0];
+
+        let (color, _) = trace(&ray, &scene, &shader_list, &environment);
+        assert!(color.min() > 0.0);
+
+        *scene
+            .get_light_mut(light_id)
+            .unwrap()
+            .get_light_linking_mut() = LightLinking::Include(std::iter::once(plane_id).collect());
+        let (color, _) = trace(&ray, &scene, &shader_list, &environment);
+        assert!(color.min() > 0.0);
+
+        *scene
+            .get_light_mut(light_id)
+            .unwrap()
+            .get_light_linking_mut() = LightLinking::Exclude(std::iter::once(plane_id).collect());
+        let (color, _) = trace(&ray, &scene, &shader_list, &environment);
+        assert_eq!(color, glm::zero());
     }
 }
@@ -1,14 +1,85 @@
-use crate::glm;
+use crate::{glm, math, object::RayVisibility, util};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Ray {
     origin: glm::DVec3,
     direction: glm::DVec3,
+    /// time at which the ray is traced, within the shutter of the
+    /// camera, used for motion blur
+    time: f64,
+    /// rays offset by a pixel along x and y on the image, used to
+    /// compute the footprint of the ray on the textures
+    differential: Option<RayDifferential>,
+    /// type of the ray, only objects visible to this type of ray are
+    /// hit, see [`crate::object::Object::get_visibility()`]
+    visibility: RayVisibility,
+}
+
+/// Offset rays of a [`Ray`] that start from the neighbouring pixels
+/// (along x and y) of the image.
+///
+/// Based on pbrt's `RayDifferential`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayDifferential {
+    rx_origin: glm::DVec3,
+    rx_direction: glm::DVec3,
+    ry_origin: glm::DVec3,
+    ry_direction: glm::DVec3,
+}
+
+impl RayDifferential {
+    pub fn new(
+        rx_origin: glm::DVec3,
+        rx_direction: glm::DVec3,
+        ry_origin: glm::DVec3,
+        ry_direction: glm::DVec3,
+    ) -> Self {
+        Self {
+            rx_origin,
+            rx_direction,
+            ry_origin,
+            ry_direction,
+        }
+    }
+
+    /// Get a reference to the ray differential's origin of the ray
+    /// offset along x.
+    pub fn get_rx_origin(&self) -> &glm::DVec3 {
+        &self.rx_origin
+    }
+
+    /// Get a reference to the ray differential's direction of the
+    /// ray offset along x.
+    pub fn get_rx_direction(&self) -> &glm::DVec3 {
+        &self.rx_direction
+    }
+
+    /// Get a reference to the ray differential's origin of the ray
+    /// offset along y.
+    pub fn get_ry_origin(&self) -> &glm::DVec3 {
+        &self.ry_origin
+    }
+
+    /// Get a reference to the ray differential's direction of the
+    /// ray offset along y.
+    pub fn get_ry_direction(&self) -> &glm::DVec3 {
+        &self.ry_direction
+    }
 }
 
 impl Ray {
     pub fn new(origin: glm::DVec3, direction: glm::DVec3) -> Self {
-        Self { origin, direction }
+        Self::new_with_time(origin, direction, 0.0)
+    }
+
+    pub fn new_with_time(origin: glm::DVec3, direction: glm::DVec3, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            differential: None,
+            visibility: RayVisibility::all(),
+        }
     }
 
     pub fn get_origin(&self) -> &glm::DVec3 {
@@ -19,10 +90,75 @@ impl Ray {
         &self.direction
     }
 
+    /// Get ray's time.
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    /// Set ray's time.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Get ray's differential.
+    pub fn get_differential(&self) -> Option<&RayDifferential> {
+        self.differential.as_ref()
+    }
+
+    /// Set ray's differential.
+    pub fn set_differential(&mut self, differential: Option<RayDifferential>) {
+        self.differential = differential;
+    }
+
+    /// Get ray's visibility.
+    pub fn get_visibility(&self) -> RayVisibility {
+        self.visibility
+    }
+
+    /// Set ray's visibility.
+    pub fn set_visibility(&mut self, visibility: RayVisibility) {
+        self.visibility = visibility;
+    }
+
+    /// Scale the offset of the differential rays from the ray, used
+    /// to shrink the footprint when multiple samples are taken per
+    /// pixel.
+    pub fn scale_differential(&mut self, scale: f64) {
+        let (origin, direction) = (self.origin, self.direction);
+        if let Some(differential) = &mut self.differential {
+            differential.rx_origin = origin + (differential.rx_origin - origin) * scale;
+            differential.ry_origin = origin + (differential.ry_origin - origin) * scale;
+            differential.rx_direction = direction + (differential.rx_direction - direction) * scale;
+            differential.ry_direction = direction + (differential.ry_direction - direction) * scale;
+        }
+    }
+
     pub fn at(&self, t: f64) -> glm::DVec3 {
         self.origin + t * self.direction
     }
 
+    /// Get the ray transformed by the given model matrix. The
+    /// direction is not normalized so that `t` of the transformed ray
+    /// refers to the same point as `t` of the original ray.
+    pub fn apply_model_matrix(&self, model: &glm::DMat4) -> Self {
+        let linear = glm::mat4_to_mat3(model);
+        let mut ray = Self::new_with_time(
+            util::vec3_apply_model_matrix(&self.origin, model),
+            linear * self.direction,
+            self.time,
+        );
+        ray.set_differential(self.differential.map(|differential| {
+            RayDifferential::new(
+                util::vec3_apply_model_matrix(&differential.rx_origin, model),
+                linear * differential.rx_direction,
+                util::vec3_apply_model_matrix(&differential.ry_origin, model),
+                linear * differential.ry_direction,
+            )
+        }));
+        ray.set_visibility(self.visibility);
+        ray
+    }
+
     /// Checks if the ray intersects with the given triangle defined
     /// by the points `v1`, `v2`, and `v3`. Returns Some((distance to
     /// point of intersection, barycentric coords of the point of
@@ -70,3 +206,60 @@ impl Ray {
         Some((lambda, glm::vec3(1.0 - u - v, u, v)))
     }
 }
+
+/// Offset the given point `p` along the normal `n` such that a ray
+/// starting at the offset point travelling in the direction `w` does
+/// not intersect the surface that `p` lies on again because of
+/// floating point error. `p_error` is the conservative bound on the
+/// absolute error of `p`.
+///
+/// This removes the need for a `t_min` epsilon when tracing rays
+/// that start from a surface.
+///
+/// Based on pbrt's `OffsetRayOrigin()`.
+pub fn offset_ray_origin(
+    p: &glm::DVec3,
+    p_error: &glm::DVec3,
+    n: &glm::DVec3,
+    w: &glm::DVec3,
+) -> glm::DVec3 {
+    let d = glm::dot(&glm::abs(n), p_error);
+    let offset = if glm::dot(w, n) < 0.0 { -d * n } else { d * n };
+    let mut po = p + offset;
+    // round away from p to ensure the offset point is on the correct
+    // side of the surface
+    for i in 0..3 {
+        if offset[i] > 0.0 {
+            po[i] = math::next_float_up(po[i]);
+        } else if offset[i] < 0.0 {
+            po[i] = math::next_float_down(po[i]);
+        }
+    }
+    po
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_ray_origin_test_01() {
+        let n = glm::vec3(1.0, 2.0, -0.5).normalize();
+        [1e-5, 1.0, 1e5].iter().for_each(|scale| {
+            let p = glm::vec3(0.3, -0.7, 0.2) * *scale;
+            let p_error = math::gamma(7) * glm::abs(&p);
+            let d = glm::dot(&glm::abs(&n), &p_error);
+
+            // offset at least by the error along the normal, on the
+            // side of the direction
+            let po = offset_ray_origin(&p, &p_error, &n, &glm::vec3(0.0, 1.0, 0.0));
+            assert!(glm::dot(&(po - p), &n) >= d * (1.0 - 1e-6));
+            let po = offset_ray_origin(&p, &p_error, &n, &glm::vec3(0.0, -1.0, 0.0));
+            assert!(glm::dot(&(po - p), &n) <= -d * (1.0 - 1e-6));
+
+            // exact point is not offset
+            let po = offset_ray_origin(&p, &glm::zero(), &n, &n);
+            assert_eq!(po, p);
+        });
+    }
+}
@@ -1,9 +1,9 @@
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{hash_map, HashMap, HashSet},
     fmt::Debug,
 };
 
-use crate::{egui, glm, path_trace::bsdfs::BSDFUiData, ui};
+use crate::{egui, glm, path_trace::bsdfs::BSDFUiData, ui, util};
 use crate::{ui::DrawUI, UiData};
 
 use super::bsdf::BSDF;
@@ -45,6 +45,7 @@ pub trait Shader: Debug + Sync + Send {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "ShaderListIO")]
 pub struct ShaderList {
     /// list of all shaders indexed by their ShaderID
     shaders: HashMap<ShaderID, Box<dyn Shader>>,
@@ -57,6 +58,30 @@ pub struct ShaderList {
     shader_type_for_add: ShaderType,
 }
 
+/// [`ShaderList`] as stored in files, the cycles in the references
+/// of the shaders are removed once loaded, see
+/// [`ShaderList::remove_cycles()`].
+#[derive(Deserialize)]
+struct ShaderListIO {
+    shaders: HashMap<ShaderID, Box<dyn Shader>>,
+    shader_ids: Vec<ShaderID>,
+    selected_shader: Option<ShaderID>,
+    shader_type_for_add: ShaderType,
+}
+
+impl From<ShaderListIO> for ShaderList {
+    fn from(io: ShaderListIO) -> Self {
+        let mut shader_list = Self {
+            shaders: io.shaders,
+            shader_ids: io.shader_ids,
+            selected_shader: io.selected_shader,
+            shader_type_for_add: io.shader_type_for_add,
+        };
+        shader_list.remove_cycles();
+        shader_list
+    }
+}
+
 impl ShaderList {
     pub fn new() -> Self {
         Self {
@@ -113,6 +138,79 @@ impl ShaderList {
 
         self.shaders.remove(&shader_id).unwrap();
     }
+
+    /// Check if the shader refers to `target` directly or through
+    /// other shaders, see [`BSDF::get_referenced_shaders()`].
+    pub fn references_shader(&self, shader_id: ShaderID, target: ShaderID) -> bool {
+        let roots = self.get_referenced_shaders(shader_id);
+        util::depth_first_search(roots, |shader_id| {
+            self.get_referenced_shaders(shader_id)
+                .into_iter()
+                .map(|referenced| ((), referenced))
+                .collect()
+        })
+        .get_post_order()
+        .contains(&target)
+    }
+
+    /// Check if the shader refers to itself directly or through
+    /// other shaders.
+    pub fn has_cycle(&self, shader_id: ShaderID) -> bool {
+        self.references_shader(shader_id, shader_id)
+    }
+
+    /// Get the shaders referred to by the shader, see
+    /// [`BSDF::get_referenced_shaders()`].
+    fn get_referenced_shaders(&self, shader_id: ShaderID) -> Vec<ShaderID> {
+        self.get_shader(shader_id)
+            .map(|shader| shader.get_bsdf().get_referenced_shaders())
+            .unwrap_or_default()
+    }
+
+    /// Remove the cycles in the references of the shaders, the
+    /// references that create a cycle are replaced by the default
+    /// shader, see [`BSDF::remove_referenced_shader()`] and
+    /// [`util::depth_first_search()`].
+    ///
+    /// Returns the number of references that were replaced.
+    pub fn remove_cycles(&mut self) -> usize {
+        let search = util::depth_first_search(self.shader_ids.iter().copied(), |shader_id| {
+            self.get_referenced_shaders(shader_id)
+                .into_iter()
+                .map(|referenced| (referenced, referenced))
+                .collect()
+        });
+        let mut cyclic_references: Vec<(ShaderID, ShaderID)> = Vec::new();
+        search.get_back_edges().iter().for_each(|reference| {
+            if !cyclic_references.contains(reference) {
+                cyclic_references.push(*reference);
+            }
+        });
+
+        cyclic_references
+            .iter()
+            .for_each(|(shader_id, referenced_shader_id)| {
+                let shader = self.shaders.get_mut(shader_id).unwrap();
+                eprintln!(
+                    "warning: shader {} refers to itself, the reference is replaced by the default shader",
+                    shader.get_shader_name()
+                );
+                shader
+                    .get_bsdf_mut()
+                    .remove_referenced_shader(*referenced_shader_id);
+            });
+        cyclic_references.len()
+    }
+
+    /// Get the shaders (along with their names) that the shader can
+    /// refer to without creating a cycle, in the order of addition.
+    pub fn get_referenceable_shaders(&self, shader_id: ShaderID) -> Vec<(ShaderID, String)> {
+        self.shader_ids
+            .iter()
+            .filter(|id| **id != shader_id && !self.references_shader(**id, shader_id))
+            .map(|id| (*id, self.shaders.get(id).unwrap().get_shader_name().clone()))
+            .collect()
+    }
 }
 
 impl Default for ShaderList {
@@ -148,6 +246,18 @@ impl DrawUI for ShaderList {
             ui.label("No shader selected");
         }
 
+        let shader_choices: HashMap<_, _> = self
+            .shader_ids
+            .iter()
+            .map(|shader_id| (*shader_id, self.get_referenceable_shaders(*shader_id)))
+            .collect();
+        let shader_cycles: HashSet<_> = self
+            .shader_ids
+            .iter()
+            .filter(|shader_id| self.has_cycle(**shader_id))
+            .copied()
+            .collect();
+
         let selected_shader = &mut self.selected_shader;
         let shaders = &mut self.shaders;
         let mut delete_shader = None;
@@ -170,8 +280,17 @@ impl DrawUI for ShaderList {
 
                 ui.text_edit_singleline(shader.get_shader_name_mut());
 
-                let bsdf_ui_data =
-                    BSDFUiData::new(extra_data.texture_list.clone(), egui::Id::new(shader_id));
+                if shader_cycles.contains(shader_id) {
+                    ui.label(
+                        "Shader refers to itself, the reference is replaced by the default shader when rendered",
+                    );
+                }
+
+                let bsdf_ui_data = BSDFUiData::new(
+                    extra_data.texture_list.clone(),
+                    egui::Id::new(shader_id),
+                    shader_choices.get(shader_id).cloned().unwrap_or_default(),
+                );
                 shader.get_bsdf().draw_ui(ui, &bsdf_ui_data);
                 shader.get_bsdf_mut().draw_ui_mut(ui, &bsdf_ui_data);
 
@@ -218,3 +337,79 @@ impl DrawUI for ShaderList {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::{bsdfs, shaders};
+
+    /// Replace the shader by an `Add` shader of the given shaders,
+    /// the shader can then refer to itself.
+    fn set_add_shader(
+        shader_list: &mut ShaderList,
+        shader_id: ShaderID,
+        shader_a: Option<ShaderID>,
+        shader_b: Option<ShaderID>,
+    ) {
+        let mut shader = shaders::Add::new(bsdfs::add::Add::new(shader_a, shader_b));
+        shader.set_shader_id(shader_id);
+        *shader_list.get_shader_mut(shader_id).unwrap() = Box::new(shader);
+    }
+
+    #[test]
+    fn shader_list_has_cycle_test_01() {
+        let mut shader_list = ShaderList::new();
+        let lambert = shader_list.add_shader(Box::new(shaders::Lambert::new(
+            bsdfs::lambert::Lambert::new(glm::vec3(0.5, 0.5, 0.5)),
+        )));
+        let add = shader_list.add_shader(Box::new(shaders::Add::new(Default::default())));
+        set_add_shader(&mut shader_list, add, Some(add), Some(add));
+
+        assert!(shader_list.has_cycle(add));
+        assert!(!shader_list.has_cycle(lambert));
+        assert!(shader_list
+            .get_referenceable_shaders(add)
+            .iter()
+            .all(|(shader_id, _)| *shader_id != add));
+
+        assert_eq!(shader_list.remove_cycles(), 1);
+        assert!(!shader_list.has_cycle(add));
+        assert!(shader_list
+            .get_shader(add)
+            .unwrap()
+            .get_bsdf()
+            .get_referenced_shaders()
+            .is_empty());
+    }
+
+    #[test]
+    fn shader_list_has_cycle_test_02() {
+        let mut shader_list = ShaderList::new();
+        let a = shader_list.add_shader(Box::new(shaders::Add::new(Default::default())));
+        let b = shader_list.add_shader(Box::new(shaders::Add::new(bsdfs::add::Add::new(
+            Some(a),
+            None,
+        ))));
+        set_add_shader(&mut shader_list, a, Some(b), None);
+
+        assert!(shader_list.has_cycle(a));
+        assert!(shader_list.has_cycle(b));
+
+        // a single reference is enough to break the cycle
+        assert_eq!(shader_list.remove_cycles(), 1);
+        assert!(!shader_list.has_cycle(a));
+        assert!(!shader_list.has_cycle(b));
+    }
+
+    #[test]
+    fn shader_list_deserialize_test_01() {
+        let mut shader_list = ShaderList::new();
+        let add = shader_list.add_shader(Box::new(shaders::Add::new(Default::default())));
+        set_add_shader(&mut shader_list, add, Some(add), None);
+        assert!(shader_list.has_cycle(add));
+
+        let shader_list: ShaderList =
+            serde_json::from_str(&serde_json::to_string(&shader_list).unwrap()).unwrap();
+        assert!(!shader_list.has_cycle(add));
+    }
+}
@@ -128,11 +128,20 @@ fn default_viewport_color() -> glm::DVec3 {
 
 ShaderFromBSDF!("default_viewport_color",
                 Lambert, bsdfs::lambert::Lambert;
+                OrenNayar, bsdfs::oren_nayar::OrenNayar;
+                Translucent, bsdfs::translucent::Translucent;
                 Glossy, bsdfs::glossy::Glossy;
+                Conductor, bsdfs::conductor::Conductor;
                 Emissive, bsdfs::emissive::Emissive;
                 Blinnphong, bsdfs::blinnphong::Blinnphong;
                 Refraction, bsdfs::refraction::Refraction;
                 Glass, bsdfs::glass::Glass;
+                ShadowCatcher, bsdfs::shadow_catcher::ShadowCatcher;
+                Holdout, bsdfs::holdout::Holdout;
+                Principled, bsdfs::principled::Principled;
+                Mix, bsdfs::mix::Mix;
+                Add, bsdfs::add::Add;
+                ShaderGraph, bsdfs::shader_graph::ShaderGraph;
                 Debug, bsdfs::debug_bsdf::DebugBSDF);
 
 impl Default for ShaderType {
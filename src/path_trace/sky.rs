@@ -0,0 +1,633 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{egui, glm, image::Image, ui::DrawUI, util};
+
+use super::equirectangular_to_direction;
+
+/// Radius of the earth (in meters).
+const EARTH_RADIUS: f64 = 6360e3;
+/// Radius of the top of the atmosphere (in meters).
+const ATMOSPHERE_RADIUS: f64 = 6420e3;
+/// Altitude of the observer above the ground (in meters).
+const OBSERVER_ALTITUDE: f64 = 1.0;
+/// Height (in meters) at which the density of the air is 1/e of the
+/// density at the ground.
+const RAYLEIGH_SCALE_HEIGHT: f64 = 7994.0;
+/// Height (in meters) at which the density of the aerosols is 1/e of
+/// the density at the ground.
+const MIE_SCALE_HEIGHT: f64 = 1200.0;
+/// Ratio of scattering to extinction of the aerosols.
+const MIE_SINGLE_SCATTERING_ALBEDO: f64 = 0.9;
+/// Mean cosine of the scattering angle of the aerosols.
+const MIE_G: f64 = 0.76;
+
+/// Number of samples along the view ray.
+const VIEW_SAMPLES: usize = 16;
+/// Number of samples along the ray towards the sun.
+const LIGHT_SAMPLES: usize = 8;
+
+/// Scattering coefficients (per meter) of the air at the ground for
+/// red, green and blue.
+fn rayleigh_scattering() -> glm::DVec3 {
+    glm::vec3(5.802e-6, 13.558e-6, 33.1e-6)
+}
+
+/// Get the far distance along the ray at which it intersects the
+/// sphere (centered at the origin) of the given radius.
+fn ray_sphere_far(origin: &glm::DVec3, direction: &glm::DVec3, radius: f64) -> Option<f64> {
+    let b = glm::dot(origin, direction);
+    let c = glm::dot(origin, origin) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = -b + discriminant.sqrt();
+    if t < 0.0 {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+/// Check if the ray intersects the earth.
+fn ray_hits_earth(origin: &glm::DVec3, direction: &glm::DVec3) -> bool {
+    let b = glm::dot(origin, direction);
+    let c = glm::dot(origin, origin) - EARTH_RADIUS * EARTH_RADIUS;
+    let discriminant = b * b - c;
+    discriminant >= 0.0 && -b - discriminant.sqrt() > 0.0
+}
+
+/// Procedural physical sky, single scattering of the light of the
+/// sun in the atmosphere.
+///
+/// reference: Nishita et al. "Display of the Earth Taking into
+/// Account Atmospheric Scattering" (1993)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sky {
+    /// Angle (in radians) of the sun above the horizon.
+    sun_elevation: f64,
+    /// Angle (in radians) of the sun about +Y, measured from +X
+    /// towards -Z.
+    sun_azimuth: f64,
+    /// Ratio of the optical thickness of the atmosphere (air and
+    /// aerosols) to the optical thickness of the air alone. 1.0 is
+    /// perfectly clear and larger values are hazier.
+    turbidity: f64,
+    /// Albedo of the ground below the horizon.
+    ground_albedo: f64,
+    /// Angular diameter (in radians) of the sun disc, the sun disc is
+    /// not visible if it is 0.0.
+    sun_size: f64,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            sun_elevation: 15.0_f64.to_radians(),
+            sun_azimuth: 0.0,
+            turbidity: 2.0,
+            ground_albedo: 0.3,
+            sun_size: 0.545_f64.to_radians(),
+        }
+    }
+}
+
+impl Sky {
+    /// Resolution of the image the sky is baked into.
+    pub const IMAGE_SIZE: (usize, usize) = (1024, 512);
+
+    /// Irradiance of the sun at the top of the atmosphere, on a
+    /// surface facing the sun. The radiance of the renderer has no
+    /// physical unit (see [`crate::camera::Exposure`]), the value is
+    /// chosen such that a white diffuse surface lit by the sun at the
+    /// zenith is about 5.0 (20.0 / pi attenuated by the atmosphere of
+    /// the default turbidity).
+    pub const SUN_IRRADIANCE: f64 = 20.0;
+
+    /// Get sky's sun elevation.
+    pub fn get_sun_elevation(&self) -> f64 {
+        self.sun_elevation
+    }
+
+    /// Set sky's sun elevation.
+    pub fn set_sun_elevation(&mut self, sun_elevation: f64) {
+        self.sun_elevation = sun_elevation;
+    }
+
+    /// Get sky's sun azimuth.
+    pub fn get_sun_azimuth(&self) -> f64 {
+        self.sun_azimuth
+    }
+
+    /// Set sky's sun azimuth.
+    pub fn set_sun_azimuth(&mut self, sun_azimuth: f64) {
+        self.sun_azimuth = sun_azimuth;
+    }
+
+    /// Get sky's turbidity.
+    pub fn get_turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    /// Set sky's turbidity.
+    pub fn set_turbidity(&mut self, turbidity: f64) {
+        self.turbidity = turbidity;
+    }
+
+    /// Get sky's ground albedo.
+    pub fn get_ground_albedo(&self) -> f64 {
+        self.ground_albedo
+    }
+
+    /// Set sky's ground albedo.
+    pub fn set_ground_albedo(&mut self, ground_albedo: f64) {
+        self.ground_albedo = ground_albedo;
+    }
+
+    /// Get sky's sun size.
+    pub fn get_sun_size(&self) -> f64 {
+        self.sun_size
+    }
+
+    /// Set sky's sun size.
+    pub fn set_sun_size(&mut self, sun_size: f64) {
+        self.sun_size = sun_size;
+    }
+
+    /// Get the direction towards the sun, without the transform of
+    /// the environment.
+    pub fn get_sun_direction(&self) -> glm::DVec3 {
+        glm::vec3(
+            self.sun_elevation.cos() * self.sun_azimuth.cos(),
+            self.sun_elevation.sin(),
+            -self.sun_elevation.cos() * self.sun_azimuth.sin(),
+        )
+    }
+
+    /// Get the extinction coefficients (per meter) of the aerosols
+    /// at the ground. The vertical optical thickness of the aerosols
+    /// is (turbidity - 1) times the vertical optical thickness of
+    /// the air (at the green wavelength).
+    fn get_mie_extinction(&self) -> f64 {
+        (self.turbidity - 1.0).max(0.0) * rayleigh_scattering()[1] * RAYLEIGH_SCALE_HEIGHT
+            / MIE_SCALE_HEIGHT
+    }
+
+    /// Get the optical depth (density integrated along the ray) of
+    /// the air and the aerosols from the given point towards the
+    /// sun. Returns `None` if the earth blocks the sun.
+    fn optical_depth_to_sun(&self, point: &glm::DVec3, sun: &glm::DVec3) -> Option<(f64, f64)> {
+        if ray_hits_earth(point, sun) {
+            return None;
+        }
+        let t_max = ray_sphere_far(point, sun, ATMOSPHERE_RADIUS)?;
+        let segment = t_max / LIGHT_SAMPLES as f64;
+        let (mut optical_depth_rayleigh, mut optical_depth_mie) = (0.0, 0.0);
+        for i in 0..LIGHT_SAMPLES {
+            let sample = point + sun * (segment * (i as f64 + 0.5));
+            let height = (glm::length(&sample) - EARTH_RADIUS).max(0.0);
+            optical_depth_rayleigh += (-height / RAYLEIGH_SCALE_HEIGHT).exp() * segment;
+            optical_depth_mie += (-height / MIE_SCALE_HEIGHT).exp() * segment;
+        }
+        Some((optical_depth_rayleigh, optical_depth_mie))
+    }
+
+    /// Get the transmittance of the atmosphere for the given optical
+    /// depth of the air and the aerosols.
+    fn transmittance(&self, optical_depth_rayleigh: f64, optical_depth_mie: f64) -> glm::DVec3 {
+        let tau = rayleigh_scattering() * optical_depth_rayleigh
+            + glm::vec3(1.0, 1.0, 1.0) * self.get_mie_extinction() * optical_depth_mie;
+        glm::vec3((-tau[0]).exp(), (-tau[1]).exp(), (-tau[2]).exp())
+    }
+
+    /// Get the transmittance of the atmosphere from the sun to the
+    /// observer.
+    pub fn get_sun_transmittance(&self) -> glm::DVec3 {
+        let origin = glm::vec3(0.0, EARTH_RADIUS + OBSERVER_ALTITUDE, 0.0);
+        self.optical_depth_to_sun(&origin, &self.get_sun_direction())
+            .map_or(
+                glm::zero(),
+                |(optical_depth_rayleigh, optical_depth_mie)| {
+                    self.transmittance(optical_depth_rayleigh, optical_depth_mie)
+                },
+            )
+    }
+
+    /// Get the colour/intensity of light scattered towards the
+    /// observer from the given direction (above the horizon).
+    fn sky_color(&self, direction: &glm::DVec3) -> glm::DVec3 {
+        let origin = glm::vec3(0.0, EARTH_RADIUS + OBSERVER_ALTITUDE, 0.0);
+        let sun = self.get_sun_direction();
+        let t_max = match ray_sphere_far(&origin, direction, ATMOSPHERE_RADIUS) {
+            Some(t_max) => t_max,
+            None => return glm::zero(),
+        };
+        let segment = t_max / VIEW_SAMPLES as f64;
+
+        let mu = glm::dot(direction, &sun);
+        let phase_rayleigh = 3.0 / (16.0 * std::f64::consts::PI) * (1.0 + mu * mu);
+        let phase_mie = {
+            let g2 = MIE_G * MIE_G;
+            3.0 / (8.0 * std::f64::consts::PI) * ((1.0 - g2) * (1.0 + mu * mu))
+                / ((2.0 + g2) * (1.0 + g2 - 2.0 * MIE_G * mu).powf(1.5))
+        };
+
+        let (mut optical_depth_rayleigh, mut optical_depth_mie) = (0.0, 0.0);
+        let mut sum_rayleigh: glm::DVec3 = glm::zero();
+        let mut sum_mie: glm::DVec3 = glm::zero();
+        for i in 0..VIEW_SAMPLES {
+            let sample = origin + direction * (segment * (i as f64 + 0.5));
+            let height = (glm::length(&sample) - EARTH_RADIUS).max(0.0);
+            let density_rayleigh = (-height / RAYLEIGH_SCALE_HEIGHT).exp() * segment;
+            let density_mie = (-height / MIE_SCALE_HEIGHT).exp() * segment;
+            optical_depth_rayleigh += density_rayleigh;
+            optical_depth_mie += density_mie;
+
+            if let Some((light_optical_depth_rayleigh, light_optical_depth_mie)) =
+                self.optical_depth_to_sun(&sample, &sun)
+            {
+                let attenuation = self.transmittance(
+                    optical_depth_rayleigh + light_optical_depth_rayleigh,
+                    optical_depth_mie + light_optical_depth_mie,
+                );
+                sum_rayleigh += attenuation * density_rayleigh;
+                sum_mie += attenuation * density_mie;
+            }
+        }
+
+        Self::SUN_IRRADIANCE
+            * (sum_rayleigh.component_mul(&rayleigh_scattering()) * phase_rayleigh
+                + sum_mie * self.get_mie_extinction() * MIE_SINGLE_SCATTERING_ALBEDO * phase_mie)
+    }
+
+    /// Bake the sky (without the sun disc) into an equirectangular
+    /// image of the given size. The ground below the horizon is a
+    /// diffuse surface lit by the sun and the sky.
+    pub fn generate_image(&self, width: usize, height: usize) -> Image {
+        let direction = |i: usize, j: usize| {
+            equirectangular_to_direction(
+                (i as f64 + 0.5) / width as f64,
+                std::f64::consts::PI * (j as f64 + 0.5) / height as f64,
+            )
+        };
+
+        let mut pixels: Vec<glm::DVec3> = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|j| {
+                let direction = &direction;
+                (0..width).map(move |i| {
+                    let direction = direction(i, j);
+                    if direction[1] >= 0.0 {
+                        self.sky_color(&direction)
+                    } else {
+                        glm::zero()
+                    }
+                })
+            })
+            .collect();
+
+        // irradiance on the ground from the sky, the sky colour is
+        // integrated over the upper hemisphere
+        let pixel_solid_angle =
+            (std::f64::consts::TAU / width as f64) * (std::f64::consts::PI / height as f64);
+        let sky_irradiance = pixels
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                let direction = direction(index % width, index / width);
+                if direction[1] > 0.0 {
+                    let sin_theta = (1.0 - direction[1] * direction[1]).max(0.0).sqrt();
+                    color * direction[1] * sin_theta * pixel_solid_angle
+                } else {
+                    glm::zero()
+                }
+            })
+            .fold(glm::zero::<glm::DVec3>(), |acc, irradiance| {
+                acc + irradiance
+            });
+        let sun_irradiance = self.get_sun_transmittance()
+            * Self::SUN_IRRADIANCE
+            * self.get_sun_direction()[1].max(0.0);
+        let ground_color =
+            (sky_irradiance + sun_irradiance) * self.ground_albedo / std::f64::consts::PI;
+
+        pixels.iter_mut().enumerate().for_each(|(index, color)| {
+            if direction(index % width, index / width)[1] < 0.0 {
+                *color = ground_color;
+            }
+        });
+
+        Image::from_pixels(width, height, pixels)
+    }
+}
+
+impl DrawUI for Sky {
+    type ExtraData = ();
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {
+        {
+            let mut sun_elevation = self.sun_elevation.to_degrees();
+            ui.add(
+                egui::Slider::new(&mut sun_elevation, -90.0..=90.0)
+                    .clamp_to_range(true)
+                    .text("Sun Elevation"),
+            );
+            self.sun_elevation = sun_elevation.to_radians();
+        }
+        {
+            let mut sun_azimuth = self.sun_azimuth.to_degrees();
+            ui.add(
+                egui::Slider::new(&mut sun_azimuth, 0.0..=360.0)
+                    .clamp_to_range(false)
+                    .text("Sun Azimuth"),
+            );
+            self.sun_azimuth = sun_azimuth.to_radians();
+        }
+        ui.add(
+            egui::Slider::new(&mut self.turbidity, 1.0..=10.0)
+                .clamp_to_range(false)
+                .text("Turbidity"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.ground_albedo, 0.0..=1.0)
+                .clamp_to_range(true)
+                .text("Ground Albedo"),
+        );
+        {
+            let mut sun_size = self.sun_size.to_degrees();
+            ui.add(
+                egui::Slider::new(&mut sun_size, 0.0..=10.0)
+                    .clamp_to_range(false)
+                    .text("Sun Size"),
+            );
+            self.sun_size = sun_size.to_radians().max(0.0);
+        }
+    }
+}
+
+/// Sun disc of the [`Sky`] in world space, the transform of the
+/// environment is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SunDisc {
+    /// Direction towards the center of the sun.
+    direction: glm::DVec3,
+    /// Cosine of half of the angular diameter of the sun.
+    cos_half_angle: f64,
+    /// Colour/intensity of light from the sun disc.
+    color: glm::DVec3,
+    /// Up of the sky, the sun disc is not visible below the horizon.
+    up: glm::DVec3,
+}
+
+impl SunDisc {
+    /// Create the sun disc of the sky given the transform (as a
+    /// matrix) of the environment. The translation of the transform
+    /// is ignored.
+    ///
+    /// Returns `None` if the sun disc is not visible.
+    pub fn new(sky: &Sky, transform: &glm::DMat4) -> Option<Self> {
+        if sky.get_sun_size() <= 0.0 {
+            return None;
+        }
+
+        let color = sky.get_sun_transmittance();
+        if color == glm::zero() {
+            return None;
+        }
+
+        // directions in world space are transformed before the
+        // lookup of the sky, so the sky directions must be inverse
+        // transformed
+        let linear = glm::mat4_to_mat3(transform);
+        let linear_inverse = linear.try_inverse()?;
+        let direction = (linear_inverse * sky.get_sun_direction()).normalize();
+        let up = glm::vec3(linear[(1, 0)], linear[(1, 1)], linear[(1, 2)]);
+
+        let cos_half_angle = (sky.get_sun_size() * 0.5).cos();
+        let solid_angle = std::f64::consts::TAU * (1.0 - cos_half_angle);
+
+        Some(Self {
+            direction,
+            cos_half_angle,
+            color: color * Sky::SUN_IRRADIANCE / solid_angle,
+            up,
+        })
+    }
+
+    /// Get a reference to the sun disc's direction.
+    pub fn get_direction(&self) -> &glm::DVec3 {
+        &self.direction
+    }
+
+    /// Get the solid angle of the sun disc.
+    pub fn get_solid_angle(&self) -> f64 {
+        std::f64::consts::TAU * (1.0 - self.cos_half_angle)
+    }
+
+    /// Get the luminance of the sun disc integrated over its solid
+    /// angle.
+    pub fn get_power(&self) -> f64 {
+        util::luminance(&self.color) * self.get_solid_angle()
+    }
+
+    /// Get the colour/intensity of light from the sun disc in the
+    /// given direction, zero if the direction is outside the sun
+    /// disc.
+    pub fn get_color(&self, direction: &glm::DVec3) -> glm::DVec3 {
+        let direction = direction.normalize();
+        if glm::dot(&direction, &self.direction) >= self.cos_half_angle
+            && glm::dot(&direction, &self.up) > 0.0
+        {
+            self.color
+        } else {
+            glm::zero()
+        }
+    }
+
+    /// Uniformly sample a direction within the sun disc.
+    pub fn sample(&self) -> glm::DVec3 {
+        let cos_theta = 1.0 - rand::random::<f64>() * (1.0 - self.cos_half_angle);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = std::f64::consts::TAU * rand::random::<f64>();
+
+        let helper = if self.direction[0].abs() > 0.9 {
+            glm::vec3(0.0, 1.0, 0.0)
+        } else {
+            glm::vec3(1.0, 0.0, 0.0)
+        };
+        let tangent = glm::cross(&self.direction, &helper).normalize();
+        let bitangent = glm::cross(&self.direction, &tangent);
+
+        (tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin())
+            + self.direction * cos_theta)
+            .normalize()
+    }
+
+    /// Get the probability density (with respect to solid angle) of
+    /// [`Self::sample()`] returning the given direction.
+    pub fn pdf(&self, direction: &glm::DVec3) -> f64 {
+        if glm::dot(&direction.normalize(), &self.direction) >= self.cos_half_angle {
+            1.0 / self.get_solid_angle()
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_trace::direction_to_equirectangular;
+
+    #[test]
+    fn sun_disc_sample_test_01() {
+        let mut sky = Sky::default();
+        sky.set_sun_elevation(60.0_f64.to_radians());
+        sky.set_sun_azimuth(30.0_f64.to_radians());
+
+        [sky.get_sun_size(), 40.0_f64.to_radians()]
+            .iter()
+            .for_each(|sun_size| {
+                sky.set_sun_size(*sun_size);
+                let sun_disc = SunDisc::new(&sky, &glm::identity()).unwrap();
+                let cos_half_angle = (sun_size * 0.5).cos();
+                assert!(
+                    (sun_disc.get_solid_angle() - std::f64::consts::TAU * (1.0 - cos_half_angle))
+                        .abs()
+                        < 1e-12
+                );
+
+                // the samples are within the disc where the pdf is
+                // constant, uniform in solid angle so the cosine from
+                // the center is uniform
+                let num_samples = 10000;
+                let mean_cos_theta = (0..num_samples)
+                    .map(|_| {
+                        let direction = sun_disc.sample();
+                        assert!((glm::length(&direction) - 1.0).abs() < 1e-12);
+                        assert_eq!(sun_disc.pdf(&direction), 1.0 / sun_disc.get_solid_angle());
+                        assert_ne!(sun_disc.get_color(&direction), glm::zero());
+                        glm::dot(&direction, sun_disc.get_direction())
+                    })
+                    .sum::<f64>()
+                    / num_samples as f64;
+                let expected_mean_cos_theta = (1.0 + cos_half_angle) * 0.5;
+                assert!(
+                    (mean_cos_theta - expected_mean_cos_theta).abs()
+                        < 0.02 * (1.0 - cos_half_angle)
+                );
+            });
+
+        // the pdf integrates to 1 over the sphere, estimated with
+        // uniformly sampled directions
+        let sun_disc = SunDisc::new(&sky, &glm::identity()).unwrap();
+        let num_samples = 200000;
+        let integral = (0..num_samples)
+            .map(|_| {
+                let cos_theta = 1.0 - 2.0 * rand::random::<f64>();
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = std::f64::consts::TAU * rand::random::<f64>();
+                let direction = glm::vec3(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                sun_disc.pdf(&direction) * 2.0 * std::f64::consts::TAU
+            })
+            .sum::<f64>()
+            / num_samples as f64;
+        assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+    }
+
+    /// The sky is brightest towards the sun, the baked image must be
+    /// looked up in the same orientation as the sun.
+    #[test]
+    fn sky_generate_image_test_01() {
+        let mut sky = Sky::default();
+        sky.set_sun_elevation(30.0_f64.to_radians());
+        sky.set_sun_azimuth(60.0_f64.to_radians());
+        let (width, height) = (64, 32);
+        let image = sky.generate_image(width, height);
+
+        let (brightest_index, _) = image
+            .get_pixels()
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| util::luminance(a).partial_cmp(&util::luminance(b)).unwrap())
+            .unwrap();
+        let (i, j) = (brightest_index % width, brightest_index / width);
+        let direction = equirectangular_to_direction(
+            (i as f64 + 0.5) / width as f64,
+            std::f64::consts::PI * (j as f64 + 0.5) / height as f64,
+        );
+        assert!(
+            glm::dot(&direction, &sky.get_sun_direction()) > 8.0_f64.to_radians().cos(),
+            "{} is not towards the sun {}",
+            direction,
+            sky.get_sun_direction()
+        );
+
+        // the environment looks up the brightest pixel in its
+        // direction
+        assert!(std::ptr::eq(
+            image.get_pixel_uv(&direction_to_equirectangular(&direction)),
+            image.get_pixel(i, j)
+        ));
+    }
+
+    /// The ground is a diffuse surface lit by the sun and the sky.
+    #[test]
+    fn sky_generate_image_test_02() {
+        let (width, height) = (64, 32);
+        let get_ground_color =
+            |sky: &Sky| *sky.generate_image(width, height).get_pixel(0, height - 1);
+
+        [30.0_f64, 90.0, -10.0].iter().for_each(|sun_elevation| {
+            let mut sky = Sky::default();
+            sky.set_sun_elevation(sun_elevation.to_radians());
+            sky.set_ground_albedo(0.5);
+            let image = sky.generate_image(width, height);
+
+            // irradiance from the upper hemisphere of the baked sky
+            let pixel_solid_angle =
+                (std::f64::consts::TAU / width as f64) * (std::f64::consts::PI / height as f64);
+            let sky_irradiance = (0..height / 2)
+                .flat_map(|j| (0..width).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let theta = std::f64::consts::PI * (j as f64 + 0.5) / height as f64;
+                    image.get_pixel(i, j) * (theta.cos() * theta.sin() * pixel_solid_angle)
+                })
+                .fold(glm::zero::<glm::DVec3>(), |acc, irradiance| {
+                    acc + irradiance
+                });
+            let sun_irradiance = sky.get_sun_transmittance()
+                * Sky::SUN_IRRADIANCE
+                * sun_elevation.to_radians().sin().max(0.0);
+
+            let expected_ground_color =
+                (sky_irradiance + sun_irradiance) * 0.5 / std::f64::consts::PI;
+            (height / 2..height).for_each(|j| {
+                (0..width).for_each(|i| {
+                    assert!(
+                        glm::distance(image.get_pixel(i, j), &expected_ground_color) < 1e-9,
+                        "{} != {}",
+                        image.get_pixel(i, j),
+                        expected_ground_color
+                    );
+                });
+            });
+
+            if *sun_elevation < 0.0 {
+                assert_eq!(sky.get_sun_transmittance(), glm::zero());
+            } else {
+                // direct sunlight through the atmosphere
+                assert!(sun_irradiance.max() < Sky::SUN_IRRADIANCE);
+                assert!(sun_irradiance.min() > 0.0);
+            }
+
+            // black ground does not reflect any light
+            sky.set_ground_albedo(0.0);
+            assert_eq!(get_ground_color(&sky), glm::zero());
+        });
+    }
+}
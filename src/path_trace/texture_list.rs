@@ -4,7 +4,13 @@ use quick_renderer::{rasterize::Rasterize, texture::TextureRGBAFloat};
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
 
-use crate::{egui, ui::DrawUI, UiData};
+use crate::{
+    egui, glm,
+    image::Image,
+    texture::{MipMap, TextureRGBAFloatExtension, TextureSampler},
+    ui::DrawUI,
+    UiData,
+};
 
 /// A unique identifier given to each `Texture` during its
 /// initialization.
@@ -17,6 +23,10 @@ pub struct TextureList {
     textures: HashMap<TextureID, TextureRGBAFloat>,
     /// list of all texture ids in the order of addition of textures
     texture_ids: Vec<TextureID>,
+    /// mip maps of the textures, generated when the textures are
+    /// added, see [`TextureList::build_mipmaps_if_needed()`]
+    #[serde(skip)]
+    mip_maps: HashMap<TextureID, MipMap>,
 }
 
 impl TextureList {
@@ -24,15 +34,20 @@ impl TextureList {
         Self {
             textures: HashMap::new(),
             texture_ids: Vec::new(),
+            mip_maps: HashMap::new(),
         }
     }
 
-    /// Load texture from disk given the path to the texture
+    /// Load texture from disk given the path to the texture, see
+    /// [`Image::load_from_disk()`] for the supported formats. The
+    /// texture keeps the full (float) precision of the file.
     pub fn load_texture<P>(&mut self, path: P)
     where
         P: AsRef<std::path::Path>,
     {
-        self.add_texture(TextureRGBAFloat::load_from_disk(path).unwrap());
+        self.add_texture(TextureRGBAFloat::from_image(
+            &Image::load_from_disk(path).unwrap(),
+        ));
     }
 
     /// Load texture from disk with file dialog to choose the texture
@@ -40,7 +55,10 @@ impl TextureList {
         if let Some(path) = FileDialog::new()
             .add_filter("png", &["png"])
             .add_filter("jpg", &["jpg", "jpeg"])
-            .add_filter("tiff", &["tiff"])
+            .add_filter("tiff", &["tiff", "tif"])
+            .add_filter("OpenEXR", &["exr"])
+            .add_filter("HDR", &["hdr"])
+            .add_filter("PFM", &["pfm"])
             .add_filter("Any", &["*"])
             .set_directory(".")
             .pick_file()
@@ -67,6 +85,7 @@ impl TextureList {
 
     pub fn add_texture(&mut self, texture: TextureRGBAFloat) -> TextureID {
         let texture_id = TextureID(rand::random());
+        self.mip_maps.insert(texture_id, MipMap::new(&texture));
         self.textures.insert(texture_id, texture);
         self.texture_ids.push(texture_id);
         texture_id
@@ -83,6 +102,48 @@ impl TextureList {
         );
 
         self.textures.remove(&texture_id).unwrap();
+        self.mip_maps.remove(&texture_id);
+    }
+
+    /// Build the mip maps of the textures that do not have one or
+    /// have changed since their mip map was built. Mip maps are not
+    /// stored in the file, so this must be called before rendering.
+    pub fn build_mipmaps_if_needed(&mut self) {
+        let mip_maps = &mut self.mip_maps;
+        self.textures.iter().for_each(|(texture_id, texture)| {
+            let needs_build = mip_maps
+                .get(texture_id)
+                .map_or(true, |mip_map| mip_map.get_texture_id() != texture.get_id());
+            if needs_build {
+                mip_maps.insert(*texture_id, MipMap::new(texture));
+            }
+        });
+    }
+
+    /// Sample the texture at the given UV coordinates with the given
+    /// sampler. The mip map of the texture is used to filter the
+    /// texture over the footprint (du/dx and dv/dx, du/dy and dv/dy)
+    /// if available, see [`MipMap::sample()`].
+    ///
+    /// Returns `None` if the texture is not in the list.
+    pub fn sample_texture(
+        &self,
+        texture_id: TextureID,
+        uv: &glm::DVec2,
+        footprint: Option<&(glm::DVec2, glm::DVec2)>,
+        sampler: &TextureSampler,
+    ) -> Option<glm::DVec4> {
+        let texture = self.get_texture(texture_id)?;
+        Some(
+            match self
+                .mip_maps
+                .get(&texture_id)
+                .filter(|mip_map| mip_map.get_texture_id() == texture.get_id())
+            {
+                Some(mip_map) => mip_map.sample(texture, uv, footprint, sampler),
+                None => texture.sample_uv(uv, sampler),
+            },
+        )
     }
 
     /// Get a reference to the texture list's texture ids.
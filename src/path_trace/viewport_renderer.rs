@@ -147,7 +147,9 @@ impl ViewportRenderer {
                     starting_dimensions.1.floor() as usize,
                     render_data.trace_max_depth,
                     samples_per_pixel,
-                    render_data.camera.clone(),
+                    render_data.camera.clone().into(),
+                    crate::default_shutter_open(),
+                    crate::default_shutter_close(),
                     rendered_image.clone(),
                 ))))
                 .unwrap();
@@ -187,7 +189,9 @@ impl ViewportRenderer {
                                 dimensions.1.floor() as usize,
                                 render_data.trace_max_depth,
                                 samples_per_pixel,
-                                render_data.camera.clone(),
+                                render_data.camera.clone().into(),
+                                crate::default_shutter_open(),
+                                crate::default_shutter_close(),
                                 rendered_image.clone(),
                             ))))
                             .unwrap();
@@ -12,13 +12,15 @@ use std::sync::{Arc, RwLock};
 #[cfg(all(not(feature = "scene_no_bvh"), not(feature = "use_embree")))]
 use crate::bvh::{RayHitData, RayHitOptionalData};
 use crate::egui;
-#[cfg(not(feature = "scene_no_bvh"))]
 use crate::glm;
+use crate::light::{Light, LightDrawData, LightID, LightLinkingUiData, LightType};
+use crate::math;
 use crate::object::{DrawError, Object, ObjectDrawData, ObjectID};
 use crate::path_trace::bsdfs::BSDFUiData;
 use crate::path_trace::intersectable::{IntersectInfo, Intersectable};
 use crate::path_trace::ray::Ray;
 use crate::path_trace::shader_list::ShaderList;
+use crate::transform::MotionTransformUiData;
 use crate::ui::DrawUI;
 use crate::UiData;
 #[cfg(feature = "use_embree")]
@@ -35,6 +37,11 @@ pub struct Scene {
     /// list of all object ids in the order of addition of objects
     object_ids: Vec<ObjectID>,
 
+    /// list of all analytic lights indexed by their LightID
+    lights: HashMap<LightID, Light>,
+    /// list of all light ids in the order of addition of lights
+    light_ids: Vec<LightID>,
+
     /// BVH over all the objects in the scene. User must handle
     /// building/rebuilding the bvh before usage.
     #[cfg(not(feature = "use_embree"))]
@@ -45,6 +52,15 @@ pub struct Scene {
     #[serde(skip)]
     embree: Option<Embree>,
 
+    /// Bounds of the scene, computed along with the BVH since the
+    /// model matrices must be applied.
+    #[serde(skip)]
+    bounds: Option<(glm::DVec3, glm::DVec3)>,
+
+    /// Shutter open and close time used for motion blur
+    #[serde(skip)]
+    shutter: (f64, f64),
+
     /// true if model matrices are currently applied
     model_matrices_applied: bool,
 
@@ -65,6 +81,10 @@ struct SceneShadow {
     objects: HashMap<ObjectID, Box<dyn Object>>,
     #[serde(default)]
     object_ids: Vec<ObjectID>,
+    #[serde(default)]
+    lights: HashMap<LightID, Light>,
+    #[serde(default)]
+    light_ids: Vec<LightID>,
     bvh: Option<BVHTree<ObjectID>>,
     model_matrices_applied: bool,
     selected_object: Option<ObjectID>,
@@ -87,10 +107,14 @@ impl From<SceneShadow> for Scene {
         Self {
             objects: scene_shadow.objects,
             object_ids,
+            lights: scene_shadow.lights,
+            light_ids: scene_shadow.light_ids,
             #[cfg(not(feature = "use_embree"))]
             bvh: scene_shadow.bvh,
             #[cfg(feature = "use_embree")]
             embree: None,
+            bounds: None,
+            shutter: (0.0, 0.0),
             model_matrices_applied: scene_shadow.model_matrices_applied,
             selected_object: scene_shadow.selected_object,
         }
@@ -108,10 +132,14 @@ impl Scene {
         Self {
             objects: HashMap::new(),
             object_ids: Vec::new(),
+            lights: HashMap::new(),
+            light_ids: Vec::new(),
             #[cfg(not(feature = "use_embree"))]
             bvh: None,
             #[cfg(feature = "use_embree")]
             embree: None,
+            bounds: None,
+            shutter: (0.0, 0.0),
             model_matrices_applied: false,
             selected_object: None,
         }
@@ -122,6 +150,7 @@ impl Scene {
         object.set_object_id(object_id);
         self.objects.insert(object_id, object);
         self.object_ids.push(object_id);
+        self.bounds = None;
         #[cfg(feature = "use_embree")]
         {
             self.embree = None;
@@ -143,6 +172,7 @@ impl Scene {
         );
         let object = self.objects.remove(&object_id);
         if object.is_some() {
+            self.bounds = None;
             #[cfg(feature = "use_embree")]
             {
                 self.embree = None;
@@ -173,6 +203,52 @@ impl Scene {
         self.objects.get_mut(&object_id)
     }
 
+    /// Add the given light to the scene, returns the [`LightID`]
+    /// assigned to the light.
+    pub fn add_light(&mut self, light: Light) -> LightID {
+        let light_id = unsafe { LightID::from_raw(rand::random()) };
+        self.lights.insert(light_id, light);
+        self.light_ids.push(light_id);
+        light_id
+    }
+
+    pub fn delete_light(&mut self, light_id: LightID) -> Option<Light> {
+        self.light_ids.retain(|id| *id != light_id);
+        self.lights.remove(&light_id)
+    }
+
+    pub fn get_lights(&self) -> hash_map::Values<'_, LightID, Light> {
+        self.lights.values()
+    }
+
+    pub fn get_light(&self, light_id: LightID) -> Option<&Light> {
+        self.lights.get(&light_id)
+    }
+
+    pub fn get_light_mut(&mut self, light_id: LightID) -> Option<&mut Light> {
+        self.lights.get_mut(&light_id)
+    }
+
+    /// Get a reference to the scene's light ids.
+    pub fn get_light_ids(&self) -> &[LightID] {
+        self.light_ids.as_slice()
+    }
+
+    /// Intersect the given ray with all the lights of the scene,
+    /// lights do not occlude each other so the radiance of all the
+    /// lights hit within `t_min` and `t_max` is returned along with
+    /// the light's id.
+    pub fn hit_lights(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<(LightID, glm::DVec3)> {
+        self.light_ids
+            .iter()
+            .filter_map(|light_id| {
+                self.lights[light_id]
+                    .hit(ray, t_min, t_max)
+                    .map(|(_, radiance)| (*light_id, radiance))
+            })
+            .collect()
+    }
+
     pub fn apply_model_matrices(&mut self) {
         if self.model_matrices_applied {
             return;
@@ -196,10 +272,16 @@ impl Scene {
     pub fn build_bvh(&mut self, epsilon: f64) {
         #[cfg(feature = "use_embree")]
         {
-            let _epsilon = epsilon;
             let mut embree = Embree::new();
+            embree.set_shutter(self.shutter.0, self.shutter.1);
+            let shutter = self.shutter;
             self.objects.values_mut().for_each(|object| {
                 object.set_cached_data();
+                // objects in motion are intersected through their own
+                // BVH, see `Embree::get_moving_objects()`
+                if Embree::is_object_moving(object.as_ref(), shutter) {
+                    object.rebuild_bvh_if_needed(epsilon);
+                }
                 embree.add_object(object.as_ref());
             });
             embree.commit_scene();
@@ -211,7 +293,7 @@ impl Scene {
 
             self.get_objects_mut().for_each(|object| {
                 object.rebuild_bvh_if_needed(epsilon);
-                let co = object.get_min_max_bounds();
+                let co = object.get_motion_min_max_bounds();
                 let co = [co.0, co.1];
                 bvh.insert(object.get_object_id(), &co);
             });
@@ -220,6 +302,8 @@ impl Scene {
 
             self.bvh = Some(bvh);
         }
+
+        self.bounds = self.compute_min_max_bounds();
     }
 
     pub fn rebuild_bvh_if_needed(&mut self, epsilon: f64) {
@@ -228,6 +312,12 @@ impl Scene {
             if self.embree.is_none() {
                 self.build_bvh(epsilon);
             }
+            let shutter = self.shutter;
+            self.get_objects_mut()
+                .filter(|object| Embree::is_object_moving(object.as_ref(), shutter))
+                .for_each(|object| {
+                    object.rebuild_bvh_if_needed(epsilon);
+                });
         }
 
         #[cfg(not(feature = "use_embree"))]
@@ -241,44 +331,72 @@ impl Scene {
         }
     }
 
-    pub fn get_min_max_bounds(&self) -> (glm::DVec3, glm::DVec3) {
+    /// Set the shutter open and close time used for motion blur. The
+    /// BVH must be rebuilt if the shutter has changed, done so through
+    /// [`Scene::rebuild_bvh_if_needed()`].
+    pub fn set_shutter(&mut self, shutter_open: f64, shutter_close: f64) {
+        if self.shutter == (shutter_open, shutter_close) {
+            return;
+        }
+        self.shutter = (shutter_open, shutter_close);
+        // embree stores the geometry at shutter open and leaves out the
+        // objects in motion within the shutter, the native BVH
+        // contains the entire motion of the objects so it does not
+        // need to be rebuilt
         #[cfg(feature = "use_embree")]
-        todo!("use embree for this");
-
-        #[cfg(not(feature = "use_embree"))]
         {
-            let bvh = self.bvh.as_ref().unwrap();
-            let bounds = bvh.get_min_max_bounds();
-
-            bounds
+            self.embree = None;
         }
+    }
 
-        // TODO: need to use the model matrices
-        // self.objects.iter().fold(
-        //     (
-        //         glm::vec3(f64::MAX, f64::MAX, f64::MAX),
-        //         glm::vec3(f64::MIN, f64::MIN, f64::MIN),
-        //     ),
-        //     |acc, object| {
-        //         let (min, max) = object.get_min_max_bounds();
-        //         (
-        //             glm::vec3(
-        //                 min[0].min(acc.0[0]),
-        //                 min[1].min(acc.0[1]),
-        //                 min[2].min(acc.0[2]),
-        //             ),
-        //             glm::vec3(
-        //                 max[0].max(acc.1[0]),
-        //                 max[1].max(acc.1[1]),
-        //                 max[2].max(acc.1[2]),
-        //             ),
-        //         )
-        //     },
-        // )
+    /// Get scene's shutter open and close time.
+    pub fn get_shutter(&self) -> (f64, f64) {
+        self.shutter
+    }
+
+    fn compute_min_max_bounds(&self) -> Option<(glm::DVec3, glm::DVec3)> {
+        self.get_objects()
+            .map(|object| object.get_motion_min_max_bounds())
+            .reduce(|acc, (min, max)| {
+                (
+                    glm::vec3(
+                        min[0].min(acc.0[0]),
+                        min[1].min(acc.0[1]),
+                        min[2].min(acc.0[2]),
+                    ),
+                    glm::vec3(
+                        max[0].max(acc.1[0]),
+                        max[1].max(acc.1[1]),
+                        max[2].max(acc.1[2]),
+                    ),
+                )
+            })
+    }
+
+    /// Get the bounds of the scene. It is available only after the
+    /// BVH is built and is None if the scene has no objects.
+    pub fn get_min_max_bounds(&self) -> Option<(glm::DVec3, glm::DVec3)> {
+        self.bounds
+    }
+
+    /// Get the largest `t` at which the given ray can intersect any
+    /// object of the scene. Any hit must lie within the bounds of the
+    /// scene so the distance to the farthest point of the bounds is
+    /// sufficient.
+    ///
+    /// Returns infinity if the bounds are not available.
+    pub fn get_ray_t_max(&self, ray: &Ray) -> f64 {
+        self.get_min_max_bounds()
+            .map_or(f64::INFINITY, |(min, max)| {
+                let center = (min + max) * 0.5;
+                let distance = (ray.get_origin() - center).norm() + (max - center).norm();
+                // account for the error in computing the distance
+                distance * (1.0 + 2.0 * math::gamma(3)) / ray.get_direction().norm()
+            })
     }
 
     pub fn try_select_object(&mut self, ray: &Ray) {
-        if let Some(info) = self.hit(ray, 0.01, 1000.0) {
+        if let Some(info) = self.hit(ray, 0.0, self.get_ray_t_max(ray)) {
             let object_id = info.get_object_id().unwrap();
 
             self.selected_object = Some(object_id);
@@ -323,7 +441,8 @@ impl Intersectable for Scene {
         {
             let hit_infos: Vec<_> = self
                 .objects
-                .iter()
+                .values()
+                .filter(|object| object.get_visibility().intersects(ray.get_visibility()))
                 .map(|object| object.hit(ray, t_min, t_max))
                 .filter(|object| object.is_some())
                 .collect();
@@ -348,22 +467,72 @@ impl Intersectable for Scene {
         {
             #[cfg(feature = "use_embree")]
             {
-                self.embree
+                let embree = self
+                    .embree
                     .as_ref()
-                    .expect("embree must be Some prior to this call")
-                    .hit(ray, t_min, t_max)
-                    .map(|mut info| {
-                        let object = &self.objects.get(&info.get_object_id().unwrap()).unwrap();
-                        info.set_shader_id(object.get_path_trace_shader_id());
-                        let data_for_interpolation = DataForInterpolation::new(
-                            *info.get_primitive_index(),
-                            PrimitiveType::Triangle,
-                            *info.get_bary_coords(),
-                            *info.get_point(),
-                        );
-                        info.set_uv(object.get_uv(&data_for_interpolation));
-                        info.set_normal(ray, &object.get_normal(&data_for_interpolation));
-                        info
+                    .expect("embree must be Some prior to this call");
+                // embree ignores geometry masks unless it is built
+                // with `EMBREE_RAY_MASK`, so hits on objects that are
+                // not visible to the ray are skipped by tracing past
+                // them
+                let mut embree_t_min = t_min;
+                let info = loop {
+                    let info = match embree.hit(ray, embree_t_min, t_max) {
+                        Some(info) => info,
+                        None => break None,
+                    };
+                    let object = self.objects.get(&info.get_object_id().unwrap()).unwrap();
+                    if object.get_visibility().intersects(ray.get_visibility()) {
+                        break Some(info);
+                    }
+                    // embree works with single precision floats, the
+                    // next representable value ensures that the same
+                    // hit is not found again
+                    embree_t_min = f32::from_bits((info.get_t() as f32).to_bits() + 1).into();
+                };
+                let info = info.map(|mut info| {
+                    let object = self.objects.get(&info.get_object_id().unwrap()).unwrap();
+                    info.set_shader_id(object.get_path_trace_shader_id());
+                    let data_for_interpolation = DataForInterpolation::new(
+                        *info.get_primitive_index(),
+                        PrimitiveType::Triangle,
+                        *info.get_bary_coords(),
+                        *info.get_point(),
+                    );
+                    info.set_uv(object.get_uv(&data_for_interpolation));
+                    // the interpolated normal is at the state of
+                    // the object when the model matrix was
+                    // applied, must be transformed to the state
+                    // at the time of the ray
+                    let normal = object.get_normal_at_time(&data_for_interpolation, ray.get_time());
+                    let normal =
+                        object
+                            .get_motion_matrix(ray.get_time())
+                            .map_or(normal, |motion_matrix| {
+                                (glm::inverse_transpose(glm::mat4_to_mat3(&motion_matrix)) * normal)
+                                    .normalize()
+                            });
+                    info.set_normal(ray, &normal);
+                    let uv_derivatives = object.get_uv_derivatives(&data_for_interpolation);
+                    info.set_uv_derivatives(object.get_motion_matrix(ray.get_time()).map_or(
+                        uv_derivatives,
+                        |motion_matrix| {
+                            let linear = glm::mat4_to_mat3(&motion_matrix);
+                            uv_derivatives.map(|(dpdu, dpdv)| (linear * dpdu, linear * dpdv))
+                        },
+                    ));
+                    info
+                });
+                // objects in motion are not part of the embree scene,
+                // see `Embree::get_moving_objects()`
+                embree
+                    .get_moving_objects()
+                    .iter()
+                    .map(|object_id| self.objects.get(object_id).unwrap())
+                    .filter(|object| object.get_visibility().intersects(ray.get_visibility()))
+                    .fold(info, |hit, object| {
+                        let t_max = hit.as_ref().map_or(t_max, |info| info.get_t());
+                        object.hit(ray, t_min, t_max).or(hit)
                     })
             }
             #[cfg(not(feature = "use_embree"))]
@@ -377,6 +546,10 @@ impl Intersectable for Scene {
 
                         let object = &self.objects.get(&object_id).unwrap();
 
+                        if !object.get_visibility().intersects(ray.get_visibility()) {
+                            return None;
+                        }
+
                         object.hit(ray, t_min, t_max).and_then(
                             |info| -> Option<RayHitData<ObjectID, IntersectInfo>> {
                                 if info.get_t() > t_min && info.get_t() < t_max {
@@ -444,6 +617,10 @@ impl Drawable for Scene {
             ));
             object.draw(&object_draw_data)
         })?;
+        let light_draw_data = LightDrawData::new(extra_data.imm.clone());
+        self.get_lights()
+            .try_for_each(|light| light.draw(&light_draw_data))
+            .map_err(DrawError::Light)?;
         Ok(())
     }
 }
@@ -454,6 +631,17 @@ impl DrawUI for Scene {
     fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
 
     fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        let object_names = self
+            .object_ids
+            .iter()
+            .map(|object_id| {
+                (
+                    *object_id,
+                    self.objects[object_id].get_object_name().to_string(),
+                )
+            })
+            .collect_vec();
+        let mut motion_changed = false;
         let mut selected_object = self.get_selected_object();
         for object_id in &self.object_ids {
             let object = self.objects.get_mut(object_id).unwrap();
@@ -470,10 +658,12 @@ impl DrawUI for Scene {
 
                     if let Some(shader_id) = object.get_path_trace_shader_id() {
                         if let Ok(mut shader_list) = extra_data.get_shader_list().try_write() {
+                            let shader_choices = shader_list.get_referenceable_shaders(shader_id);
                             if let Some(shader) = shader_list.get_shader_mut(shader_id) {
                                 let bsdf_ui_data = BSDFUiData::new(
                                     extra_data.texture_list.clone(),
                                     egui::Id::new(shader_id),
+                                    shader_choices,
                                 );
                                 shader.get_bsdf().draw_ui(ui, &bsdf_ui_data);
                                 shader.get_bsdf_mut().draw_ui_mut(ui, &bsdf_ui_data);
@@ -486,6 +676,30 @@ impl DrawUI for Scene {
                     } else {
                         ui.label("No shader assigned");
                     }
+
+                    let mut visibility = object.get_visibility();
+                    visibility.draw_ui_mut(ui, &());
+                    if visibility != object.get_visibility() {
+                        object.set_visibility(visibility);
+                    }
+
+                    object.get_light_linking_mut().draw_ui_mut(
+                        ui,
+                        &LightLinkingUiData::new(egui::Id::new(object_id), object_names.clone()),
+                    );
+
+                    let mut motion_transform = object.get_motion_transform().clone();
+                    motion_transform.draw_ui_mut(
+                        ui,
+                        &MotionTransformUiData::new(
+                            egui::Id::new(object_id),
+                            object.get_model_matrix().unwrap_or_else(glm::identity),
+                        ),
+                    );
+                    if motion_transform != *object.get_motion_transform() {
+                        object.set_motion_transform(motion_transform);
+                        motion_changed = true;
+                    }
                 })
                 .header_response;
 
@@ -495,6 +709,17 @@ impl DrawUI for Scene {
         }
         self.selected_object = selected_object;
 
+        // the bounds of the objects change with their motion, embree
+        // also leaves out the objects in motion
+        #[cfg(feature = "use_embree")]
+        if motion_changed {
+            self.embree = None;
+        }
+        #[cfg(not(feature = "use_embree"))]
+        if motion_changed {
+            self.bvh = None;
+        }
+
         if let Some(object_id) = self.get_selected_object() {
             if ui.button("Delete selected object").clicked() {
                 self.selected_object = None;
@@ -506,6 +731,49 @@ impl DrawUI for Scene {
         } else {
             ui.label("No object currently selected");
         }
+
+        ui.separator();
+
+        ui.label("Lights");
+        let mut light_to_delete = None;
+        for light_id in &self.light_ids {
+            let light = self.lights.get_mut(light_id).unwrap();
+            egui::CollapsingHeader::new(light.get_name().to_string())
+                .id_source(egui::Id::new(light_id))
+                .show(ui, |ui| {
+                    light.draw_ui(ui, extra_data);
+                    light.draw_ui_mut(ui, extra_data);
+
+                    light.get_light_linking_mut().draw_ui_mut(
+                        ui,
+                        &LightLinkingUiData::new(egui::Id::new(light_id), object_names.clone()),
+                    );
+
+                    if ui.button("Delete light").clicked() {
+                        light_to_delete = Some(*light_id);
+                    }
+                });
+        }
+        if let Some(light_id) = light_to_delete {
+            self.delete_light(light_id);
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            LightType::all_names().for_each(|name| {
+                if ui.button(format!("Add {}", name)).clicked() {
+                    self.add_light(Light::new(
+                        format!("{} Light", name),
+                        LightType::from_name(name).unwrap(),
+                        glm::vec3(1.0, 1.0, 1.0),
+                        match name {
+                            "Directional" => 1.0,
+                            _ => 100.0,
+                        },
+                        Default::default(),
+                    ));
+                }
+            });
+        });
     }
 }
 
@@ -516,3 +784,73 @@ impl Rasterize for Scene {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{object::objects::Sphere, sphere::Sphere as SphereData};
+
+    fn get_scene(scale: f64) -> Scene {
+        let mut scene = Scene::new();
+        [
+            (glm::vec3(1.0, -2.0, 3.0), 0.5),
+            (glm::vec3(-4.0, 1.0, 0.5), 1.0),
+        ]
+        .iter()
+        .for_each(|(center, radius)| {
+            let mut sphere = Sphere::new(
+                SphereData::new(center * scale, radius * scale),
+                glm::vec4(0.0, 0.0, 0.0, 1.0),
+                glm::vec4(0.0, 0.0, 0.0, 1.0),
+            );
+            sphere.set_model_matrix(glm::identity());
+            scene.add_object(Box::new(sphere));
+        });
+        scene.apply_model_matrices();
+        scene
+    }
+
+    #[test]
+    fn get_ray_t_max_test_01() {
+        [1e-5, 1.0, 1e5].iter().for_each(|scale| {
+            let mut scene = get_scene(*scale);
+            let ray = Ray::new(glm::zero(), glm::vec3(1.0, 0.0, 0.0));
+            assert_eq!(scene.get_ray_t_max(&ray), f64::INFINITY);
+
+            scene.build_bvh(0.01);
+            let (min, max) = scene.get_min_max_bounds().unwrap();
+            let corners = (0..8)
+                .map(|i| {
+                    glm::vec3(
+                        if i & 1 == 0 { min[0] } else { max[0] },
+                        if i & 2 == 0 { min[1] } else { max[1] },
+                        if i & 4 == 0 { min[2] } else { max[2] },
+                    )
+                })
+                .collect_vec();
+
+            (0..1000).for_each(|_| {
+                // origins inside and outside of the bounds, directions
+                // that are not normalized
+                let origin = (min + max) * 0.5 + math::random_unit_vector() * 20.0 * *scale;
+                let direction = math::random_unit_vector() * rand::random::<f64>() * 10.0;
+                let ray = Ray::new(origin, direction);
+                let t_max = scene.get_ray_t_max(&ray);
+                corners.iter().for_each(|corner| {
+                    assert!((corner - origin).norm() / direction.norm() <= t_max);
+                });
+            });
+
+            // rays starting within the spheres hit them from the
+            // inside within `t_max`
+            [glm::vec3(1.0, -2.0, 3.0), glm::vec3(-4.0, 1.0, 0.5)]
+                .iter()
+                .for_each(|center| {
+                    (0..100).for_each(|_| {
+                        let ray = Ray::new(center * *scale, math::random_unit_vector());
+                        assert!(scene.hit(&ray, 0.0, scene.get_ray_t_max(&ray)).is_some());
+                    });
+                });
+        });
+    }
+}
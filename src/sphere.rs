@@ -7,10 +7,10 @@ use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::glm;
 use crate::path_trace::intersectable::{IntersectInfo, Intersectable};
 use crate::path_trace::ray::Ray;
 use crate::util::vec3_apply_model_matrix;
+use crate::{glm, math};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Sphere {
@@ -57,8 +57,15 @@ impl Intersectable for Sphere {
         }
 
         let t = root;
-        let intersect_point = ray.at(t);
-        let outward_normal = (intersect_point - self.get_center()) / self.get_radius();
+        // refine the point of intersection by reprojecting it on to
+        // the surface of the sphere, this keeps the error bounds of
+        // the point small
+        let relative_point = ray.at(t) - self.get_center();
+        let relative_point = relative_point * self.get_radius() / relative_point.norm();
+        let intersect_point = self.get_center() + relative_point;
+        let point_error = math::gamma(5) * glm::abs(&relative_point)
+            + math::gamma(1) * glm::abs(&intersect_point);
+        let outward_normal = relative_point / self.get_radius();
         let mut info = IntersectInfo::new(
             t,
             intersect_point,
@@ -67,6 +74,8 @@ impl Intersectable for Sphere {
             // with respect to the center
             (intersect_point - self.get_center()).normalize(),
         );
+        info.set_point_error(point_error);
+        info.set_geometric_normal(outward_normal);
         info.set_normal(ray, &outward_normal);
 
         Some(info)
@@ -1,10 +1,15 @@
 use quick_renderer::texture::TextureRGBAFloat;
+use serde::{Deserialize, Serialize};
 
-use crate::{glm, image::Image};
+use crate::{egui, glm, image::Image, ui::DrawUI};
 
 pub trait TextureRGBAFloatExtension {
     fn from_image(tex: &Image) -> Self;
     fn update_from_image(&mut self, tex: &Image);
+
+    /// Sample the texture at the given UV coordinates with the given
+    /// sampler, see [`TextureSampler`].
+    fn sample_uv(&self, uv: &glm::DVec2, sampler: &TextureSampler) -> glm::DVec4;
 }
 
 impl TextureRGBAFloatExtension for TextureRGBAFloat {
@@ -12,12 +17,17 @@ impl TextureRGBAFloatExtension for TextureRGBAFloat {
         let mut res = Self::from_pixels(
             tex.width(),
             tex.height(),
-            tex.get_pixels()
-                .chunks(tex.width())
+            (0..tex.height())
                 .rev()
-                .flat_map(|row| {
-                    row.iter().map(|pixel| {
-                        glm::vec4(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32, 1.0)
+                .flat_map(|j| {
+                    (0..tex.width()).map(move |i| {
+                        let pixel = tex.get_pixel(i, j);
+                        glm::vec4(
+                            pixel[0] as f32,
+                            pixel[1] as f32,
+                            pixel[2] as f32,
+                            tex.get_alpha(i, j) as f32,
+                        )
                     })
                 })
                 .collect(),
@@ -37,4 +47,800 @@ impl TextureRGBAFloatExtension for TextureRGBAFloat {
 
         *self = Self::from_image(tex);
     }
+
+    fn sample_uv(&self, uv: &glm::DVec2, sampler: &TextureSampler) -> glm::DVec4 {
+        let width = self.get_width();
+        let pixels = self.get_pixels();
+        // pixels of the texture are stored from the bottom left row
+        // wise, same as the texel coordinates of the sampler
+        sampler.sample(uv, width, self.get_height(), |i, j| {
+            glm::convert(pixels[j * width + i])
+        })
+    }
+}
+
+/// Filtering used to compute the value of a texture between the
+/// texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureFilter {
+    /// Value of the nearest texel.
+    Nearest,
+    /// Linear interpolation of the 2x2 nearest texels.
+    Bilinear,
+    /// Cubic B-spline interpolation of the 4x4 nearest texels.
+    Bicubic,
+}
+
+impl Default for TextureFilter {
+    fn default() -> Self {
+        Self::Bilinear
+    }
+}
+
+impl TextureFilter {
+    pub fn all() -> impl Iterator<Item = Self> {
+        [Self::Nearest, Self::Bilinear, Self::Bicubic]
+            .iter()
+            .copied()
+    }
+}
+
+impl std::fmt::Display for TextureFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureFilter::Nearest => write!(f, "Nearest"),
+            TextureFilter::Bilinear => write!(f, "Bilinear"),
+            TextureFilter::Bicubic => write!(f, "Bicubic"),
+        }
+    }
+}
+
+/// Handling of the texture coordinates outside the texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureWrap {
+    /// Texture is repeated.
+    Repeat,
+    /// Texels at the edge of the texture are extended.
+    Clamp,
+    /// Texture is repeated, mirrored every other repetition.
+    Mirror,
+}
+
+impl Default for TextureWrap {
+    fn default() -> Self {
+        Self::Repeat
+    }
+}
+
+impl TextureWrap {
+    pub fn all() -> impl Iterator<Item = Self> {
+        [Self::Repeat, Self::Clamp, Self::Mirror].iter().copied()
+    }
+
+    /// Wrap the texel coordinate into the texture of the given size.
+    pub fn wrap(&self, coord: isize, size: usize) -> usize {
+        let size = size as isize;
+        let coord = match self {
+            TextureWrap::Repeat => coord.rem_euclid(size),
+            TextureWrap::Clamp => coord.clamp(0, size - 1),
+            TextureWrap::Mirror => {
+                let coord = coord.rem_euclid(2 * size);
+                if coord >= size {
+                    2 * size - 1 - coord
+                } else {
+                    coord
+                }
+            }
+        };
+        coord as usize
+    }
+}
+
+impl std::fmt::Display for TextureWrap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureWrap::Repeat => write!(f, "Repeat"),
+            TextureWrap::Clamp => write!(f, "Clamp"),
+            TextureWrap::Mirror => write!(f, "Mirror"),
+        }
+    }
+}
+
+/// Filtering across the levels of the [`MipMap`] of a texture based
+/// on the footprint of the ray on the texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MipMapFilter {
+    /// Mip maps are not used, the texture is sampled at full
+    /// resolution.
+    None,
+    /// Linear interpolation between the two levels closest to the
+    /// width of the footprint, each sampled with the filter of the
+    /// sampler.
+    Trilinear,
+    /// Elliptically weighted average (gaussian) of the texels within
+    /// the footprint, handles anisotropic footprints.
+    Ewa,
+}
+
+impl Default for MipMapFilter {
+    fn default() -> Self {
+        Self::Trilinear
+    }
+}
+
+impl MipMapFilter {
+    pub fn all() -> impl Iterator<Item = Self> {
+        [Self::None, Self::Trilinear, Self::Ewa].iter().copied()
+    }
+}
+
+impl std::fmt::Display for MipMapFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MipMapFilter::None => write!(f, "None"),
+            MipMapFilter::Trilinear => write!(f, "Trilinear"),
+            MipMapFilter::Ewa => write!(f, "EWA"),
+        }
+    }
+}
+
+/// Settings used to sample a texture, the filtering and the wrapping
+/// along U and V.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TextureSampler {
+    filter: TextureFilter,
+    wrap_u: TextureWrap,
+    wrap_v: TextureWrap,
+    #[serde(default)]
+    mip_map_filter: MipMapFilter,
+}
+
+impl TextureSampler {
+    pub fn new(filter: TextureFilter, wrap_u: TextureWrap, wrap_v: TextureWrap) -> Self {
+        Self {
+            filter,
+            wrap_u,
+            wrap_v,
+            mip_map_filter: MipMapFilter::default(),
+        }
+    }
+
+    /// Get texture sampler's filter.
+    pub fn get_filter(&self) -> TextureFilter {
+        self.filter
+    }
+
+    /// Set texture sampler's filter.
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.filter = filter;
+    }
+
+    /// Get texture sampler's wrap along U.
+    pub fn get_wrap_u(&self) -> TextureWrap {
+        self.wrap_u
+    }
+
+    /// Set texture sampler's wrap along U.
+    pub fn set_wrap_u(&mut self, wrap_u: TextureWrap) {
+        self.wrap_u = wrap_u;
+    }
+
+    /// Get texture sampler's wrap along V.
+    pub fn get_wrap_v(&self) -> TextureWrap {
+        self.wrap_v
+    }
+
+    /// Set texture sampler's wrap along V.
+    pub fn set_wrap_v(&mut self, wrap_v: TextureWrap) {
+        self.wrap_v = wrap_v;
+    }
+
+    /// Get texture sampler's mip map filter.
+    pub fn get_mip_map_filter(&self) -> MipMapFilter {
+        self.mip_map_filter
+    }
+
+    /// Set texture sampler's mip map filter.
+    pub fn set_mip_map_filter(&mut self, mip_map_filter: MipMapFilter) {
+        self.mip_map_filter = mip_map_filter;
+    }
+
+    /// Sample the texture of the given size at the given UV
+    /// coordinates, `texel` must return the value of the texel at
+    /// the given coordinates.
+    ///
+    /// UV bottom left is (0.0, 0.0) and top right is (1.0, 1.0), same
+    /// as OpenGL. The texel coordinates are also from the bottom
+    /// left.
+    pub fn sample<V, F>(&self, uv: &glm::DVec2, width: usize, height: usize, texel: F) -> V
+    where
+        V: Copy + std::ops::Add<Output = V> + std::ops::Mul<f64, Output = V>,
+        F: Fn(usize, usize) -> V,
+    {
+        let texel =
+            |i: isize, j: isize| texel(self.wrap_u.wrap(i, width), self.wrap_v.wrap(j, height));
+
+        let x = uv[0] * width as f64;
+        let y = uv[1] * height as f64;
+
+        match self.filter {
+            TextureFilter::Nearest => texel(x.floor() as isize, y.floor() as isize),
+            TextureFilter::Bilinear => {
+                // texel centers are at half integers
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (i, j) = (x.floor(), y.floor());
+                let (fx, fy) = (x - i, y - j);
+                let (i, j) = (i as isize, j as isize);
+
+                (texel(i, j) * (1.0 - fx) + texel(i + 1, j) * fx) * (1.0 - fy)
+                    + (texel(i, j + 1) * (1.0 - fx) + texel(i + 1, j + 1) * fx) * fy
+            }
+            TextureFilter::Bicubic => {
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (i, j) = (x.floor(), y.floor());
+                let weights_x = cubic_bspline_weights(x - i);
+                let weights_y = cubic_bspline_weights(y - j);
+                let (i, j) = (i as isize, j as isize);
+
+                let row = |j: isize| {
+                    texel(i - 1, j) * weights_x[0]
+                        + texel(i, j) * weights_x[1]
+                        + texel(i + 1, j) * weights_x[2]
+                        + texel(i + 2, j) * weights_x[3]
+                };
+
+                row(j - 1) * weights_y[0]
+                    + row(j) * weights_y[1]
+                    + row(j + 1) * weights_y[2]
+                    + row(j + 2) * weights_y[3]
+            }
+        }
+    }
+}
+
+/// Weights of the 4 texels (at -1, 0, 1, 2) for a uniform cubic
+/// B-spline at `t` between texel 0 and texel 1.
+fn cubic_bspline_weights(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        (1.0 - t).powi(3) / 6.0,
+        (3.0 * t3 - 6.0 * t2 + 4.0) / 6.0,
+        (-3.0 * t3 + 3.0 * t2 + 3.0 * t + 1.0) / 6.0,
+        t3 / 6.0,
+    ]
+}
+
+impl DrawUI for TextureSampler {
+    type ExtraData = egui::Id;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        egui::ComboBox::from_id_source(extra_data.with("Texture Filter"))
+            .selected_text(format!("Filter: {}", self.filter))
+            .show_ui(ui, |ui| {
+                TextureFilter::all().for_each(|filter| {
+                    ui.selectable_value(&mut self.filter, filter, format!("{}", filter));
+                });
+            });
+        egui::ComboBox::from_id_source(extra_data.with("Texture Wrap U"))
+            .selected_text(format!("Wrap U: {}", self.wrap_u))
+            .show_ui(ui, |ui| {
+                TextureWrap::all().for_each(|wrap| {
+                    ui.selectable_value(&mut self.wrap_u, wrap, format!("{}", wrap));
+                });
+            });
+        egui::ComboBox::from_id_source(extra_data.with("Texture Wrap V"))
+            .selected_text(format!("Wrap V: {}", self.wrap_v))
+            .show_ui(ui, |ui| {
+                TextureWrap::all().for_each(|wrap| {
+                    ui.selectable_value(&mut self.wrap_v, wrap, format!("{}", wrap));
+                });
+            });
+        egui::ComboBox::from_id_source(extra_data.with("Mip Map Filter"))
+            .selected_text(format!("Mip Map: {}", self.mip_map_filter))
+            .show_ui(ui, |ui| {
+                MipMapFilter::all().for_each(|mip_map_filter| {
+                    ui.selectable_value(
+                        &mut self.mip_map_filter,
+                        mip_map_filter,
+                        format!("{}", mip_map_filter),
+                    );
+                });
+            });
+    }
+}
+
+/// Single level of a [`MipMap`], pixels are stored from the bottom
+/// left row wise, same as [`TextureRGBAFloat`].
+#[derive(Debug)]
+struct MipMapLevel {
+    width: usize,
+    height: usize,
+    pixels: Vec<glm::Vec4>,
+}
+
+/// Pyramid of successively halved (box filtered) versions of a
+/// texture, used to filter the texture over the footprint of a ray.
+///
+/// The first level (level 0) is the texture itself and is not
+/// stored, so the texture must be given when sampling.
+#[derive(Debug)]
+pub struct MipMap {
+    /// id of the texture that the mip map is generated from
+    texture_id: usize,
+    /// levels 1 and beyond
+    levels: Vec<MipMapLevel>,
+}
+
+/// Maximum ratio of the major axis to the minor axis of the footprint
+/// for EWA filtering, larger ratios are clamped by increasing the
+/// minor axis (blurring) to bound the number of texels processed.
+const MAX_ANISOTROPY: f64 = 8.0;
+
+impl MipMap {
+    pub fn new(texture: &TextureRGBAFloat) -> Self {
+        let mut levels: Vec<MipMapLevel> = Vec::new();
+        let mut width = texture.get_width();
+        let mut height = texture.get_height();
+        while width > 1 || height > 1 {
+            let pixels: &[glm::Vec4] = match levels.last() {
+                Some(level) => &level.pixels,
+                None => texture.get_pixels(),
+            };
+            let new_width = (width / 2).max(1);
+            let new_height = (height / 2).max(1);
+            let new_pixels = (0..new_height)
+                .flat_map(|j| (0..new_width).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    // odd sizes drop the last row or column
+                    let i0 = (2 * i).min(width - 1);
+                    let i1 = (2 * i + 1).min(width - 1);
+                    let j0 = (2 * j).min(height - 1);
+                    let j1 = (2 * j + 1).min(height - 1);
+                    (pixels[j0 * width + i0]
+                        + pixels[j0 * width + i1]
+                        + pixels[j1 * width + i0]
+                        + pixels[j1 * width + i1])
+                        * 0.25
+                })
+                .collect();
+            levels.push(MipMapLevel {
+                width: new_width,
+                height: new_height,
+                pixels: new_pixels,
+            });
+            width = new_width;
+            height = new_height;
+        }
+
+        Self {
+            texture_id: texture.get_id(),
+            levels,
+        }
+    }
+
+    /// Get id of the texture that the mip map is generated from.
+    pub fn get_texture_id(&self) -> usize {
+        self.texture_id
+    }
+
+    /// Get number of levels of the mip map including the texture
+    /// itself.
+    pub fn get_num_levels(&self) -> usize {
+        self.levels.len() + 1
+    }
+
+    fn get_level_size(&self, texture: &TextureRGBAFloat, level: usize) -> (usize, usize) {
+        if level == 0 {
+            (texture.get_width(), texture.get_height())
+        } else {
+            let level = &self.levels[level - 1];
+            (level.width, level.height)
+        }
+    }
+
+    fn get_level_pixels<'a>(
+        &'a self,
+        texture: &'a TextureRGBAFloat,
+        level: usize,
+    ) -> &'a [glm::Vec4] {
+        if level == 0 {
+            texture.get_pixels()
+        } else {
+            &self.levels[level - 1].pixels
+        }
+    }
+
+    /// Sample the given level with the filter of the sampler.
+    fn sample_level(
+        &self,
+        texture: &TextureRGBAFloat,
+        level: usize,
+        uv: &glm::DVec2,
+        sampler: &TextureSampler,
+    ) -> glm::DVec4 {
+        let (width, height) = self.get_level_size(texture, level);
+        let pixels = self.get_level_pixels(texture, level);
+        sampler.sample(uv, width, height, |i, j| {
+            glm::convert(pixels[j * width + i])
+        })
+    }
+
+    /// Sample the texture (that the mip map is generated from) at the
+    /// given UV coordinates filtered over the given footprint (du/dx
+    /// and dv/dx, du/dy and dv/dy), see [`MipMapFilter`].
+    ///
+    /// Based on pbrt's `MIPMap::Lookup()`.
+    pub fn sample(
+        &self,
+        texture: &TextureRGBAFloat,
+        uv: &glm::DVec2,
+        footprint: Option<&(glm::DVec2, glm::DVec2)>,
+        sampler: &TextureSampler,
+    ) -> glm::DVec4 {
+        debug_assert_eq!(self.texture_id, texture.get_id());
+        let (duvdx, duvdy) = match footprint {
+            Some(footprint) => footprint,
+            None => return texture.sample_uv(uv, sampler),
+        };
+
+        // footprint in texels of the texture
+        let size = glm::vec2(texture.get_width() as f64, texture.get_height() as f64);
+        let dx = duvdx.component_mul(&size);
+        let dy = duvdy.component_mul(&size);
+        let max_level = (self.get_num_levels() - 1) as f64;
+
+        match sampler.get_mip_map_filter() {
+            MipMapFilter::None => texture.sample_uv(uv, sampler),
+            MipMapFilter::Trilinear => {
+                let width = dx.norm().max(dy.norm()).max(1e-8);
+                let level = width.log2().clamp(0.0, max_level);
+                self.sample_between_levels(level, |level| {
+                    self.sample_level(texture, level, uv, sampler)
+                })
+            }
+            MipMapFilter::Ewa => {
+                let (mut major, mut minor) = if dx.norm_squared() < dy.norm_squared() {
+                    (dy, dx)
+                } else {
+                    (dx, dy)
+                };
+                let major_length = major.norm();
+                let mut minor_length = minor.norm();
+
+                if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0.0 {
+                    let scale = major_length / (minor_length * MAX_ANISOTROPY);
+                    minor *= scale;
+                    minor_length *= scale;
+                }
+                if minor_length == 0.0 {
+                    return self.sample_level(texture, 0, uv, sampler);
+                }
+
+                // footprint is relative to the texture, convert back
+                // to uv
+                major = major.component_div(&size);
+                minor = minor.component_div(&size);
+
+                let level = minor_length.max(1e-8).log2().clamp(0.0, max_level);
+                self.sample_between_levels(level, |level| {
+                    self.ewa(texture, level, uv, &major, &minor, sampler)
+                })
+            }
+        }
+    }
+
+    /// Linearly interpolate the samples of the two levels on either
+    /// side of the given (fractional) level.
+    fn sample_between_levels<F>(&self, level: f64, sample: F) -> glm::DVec4
+    where
+        F: Fn(usize) -> glm::DVec4,
+    {
+        let level_floor = level.floor();
+        let t = level - level_floor;
+        let level_floor = level_floor as usize;
+        if t == 0.0 || level_floor + 1 >= self.get_num_levels() {
+            sample(level_floor)
+        } else {
+            sample(level_floor) * (1.0 - t) + sample(level_floor + 1) * t
+        }
+    }
+
+    /// Elliptically weighted average of the texels of the given level
+    /// within the ellipse with the given axes (in uv) centered at
+    /// `uv`.
+    ///
+    /// Based on pbrt's `MIPMap::EWA()`.
+    fn ewa(
+        &self,
+        texture: &TextureRGBAFloat,
+        level: usize,
+        uv: &glm::DVec2,
+        axis_0: &glm::DVec2,
+        axis_1: &glm::DVec2,
+        sampler: &TextureSampler,
+    ) -> glm::DVec4 {
+        let (width, height) = self.get_level_size(texture, level);
+        let pixels = self.get_level_pixels(texture, level);
+        let size = glm::vec2(width as f64, height as f64);
+
+        // texel centers are at half integers
+        let s = uv[0] * size[0] - 0.5;
+        let t = uv[1] * size[1] - 0.5;
+        let axis_0 = axis_0.component_mul(&size);
+        let axis_1 = axis_1.component_mul(&size);
+
+        // implicit ellipse coefficients
+        let mut a = axis_0[1] * axis_0[1] + axis_1[1] * axis_1[1] + 1.0;
+        let mut b = -2.0 * (axis_0[0] * axis_0[1] + axis_1[0] * axis_1[1]);
+        let mut c = axis_0[0] * axis_0[0] + axis_1[0] * axis_1[0] + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        // bounding box of the ellipse
+        let determinant = -b * b + 4.0 * a * c;
+        let inv_determinant = 1.0 / determinant;
+        let u_sqrt = (determinant * c).sqrt();
+        let v_sqrt = (a * determinant).sqrt();
+        let s0 = (s - 2.0 * inv_determinant * u_sqrt).ceil() as isize;
+        let s1 = (s + 2.0 * inv_determinant * u_sqrt).floor() as isize;
+        let t0 = (t - 2.0 * inv_determinant * v_sqrt).ceil() as isize;
+        let t1 = (t + 2.0 * inv_determinant * v_sqrt).floor() as isize;
+
+        // gaussian falloff, zero at the edge of the ellipse
+        const ALPHA: f64 = 2.0;
+        let edge_weight = (-ALPHA).exp();
+
+        let mut sum: glm::DVec4 = glm::zero();
+        let mut sum_weights = 0.0;
+        for it in t0..=t1 {
+            let tt = it as f64 - t;
+            for is in s0..=s1 {
+                let ss = is as f64 - s;
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1.0 {
+                    let weight = (-ALPHA * r2).exp() - edge_weight;
+                    let i = sampler.get_wrap_u().wrap(is, width);
+                    let j = sampler.get_wrap_v().wrap(it, height);
+                    let pixel: glm::DVec4 = glm::convert(pixels[j * width + i]);
+                    sum += pixel * weight;
+                    sum_weights += weight;
+                }
+            }
+        }
+
+        if sum_weights > 0.0 {
+            sum / sum_weights
+        } else {
+            self.sample_level(texture, level, uv, sampler)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Texel of a 4x4 texture whose value is linear in the texel
+    /// coordinates.
+    fn linear_texel(i: usize, j: usize) -> f64 {
+        i as f64 + 10.0 * j as f64
+    }
+
+    #[test]
+    fn texture_wrap_test_01() {
+        let wrap = TextureWrap::Repeat;
+        assert_eq!(wrap.wrap(0, 4), 0);
+        assert_eq!(wrap.wrap(3, 4), 3);
+        assert_eq!(wrap.wrap(4, 4), 0);
+        assert_eq!(wrap.wrap(9, 4), 1);
+        assert_eq!(wrap.wrap(-1, 4), 3);
+        assert_eq!(wrap.wrap(-6, 4), 2);
+
+        let wrap = TextureWrap::Clamp;
+        assert_eq!(wrap.wrap(2, 4), 2);
+        assert_eq!(wrap.wrap(4, 4), 3);
+        assert_eq!(wrap.wrap(100, 4), 3);
+        assert_eq!(wrap.wrap(-1, 4), 0);
+        assert_eq!(wrap.wrap(-100, 4), 0);
+
+        let wrap = TextureWrap::Mirror;
+        assert_eq!(wrap.wrap(2, 4), 2);
+        assert_eq!(wrap.wrap(4, 4), 3);
+        assert_eq!(wrap.wrap(5, 4), 2);
+        assert_eq!(wrap.wrap(7, 4), 0);
+        assert_eq!(wrap.wrap(8, 4), 0);
+        assert_eq!(wrap.wrap(-1, 4), 0);
+        assert_eq!(wrap.wrap(-2, 4), 1);
+        assert_eq!(wrap.wrap(-5, 4), 3);
+    }
+
+    #[test]
+    fn texture_sampler_nearest_test_01() {
+        let sampler = TextureSampler::new(
+            TextureFilter::Nearest,
+            TextureWrap::Repeat,
+            TextureWrap::Clamp,
+        );
+        let sample = |u: f64, v: f64| sampler.sample(&glm::vec2(u, v), 4, 4, linear_texel);
+
+        assert_eq!(sample(0.1, 0.1), 0.0);
+        assert_eq!(sample(0.3, 0.6), 21.0);
+        assert_eq!(sample(0.99, 0.99), 33.0);
+        // u repeats, v clamps
+        assert_eq!(sample(1.1, 1.1), 30.0);
+        assert_eq!(sample(-0.1, -0.1), 3.0);
+    }
+
+    #[test]
+    fn texture_sampler_bilinear_test_01() {
+        let sampler = TextureSampler::new(
+            TextureFilter::Bilinear,
+            TextureWrap::Clamp,
+            TextureWrap::Clamp,
+        );
+        let sample = |u: f64, v: f64| sampler.sample(&glm::vec2(u, v), 4, 4, linear_texel);
+
+        // texel centers give the texel exactly
+        assert!((sample(0.125, 0.125) - 0.0).abs() < 1e-10);
+        assert!((sample(0.625, 0.375) - 12.0).abs() < 1e-10);
+        // between texel centers the texels are linearly interpolated
+        assert!((sample(0.25, 0.125) - 0.5).abs() < 1e-10);
+        assert!((sample(0.5, 0.5) - 16.5).abs() < 1e-10);
+        assert!((sample(0.3, 0.45) - (0.7 + 13.0)).abs() < 1e-10);
+        // beyond the outer texel centers the edge is clamped
+        assert!((sample(0.0, 0.0) - 0.0).abs() < 1e-10);
+        assert!((sample(1.0, 0.125) - 3.0).abs() < 1e-10);
+
+        let sampler = TextureSampler::new(
+            TextureFilter::Bilinear,
+            TextureWrap::Repeat,
+            TextureWrap::Mirror,
+        );
+        let sample = |u: f64, v: f64| sampler.sample(&glm::vec2(u, v), 4, 4, linear_texel);
+
+        // u wraps around to the first column, v mirrors the first row
+        assert!((sample(1.0, 0.125) - 1.5).abs() < 1e-10);
+        assert!((sample(0.125, 0.0) - 0.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn texture_sampler_bicubic_test_01() {
+        let sampler = TextureSampler::new(
+            TextureFilter::Bicubic,
+            TextureWrap::Repeat,
+            TextureWrap::Repeat,
+        );
+
+        // weights of the B-spline sum to 1, constant is reproduced
+        [0.0, 0.2, 0.5, 0.9].iter().for_each(|&t| {
+            let weights = cubic_bspline_weights(t);
+            assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-10);
+            let value = sampler.sample(&glm::vec2(t, 1.0 - t), 4, 4, |_, _| 2.5);
+            assert!((value - 2.5).abs() < 1e-10);
+        });
+
+        // away from the edges (where Repeat breaks linearity) a
+        // linear texture is reproduced
+        let sample = |u: f64, v: f64| sampler.sample(&glm::vec2(u, v), 8, 8, linear_texel);
+        [(0.25, 0.25), (0.3, 0.6), (0.45, 0.5), (0.6, 0.35)]
+            .iter()
+            .for_each(|&(u, v)| {
+                let expected = (u * 8.0 - 0.5) + 10.0 * (v * 8.0 - 0.5);
+                assert!((sample(u, v) - expected).abs() < 1e-10);
+            });
+
+        // unlike bilinear, B-spline does not interpolate the texels,
+        // at the center of a single lit texel the weight is (4/6)^2
+        let impulse = |i: usize, j: usize| if i == 3 && j == 3 { 1.0 } else { 0.0 };
+        let value = sampler.sample(&glm::vec2(3.5 / 8.0, 3.5 / 8.0), 8, 8, impulse);
+        assert!((value - 4.0 / 9.0).abs() < 1e-10);
+        let value = sampler.sample(&glm::vec2(4.5 / 8.0, 3.5 / 8.0), 8, 8, impulse);
+        assert!((value - 4.0 / 36.0).abs() < 1e-10);
+    }
+
+    /// Texture whose pixels (all channels) are the index of the
+    /// pixel.
+    fn get_index_texture(width: usize, height: usize) -> TextureRGBAFloat {
+        TextureRGBAFloat::from_pixels(
+            width,
+            height,
+            (0..width * height)
+                .map(|index| glm::vec4(index as f32, index as f32, index as f32, index as f32))
+                .collect(),
+        )
+    }
+
+    fn get_level_values(mip_map: &MipMap, level: usize) -> Vec<f32> {
+        mip_map.levels[level - 1]
+            .pixels
+            .iter()
+            .map(|pixel| {
+                assert_eq!(*pixel, glm::vec4(pixel[0], pixel[0], pixel[0], pixel[0]));
+                pixel[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mip_map_level_sizes_test_01() {
+        let texture = get_index_texture(8, 4);
+        let mip_map = MipMap::new(&texture);
+        assert_eq!(mip_map.get_texture_id(), texture.get_id());
+        assert_eq!(mip_map.get_num_levels(), 4);
+        assert_eq!(mip_map.get_level_size(&texture, 0), (8, 4));
+        assert_eq!(mip_map.get_level_size(&texture, 1), (4, 2));
+        assert_eq!(mip_map.get_level_size(&texture, 2), (2, 1));
+        assert_eq!(mip_map.get_level_size(&texture, 3), (1, 1));
+
+        let texture = get_index_texture(5, 3);
+        let mip_map = MipMap::new(&texture);
+        assert_eq!(mip_map.get_num_levels(), 3);
+        assert_eq!(mip_map.get_level_size(&texture, 1), (2, 1));
+        assert_eq!(mip_map.get_level_size(&texture, 2), (1, 1));
+
+        let texture = get_index_texture(1, 1);
+        let mip_map = MipMap::new(&texture);
+        assert_eq!(mip_map.get_num_levels(), 1);
+    }
+
+    #[test]
+    fn mip_map_box_filter_test_01() {
+        let texture = get_index_texture(4, 4);
+        let mip_map = MipMap::new(&texture);
+        assert_eq!(get_level_values(&mip_map, 1), vec![2.5, 4.5, 10.5, 12.5]);
+        assert_eq!(get_level_values(&mip_map, 2), vec![7.5]);
+
+        // odd sizes drop the last row and column
+        let texture = get_index_texture(5, 3);
+        let mip_map = MipMap::new(&texture);
+        assert_eq!(get_level_values(&mip_map, 1), vec![3.0, 5.0]);
+        assert_eq!(get_level_values(&mip_map, 2), vec![4.0]);
+    }
+
+    #[test]
+    fn mip_map_sample_trilinear_test_01() {
+        let texture = get_index_texture(4, 4);
+        let mip_map = MipMap::new(&texture);
+        let mut sampler = TextureSampler::new(
+            TextureFilter::Bilinear,
+            TextureWrap::Clamp,
+            TextureWrap::Clamp,
+        );
+        sampler.set_mip_map_filter(MipMapFilter::Trilinear);
+        let uv = glm::vec2(0.25, 0.25);
+        let sample = |footprint_width: f64| {
+            mip_map.sample(
+                &texture,
+                &uv,
+                Some(&(glm::vec2(footprint_width, 0.0), glm::vec2(0.0, 0.0))),
+                &sampler,
+            )[0]
+        };
+
+        // without a footprint and with a footprint of a texel the
+        // texture is sampled directly
+        let expected = texture.sample_uv(&uv, &sampler)[0];
+        assert!((mip_map.sample(&texture, &uv, None, &sampler)[0] - expected).abs() < 1e-10);
+        assert!((sample(0.25) - expected).abs() < 1e-10);
+        // footprint of 2 texels is level 1, at the center of its
+        // first texel
+        assert!((sample(0.5) - 2.5).abs() < 1e-10);
+        // footprint of the whole texture is the average
+        assert!((sample(1.0) - 7.5).abs() < 1e-10);
+        assert!((sample(10.0) - 7.5).abs() < 1e-10);
+        // halfway between level 1 and level 2
+        assert!((sample(2.0_f64.powf(1.5) / 4.0) - 5.0).abs() < 1e-6);
+
+        sampler.set_mip_map_filter(MipMapFilter::None);
+        let footprint = (glm::vec2(1.0, 0.0), glm::vec2(0.0, 0.0));
+        let value = mip_map.sample(&texture, &uv, Some(&footprint), &sampler)[0];
+        assert!((value - expected).abs() < 1e-10);
+    }
 }
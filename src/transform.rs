@@ -1,5 +1,5 @@
 use crate::ui::DrawUI;
-use crate::{egui, glm, UiData};
+use crate::{egui, glm, util, UiData};
 
 use serde::{Deserialize, Serialize};
 
@@ -109,4 +109,371 @@ impl Transform {
 
         glm::scale(&rotated_mat, &self.scale)
     }
+
+    /// Create the transform from the given model matrix. Assumes
+    /// that the model matrix does not contain any shear.
+    pub fn from_matrix(model: &glm::DMat4) -> Self {
+        let (location, rotation, scale) = decompose_model_matrix(model);
+        let rotation = util::rotation_matrix_to_euler(
+            &glm::quat_to_mat3(&rotation),
+            util::RotationModes::EulerXYZ,
+        );
+        Self::new(
+            location,
+            glm::vec3(
+                rotation[0].to_degrees(),
+                rotation[1].to_degrees(),
+                rotation[2].to_degrees(),
+            ),
+            scale,
+        )
+    }
+}
+
+/// For the given keys (sorted by time), get the keys between which
+/// `time` lies along with the interpolation factor between them. If
+/// `time` is outside the range of the keys, the first or last key is
+/// used.
+///
+/// Returns None if there are no keys.
+pub fn find_motion_keys<T>(keys: &[(f64, T)], time: f64) -> Option<(&T, &T, f64)> {
+    let (first, last) = (keys.first()?, keys.last()?);
+    if time <= first.0 {
+        return Some((&first.1, &first.1, 0.0));
+    }
+    if time >= last.0 {
+        return Some((&last.1, &last.1, 0.0));
+    }
+    keys.windows(2).find_map(|keys| {
+        let (key_1, key_2) = (&keys[0], &keys[1]);
+        if time >= key_1.0 && time <= key_2.0 {
+            let range = key_2.0 - key_1.0;
+            let factor = if range > 0.0 {
+                (time - key_1.0) / range
+            } else {
+                0.0
+            };
+            Some((&key_1.1, &key_2.1, factor))
+        } else {
+            None
+        }
+    })
+}
+
+/// Decompose the given model matrix into translation, rotation and
+/// scale. Assumes that the model matrix does not contain any shear.
+fn decompose_model_matrix(model: &glm::DMat4) -> (glm::DVec3, glm::DQuat, glm::DVec3) {
+    let translation = glm::vec3(model[(0, 3)], model[(1, 3)], model[(2, 3)]);
+    let linear = glm::mat4_to_mat3(model);
+    let mut scale = glm::vec3(
+        linear.column(0).norm(),
+        linear.column(1).norm(),
+        linear.column(2).norm(),
+    );
+    // a negative determinant means there is a reflection, can be
+    // represented by a negative scale
+    if glm::determinant(&linear) < 0.0 {
+        scale[0] = -scale[0];
+    }
+    let mut rotation = linear;
+    for i in 0..3 {
+        if scale[i] != 0.0 {
+            let column = rotation.column(i) / scale[i];
+            rotation.set_column(i, &column);
+        }
+    }
+    (translation, glm::mat3_to_quat(&rotation), scale)
+}
+
+/// Interpolate between the given model matrices. Translation and
+/// scale are interpolated linearly, rotation is spherically
+/// interpolated.
+pub fn interpolate_model_matrices(
+    model_1: &glm::DMat4,
+    model_2: &glm::DMat4,
+    factor: f64,
+) -> glm::DMat4 {
+    if factor <= 0.0 {
+        return *model_1;
+    }
+    if factor >= 1.0 {
+        return *model_2;
+    }
+    let (translation_1, rotation_1, scale_1) = decompose_model_matrix(model_1);
+    let (translation_2, rotation_2, scale_2) = decompose_model_matrix(model_2);
+
+    // take the shortest path for the rotation
+    let rotation_2 = if glm::quat_dot(&rotation_1, &rotation_2) < 0.0 {
+        -rotation_2
+    } else {
+        rotation_2
+    };
+
+    let translation = glm::lerp(&translation_1, &translation_2, factor);
+    let rotation = glm::quat_normalize(&glm::quat_slerp(&rotation_1, &rotation_2, factor));
+    let scale = glm::lerp(&scale_1, &scale_2, factor);
+
+    glm::translation(&translation) * glm::quat_to_mat4(&rotation) * glm::scaling(&scale)
+}
+
+/// Model matrices of an object keyed at specific times. Used for
+/// transformation motion blur, the model matrix of the object at any
+/// time within the shutter of the camera is interpolated from the
+/// keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MotionTransform {
+    /// Keys as (time, model matrix), sorted by time
+    keys: Vec<(f64, glm::DMat4)>,
+}
+
+impl MotionTransform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key at the given time, replaces the key if a key already
+    /// exists at that time.
+    pub fn add_key(&mut self, time: f64, model: glm::DMat4) {
+        match self
+            .keys
+            .binary_search_by(|(key_time, _)| key_time.partial_cmp(&time).unwrap())
+        {
+            Ok(index) => self.keys[index] = (time, model),
+            Err(index) => self.keys.insert(index, (time, model)),
+        }
+    }
+
+    /// Remove all the keys.
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    /// Get a reference to the motion transform's keys.
+    pub fn get_keys(&self) -> &[(f64, glm::DMat4)] {
+        self.keys.as_slice()
+    }
+
+    /// Check if the transform changes over time, needs at least 2
+    /// keys.
+    pub fn is_animated(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// Get the model matrix at the given time. Returns None if there
+    /// are no keys.
+    pub fn get_matrix(&self, time: f64) -> Option<glm::DMat4> {
+        find_motion_keys(&self.keys, time)
+            .map(|(model_1, model_2, factor)| interpolate_model_matrices(model_1, model_2, factor))
+    }
+}
+
+/// Extra data needed to draw the UI of a [`MotionTransform`].
+pub struct MotionTransformUiData {
+    id: egui::Id,
+    /// model matrix of the object, new keys start from it
+    model: glm::DMat4,
+}
+
+impl MotionTransformUiData {
+    pub fn new(id: egui::Id, model: glm::DMat4) -> Self {
+        Self { id, model }
+    }
+}
+
+/// Draw the components of the vector as drag values, returns true if
+/// any of them changed.
+fn drag_dvec3(ui: &mut egui::Ui, text: &str, value: &mut glm::DVec3, speed: f64) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(text);
+        (0..3).for_each(|i| {
+            changed |= ui
+                .add(egui::DragValue::new(&mut value[i]).speed(speed))
+                .changed();
+        });
+    });
+    changed
+}
+
+impl DrawUI for MotionTransform {
+    type ExtraData = MotionTransformUiData;
+
+    fn draw_ui(&self, _ui: &mut egui::Ui, _extra_data: &Self::ExtraData) {}
+
+    fn draw_ui_mut(&mut self, ui: &mut egui::Ui, extra_data: &Self::ExtraData) {
+        egui::CollapsingHeader::new("Motion Blur")
+            .id_source(extra_data.id.with("Motion Blur"))
+            .show(ui, |ui| {
+                if !self.is_animated() {
+                    ui.label("Needs at least 2 keys for motion blur");
+                }
+
+                // keys are edited as transforms, the keys are set again
+                // only if they change so that the model matrices do not
+                // drift through the conversions
+                let mut keys: Vec<_> = self
+                    .keys
+                    .iter()
+                    .map(|(time, model)| (*time, Transform::from_matrix(model)))
+                    .collect();
+                let mut changed = false;
+                let mut delete_key = None;
+                keys.iter_mut()
+                    .enumerate()
+                    .for_each(|(index, (time, transform))| {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.label("Time");
+                            changed |= ui.add(egui::DragValue::new(time).speed(0.01)).changed();
+                            if ui.button("Delete Key").clicked() {
+                                delete_key = Some(index);
+                            }
+                        });
+                        changed |= drag_dvec3(ui, "Location", &mut transform.location, 0.01);
+                        changed |= drag_dvec3(ui, "Rotation", &mut transform.rotation, 1.0);
+                        changed |= drag_dvec3(ui, "Scale", &mut transform.scale, 0.01);
+                    });
+                if let Some(index) = delete_key {
+                    keys.remove(index);
+                    changed = true;
+                }
+
+                if changed {
+                    self.clear();
+                    keys.iter().for_each(|(time, transform)| {
+                        self.add_key(*time, transform.get_matrix());
+                    });
+                }
+
+                // the new key is after the last key, starting from the
+                // current state of the object
+                if ui.button("Add Key").clicked() {
+                    let time = self.keys.last().map_or(0.0, |(time, _)| time + 1.0);
+                    self.add_key(time, extra_data.model);
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_eq(m1: &glm::DMat4, m2: &glm::DMat4) {
+        assert!(
+            m1.iter().zip(m2.iter()).all(|(a, b)| (a - b).abs() < 1e-6),
+            "{} != {}",
+            m1,
+            m2
+        );
+    }
+
+    fn get_matrix(location: glm::DVec3, rotation: glm::DVec3, scale: glm::DVec3) -> glm::DMat4 {
+        Transform {
+            location,
+            rotation,
+            scale,
+        }
+        .get_matrix()
+    }
+
+    #[test]
+    fn find_motion_keys_test_01() {
+        let keys: Vec<(f64, i32)> = vec![];
+        assert!(find_motion_keys(&keys, 0.0).is_none());
+
+        // a single key is used at all times
+        let keys = vec![(0.5, 1)];
+        [-1.0, 0.5, 2.0].iter().for_each(|time| {
+            assert_eq!(find_motion_keys(&keys, *time), Some((&1, &1, 0.0)));
+        });
+    }
+
+    #[test]
+    fn find_motion_keys_test_02() {
+        let keys = vec![(0.0, 1), (1.0, 2), (3.0, 3)];
+
+        // clamped to the first and last keys
+        assert_eq!(find_motion_keys(&keys, -1.0), Some((&1, &1, 0.0)));
+        assert_eq!(find_motion_keys(&keys, 4.0), Some((&3, &3, 0.0)));
+
+        // exactly at the times of the keys
+        assert_eq!(find_motion_keys(&keys, 0.0), Some((&1, &1, 0.0)));
+        assert_eq!(find_motion_keys(&keys, 1.0), Some((&1, &2, 1.0)));
+        assert_eq!(find_motion_keys(&keys, 3.0), Some((&3, &3, 0.0)));
+
+        // between the keys
+        assert_eq!(find_motion_keys(&keys, 0.25), Some((&1, &2, 0.25)));
+        assert_eq!(find_motion_keys(&keys, 2.5), Some((&2, &3, 0.75)));
+
+        // no keys can be found for NaN
+        assert!(find_motion_keys(&keys, f64::NAN).is_none());
+    }
+
+    #[test]
+    fn interpolate_model_matrices_test_01() {
+        let model_1 = get_matrix(
+            glm::vec3(1.0, 2.0, 3.0),
+            glm::vec3(10.0, 20.0, 30.0),
+            glm::vec3(1.0, 2.0, 1.0),
+        );
+        let model_2 = get_matrix(
+            glm::vec3(-1.0, 0.0, 5.0),
+            glm::vec3(40.0, 0.0, 60.0),
+            glm::vec3(2.0, 1.0, 3.0),
+        );
+
+        assert_matrix_eq(
+            &interpolate_model_matrices(&model_1, &model_2, 0.0),
+            &model_1,
+        );
+        assert_matrix_eq(
+            &interpolate_model_matrices(&model_1, &model_2, 1.0),
+            &model_2,
+        );
+        assert_matrix_eq(
+            &interpolate_model_matrices(&model_1, &model_2, -1.0),
+            &model_1,
+        );
+        assert_matrix_eq(
+            &interpolate_model_matrices(&model_1, &model_2, 2.0),
+            &model_2,
+        );
+    }
+
+    #[test]
+    fn interpolate_model_matrices_test_02() {
+        let model_1 = get_matrix(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 1.0, 1.0),
+        );
+        let model_2 = get_matrix(
+            glm::vec3(2.0, -4.0, 6.0),
+            glm::vec3(0.0, 0.0, 90.0),
+            glm::vec3(3.0, 1.0, 2.0),
+        );
+
+        // translation and scale are linearly interpolated, rotation
+        // is spherically interpolated
+        let expected = get_matrix(
+            glm::vec3(1.0, -2.0, 3.0),
+            glm::vec3(0.0, 0.0, 45.0),
+            glm::vec3(2.0, 1.0, 1.5),
+        );
+        assert_matrix_eq(
+            &interpolate_model_matrices(&model_1, &model_2, 0.5),
+            &expected,
+        );
+
+        let expected = get_matrix(
+            glm::vec3(0.5, -1.0, 1.5),
+            glm::vec3(0.0, 0.0, 22.5),
+            glm::vec3(1.5, 1.0, 1.25),
+        );
+        assert_matrix_eq(
+            &interpolate_model_matrices(&model_1, &model_2, 0.25),
+            &expected,
+        );
+    }
 }
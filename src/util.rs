@@ -1,7 +1,7 @@
 use egui_glfw::egui;
 use lazy_static::lazy_static;
 
-use std::{convert::TryFrom, fmt::Display};
+use std::{collections::HashSet, convert::TryFrom, fmt::Display};
 
 use crate::{glm, ui::DrawUI};
 
@@ -27,6 +27,40 @@ pub fn normal_apply_model_matrix(normal: &glm::DVec3, model: &glm::DMat4) -> glm
     vec3_apply_model_matrix(normal, &glm::inverse_transpose(*model))
 }
 
+/// Get the bounds that contain both the given bounds.
+pub fn min_max_bounds_union(
+    bounds_1: &(glm::DVec3, glm::DVec3),
+    bounds_2: &(glm::DVec3, glm::DVec3),
+) -> (glm::DVec3, glm::DVec3) {
+    (
+        glm::min2(&bounds_1.0, &bounds_2.0),
+        glm::max2(&bounds_1.1, &bounds_2.1),
+    )
+}
+
+/// Get the (axis aligned) bounds of the given bounds after the model
+/// matrix is applied to it.
+pub fn min_max_bounds_apply_model_matrix(
+    bounds: &(glm::DVec3, glm::DVec3),
+    model: &glm::DMat4,
+) -> (glm::DVec3, glm::DVec3) {
+    let (min, max) = bounds;
+    let corner = |i: usize| {
+        vec3_apply_model_matrix(
+            &glm::vec3(
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ),
+            model,
+        )
+    };
+    (1..8).fold((corner(0), corner(0)), |acc, i| {
+        let corner = corner(i);
+        min_max_bounds_union(&acc, &(corner, corner))
+    })
+}
+
 pub fn focal_length_to_fov(focal_length: f64, camera_sensor_size: f64) -> f64 {
     2.0 * (camera_sensor_size / (2.0 * focal_length)).atan()
 }
@@ -69,6 +103,36 @@ pub fn vec2_apply_bary_coord(
     v1 * bary_coord[0] + v2 * bary_coord[1] + v3 * bary_coord[2]
 }
 
+/// Get the partial derivatives of the position with respect to the
+/// UVs (dp/du, dp/dv) on the triangle with the given positions and
+/// UVs. Returns `None` if the UVs of the triangle are degenerate.
+///
+/// Based on pbrt's `Triangle::Intersect()`.
+pub fn triangle_uv_derivatives(
+    p1: &glm::DVec3,
+    p2: &glm::DVec3,
+    p3: &glm::DVec3,
+    uv1: &glm::DVec2,
+    uv2: &glm::DVec2,
+    uv3: &glm::DVec2,
+) -> Option<(glm::DVec3, glm::DVec3)> {
+    let duv13 = uv1 - uv3;
+    let duv23 = uv2 - uv3;
+    let dp13 = p1 - p3;
+    let dp23 = p2 - p3;
+
+    let determinant = duv13[0] * duv23[1] - duv13[1] * duv23[0];
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inv_determinant = 1.0 / determinant;
+
+    Some((
+        (duv23[1] * dp13 - duv13[1] * dp23) * inv_determinant,
+        (duv13[0] * dp23 - duv23[0] * dp13) * inv_determinant,
+    ))
+}
+
 /// convert linear rgb to srgb
 ///
 /// `linear`: rgb linear values between 0.0 and 1.0
@@ -117,6 +181,176 @@ pub fn srgb_to_linear<const R: usize>(srgb: &glm::TVec<f64, R>) -> glm::TVec<f64
     linear
 }
 
+/// Get the luminance (CIE Y) of the linear rgb (sRGB primaries)
+/// color.
+pub fn luminance(linear: &glm::DVec3) -> f64 {
+    0.2126729 * linear[0] + 0.7151522 * linear[1] + 0.0721750 * linear[2]
+}
+
+/// Matrix to convert linear rgb (sRGB primaries, D65 white point)
+/// to CIE XYZ.
+///
+/// reference: <http://www.brucelindbloom.com/index.html?Eqn_RGB_XYZ_Matrix.html>
+pub fn linear_rgb_to_xyz_matrix() -> glm::DMat3 {
+    glm::mat3(
+        0.4124564, 0.3575761, 0.1804375, 0.2126729, 0.7151522, 0.0721750, 0.0193339, 0.1191920,
+        0.9503041,
+    )
+}
+
+/// Matrix to convert CIE XYZ to linear rgb (sRGB primaries, D65
+/// white point).
+///
+/// reference: <http://www.brucelindbloom.com/index.html?Eqn_RGB_XYZ_Matrix.html>
+pub fn xyz_to_linear_rgb_matrix() -> glm::DMat3 {
+    glm::mat3(
+        3.2404542, -1.5371385, -0.4985314, -0.9692660, 1.8760108, 0.0415560, 0.0556434, -0.2040259,
+        1.0572252,
+    )
+}
+
+/// Convert CIE xy chromaticity to CIE XYZ with the given luminance
+/// (`Y`).
+pub fn xy_to_xyz(xy: &glm::DVec2, luminance: f64) -> glm::DVec3 {
+    glm::vec3(
+        xy[0] * luminance / xy[1],
+        luminance,
+        (1.0 - xy[0] - xy[1]) * luminance / xy[1],
+    )
+}
+
+/// Convert CIE xy chromaticity to CIE 1960 uv chromaticity.
+pub fn xy_to_uv(xy: &glm::DVec2) -> glm::DVec2 {
+    let denominator = -2.0 * xy[0] + 12.0 * xy[1] + 3.0;
+    glm::vec2(4.0 * xy[0] / denominator, 6.0 * xy[1] / denominator)
+}
+
+/// Convert CIE 1960 uv chromaticity to CIE xy chromaticity.
+pub fn uv_to_xy(uv: &glm::DVec2) -> glm::DVec2 {
+    let denominator = 2.0 * uv[0] - 8.0 * uv[1] + 4.0;
+    glm::vec2(3.0 * uv[0] / denominator, 2.0 * uv[1] / denominator)
+}
+
+/// Get the CIE xy chromaticity of a black body at the given
+/// temperature (in kelvin), the temperature is clamped to the valid
+/// range of the approximation (1667K to 25000K).
+///
+/// reference: Kim et al. "Design of Advanced Color Temperature
+/// Control System for HDTV Applications" (2002)
+pub fn planckian_locus_xy(temperature: f64) -> glm::DVec2 {
+    let t = temperature.clamp(1667.0, 25000.0);
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+
+    let x2 = x * x;
+    let x3 = x2 * x;
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+
+    glm::vec2(x, y)
+}
+
+/// Get the CIE xy chromaticity of the white point at the given
+/// temperature (in kelvin) offset from the planckian locus by `duv`
+/// in CIE 1960 uv. Positive `duv` is towards green and negative
+/// `duv` is towards magenta.
+pub fn white_point_xy(temperature: f64, duv: f64) -> glm::DVec2 {
+    let uv = xy_to_uv(&planckian_locus_xy(temperature));
+    if duv == 0.0 {
+        return uv_to_xy(&uv);
+    }
+
+    // normal of the planckian locus, the locus moves towards lower
+    // u as the temperature increases, the normal points towards
+    // higher v (green)
+    let tangent = xy_to_uv(&planckian_locus_xy(temperature + 1.0))
+        - xy_to_uv(&planckian_locus_xy(temperature - 1.0));
+    let normal = glm::vec2(tangent[1], -tangent[0]).normalize();
+    let normal = if normal[1] < 0.0 { -normal } else { normal };
+
+    uv_to_xy(&(uv + normal * duv))
+}
+
+/// Get the linear rgb (sRGB primaries) color of a black body at the
+/// given temperature (in kelvin), normalized to unit luminance. See
+/// [`planckian_locus_xy()`] for the valid range of the temperature,
+/// colors that lie outside the sRGB gamut are clamped.
+pub fn blackbody_to_linear_rgb(temperature: f64) -> glm::DVec3 {
+    let rgb = xyz_to_linear_rgb_matrix() * xy_to_xyz(&planckian_locus_xy(temperature), 1.0);
+    let rgb = glm::max(&rgb, 0.0);
+    rgb / luminance(&rgb)
+}
+
+/// Get the matrix that adapts CIE XYZ colors seen under
+/// `source_white` (CIE XYZ) to the colors as seen under
+/// `destination_white` (CIE XYZ) using the Bradford transform.
+///
+/// reference: <http://www.brucelindbloom.com/index.html?Eqn_ChromAdapt.html>
+pub fn chromatic_adaptation_matrix(
+    source_white: &glm::DVec3,
+    destination_white: &glm::DVec3,
+) -> glm::DMat3 {
+    let bradford = glm::mat3(
+        0.8951000, 0.2664000, -0.1614000, -0.7502000, 1.7135000, 0.0367000, 0.0389000, -0.0685000,
+        1.0296000,
+    );
+    let bradford_inverse = glm::mat3(
+        0.9869929, -0.1470543, 0.1599627, 0.4323053, 0.5183603, 0.0492912, -0.0085287, 0.0400428,
+        0.9684867,
+    );
+
+    let source_cone = bradford * source_white;
+    let destination_cone = bradford * destination_white;
+    let scale = glm::DMat3::from_diagonal(&destination_cone.component_div(&source_cone));
+
+    bradford_inverse * scale * bradford
+}
+
+/// Get two unit vectors perpendicular to the given unit vector and to
+/// each other.
+///
+/// reference: Duff et al., "Building an Orthonormal Basis,
+/// Revisited"
+pub fn orthonormal_basis(n: &glm::DVec3) -> (glm::DVec3, glm::DVec3) {
+    let sign = 1.0_f64.copysign(n[2]);
+    let a = -1.0 / (sign + n[2]);
+    let b = n[0] * n[1] * a;
+    (
+        glm::vec3(1.0 + sign * n[0] * n[0] * a, sign * b, -sign * n[0]),
+        glm::vec3(b, sign + n[1] * n[1] * a, -n[1]),
+    )
+}
+
+/// Rotate `v` by the smallest rotation that takes the unit vector
+/// `from` to the unit vector `to` (Rodrigues' rotation formula).
+pub fn rotate_between(v: &glm::DVec3, from: &glm::DVec3, to: &glm::DVec3) -> glm::DVec3 {
+    let axis = glm::cross(from, to);
+    let sin = glm::length(&axis);
+    let cos = glm::dot(from, to);
+    if sin < 1e-12 {
+        if cos > 0.0 {
+            return *v;
+        }
+        // opposite vectors, rotate by 180 degrees about any axis
+        // perpendicular to them
+        let (axis, _) = orthonormal_basis(from);
+        return 2.0 * glm::dot(&axis, v) * axis - v;
+    }
+    let axis = axis / sin;
+    v * cos + glm::cross(&axis, v) * sin + axis * (glm::dot(&axis, v) * (1.0 - cos))
+}
+
 /// Convert normal represented in a slice of i16 to glm::DVec3
 ///
 /// This is based on Blender's `normal_short_to_float_v3()` function
@@ -673,10 +907,132 @@ macro_rules! assert_vec_relative_eq {
     };
 }
 
+/// Result of a [`depth_first_search()`].
+#[derive(Debug)]
+pub struct DepthFirstSearch<N, E> {
+    /// nodes in the order that their search finished
+    post_order: Vec<N>,
+    /// edges that lead to a node on the current path
+    back_edges: Vec<(N, E)>,
+}
+
+impl<N, E> DepthFirstSearch<N, E> {
+    /// Get the nodes reached by the search in the order that their
+    /// search finished. A node comes after all the nodes it leads to
+    /// (apart from those of the back edges), the reverse is a
+    /// topological order.
+    pub fn get_post_order(&self) -> &[N] {
+        &self.post_order
+    }
+
+    /// Get the edges (node, edge) that lead to a node on the path
+    /// from the root to `node`, each of them creates a cycle. The
+    /// graph is acyclic once they are removed.
+    pub fn get_back_edges(&self) -> &[(N, E)] {
+        &self.back_edges
+    }
+}
+
+/// Depth first search of a directed graph from the given roots,
+/// `get_edges` gives the edges of a node along with the node that
+/// each of them leads to. Every node is searched once.
+///
+/// Used for the graphs that must be acyclic (the references of the
+/// shaders and the links of the shader graphs). Cycles are rejected
+/// when the edges are made through the UI but files can still
+/// contain them, they are broken by removing the back edges.
+pub fn depth_first_search<N, E, F>(
+    roots: impl IntoIterator<Item = N>,
+    mut get_edges: F,
+) -> DepthFirstSearch<N, E>
+where
+    N: Copy + Eq + std::hash::Hash,
+    F: FnMut(N) -> Vec<(E, N)>,
+{
+    fn visit<N, E, F>(
+        node: N,
+        get_edges: &mut F,
+        visited: &mut HashSet<N>,
+        on_path: &mut HashSet<N>,
+        search: &mut DepthFirstSearch<N, E>,
+    ) where
+        N: Copy + Eq + std::hash::Hash,
+        F: FnMut(N) -> Vec<(E, N)>,
+    {
+        if !visited.insert(node) {
+            return;
+        }
+        on_path.insert(node);
+        get_edges(node).into_iter().for_each(|(edge, next)| {
+            if on_path.contains(&next) {
+                search.back_edges.push((node, edge));
+            } else {
+                visit(next, get_edges, visited, on_path, search);
+            }
+        });
+        on_path.remove(&node);
+        search.post_order.push(node);
+    }
+
+    let mut search = DepthFirstSearch {
+        post_order: Vec::new(),
+        back_edges: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    roots.into_iter().for_each(|root| {
+        visit(
+            root,
+            &mut get_edges,
+            &mut visited,
+            &mut HashSet::new(),
+            &mut search,
+        )
+    });
+    search
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn depth_first_search_test_01() {
+        // 0 -> 1 -> 2 -> 0 (back edge), 1 -> 3, 2 -> 3, 4 is not
+        // reachable
+        let edges = |node: usize| match node {
+            0 => vec![("0-1", 1)],
+            1 => vec![("1-2", 2), ("1-3", 3)],
+            2 => vec![("2-0", 0), ("2-3", 3)],
+            3 => vec![],
+            _ => vec![("4-0", 0)],
+        };
+
+        let search = depth_first_search(vec![0], edges);
+        assert_eq!(search.get_post_order(), &[3, 2, 1, 0]);
+        assert_eq!(search.get_back_edges(), &[(2, "2-0")]);
+
+        // the roots are searched in order, nodes are searched once
+        let search = depth_first_search(vec![3, 1, 4], edges);
+        assert_eq!(search.get_post_order(), &[3, 0, 2, 1, 4]);
+        assert_eq!(search.get_back_edges(), &[(0, "0-1")]);
+    }
+
+    #[test]
+    fn rotate_between_test_01() {
+        let x = glm::vec3(1.0, 0.0, 0.0);
+        let y = glm::vec3(0.0, 1.0, 0.0);
+        let z = glm::vec3(0.0, 0.0, 1.0);
+        assert!(glm::distance(&rotate_between(&x, &x, &y), &y) < 1e-12);
+        // the rotation is about the Z axis
+        assert!(glm::distance(&rotate_between(&y, &x, &y), &-x) < 1e-12);
+        assert!(glm::distance(&rotate_between(&z, &x, &y), &z) < 1e-12);
+        assert_eq!(rotate_between(&y, &x, &x), y);
+
+        let rotated = rotate_between(&x, &z, &-z);
+        assert!(glm::distance(&rotate_between(&z, &z, &-z), &-z) < 1e-12);
+        assert!((glm::length(&rotated) - 1.0).abs() < 1e-12);
+    }
+
     #[test]
     fn axis_conversion_matrix_test_01() {
         let conversion_matrix = axis_conversion_matrix(Axis::X, Axis::Y, Axis::X, Axis::Y).unwrap();
@@ -861,4 +1217,45 @@ mod tests {
     test_rotation_matrix_to_euler_02!(rotation_matrix_to_euler_test_yzx_02, EulerYZX);
     test_rotation_matrix_to_euler_02!(rotation_matrix_to_euler_test_zxy_02, EulerZXY);
     test_rotation_matrix_to_euler_02!(rotation_matrix_to_euler_test_zyx_02, EulerZYX);
+
+    #[test]
+    fn planckian_locus_xy_test_01() {
+        // D65 is close to (but not on) the planckian locus at 6504K
+        let xy = planckian_locus_xy(6504.0);
+        assert!((xy[0] - 0.3127).abs() < 2e-3);
+        assert!((xy[1] - 0.3290).abs() < 6e-3);
+
+        // illuminant A is on the planckian locus at 2856K
+        let xy = planckian_locus_xy(2856.0);
+        assert!((xy[0] - 0.44757).abs() < 1e-3);
+        assert!((xy[1] - 0.40745).abs() < 1e-3);
+    }
+
+    #[test]
+    fn chromatic_adaptation_matrix_test_01() {
+        let white = xy_to_xyz(&white_point_xy(6500.0, 0.0), 1.0);
+        let identity = chromatic_adaptation_matrix(&white, &white);
+        assert!((identity - glm::DMat3::identity()).abs().max() < 1e-6);
+
+        let source_white = xy_to_xyz(&white_point_xy(3200.0, 0.01), 1.0);
+        let adaptation = chromatic_adaptation_matrix(&source_white, &white);
+        assert!(glm::distance(&(adaptation * source_white), &white) < 1e-6);
+
+        let rgb = glm::vec3(0.2, 0.5, 0.7);
+        let rgb_roundtrip = xyz_to_linear_rgb_matrix() * (linear_rgb_to_xyz_matrix() * rgb);
+        assert!(glm::distance(&rgb, &rgb_roundtrip) < 1e-6);
+    }
+
+    #[test]
+    fn blackbody_to_linear_rgb_test_01() {
+        let rgb = blackbody_to_linear_rgb(6504.0);
+        assert!((luminance(&rgb) - 1.0).abs() < 1e-6);
+        assert!((rgb - glm::vec3(1.0, 1.0, 1.0)).abs().max() < 0.1);
+
+        // low temperatures are red, high temperatures are blue
+        let rgb = blackbody_to_linear_rgb(2000.0);
+        assert!(rgb[0] > rgb[1] && rgb[1] > rgb[2]);
+        let rgb = blackbody_to_linear_rgb(20000.0);
+        assert!(rgb[2] > rgb[1] && rgb[1] > rgb[0]);
+    }
 }